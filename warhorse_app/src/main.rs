@@ -5,8 +5,8 @@ use std::{
 };
 
 use dioxus::{desktop::WindowBuilder, prelude::*};
-use tracing::{error, info};
-use warhorse_client::{warhorse_protocol::*, WarhorseClient, WarhorseEvent};
+use tracing::{error, info, warn};
+use warhorse_client::{warhorse_protocol::*, ConnectionState, WarhorseClient, WarhorseEvent};
 
 const FAVICON: Asset = asset!("/assets/favicon.ico");
 const MAIN_CSS: Asset = asset!("/assets/main.css");
@@ -17,7 +17,7 @@ pub fn main() {
     std::env::set_var("RUST_BACKTRACE", "full");
 
     // Initialize client before Dioxus starts
-    match WarhorseClient::new("http://localhost:3000") {
+    match WarhorseClient::new(Language::English, "http://localhost:3000") {
         Ok(client) => {
             info!("Warhorse client initialized");
             start_dioxus(client);
@@ -58,9 +58,11 @@ pub enum InteractiveState {
     UnblockFriendModal(Friend),
     AcceptFriendRequestModal(Friend),
     RejectFriendRequestModal(Friend),
-    FriendContextMenu(String),
+    FriendContextMenu(UserId),
 }
 
+pub struct ConnectionStatus(pub ConnectionState);
+
 pub struct ReceivedHello(pub bool);
 
 pub struct ReceivedLoggedIn(pub bool);
@@ -69,6 +71,8 @@ pub struct FriendsList(pub HashMap<FriendStatus, Vec<Friend>>);
 
 pub struct ChatMessages(pub Vec<ChatMessage>);
 
+pub struct SearchResults(pub Vec<UserPartial>);
+
 #[derive(Clone, PartialEq)]
 pub struct Notification {
     pub message: String,
@@ -91,17 +95,21 @@ pub fn App() -> Element {
 
     let mut notifications = use_signal(|| Notifications(Vec::new()));
 
+    let mut connection_status = use_signal(|| ConnectionStatus(ConnectionState::Connecting));
     let mut received_hello = use_signal(|| ReceivedHello(false));
     let mut received_logged_in = use_signal(|| ReceivedLoggedIn(false));
     let mut friends_list = use_signal(|| FriendsList(HashMap::new()));
     let mut chat_messages = use_signal(|| ChatMessages(vec![]));
+    let mut search_results = use_signal(|| SearchResults(vec![]));
     let interactive_state = use_signal(|| InteractiveState::Nothing);
 
     provide_context(wh.clone());
+    provide_context(connection_status);
     provide_context(received_hello);
     provide_context(received_logged_in);
     provide_context(friends_list);
     provide_context(chat_messages);
+    provide_context(search_results);
     provide_context(interactive_state);
     provide_context(notifications);
 
@@ -113,7 +121,15 @@ pub fn App() -> Element {
             loop {
                 interval.tick().await;
 
-                let events = wh_cloned.lock().unwrap().pump();
+                let (state, events) = {
+                    let wh = wh_cloned.lock().unwrap();
+                    (wh.connection_state(), wh.pump())
+                };
+
+                if connection_status.read().0 != state {
+                    connection_status.write().0 = state;
+                }
+
                 for event in events {
                     match event {
                         WarhorseEvent::Hello => {
@@ -129,13 +145,36 @@ pub fn App() -> Element {
                                 notification_type: NotificationType::Generic,
                             });
                         }
-                        WarhorseEvent::Error(error) => {
-                            info!("Received Error event: {:?}", error);
+                        WarhorseEvent::Error { code, message } => {
+                            info!("Received Error event: {:?} {:?}", code, message);
                         }
                         WarhorseEvent::FriendsList(friends) => {
                             info!("Received FriendsList event");
                             friends_list.write().0 = categorize_friends(friends);
                         }
+                        WarhorseEvent::FriendsPage { offset, total, friends } => {
+                            info!("Received FriendsPage event");
+                            let received = offset as usize + friends.len();
+                            {
+                                let mut list = friends_list.write();
+                                if offset == 0 {
+                                    list.0.clear();
+                                }
+                                for (status, mut page) in categorize_friends(friends) {
+                                    list.0.entry(status).or_default().append(&mut page);
+                                }
+                            }
+                            // keep asking until we have the whole list
+                            if received < total as usize {
+                                if let Err(e) = wh_cloned.lock().unwrap().request_friends_page(received as u32, 100) {
+                                    error!("Failed to request friends page: {:?}", e);
+                                }
+                            }
+                        }
+                        WarhorseEvent::BlockedList(blocked_users) => {
+                            info!("Received BlockedList event");
+                            friends_list.write().0.insert(FriendStatus::Blocked, blocked_users);
+                        }
                         WarhorseEvent::FriendRequestReceived(friend) => {
                             info!("Received FriendRequestReceived event");
                             notifications.write().0.push(Notification {
@@ -162,6 +201,137 @@ pub fn App() -> Element {
                             info!("Received ChatMessage event");
                             chat_messages.write().0.push(message);
                         }
+                        WarhorseEvent::ChatHistory(history) => {
+                            info!("Received ChatHistory event");
+                            // history is older than anything we've already got
+                            chat_messages.write().0.splice(0..0, history);
+                        }
+                        WarhorseEvent::Typing { .. } => {
+                            // typing indicators aren't shown yet
+                        }
+                        WarhorseEvent::SearchResults(users) => {
+                            info!("Received SearchResults event");
+                            search_results.write().0 = users;
+                        }
+                        WarhorseEvent::Profile(profile) => {
+                            info!("Received profile for {}", profile.display_name);
+                        }
+                        WarhorseEvent::RoomJoined(room_id) => {
+                            info!("Joined room {}", room_id);
+                        }
+                        WarhorseEvent::RoomLeft(room_id) => {
+                            info!("Left room {}", room_id);
+                        }
+                        WarhorseEvent::PartyInviteReceived { party_id, friend } => {
+                            info!("Received PartyInviteReceived event for {}", party_id);
+                            notifications.write().0.push(Notification {
+                                message: format!(
+                                    "{} has invited you to their party",
+                                    friend.display_name
+                                ),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::PartyUpdate { party_id, members } => {
+                            info!("Party {} now has {} members", party_id, members.len());
+                        }
+                        WarhorseEvent::Reconnecting { attempt } => {
+                            info!("Received Reconnecting event");
+                            notifications.write().0.push(Notification {
+                                message: format!(
+                                    "Connection lost, reconnecting (attempt {})",
+                                    attempt
+                                ),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::Reconnected => {
+                            info!("Received Reconnected event");
+                            notifications.write().0.push(Notification {
+                                message: "Reconnected to Warhorse".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::ConnectionLost => {
+                            // a Reconnecting notification follows right after
+                            warn!("The server went silent, reconnecting");
+                        }
+                        WarhorseEvent::HandshakeTimeout => {
+                            warn!("The server never said hello, retrying");
+                            notifications.write().0.push(Notification {
+                                message: "Can't reach the server, retrying".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::EventsDropped { count } => {
+                            warn!("Dropped {} events that were never pumped", count);
+                        }
+                        WarhorseEvent::VersionMismatch { server, client } => {
+                            error!("Server protocol version {} doesn't match ours ({})", server, client);
+                            notifications.write().0.push(Notification {
+                                message: "This version is out of date, please update to keep playing online".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::AccountDeleted => {
+                            info!("Received AccountDeleted event");
+                            received_logged_in.write().0 = false;
+                            notifications.write().0.push(Notification {
+                                message: "Your account has been deleted".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::ServerShutdown { reason } => {
+                            info!("Received ServerShutdown event: {:?}", reason);
+                            notifications.write().0.push(Notification {
+                                message: reason.unwrap_or_else(|| "The server is shutting down".to_string()),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::SendAck { request_id, result } => {
+                            if let Err(e) = result {
+                                warn!("Chat message {} was not sent: {:?}", request_id, e);
+                                notifications.write().0.push(Notification {
+                                    message: e.message,
+                                    timestamp: Instant::now(),
+                                    notification_type: NotificationType::Generic,
+                                });
+                            }
+                        }
+                        WarhorseEvent::LoggedOut => {
+                            info!("Received LoggedOut event");
+                            received_logged_in.write().0 = false;
+                            friends_list.write().0.clear();
+                            chat_messages.write().0.clear();
+                            search_results.write().0.clear();
+                            notifications.write().0.push(Notification {
+                                message: "You have logged out".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::Banned { reason } => {
+                            info!("Received Banned event: {:?}", reason);
+                            received_logged_in.write().0 = false;
+                            notifications.write().0.push(Notification {
+                                message: match reason {
+                                    Some(reason) => format!("You have been banned: {}", reason),
+                                    None => "You have been banned".to_string(),
+                                },
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        event => {
+                            info!("Ignoring {} event", event);
+                        }
                     }
                 }
             }
@@ -235,12 +405,13 @@ fn wh_notification(notification: Notification) -> Element {
 
 #[component]
 fn wh_login() -> Element {
-    let received_hello = use_context::<Signal<ReceivedHello>>();
+    let connection_status = use_context::<Signal<ConnectionStatus>>();
     let wh_cloned = use_context::<Arc<Mutex<WarhorseClient>>>();
     let wh_cloned2 = wh_cloned.clone();
+    let mut registration_error = use_signal(|| None::<String>);
 
     rsx! {
-        if received_hello.read().0 {
+        if connection_status.read().0 == ConnectionState::Connected {
             section { class: "login",
                 h2 { "Login" }
                 form {
@@ -276,15 +447,27 @@ fn wh_login() -> Element {
                     class: "register-form",
                     onsubmit: move |e| {
                         e.prevent_default();
+                        let value = |name: &str| e.values().get(name).unwrap_or(&FormValue(vec![])).as_value();
+                        let account_name = value("account_name");
+                        let password = value("password");
+                        let display_name = value("display_name");
+                        let email = value("email");
+
+                        // catch what we can before the round-trip, the server checks it all again anyway
+                        let validated = validation::validate_account_name(&account_name)
+                            .and_then(|_| validation::validate_display_name(&display_name))
+                            .and_then(|_| validation::validate_email(&email))
+                            .and_then(|_| validation::validate_password(&password));
+                        if let Err(e) = validated {
+                            registration_error.set(Some(e.to_string()));
+                            return;
+                        }
+                        registration_error.set(None);
+
                         if let Err(e) = wh_cloned2
                             .lock()
                             .unwrap()
-                            .send_user_registration_request(
-                                e.values().get("account_name").unwrap_or(&FormValue(vec![])).as_value(),
-                                e.values().get("password").unwrap_or(&FormValue(vec![])).as_value(),
-                                e.values().get("display_name").unwrap_or(&FormValue(vec![])).as_value(),
-                                e.values().get("email").unwrap_or(&FormValue(vec![])).as_value(),
-                            )
+                            .send_user_registration_request(account_name, password, display_name, email)
                         {
                             error!("Failed to send registration request: {:?}", e);
                         }
@@ -305,12 +488,21 @@ fn wh_login() -> Element {
                         name: "password",
                         placeholder: "Password",
                     }
+                    if let Some(error) = registration_error.read().as_ref() {
+                        p { class: "form-error", "{error}" }
+                    }
                     button { r#type: "submit", "Register" }
                 }
             }
         } else {
             section { class: "login",
-                h2 { "Connecting to Warhorse..." }
+                if connection_status.read().0 == ConnectionState::Reconnecting {
+                    h2 { "Reconnecting to Warhorse..." }
+                } else if connection_status.read().0 == ConnectionState::Incompatible {
+                    h2 { "Please update to connect to Warhorse" }
+                } else {
+                    h2 { "Connecting to Warhorse..." }
+                }
             }
         }
     }
@@ -319,6 +511,7 @@ fn wh_login() -> Element {
 #[component]
 fn wh_main() -> Element {
     let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
+    let wh_logout = wh.clone();
     let interactive_state = use_context::<Signal<InteractiveState>>();
     let chat_messages = use_context::<Signal<ChatMessages>>();
 
@@ -326,6 +519,15 @@ fn wh_main() -> Element {
 
     rsx! {
         header {
+            button {
+                class: "secondary logout",
+                onclick: move |_| {
+                    if let Err(e) = wh_logout.lock().unwrap().send_logout_request() {
+                        error!("Failed to log out: {:?}", e);
+                    }
+                },
+                "Log Out"
+            }
             h1 { "Warhorse" }
             p { "A social backend for video games" }
         }
@@ -336,7 +538,9 @@ fn wh_main() -> Element {
                 for message in chat_messages.read().0.iter() {
                     wh_chat_message {
                         display_name: message.display_name.clone(),
-                        time: message.time.to_string(),
+                        time: message.time.to_datetime()
+                            .map(|time| time.with_timezone(&chrono::Local).format("%H:%M").to_string())
+                            .unwrap_or_default(),
                         message: message.message.clone(),
                     }
                 }
@@ -403,38 +607,12 @@ fn wh_sidebar() -> Element {
             div { class: "friends-container",
                 h2 { "Friends" }
                 div { class: "friends",
-                    if let Some(friends) = friends_list.read().0.get(&FriendStatus::FriendRequestReceived) {
-                        wh_friend_category {
-                            status: FriendStatus::FriendRequestReceived,
-                            friends: friends.clone(),
-                        }
-                    }
-
-                    if let Some(friends) = friends_list.read().0.get(&FriendStatus::Online) {
-                        wh_friend_category {
-                            status: FriendStatus::Online,
-                            friends: friends.clone(),
-                        }
-                    }
-
-                    if let Some(friends) = friends_list.read().0.get(&FriendStatus::Offline) {
-                        wh_friend_category {
-                            status: FriendStatus::Offline,
-                            friends: friends.clone(),
-                        }
-                    }
-
-                    if let Some(friends) = friends_list.read().0.get(&FriendStatus::FriendRequestSent) {
-                        wh_friend_category {
-                            status: FriendStatus::FriendRequestSent,
-                            friends: friends.clone(),
-                        }
-                    }
-
-                    if let Some(friends) = friends_list.read().0.get(&FriendStatus::Blocked) {
-                        wh_friend_category {
-                            status: FriendStatus::Blocked,
-                            friends: friends.clone(),
+                    for status in FriendStatus::all_ordered() {
+                        if let Some(friends) = friends_list.read().0.get(&status) {
+                            wh_friend_category {
+                                status,
+                                friends: friends.clone(),
+                            }
                         }
                     }
                 }
@@ -454,6 +632,8 @@ fn wh_sidebar() -> Element {
 fn wh_friend_category(status: FriendStatus, friends: Vec<Friend>) -> Element {
     let status = match status {
         FriendStatus::Online => "Online",
+        FriendStatus::Away => "Away",
+        FriendStatus::Busy => "Busy",
         FriendStatus::Offline => "Offline",
         FriendStatus::FriendRequestSent => "Friend Requests Sent",
         FriendStatus::FriendRequestReceived => "Friend Requests Received",
@@ -470,6 +650,16 @@ fn wh_friend_category(status: FriendStatus, friends: Vec<Friend>) -> Element {
     }
 }
 
+fn last_seen_ago(last_seen: Timestamp) -> String {
+    let seconds = (Timestamp::now().0 - last_seen.0).max(0);
+    match seconds {
+        0..60 => "last seen just now".to_string(),
+        60..3600 => format!("last seen {}m ago", seconds / 60),
+        3600..86400 => format!("last seen {}h ago", seconds / 3600),
+        _ => format!("last seen {}d ago", seconds / 86400),
+    }
+}
+
 #[component]
 fn wh_friend(friend: Friend) -> Element {
     let mut interactive_state = use_context::<Signal<InteractiveState>>();
@@ -482,7 +672,16 @@ fn wh_friend(friend: Friend) -> Element {
                     friend_id.clone(),
                 );
             },
+            if let Some(avatar_url) = &friend.avatar_url {
+                img { class: "friend-avatar", src: "{avatar_url}" }
+            }
             span { class: "friend-name", "{friend.display_name}" }
+            if let Some(activity) = &friend.activity {
+                span { class: "friend-activity", "{activity}" }
+            }
+            if let Some(last_seen) = friend.last_seen {
+                span { class: "friend-last-seen", "{last_seen_ago(last_seen)}" }
+            }
             span { class: "friend-menu", "⋮" }
         }
         if match &*interactive_state.read() {
@@ -506,7 +705,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
     rsx! {
         div { class: "friend-context-menu",
 
-            if friend.status == FriendStatus::Online {
+            if friend.status.can_whisper() {
                 button {
                     onclick: move |e| {
                         e.stop_propagation();
@@ -518,7 +717,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
                 }
             }
 
-            if friend.status != FriendStatus::Blocked {
+            if friend.status.can_block() {
                 button {
                     class: "secondary",
                     onclick: move |e| {
@@ -531,7 +730,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
                 }
             }
 
-            if friend.status == FriendStatus::Blocked {
+            if friend.status.is_blocked() {
                 button {
                     class: "secondary",
                     onclick: move |e| {
@@ -544,7 +743,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
                 }
             }
 
-            if friend.status == FriendStatus::FriendRequestReceived {
+            if friend.status.is_request_received() {
                 button {
                     class: "secondary",
                     onclick: move |e| {
@@ -589,6 +788,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
 fn wh_add_friend_modal() -> Element {
     let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
     let mut interactive_state = use_context::<Signal<InteractiveState>>();
+    let mut search_results = use_context::<Signal<SearchResults>>();
     rsx! {
         div { class: "modal",
             div { class: "modal-content",
@@ -597,29 +797,36 @@ fn wh_add_friend_modal() -> Element {
                     class: "add-friend-form",
                     onsubmit: move |e| {
                         e.prevent_default();
-                        *interactive_state.write() = InteractiveState::Nothing;
                         if let Err(e) = wh
                             .lock()
                             .unwrap()
-                            .send_friend_request(
-                                e.values().get("friend_id").unwrap_or(&FormValue(vec![])).as_value(),
+                            .send_user_search(
+                                e.values().get("query").unwrap_or(&FormValue(vec![])).as_value(),
                             )
                         {
-                            error!("Failed to send friend request: {:?}", e);
+                            error!("Failed to search for users: {:?}", e);
                         }
                     },
                     input {
                         r#type: "text",
-                        name: "friend_id",
-                        placeholder: "Friend ID",
+                        name: "query",
+                        placeholder: "Display name",
+                    }
+                    button { r#type: "submit", "Search" }
+                }
+                div { class: "search-results",
+                    for user in search_results.read().0.iter() {
+                        wh_search_result { user: user.clone() }
                     }
-                    button { r#type: "submit", "Request" }
                 }
             }
             div { class: "modal-buttons",
                 button {
                     class: "secondary",
-                    onclick: move |_| *interactive_state.write() = InteractiveState::Nothing,
+                    onclick: move |_| {
+                        search_results.write().0.clear();
+                        *interactive_state.write() = InteractiveState::Nothing;
+                    },
                     "Close"
                 }
             }
@@ -627,6 +834,29 @@ fn wh_add_friend_modal() -> Element {
     }
 }
 
+#[component]
+fn wh_search_result(user: UserPartial) -> Element {
+    let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
+    let mut interactive_state = use_context::<Signal<InteractiveState>>();
+    let mut search_results = use_context::<Signal<SearchResults>>();
+    rsx! {
+        div { class: "search-result",
+            span { "{user.display_name}" }
+            button {
+                class: "secondary",
+                onclick: move |_| {
+                    if let Err(e) = wh.lock().unwrap().send_friend_request(user.id.clone()) {
+                        error!("Failed to send friend request: {:?}", e);
+                    }
+                    search_results.write().0.clear();
+                    *interactive_state.write() = InteractiveState::Nothing;
+                },
+                "Request"
+            }
+        }
+    }
+}
+
 #[component]
 fn wh_block_friend_modal(friend: Friend) -> Element {
     let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use warhorse_protocol::Language;
+
+use crate::error::ClientError;
+use crate::transport::{SocketIoTransportType, Transport};
+use crate::WarhorseClient;
+
+/// Tunables for a `WarhorseClient`.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Delay before the first reconnection attempt, doubled on every failed attempt.
+    pub reconnect_base_delay: Duration,
+    /// Upper bound for the delay between reconnection attempts.
+    pub reconnect_max_delay: Duration,
+    /// How many received events are held until the next `pump`.
+    /// Past this, older friends lists are coalesced and then the oldest events are dropped.
+    pub max_queue_len: usize,
+    /// How many recent chat message IDs are remembered to drop messages we've already received.
+    pub dedup_window: usize,
+    /// How long the server may go without sending anything before the connection is considered lost.
+    /// Should comfortably exceed the server's heartbeat interval.
+    pub heartbeat_timeout: Duration,
+    /// How long the server has to say hello after we connect, before we give up and try again.
+    pub handshake_timeout: Duration,
+    /// The socket.io namespace to connect to, must match the server's.
+    pub namespace: String,
+    /// Whether socket.io uses websockets, long-polling or starts with one and upgrades to the other.
+    /// Ignored by transports other than socket.io.
+    pub transport_type: SocketIoTransportType,
+    /// Asks the server to compress large payloads like long friends lists, if it says it can.
+    /// Saves bandwidth at the cost of some CPU on both ends, so it's off by default.
+    pub compression: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            reconnect_base_delay: Duration::from_millis(500),
+            reconnect_max_delay: Duration::from_secs(30),
+            max_queue_len: 1024,
+            dedup_window: 256,
+            heartbeat_timeout: Duration::from_secs(45),
+            handshake_timeout: Duration::from_secs(10),
+            namespace: "/".to_string(),
+            transport_type: SocketIoTransportType::default(),
+            compression: false,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Gets the delay to wait before a reconnection attempt, starting at 1.
+    pub fn reconnect_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        self.reconnect_base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.reconnect_max_delay)
+    }
+}
+
+/// Builds a `WarhorseClient` from a `ClientConfig`, see `WarhorseClient::builder`.
+pub struct WarhorseClientBuilder {
+    connection_string: String,
+    language: Language,
+    config: ClientConfig,
+}
+
+impl WarhorseClientBuilder {
+    pub(crate) fn new(connection_string: &str) -> Self {
+        Self {
+            connection_string: connection_string.to_string(),
+            language: Language::English,
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// Replaces every setting at once.
+    pub fn config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.config.namespace = namespace.into();
+        self
+    }
+
+    pub fn transport_type(mut self, transport_type: SocketIoTransportType) -> Self {
+        self.config.transport_type = transport_type;
+        self
+    }
+
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.config.compression = compression;
+        self
+    }
+
+    /// Sets the delay before the first reconnection attempt and the longest it backs off to.
+    pub fn reconnect_backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.config.reconnect_base_delay = base_delay;
+        self.config.reconnect_max_delay = max_delay;
+        self
+    }
+
+    pub fn max_queue_len(mut self, max_queue_len: usize) -> Self {
+        self.config.max_queue_len = max_queue_len;
+        self
+    }
+
+    pub fn heartbeat_timeout(mut self, heartbeat_timeout: Duration) -> Self {
+        self.config.heartbeat_timeout = heartbeat_timeout;
+        self
+    }
+
+    pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.config.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    pub fn connect(self) -> Result<WarhorseClient, ClientError> {
+        WarhorseClient::new_with_config(self.language, &self.connection_string, self.config)
+    }
+
+    /// Connects through `transport` rather than socket.io, ignoring the connection string and namespace.
+    pub fn connect_with_transport(self, transport: impl Transport) -> Result<WarhorseClient, ClientError> {
+        WarhorseClient::new_with_transport(self.language, transport, self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+
+    #[test]
+    fn test_reconnect_delay_backs_off_exponentially() {
+        let config = ClientConfig {
+            reconnect_base_delay: Duration::from_millis(100),
+            reconnect_max_delay: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        assert_eq!(config.reconnect_delay(1), Duration::from_millis(100));
+        assert_eq!(config.reconnect_delay(2), Duration::from_millis(200));
+        assert_eq!(config.reconnect_delay(3), Duration::from_millis(400));
+        assert_eq!(config.reconnect_delay(4), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_reconnect_delay_is_capped() {
+        let config = ClientConfig {
+            reconnect_base_delay: Duration::from_millis(100),
+            reconnect_max_delay: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        assert_eq!(config.reconnect_delay(5), Duration::from_secs(1));
+        assert_eq!(config.reconnect_delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_builder_stores_its_options() {
+        let client = WarhorseClient::builder("http://localhost:3000")
+            .language(Language::Spanish)
+            .namespace("/chat")
+            .reconnect_backoff(Duration::from_millis(100), Duration::from_secs(5))
+            .max_queue_len(16)
+            .heartbeat_timeout(Duration::from_secs(90))
+            .connect_with_transport(MockTransport::new())
+            .unwrap();
+
+        assert_eq!(client.language(), Language::Spanish);
+        let config = client.config();
+        assert_eq!(config.namespace, "/chat");
+        assert_eq!(config.reconnect_base_delay, Duration::from_millis(100));
+        assert_eq!(config.reconnect_max_delay, Duration::from_secs(5));
+        assert_eq!(config.max_queue_len, 16);
+        assert_eq!(config.heartbeat_timeout, Duration::from_secs(90));
+        assert_eq!(config.transport_type, SocketIoTransportType::WebsocketUpgrade);
+    }
+
+    #[test]
+    fn test_builder_can_pin_the_transport_to_websocket() {
+        let client = WarhorseClient::builder("http://localhost:3000")
+            .transport_type(SocketIoTransportType::Websocket)
+            .connect_with_transport(MockTransport::new())
+            .unwrap();
+
+        assert_eq!(client.config().transport_type, SocketIoTransportType::Websocket);
+    }
+}
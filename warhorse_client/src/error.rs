@@ -1,42 +1,82 @@
 use std::fmt::Display;
 use tracing::subscriber::SetGlobalDefaultError;
 use warhorse_protocol::error::Error;
+use warhorse_protocol::validation::ValidationError;
 
 #[derive(Debug)]
-pub struct ClientError(pub String);
+pub enum ClientError {
+    /// Couldn't reach the server, with what the transport said about it.
+    Connect(String),
+    /// The transport wouldn't send a message.
+    Emit(String),
+    /// The transport wouldn't close the connection.
+    Disconnect(String),
+    /// A request couldn't be turned into JSON.
+    Serialize(Error),
+    /// A request the server would only reject, so it was never sent.
+    Invalid(ValidationError),
+    /// The client is shutting down and nothing more can be queued, with what we were trying to send.
+    QueueClosed(&'static str),
+    /// Anything else, as it was described.
+    Other(String),
+}
 
 impl Display for ClientError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            ClientError::Connect(e) => write!(f, "Failed to connect: {}", e),
+            ClientError::Emit(e) => write!(f, "Failed to emit: {}", e),
+            ClientError::Disconnect(e) => write!(f, "Failed to disconnect: {}", e),
+            ClientError::Serialize(e) => write!(f, "{}", e),
+            ClientError::Invalid(e) => write!(f, "{}", e),
+            ClientError::QueueClosed(what) => write!(f, "Failed to queue {}: the client has shut down", what),
+            ClientError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Serialize(e) => Some(e),
+            ClientError::Invalid(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
 impl From<Error> for ClientError {
     fn from(e: Error) -> Self {
-        ClientError(e.0)
+        ClientError::Serialize(e)
+    }
+}
+
+impl From<ValidationError> for ClientError {
+    fn from(e: ValidationError) -> Self {
+        ClientError::Invalid(e)
     }
 }
 
 impl From<SetGlobalDefaultError> for ClientError {
     fn from(e: SetGlobalDefaultError) -> Self {
-        ClientError(e.to_string())
+        ClientError::Other(e.to_string())
     }
 }
 
 impl From<Box<dyn std::error::Error>> for ClientError {
     fn from(e: Box<dyn std::error::Error>) -> Self {
-        ClientError(e.to_string())
+        ClientError::Other(e.to_string())
     }
 }
 
 impl From<String> for ClientError {
     fn from(e: String) -> Self {
-        ClientError(e)
+        ClientError::Other(e)
     }
 }
 
 impl From<&str> for ClientError {
     fn from(e: &str) -> Self {
-        ClientError(e.to_string())
+        ClientError::Other(e.to_string())
     }
-}
\ No newline at end of file
+}
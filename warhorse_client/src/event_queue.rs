@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::WarhorseEvent;
+
+pub(crate) type EventCallback = Arc<dyn Fn(&WarhorseEvent) + Send + Sync + 'static>;
+
+/// Events we've received but haven't been pumped yet.
+/// It's bounded so a consumer that stops pumping (e.g. during a load screen) can't grow it forever.
+/// Every event is also handed to the registered callbacks as it arrives.
+pub(crate) struct EventQueue {
+    capacity: usize,
+    inner: RwLock<EventQueueInner>,
+    callbacks: Mutex<Vec<EventCallback>>,
+}
+
+struct EventQueueInner {
+    events: VecDeque<WarhorseEvent>,
+    // how many events were dropped since the last drain
+    dropped: usize,
+}
+
+impl EventQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: RwLock::new(EventQueueInner {
+                events: VecDeque::new(),
+                dropped: 0,
+            }),
+            callbacks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn subscribe(&self, callback: EventCallback) {
+        if let Ok(mut callbacks) = self.callbacks.lock() {
+            callbacks.push(callback);
+        }
+    }
+
+    /// Hands an event to the callbacks and queues it for the next drain.
+    /// The queue makes room by coalescing friends lists and then dropping the oldest events.
+    pub(crate) fn push(&self, event: WarhorseEvent) {
+        // copied out so a callback can subscribe another one without deadlocking
+        let callbacks = match self.callbacks.lock() {
+            Ok(callbacks) => callbacks.clone(),
+            Err(_) => Vec::new(),
+        };
+        for callback in callbacks.iter() {
+            callback(&event);
+        }
+
+        if let Ok(mut inner) = self.inner.write() {
+            inner.events.push_back(event);
+
+            if inner.events.len() > self.capacity {
+                // a friends list is the full list, so only the latest one matters
+                let latest_friends_list = inner.events.iter()
+                    .rposition(|e| matches!(e, WarhorseEvent::FriendsList(_)));
+                if let Some(latest) = latest_friends_list {
+                    let mut index = 0;
+                    inner.events.retain(|e| {
+                        let keep = index >= latest || !matches!(e, WarhorseEvent::FriendsList(_));
+                        index += 1;
+                        keep
+                    });
+                }
+            }
+
+            while inner.events.len() > self.capacity {
+                inner.events.pop_front();
+                inner.dropped += 1;
+            }
+        }
+    }
+
+    /// Takes every queued event, led by an `EventsDropped` if we had to throw any away.
+    pub(crate) fn drain(&self) -> Vec<WarhorseEvent> {
+        let mut events = Vec::new();
+        if let Ok(mut inner) = self.inner.write() {
+            if inner.dropped > 0 {
+                events.push(WarhorseEvent::EventsDropped { count: inner.dropped });
+                inner.dropped = 0;
+            }
+            events.extend(inner.events.drain(..));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warhorse_protocol::{ChatChannel, ChatMessage, RoomId, Timestamp, Uuid};
+
+    fn chat_message(message: &str) -> WarhorseEvent {
+        WarhorseEvent::ChatMessage(ChatMessage {
+            id: Uuid::new_v4(),
+            display_name: "Test User".to_string(),
+            channel: ChatChannel::Room(RoomId::from("general")),
+            message: message.to_string(),
+            time: Timestamp(0),
+        })
+    }
+
+    fn message_text(event: &WarhorseEvent) -> &str {
+        match event {
+            WarhorseEvent::ChatMessage(message) => &message.message,
+            _ => panic!("expected a ChatMessage event"),
+        }
+    }
+
+    #[test]
+    fn test_flooding_past_capacity_keeps_newest_events() {
+        let queue = EventQueue::new(3);
+        for i in 0..5 {
+            queue.push(chat_message(&i.to_string()));
+        }
+
+        let events = queue.drain();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0], WarhorseEvent::EventsDropped { count: 2 }));
+        assert_eq!(message_text(&events[1]), "2");
+        assert_eq!(message_text(&events[2]), "3");
+        assert_eq!(message_text(&events[3]), "4");
+    }
+
+    #[test]
+    fn test_friends_lists_are_coalesced_before_dropping() {
+        let queue = EventQueue::new(2);
+        queue.push(WarhorseEvent::FriendsList(vec![]));
+        queue.push(chat_message("0"));
+        queue.push(WarhorseEvent::FriendsList(vec![]));
+
+        let events = queue.drain();
+        assert_eq!(events.len(), 2);
+        assert_eq!(message_text(&events[0]), "0");
+        assert!(matches!(events[1], WarhorseEvent::FriendsList(_)));
+    }
+
+    #[test]
+    fn test_callbacks_can_subscribe_from_a_callback() {
+        let queue = Arc::new(EventQueue::new(4));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        queue.subscribe(Arc::new({
+            let queue = Arc::downgrade(&queue);
+            move |_| {
+                if let Some(queue) = queue.upgrade() {
+                    let sender = sender.clone();
+                    queue.subscribe(Arc::new(move |event| {
+                        let _ = sender.send(message_text(event).to_string());
+                    }));
+                }
+            }
+        }));
+
+        queue.push(chat_message("0"));
+        queue.push(chat_message("1"));
+        assert_eq!(receiver.try_recv().unwrap(), "1");
+    }
+
+    #[test]
+    fn test_drain_resets_dropped_count() {
+        let queue = EventQueue::new(1);
+        queue.push(chat_message("0"));
+        queue.push(chat_message("1"));
+        assert_eq!(queue.drain().len(), 2);
+
+        queue.push(chat_message("2"));
+        let events = queue.drain();
+        assert_eq!(events.len(), 1);
+        assert_eq!(message_text(&events[0]), "2");
+    }
+}
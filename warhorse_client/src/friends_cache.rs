@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use warhorse_protocol::{categorize_friends, Friend, FriendStatus, UserId};
+
+/// Everyone in our friends and blocked lists as of the latest events, so UIs don't have to track them.
+#[derive(Default)]
+pub(crate) struct FriendsCache {
+    friends: Mutex<HashMap<UserId, Friend>>,
+}
+
+impl FriendsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts over from a full friends list, which has pending requests and blocked users in it too.
+    pub fn set_friends(&self, friends: &[Friend]) {
+        let mut cached = self.friends.lock().unwrap();
+        cached.clear();
+        cached.extend(friends.iter().map(|friend| (friend.id.clone(), friend.clone())));
+    }
+
+    /// Adds a page of the friends list, the first page starts over.
+    pub fn add_page(&self, offset: u32, friends: &[Friend]) {
+        if offset == 0 {
+            self.set_friends(friends);
+        } else {
+            friends.iter().for_each(|friend| self.update(friend));
+        }
+    }
+
+    /// Replaces just the blocked users.
+    pub fn set_blocked(&self, blocked: &[Friend]) {
+        let mut cached = self.friends.lock().unwrap();
+        cached.retain(|_, friend| friend.status != FriendStatus::Blocked);
+        cached.extend(blocked.iter().map(|user| (user.id.clone(), Friend { status: FriendStatus::Blocked, ..user.clone() })));
+    }
+
+    /// Replaces just the friend requests waiting for us.
+    pub fn set_received_requests(&self, friend_requests: &[Friend]) {
+        let mut cached = self.friends.lock().unwrap();
+        cached.retain(|_, friend| friend.status != FriendStatus::FriendRequestReceived);
+        cached.extend(friend_requests.iter().map(|user| {
+            (user.id.clone(), Friend { status: FriendStatus::FriendRequestReceived, ..user.clone() })
+        }));
+    }
+
+    pub fn update(&self, friend: &Friend) {
+        self.friends.lock().unwrap().insert(friend.id.clone(), friend.clone());
+    }
+
+    pub fn status(&self, user_id: &str) -> Option<FriendStatus> {
+        self.friends.lock().unwrap().get(user_id).map(|friend| friend.status)
+    }
+
+    /// Everyone, grouped and sorted the same way as `categorize_friends`.
+    pub fn by_status(&self) -> HashMap<FriendStatus, Vec<Friend>> {
+        categorize_friends(self.friends.lock().unwrap().values().cloned().collect())
+    }
+
+    pub fn clear(&self) {
+        self.friends.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn friend(id: &str, status: FriendStatus) -> Friend {
+        Friend {
+            id: UserId::from(id),
+            display_name: id.to_string(),
+            status,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        }
+    }
+
+    #[test]
+    fn test_blocked_list_only_replaces_blocked_users() {
+        let cache = FriendsCache::new();
+        cache.set_friends(&[friend("1", FriendStatus::Online), friend("2", FriendStatus::Blocked)]);
+
+        cache.set_blocked(&[friend("3", FriendStatus::Blocked)]);
+
+        assert_eq!(cache.status("1"), Some(FriendStatus::Online));
+        assert_eq!(cache.status("2"), None);
+        assert_eq!(cache.status("3"), Some(FriendStatus::Blocked));
+    }
+
+    #[test]
+    fn test_later_pages_add_to_the_first() {
+        let cache = FriendsCache::new();
+        cache.set_friends(&[friend("old", FriendStatus::Online)]);
+
+        cache.add_page(0, &[friend("1", FriendStatus::Online)]);
+        cache.add_page(1, &[friend("2", FriendStatus::Offline)]);
+
+        assert_eq!(cache.status("old"), None);
+        assert_eq!(cache.status("1"), Some(FriendStatus::Online));
+        assert_eq!(cache.status("2"), Some(FriendStatus::Offline));
+    }
+
+    #[test]
+    fn test_by_status_groups_and_sorts() {
+        let cache = FriendsCache::new();
+        cache.set_friends(&[friend("b", FriendStatus::Online), friend("a", FriendStatus::Online), friend("c", FriendStatus::Offline)]);
+
+        let grouped = cache.by_status();
+
+        let ids = |status| grouped[&status].iter().map(|friend| friend.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids(FriendStatus::Online), vec!["a", "b"]);
+        assert_eq!(ids(FriendStatus::Offline), vec!["c"]);
+        assert!(!grouped.contains_key(&FriendStatus::Blocked));
+    }
+
+    #[test]
+    fn test_by_status_ignores_case_and_breaks_ties_by_id() {
+        let cache = FriendsCache::new();
+        let named = |id, display_name: &str| Friend { display_name: display_name.to_string(), ..friend(id, FriendStatus::Online) };
+        cache.set_friends(&[named("3", "bob"), named("2", "Bob"), named("1", "alice"), named("4", "Carol")]);
+
+        let ids = cache.by_status()[&FriendStatus::Online].iter().map(|friend| friend.id.to_string()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["1", "2", "3", "4"]);
+    }
+}
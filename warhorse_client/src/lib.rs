@@ -1,228 +1,441 @@
+pub mod config;
 pub mod error;
+mod event_queue;
+mod message_dedup;
+mod friends_cache;
+pub mod transport;
+mod watchdog;
 
-use rust_socketio::{ClientBuilder, Payload};
-use std::collections::VecDeque;
-use std::sync::{Arc, RwLock};
-use tracing::error;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
+use crate::config::{ClientConfig, WarhorseClientBuilder};
 use crate::error::ClientError;
+use crate::event_queue::EventQueue;
+use crate::friends_cache::FriendsCache;
+use crate::message_dedup::MessageDedup;
+use crate::transport::{Connection, Payload, SocketIoTransport, Transport};
+use crate::watchdog::{HandshakeTimer, Watchdog};
 use warhorse_protocol::*;
+use warhorse_protocol::compression::decompress;
 
 // re-exports
 pub use warhorse_protocol;
 
-#[derive(Clone)]
+// how often the background thread wakes up to check for a dropped connection
+const DISCONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// the most often `notify_activity` tells the server we're here, it only needs to beat the server's idle timeout
+const ACTIVITY_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A message queued to send, with the id to report the server's acknowledgement under if it wants one.
+type Outgoing = (String, serde_json::Value, Option<RequestId>);
+
+/// Everything the client reports back through `pump` and `on_event`.
+///
+/// New variants are added as the server learns new tricks, so this is `#[non_exhaustive]`:
+/// matches outside this crate need a wildcard arm, e.g. `other => info!("Ignoring {} event", other)`.
+/// Code that matched every variant before only has to add that arm to keep compiling across upgrades.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
 pub enum WarhorseEvent {
     Hello,
     LoggedIn,
-    Error(String),
+    /// We logged out, the connection stays open for logging in again.
+    LoggedOut,
+    /// Who we're logged in as, arrives right before `LoggedIn`. Also kept for `WarhorseClient::current_user`.
+    CurrentUser(UserPartial),
+    /// A request failed, `message` is in the client's language.
+    Error { code: ErrorCode, message: String },
     FriendsList(Vec<Friend>),
+    /// A page of the friends list, sent instead of `FriendsList` when it's long.
+    FriendsPage { offset: u32, total: u32, friends: Vec<Friend> },
+    BlockedList(Vec<Friend>),
+    /// Everyone with a friend request waiting for us, arrives after logging in.
+    FriendRequests(Vec<Friend>),
+    /// A friend request that just arrived.
     FriendRequestReceived(Friend),
     FriendRequestAccepted(Friend),
     ChatMessage(ChatMessage),
+    ChatHistory(Vec<ChatMessage>),
+    Typing { display_name: String, channel: ChatChannel, is_typing: bool },
+    SearchResults(Vec<UserPartial>),
+    Profile(PublicProfile),
+    RoomJoined(RoomId),
+    RoomLeft(RoomId),
+    /// Who's in a room, the answer to `WarhorseClient::request_room_members`.
+    RoomMembers { room_id: RoomId, members: Vec<Friend> },
+    PartyInviteReceived { party_id: PartyId, friend: Friend },
+    PartyUpdate { party_id: PartyId, members: Vec<Friend> },
+    Reconnecting { attempt: u32 },
+    Reconnected,
+    /// The server went silent for longer than `ClientConfig::heartbeat_timeout`, reconnecting starts.
+    ConnectionLost,
+    /// The server never said hello within `ClientConfig::handshake_timeout`, so it probably can't be reached.
+    /// Reconnecting starts right after.
+    HandshakeTimeout,
+    EventsDropped { count: usize },
+    /// The server speaks a different protocol version, nothing more will be sent to it.
+    VersionMismatch { server: u32, client: u32 },
+    /// Our account was deleted, the server disconnects us right after.
+    AccountDeleted,
+    /// The server is going down, the connection drops right after and reconnecting starts.
+    ServerShutdown { reason: Option<String> },
+    /// An admin banned us, or we tried to log in while banned. The server disconnects us right after a ban.
+    Banned { reason: Option<String> },
+    /// The server's answer to a chat message, `request_id` is what the send returned.
+    SendAck { request_id: RequestId, result: Result<(), RequestError> },
+}
+
+impl WarhorseEvent {
+    /// Which variant this is, without the data.
+    pub fn kind(&self) -> WarhorseEventKind {
+        match self {
+            WarhorseEvent::Hello => WarhorseEventKind::Hello,
+            WarhorseEvent::LoggedIn => WarhorseEventKind::LoggedIn,
+            WarhorseEvent::LoggedOut => WarhorseEventKind::LoggedOut,
+            WarhorseEvent::CurrentUser(_) => WarhorseEventKind::CurrentUser,
+            WarhorseEvent::Error { .. } => WarhorseEventKind::Error,
+            WarhorseEvent::FriendsList(_) => WarhorseEventKind::FriendsList,
+            WarhorseEvent::FriendsPage { .. } => WarhorseEventKind::FriendsPage,
+            WarhorseEvent::BlockedList(_) => WarhorseEventKind::BlockedList,
+            WarhorseEvent::FriendRequests(_) => WarhorseEventKind::FriendRequests,
+            WarhorseEvent::FriendRequestReceived(_) => WarhorseEventKind::FriendRequestReceived,
+            WarhorseEvent::FriendRequestAccepted(_) => WarhorseEventKind::FriendRequestAccepted,
+            WarhorseEvent::ChatMessage(_) => WarhorseEventKind::ChatMessage,
+            WarhorseEvent::ChatHistory(_) => WarhorseEventKind::ChatHistory,
+            WarhorseEvent::Typing { .. } => WarhorseEventKind::Typing,
+            WarhorseEvent::SearchResults(_) => WarhorseEventKind::SearchResults,
+            WarhorseEvent::Profile(_) => WarhorseEventKind::Profile,
+            WarhorseEvent::RoomJoined(_) => WarhorseEventKind::RoomJoined,
+            WarhorseEvent::RoomLeft(_) => WarhorseEventKind::RoomLeft,
+            WarhorseEvent::RoomMembers { .. } => WarhorseEventKind::RoomMembers,
+            WarhorseEvent::PartyInviteReceived { .. } => WarhorseEventKind::PartyInviteReceived,
+            WarhorseEvent::PartyUpdate { .. } => WarhorseEventKind::PartyUpdate,
+            WarhorseEvent::Reconnecting { .. } => WarhorseEventKind::Reconnecting,
+            WarhorseEvent::Reconnected => WarhorseEventKind::Reconnected,
+            WarhorseEvent::ConnectionLost => WarhorseEventKind::ConnectionLost,
+            WarhorseEvent::HandshakeTimeout => WarhorseEventKind::HandshakeTimeout,
+            WarhorseEvent::EventsDropped { .. } => WarhorseEventKind::EventsDropped,
+            WarhorseEvent::VersionMismatch { .. } => WarhorseEventKind::VersionMismatch,
+            WarhorseEvent::AccountDeleted => WarhorseEventKind::AccountDeleted,
+            WarhorseEvent::ServerShutdown { .. } => WarhorseEventKind::ServerShutdown,
+            WarhorseEvent::Banned { .. } => WarhorseEventKind::Banned,
+            WarhorseEvent::SendAck { .. } => WarhorseEventKind::SendAck,
+        }
+    }
+}
+
+impl std::fmt::Display for WarhorseEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+/// The variants of `WarhorseEvent` without their data, for logging and for telling events apart cheaply.
+/// The names `Display` writes never change, so they're safe to log, store or send across the FFI.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarhorseEventKind {
+    Hello,
+    LoggedIn,
+    LoggedOut,
+    CurrentUser,
+    Error,
+    FriendsList,
+    FriendsPage,
+    BlockedList,
+    FriendRequests,
+    FriendRequestReceived,
+    FriendRequestAccepted,
+    ChatMessage,
+    ChatHistory,
+    Typing,
+    SearchResults,
+    Profile,
+    RoomJoined,
+    RoomLeft,
+    RoomMembers,
+    PartyInviteReceived,
+    PartyUpdate,
+    Reconnecting,
+    Reconnected,
+    ConnectionLost,
+    HandshakeTimeout,
+    EventsDropped,
+    VersionMismatch,
+    AccountDeleted,
+    ServerShutdown,
+    Banned,
+    SendAck,
+}
+
+impl std::fmt::Display for WarhorseEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            WarhorseEventKind::Hello => "hello",
+            WarhorseEventKind::LoggedIn => "logged_in",
+            WarhorseEventKind::LoggedOut => "logged_out",
+            WarhorseEventKind::CurrentUser => "current_user",
+            WarhorseEventKind::Error => "error",
+            WarhorseEventKind::FriendsList => "friends_list",
+            WarhorseEventKind::FriendsPage => "friends_page",
+            WarhorseEventKind::BlockedList => "blocked_list",
+            WarhorseEventKind::FriendRequests => "friend_requests",
+            WarhorseEventKind::FriendRequestReceived => "friend_request_received",
+            WarhorseEventKind::FriendRequestAccepted => "friend_request_accepted",
+            WarhorseEventKind::ChatMessage => "chat_message",
+            WarhorseEventKind::ChatHistory => "chat_history",
+            WarhorseEventKind::Typing => "typing",
+            WarhorseEventKind::SearchResults => "search_results",
+            WarhorseEventKind::Profile => "profile",
+            WarhorseEventKind::RoomJoined => "room_joined",
+            WarhorseEventKind::RoomLeft => "room_left",
+            WarhorseEventKind::RoomMembers => "room_members",
+            WarhorseEventKind::PartyInviteReceived => "party_invite_received",
+            WarhorseEventKind::PartyUpdate => "party_update",
+            WarhorseEventKind::Reconnecting => "reconnecting",
+            WarhorseEventKind::Reconnected => "reconnected",
+            WarhorseEventKind::ConnectionLost => "connection_lost",
+            WarhorseEventKind::HandshakeTimeout => "handshake_timeout",
+            WarhorseEventKind::EventsDropped => "events_dropped",
+            WarhorseEventKind::VersionMismatch => "version_mismatch",
+            WarhorseEventKind::AccountDeleted => "account_deleted",
+            WarhorseEventKind::ServerShutdown => "server_shutdown",
+            WarhorseEventKind::Banned => "banned",
+            WarhorseEventKind::SendAck => "send_ack",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The state of the underlying socket.io transport.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+    Reconnecting,
+    /// The server's protocol version doesn't match ours.
+    Incompatible,
+}
+
+impl From<u8> for ConnectionState {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => ConnectionState::Connecting,
+            1 => ConnectionState::Connected,
+            2 => ConnectionState::Disconnected,
+            3 => ConnectionState::Reconnecting,
+            _ => ConnectionState::Incompatible,
+        }
+    }
+}
+
+/// A `ConnectionState` that can be shared between the socket.io callbacks and the client.
+#[derive(Debug)]
+struct AtomicConnectionState(AtomicU8);
+
+impl AtomicConnectionState {
+    fn new(state: ConnectionState) -> Self {
+        Self(AtomicU8::new(state as u8))
+    }
+
+    fn load(&self) -> ConnectionState {
+        self.0.load(Ordering::SeqCst).into()
+    }
+
+    fn store(&self, state: ConnectionState) {
+        self.0.store(state as u8, Ordering::SeqCst);
+    }
+
+    /// Moves to `to` only if we're still in `from`, so a transition made by a callback isn't overwritten.
+    fn transition(&self, from: ConnectionState, to: ConnectionState) -> bool {
+        self.0
+            .compare_exchange(from as u8, to as u8, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
 }
 
 pub struct WarhorseClient {
+    // language sent with every request, used by the server to localize responses
+    language: Language,
+    // what the client was built with
+    config: ClientConfig,
+    // state of the underlying transport, updated from the socket.io callbacks
+    connection_state: Arc<AtomicConnectionState>,
     // events we've received but haven't processed yet
-    pending_receives: Arc<RwLock<VecDeque<WarhorseEvent>>>,
+    pending_receives: Arc<EventQueue>,
     // messages we've queued to send but haven't yet
-    pending_sends: std::sync::mpsc::Sender<(String, serde_json::Value)>,
+    pending_sends: std::sync::mpsc::Sender<Outgoing>,
+    // sends queued messages and reconnects, exits once `pending_sends` is dropped
+    emit_thread: Option<JoinHandle<()>>,
+    // when we last told the server we're at the keyboard
+    last_activity_ping: std::sync::Mutex<Option<Instant>>,
+    // who we're logged in as, set by the handlers
+    current_user: Arc<std::sync::Mutex<Option<UserPartial>>>,
+    // the latest friends and blocked lists, set by the handlers
+    friends_cache: Arc<FriendsCache>,
+    // whether the server's hello offered to compress large payloads, set by the handlers
+    server_compression: Arc<AtomicBool>,
 }
 
 impl WarhorseClient {
-    pub fn new(connection_string: &str) -> Result<Self, ClientError> {
-        let pending_events = Arc::new(RwLock::new(VecDeque::new()));
-        let socket_io = ClientBuilder::new(connection_string)
-            .namespace("/")
-            .on(EVENT_RECEIVE_USER_LOGIN, {
-                let pending_events_clone = pending_events.clone();
-                move |_payload, _socket| {
-                    if let Ok(mut event_queue) = pending_events_clone.write() {
-                        event_queue.push_back(WarhorseEvent::LoggedIn);
-                    }
-                }
-            })
-            .on(EVENT_RECEIVE_HELLO, {
-                let pending_events_clone = pending_events.clone();
-                move |payload, _socket| match payload {
-                    Payload::Text(_) => {
-                        if let Ok(mut event_queue) = pending_events_clone.write() {
-                            event_queue.push_back(WarhorseEvent::Hello);
-                        }
-                    }
-                    _ => {
-                        error!("Unexpected payload: {:?}", payload);
-                    }
-                }
-            })
-            .on(EVENT_RECEIVE_ERROR, {
-                let pending_events_clone = pending_events.clone();
-                move |payload, _socket| match payload {
-                    Payload::Text(text) => {
-                        for line in text {
-                            match RequestError::from_json(line.clone()) {
-                                Ok(e) => {
-                                    if let Ok(mut event_queue) = pending_events_clone.write() {
-                                        event_queue.push_back(WarhorseEvent::Error(e.0));
-                                    }
-                                }
-                                Err(e) => error!("Failed to parse error: {:?}", e),
-                            }
-                        }
-                    }
-                    _ => {
-                        error!("Unexpected payload: {:?}", payload);
-                    }
-                }
-            })
-            .on(EVENT_RECEIVE_FRIENDS, {
-                let pending_events_clone = pending_events.clone();
-                move |payload, _socket| match payload {
-                    Payload::Text(text) => {
-                        if let Some(first) = text.first() {
-                            match json_to_vec::<Friend>(first.clone()) {
-                                Ok(friends) => {
-                                    if let Ok(mut event_queue) = pending_events_clone.write() {
-                                        event_queue.push_back(WarhorseEvent::FriendsList(friends));
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to parse friends list: {:?}", e);
-                                }
-                            }
-                        }
-                    }
-                    _ => {
-                        error!("Unexpected payload: {:?}", payload);
-                    }
-                }
-            })
-            .on(EVENT_RECEIVE_FRIEND_REQUESTS, {
-                let pending_events_clone = pending_events.clone();
-                move |payload, _socket| match payload {
-                    Payload::Text(text) => {
-                        if let Some(first) = text.first() {
-                            match json_to_vec::<Friend>(first.clone()) {
-                                Ok(mut friend_requests) => {
-                                    if let Some(friend_request) = friend_requests.pop() {
-                                        if let Ok(mut event_queue) = pending_events_clone.write() {
-                                            event_queue.push_back(
-                                                WarhorseEvent::FriendRequestReceived(
-                                                    friend_request,
-                                                ),
-                                            );
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to parse friend requests: {:?}", e);
-                                }
-                            }
-                        }
-                    }
-                    _ => {
-                        error!("Unexpected payload: {:?}", payload);
-                    }
-                }
-            })
-            .on(EVENT_RECEIVE_FRIEND_REQUEST_ACCEPTED, {
-                let pending_events_clone = pending_events.clone();
-                move |payload, _socket| match payload {
-                    Payload::Text(text) => {
-                        if let Some(first) = text.first() {
-                            match json_to_vec::<Friend>(first.clone()) {
-                                Ok(mut friends) => {
-                                    if let Some(friend) = friends.pop() {
-                                        if let Ok(mut event_queue) = pending_events_clone.write() {
-                                            event_queue.push_back(
-                                                WarhorseEvent::FriendRequestAccepted(friend),
-                                            );
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to parse friend request accepted: {:?}", e);
-                                }
-                            }
-                        }
-                    }
-                    _ => {
-                        error!("Unexpected payload: {:?}", payload);
-                    }
-                }
-            })
-            .on(EVENT_RECEIVE_CHAT_MESSAGE, {
-                let pending_events_clone = pending_events.clone();
-                move |payload, _socket| match payload {
-                    Payload::Text(text) => {
-                        if let Some(first) = text.first() {
-                            match ChatMessage::from_json(first.clone()) {
-                                Ok(chat_message) => {
-                                    if let Ok(mut event_queue) = pending_events_clone.write() {
-                                        event_queue
-                                            .push_back(WarhorseEvent::ChatMessage(chat_message));
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to parse chat message: {:?}", e);
-                                }
-                            }
-                        }
-                    }
-                    _ => {
-                        error!("Unexpected payload: {:?}", payload);
-                    }
-                }
-            })
-            .connect();
+    pub fn new(language: Language, connection_string: &str) -> Result<Self, ClientError> {
+        Self::builder(connection_string).language(language).connect()
+    }
 
-        if let Err(e) = socket_io {
-            return Err(ClientError(format!("Failed to connect: {:?}", e)));
-        }
+    /// Starts configuring a client, anything left unset keeps its default.
+    pub fn builder(connection_string: &str) -> WarhorseClientBuilder {
+        WarhorseClientBuilder::new(connection_string)
+    }
 
-        let socket_io = Arc::new(socket_io.unwrap());
-        let socket_io_clone = socket_io.clone();
+    pub fn new_with_config(
+        language: Language,
+        connection_string: &str,
+        config: ClientConfig,
+    ) -> Result<Self, ClientError> {
+        Self::new_with_transport(language, SocketIoTransport::new(connection_string, &config.namespace).with_transport_type(config.transport_type), config)
+    }
+
+    /// Connects through `transport` rather than socket.io, e.g. a `transport::MockTransport` in tests.
+    pub fn new_with_transport(
+        language: Language,
+        transport: impl Transport,
+        config: ClientConfig,
+    ) -> Result<Self, ClientError> {
+        let pending_events = Arc::new(EventQueue::new(config.max_queue_len));
+        let connection_state = Arc::new(AtomicConnectionState::new(ConnectionState::Connecting));
+        let current_user = Arc::new(std::sync::Mutex::new(None));
+        let friends_cache = Arc::new(FriendsCache::new());
+        let server_compression = Arc::new(AtomicBool::new(false));
+        let handlers = EventHandlers {
+            pending_events: pending_events.clone(),
+            connection_state: connection_state.clone(),
+            current_user: current_user.clone(),
+            friends_cache: friends_cache.clone(),
+            // kept across reconnects, since that's when messages are most likely to be replayed
+            message_dedup: Arc::new(MessageDedup::new(config.dedup_window)),
+            watchdog: Arc::new(Watchdog::new(config.heartbeat_timeout)),
+            handshake: Arc::new(HandshakeTimer::new(config.handshake_timeout)),
+            server_compression: server_compression.clone(),
+        };
+        // started before connecting so a quick hello can't beat it
+        handlers.handshake.start();
+        let socket_io = transport.connect(handlers.clone())?;
+        connection_state.transition(ConnectionState::Connecting, ConnectionState::Connected);
 
         // Create a channel for sending socket messages
-        let (sender, receiver) = std::sync::mpsc::channel::<(String, serde_json::Value)>();
+        let (sender, receiver) = std::sync::mpsc::channel::<Outgoing>();
 
-        // Start a background thread for handling socket emissions
-        std::thread::spawn(move || {
-            while let Ok((event, json)) = receiver.recv() {
-                match socket_io_clone.emit(event, json) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("Failed to send message: {:?}", e);
-                    }
-                }
-            }
+        // Start a background thread for handling socket emissions and reconnection
+        let pending_events_clone = pending_events.clone();
+        let connection_state_clone = connection_state.clone();
+        let config_clone = config.clone();
+        let emit_thread = std::thread::spawn(move || {
+            emit_loop(socket_io, &receiver, &connection_state_clone, &handlers.watchdog, &handlers.handshake, &pending_events_clone, |backlog| {
+                reconnect(&config_clone, &pending_events_clone, &connection_state_clone, &receiver, backlog, || {
+                    handlers.handshake.start();
+                    transport.connect(handlers.clone())
+                })
+            });
         });
 
         Ok(WarhorseClient {
+            language,
+            config,
+            connection_state,
             pending_receives: pending_events,
             pending_sends: sender,
+            emit_thread: Some(emit_thread),
+            last_activity_ping: std::sync::Mutex::new(None),
+            current_user,
+            friends_cache,
+            server_compression,
         })
     }
 
+    /// Gets the language sent with every request
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Changes the language sent with every request, so the server's error messages come back localized.
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Changes the language sent with every request from here on, e.g. when the player changes it in a menu.
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    /// Gets what the client was built with
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Gets the current state of the connection to the server
+    pub fn connection_state(&self) -> ConnectionState {
+        self.connection_state.load()
+    }
+
+    /// Gets who we're logged in as, `None` until the server has told us
+    pub fn current_user(&self) -> Option<UserPartial> {
+        self.current_user.lock().unwrap().clone()
+    }
+
+    /// Gets where we stand with a user as of the latest friends and blocked lists, `None` for strangers.
+    /// Handy for deciding whether to offer "Add friend" without tracking the events yourself.
+    pub fn relationship(&self, user_id: &str) -> Option<FriendStatus> {
+        self.friends_cache.status(user_id)
+    }
+
+    /// Gets everyone in the latest friends and blocked lists, grouped by status and sorted by display name.
+    /// Kept up to date as events arrive, they still come out of `pump` for anything that wants to react to them.
+    pub fn friends(&self) -> HashMap<FriendStatus, Vec<Friend>> {
+        self.friends_cache.by_status()
+    }
+
+    /// Gets the users with one status from the latest friends and blocked lists, sorted by display name.
+    pub fn friends_by_status(&self, status: FriendStatus) -> Vec<Friend> {
+        self.friends_cache.by_status().remove(&status).unwrap_or_default()
+    }
+
     pub fn send_user_login_request(
         &self,
         username: String,
         password: String,
     ) -> Result<(), ClientError> {
         let user_login = UserLogin {
-            language: Language::English,
+            language: self.language,
             identity: if Self::is_email_as_username(&username) {
                 LoginUserIdentity::Email(username)
             } else {
                 LoginUserIdentity::AccountName(username)
             },
             password,
+            compression: self.config.compression && self.server_compression.load(Ordering::Relaxed),
         };
 
         let json = user_login.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_USER_LOGIN.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue login request: {:?}", e)))
+            .send((EVENT_SEND_USER_LOGIN.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("login request"))
+    }
+
+    /// Logs out but stays connected, `WarhorseEvent::LoggedOut` follows once the server is done.
+    pub fn send_logout_request(&self) -> Result<(), ClientError> {
+        self.pending_sends
+            .send((EVENT_SEND_USER_LOGOUT.to_string(), serde_json::json!({}), None))
+            .map_err(|_| ClientError::QueueClosed("logout request"))
     }
 
     pub fn send_user_registration_request(
@@ -237,123 +450,1795 @@ impl WarhorseClient {
             password,
             email,
             display_name,
-            language: Language::English,
+            language: self.language,
         };
 
         let json = user_registration.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_USER_REGISTER.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue registration request: {:?}", e)))
+            .send((EVENT_SEND_USER_REGISTER.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("registration request"))
+    }
+
+    /// Deletes the logged in account, the password is checked again before anything is removed.
+    pub fn send_account_delete(&self, password: String) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = DeleteAccount {
+            language: self.language,
+            password,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ACCOUNT_DELETE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("account delete request"))
     }
 
-    pub fn send_friend_request(&self, friend_id: String) -> Result<(), ClientError> {
+    pub fn send_friend_request(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
         let request = FriendRequest {
-            language: Language::English,
+            language: self.language,
             friend_id,
         };
 
         let json = request.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_FRIEND_REQUEST.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue friend request: {:?}", e)))
+            .send((EVENT_SEND_FRIEND_REQUEST.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("friend request"))
     }
 
-    fn send_chat_message(&self, message: String, channel: ChatChannel) -> Result<(), ClientError> {
+    /// Sends a message to any channel, `send_room_message` and `send_whisper_message` are shorthands for this.
+    /// Returns the id of the `WarhorseEvent::SendAck` that says whether the server accepted it.
+    pub fn send_to_channel(&self, channel: ChatChannel, message: String) -> Result<RequestId, ClientError> {
+        // the server would only reject it
+        validation::validate_chat_message(&message)?;
+        self.notify_activity()?;
         let chat_message = SendChatMessage {
-            language: Language::English,
+            language: self.language,
             message,
             channel,
         };
 
         let json = chat_message.to_json()?;
+        let request_id = RequestId::new_v4();
         self.pending_sends
-            .send((EVENT_SEND_CHAT_MESSAGE.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue chat message: {:?}", e)))
+            .send((EVENT_SEND_CHAT_MESSAGE.to_string(), json, Some(request_id)))
+            .map(|_| request_id)
+            .map_err(|_| ClientError::QueueClosed("chat message"))
     }
 
+    /// Sends a private message to a friend.
+    /// Returns the id of the `WarhorseEvent::SendAck` that says whether the server accepted it.
     pub fn send_whisper_message(
         &self,
-        friend_id: String,
+        friend_id: UserId,
         message: String,
-    ) -> Result<(), ClientError> {
-        self.send_chat_message(message, ChatChannel::PrivateMessage(friend_id))
+    ) -> Result<RequestId, ClientError> {
+        self.send_to_channel(ChatChannel::PrivateMessage(friend_id), message)
+    }
+
+    /// Sends a message to a room we're in.
+    /// The server echoes it back to us as a `WarhorseEvent::ChatMessage` along with everyone else in the room.
+    /// Returns the id of the `WarhorseEvent::SendAck` that says whether the server accepted it.
+    pub fn send_room_message(&self, room: RoomId, message: String) -> Result<RequestId, ClientError> {
+        self.send_to_channel(ChatChannel::Room(room), message)
+    }
+
+    /// Sets how we appear to our friends, `PresenceKind::Invisible` appears offline.
+    pub fn set_presence(&self, status: PresenceKind) -> Result<(), ClientError> {
+        let request = SetPresence {
+            language: self.language,
+            status,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_PRESENCE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("presence"))
+    }
+
+    /// Sets what we're currently doing for our friends to see, or clears it with `None`.
+    pub fn set_activity(&self, activity: Option<String>) -> Result<(), ClientError> {
+        let request = SetActivity {
+            language: self.language,
+            activity,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ACTIVITY.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("activity"))
+    }
+
+    /// Sets the URL of the avatar our friends see, or clears it with `None`.
+    pub fn set_avatar(&self, avatar_url: Option<String>) -> Result<(), ClientError> {
+        if let Some(avatar_url) = &avatar_url {
+            validation::validate_avatar_url(avatar_url)?;
+        }
+        let request = SetAvatar {
+            language: self.language,
+            avatar_url,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_SET_AVATAR.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("avatar"))
+    }
+
+    /// Tells the channel that we've started or stopped typing.
+    pub fn send_typing(&self, channel: ChatChannel, is_typing: bool) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = TypingIndicator {
+            language: self.language,
+            channel,
+            is_typing,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_TYPING.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("typing indicator"))
+    }
+
+    /// Joins a chat room, confirmed by a `WarhorseEvent::RoomJoined`.
+    pub fn join_room(&self, room_id: RoomId) -> Result<(), ClientError> {
+        let request = JoinRoom {
+            language: self.language,
+            room_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ROOM_JOIN.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("room join"))
+    }
+
+    /// Leaves a chat room, confirmed by a `WarhorseEvent::RoomLeft`.
+    pub fn leave_room(&self, room_id: RoomId) -> Result<(), ClientError> {
+        let request = LeaveRoom {
+            language: self.language,
+            room_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ROOM_LEAVE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("room leave"))
+    }
+
+    /// Requests who's in a room we're in, they arrive as a `WarhorseEvent::RoomMembers`.
+    pub fn request_room_members(&self, room_id: RoomId) -> Result<(), ClientError> {
+        let request = GetRoomMembers {
+            language: self.language,
+            room_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ROOM_MEMBERS.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("room members request"))
+    }
+
+    /// Invites a friend to a party, creating it with us in it if it doesn't exist yet.
+    pub fn send_party_invite(&self, party_id: PartyId, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = PartyInvite {
+            language: self.language,
+            party_id,
+            friend_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_PARTY_INVITE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("party invite"))
+    }
+
+    /// Joins a party we've been invited to, the members arrive as a `WarhorseEvent::PartyUpdate`.
+    pub fn join_party(&self, party_id: PartyId) -> Result<(), ClientError> {
+        let request = PartyJoin {
+            language: self.language,
+            party_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_PARTY_JOIN.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("party join"))
+    }
+
+    /// Leaves a party.
+    pub fn leave_party(&self, party_id: PartyId) -> Result<(), ClientError> {
+        let request = PartyLeave {
+            language: self.language,
+            party_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_PARTY_LEAVE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("party leave"))
+    }
+
+    /// Searches for users whose display name starts with `query`.
+    /// The results arrive as a `WarhorseEvent::SearchResults`.
+    pub fn send_user_search(&self, query: String) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = SearchUsers {
+            language: self.language,
+            query,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_USER_SEARCH.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("user search"))
+    }
+
+    /// Requests another user's profile.
+    /// The profile arrives as a `WarhorseEvent::Profile`.
+    pub fn request_profile(&self, user_id: UserId) -> Result<(), ClientError> {
+        let request = GetProfile {
+            language: self.language,
+            user_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_GET_PROFILE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("profile request"))
+    }
+
+    /// Requests a page of the friends list, the server caps `limit` at 100.
+    pub fn request_friends_page(&self, offset: u32, limit: u32) -> Result<(), ClientError> {
+        let request = GetFriends {
+            language: self.language,
+            offset,
+            limit,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_FRIENDS_PAGE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("friends page request"))
     }
 
-    pub fn send_room_message(&self, room: String, message: String) -> Result<(), ClientError> {
-        self.send_chat_message(message, ChatChannel::Room(room))
+    /// Requests up to `limit` messages sent to a room before `before`, or the latest messages if `None`.
+    /// The page arrives as a `WarhorseEvent::ChatHistory`, oldest first.
+    pub fn request_chat_history(
+        &self,
+        channel: ChatChannel,
+        before: Option<Timestamp>,
+        limit: u32,
+    ) -> Result<(), ClientError> {
+        let request = GetChatHistory {
+            language: self.language,
+            channel,
+            before,
+            limit,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_CHAT_HISTORY.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("chat history request"))
     }
 
-    pub fn send_block_friend(&self, friend_id: String) -> Result<(), ClientError> {
+    pub fn send_block_friend(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
         let request = BlockUserRequest {
-            language: Language::English,
+            language: self.language,
             user_id: friend_id,
         };
 
         let json = request.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_USER_BLOCK.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue block friend request: {:?}", e)))
+            .send((EVENT_SEND_USER_BLOCK.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("block friend request"))
     }
 
-    pub fn send_unblock_friend(&self, friend_id: String) -> Result<(), ClientError> {
+    pub fn send_unblock_friend(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
         let request = UnblockUserRequest {
-            language: Language::English,
+            language: self.language,
             user_id: friend_id,
         };
 
         let json = request.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_USER_UNBLOCK.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue unblock friend request: {:?}", e)))
+            .send((EVENT_SEND_USER_UNBLOCK.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("unblock friend request"))
     }
 
-    pub fn send_accept_friend_request(&self, friend_id: String) -> Result<(), ClientError> {
+    /// Disconnects a user, only works if the logged in user is an admin.
+    pub fn send_admin_kick(&self, user_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = AdminKick {
+            language: self.language,
+            user_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ADMIN_KICK.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("kick request"))
+    }
+
+    /// Bans a user so they're disconnected and can't log in again, only works if the logged in user is an admin.
+    pub fn send_admin_ban(&self, user_id: UserId, reason: Option<String>) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = AdminBan {
+            language: self.language,
+            user_id,
+            reason,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_ADMIN_BAN.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("ban request"))
+    }
+
+    pub fn send_accept_friend_request(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
         let request = AcceptFriendRequest {
-            language: Language::English,
+            language: self.language,
             friend_id,
         };
 
         let json = request.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_FRIEND_REQUEST_ACCEPT.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue accept friend request: {:?}", e)))
+            .send((EVENT_SEND_FRIEND_REQUEST_ACCEPT.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("accept friend request"))
     }
 
-    pub fn send_reject_friend_request(&self, friend_id: String) -> Result<(), ClientError> {
+    pub fn send_reject_friend_request(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
         let request = RejectFriendRequest {
-            language: Language::English,
+            language: self.language,
+            friend_id,
+        };
+
+        let json = request.to_json()?;
+        self.pending_sends
+            .send((EVENT_SEND_FRIEND_REQUEST_REJECT.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("reject friend request"))
+    }
+
+    /// Takes back a friend request we sent.
+    pub fn send_cancel_friend_request(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
+        let request = CancelFriendRequest {
+            language: self.language,
             friend_id,
         };
 
         let json = request.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_FRIEND_REQUEST_REJECT.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue reject friend request: {:?}", e)))
+            .send((EVENT_SEND_FRIEND_REQUEST_CANCEL.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("cancel friend request"))
     }
 
-    pub fn send_remove_friend(&self, friend_id: String) -> Result<(), ClientError> {
+    pub fn send_remove_friend(&self, friend_id: UserId) -> Result<(), ClientError> {
+        self.notify_activity()?;
         let request = RemoveFriendRequest {
-            language: Language::English,
+            language: self.language,
             friend_id,
         };
 
         let json = request.to_json()?;
         self.pending_sends
-            .send((EVENT_SEND_FRIEND_REMOVE.to_string(), json))
-            .map_err(|e| ClientError(format!("Failed to queue remove friend request: {:?}", e)))
+            .send((EVENT_SEND_FRIEND_REMOVE.to_string(), json, None))
+            .map_err(|_| ClientError::QueueClosed("remove friend request"))
     }
 
-    pub fn pump(&self) -> Vec<WarhorseEvent> {
-        let mut events = Vec::new();
-        if let Ok(mut event_queue) = self.pending_receives.write() {
-            while let Some(event) = event_queue.pop_front() {
-                events.push(event);
+    /// Tells the server the player is at the keyboard, so they aren't shown as away for going idle.
+    /// Call it on any input, it's rate limited so calling it every frame is fine. Every `send_*` call does it for you.
+    pub fn notify_activity(&self) -> Result<(), ClientError> {
+        {
+            let mut last_activity_ping = self.last_activity_ping.lock().unwrap();
+            if last_activity_ping.is_some_and(|sent_at| sent_at.elapsed() < ACTIVITY_PING_INTERVAL) {
+                return Ok(());
             }
+            *last_activity_ping = Some(Instant::now());
         }
-        events
+
+        self.pending_sends
+            .send((EVENT_SEND_ACTIVITY_PING.to_string(), serde_json::json!({}), None))
+            .map_err(|_| ClientError::QueueClosed("activity ping"))
+    }
+
+    /// Registers a callback that's invoked for every event as soon as it arrives.
+    /// Callbacks run on the socket.io thread, so they must not block; hand the work off to your own thread if it's slow.
+    /// A callback may register more callbacks, they start with the next event.
+    /// Events are still queued for `pump`, so both can be used together.
+    pub fn on_event(&self, f: impl Fn(&WarhorseEvent) + Send + Sync + 'static) {
+        self.pending_receives.subscribe(Arc::new(f));
+    }
+
+    pub fn pump(&self) -> Vec<WarhorseEvent> {
+        self.pending_receives.drain()
     }
 
     fn is_email_as_username(input: &str) -> bool {
         input.contains('@')
     }
 }
+
+impl Drop for WarhorseClient {
+    fn drop(&mut self) {
+        // swapping out the only sender closes the channel, which tells the emit thread to disconnect and exit
+        let (closed, _) = std::sync::mpsc::channel();
+        drop(std::mem::replace(&mut self.pending_sends, closed));
+        if let Some(emit_thread) = self.emit_thread.take() {
+            if emit_thread.join().is_err() {
+                error!("Emit thread panicked");
+            }
+        }
+    }
+}
+
+/// Sends queued messages until the client is dropped, reconnecting whenever the connection is lost
+/// or the server goes silent. Disconnects from the server on the way out.
+fn emit_loop<E: Connection>(
+    socket_io: E,
+    receiver: &Receiver<Outgoing>,
+    connection_state: &AtomicConnectionState,
+    watchdog: &Watchdog,
+    handshake: &HandshakeTimer,
+    pending_events: &Arc<EventQueue>,
+    mut reconnect: impl FnMut(&mut VecDeque<Outgoing>) -> Option<E>,
+) {
+    let mut socket_io = socket_io;
+    let mut backlog = VecDeque::new();
+    loop {
+        if connection_state.load() == ConnectionState::Disconnected {
+            match reconnect(&mut backlog) {
+                Some(reconnected) => {
+                    socket_io = reconnected;
+                    watchdog.feed();
+                }
+                // the client was dropped while we were reconnecting, there's nothing to disconnect
+                None => return,
+            }
+        }
+
+        // anything queued while we were disconnected or waiting for hello goes out first
+        if !handshake.waiting() {
+            while let Some(message) = backlog.pop_front() {
+                if connection_state.load() == ConnectionState::Incompatible {
+                    warn!("Not sending {} to a server with an incompatible protocol version", message.0);
+                } else {
+                    send_message(&socket_io, message, pending_events);
+                }
+            }
+        }
+
+        match receiver.recv_timeout(DISCONNECT_POLL_INTERVAL) {
+            Ok((event, _, _)) if connection_state.load() == ConnectionState::Incompatible => {
+                warn!("Not sending {} to a server with an incompatible protocol version", event);
+            }
+            // the server drops anything that arrives before it's ready, hello is how it says it is
+            Ok(message) if handshake.waiting() => backlog.push_back(message),
+            Ok(message) => send_message(&socket_io, message, pending_events),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if watchdog.check(connection_state) {
+            warn!("No traffic from the server, assuming the connection is lost");
+            pending_events.push(WarhorseEvent::ConnectionLost);
+            if let Err(e) = socket_io.disconnect() {
+                error!("Failed to disconnect: {:?}", e);
+            }
+        }
+
+        if handshake.check(connection_state) {
+            warn!("The server never said hello, assuming it can't be reached");
+            pending_events.push(WarhorseEvent::HandshakeTimeout);
+            if let Err(e) = socket_io.disconnect() {
+                error!("Failed to disconnect: {:?}", e);
+            }
+        }
+    }
+
+    if let Err(e) = socket_io.disconnect() {
+        error!("Failed to disconnect: {:?}", e);
+    }
+}
+
+/// Sends a queued message, asking for an acknowledgement if it has a request id to report it under.
+fn send_message<E: Connection>(socket_io: &E, (event, json, request_id): Outgoing, pending_events: &Arc<EventQueue>) {
+    let result = match request_id {
+        Some(request_id) => {
+            let pending_events = pending_events.clone();
+            socket_io.emit_with_ack(event, json, Box::new(move |payload| handle_send_ack(request_id, payload, &pending_events)))
+        }
+        None => socket_io.emit(event, json),
+    };
+
+    if let Err(e) = result {
+        error!("Failed to send message: {:?}", e);
+    }
+}
+
+/// Turns what the server sends into `WarhorseEvent`s.
+/// A `Transport` is handed these when it connects and calls them from whatever thread it receives on.
+#[derive(Clone)]
+pub struct EventHandlers {
+    pending_events: Arc<EventQueue>,
+    connection_state: Arc<AtomicConnectionState>,
+    current_user: Arc<std::sync::Mutex<Option<UserPartial>>>,
+    friends_cache: Arc<FriendsCache>,
+    message_dedup: Arc<MessageDedup>,
+    watchdog: Arc<Watchdog>,
+    handshake: Arc<HandshakeTimer>,
+    server_compression: Arc<AtomicBool>,
+}
+
+impl EventHandlers {
+    /// Call once the connection to the server is open.
+    pub fn connected(&self) {
+        self.watchdog.feed();
+        self.connection_state.store(ConnectionState::Connected);
+    }
+
+    /// Call when the connection to the server is lost, the client will reconnect through its `Transport`.
+    pub fn closed(&self) {
+        self.connection_state.store(ConnectionState::Disconnected);
+    }
+
+    /// Call with every event the server sends, events the client doesn't know are ignored.
+    pub fn handle(&self, event: &str, payload: Payload) {
+        // any event counts as traffic, the server's pings just make sure there is some
+        self.watchdog.feed();
+        let payload = decompress_payload(payload);
+        match event {
+            EVENT_RECEIVE_USER_LOGIN => {
+                self.pending_events.push(WarhorseEvent::LoggedIn);
+            }
+            EVENT_RECEIVE_CURRENT_USER => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match UserPartial::from_json(first.clone()) {
+                            Ok(user) => {
+                                *self.current_user.lock().unwrap() = Some(user.clone());
+                                self.pending_events.push(WarhorseEvent::CurrentUser(user));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse current user: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_USER_LOGOUT => {
+                self.current_user.lock().unwrap().take();
+                self.friends_cache.clear();
+                self.pending_events.push(WarhorseEvent::LoggedOut);
+            }
+            EVENT_RECEIVE_ACCOUNT_DELETED => {
+                self.current_user.lock().unwrap().take();
+                self.friends_cache.clear();
+                self.pending_events.push(WarhorseEvent::AccountDeleted);
+            }
+            EVENT_RECEIVE_SERVER_SHUTDOWN => handle_server_shutdown(payload, &self.pending_events),
+            EVENT_RECEIVE_BANNED => {
+                self.current_user.lock().unwrap().take();
+                self.friends_cache.clear();
+                handle_banned(payload, &self.pending_events);
+            }
+            EVENT_RECEIVE_HELLO => {
+                self.handshake.finish();
+                handle_hello(payload, &self.pending_events, &self.connection_state, &self.server_compression);
+            }
+            EVENT_RECEIVE_ERROR => match payload {
+                Payload::Text(text) => {
+                    for line in text {
+                        match RequestError::from_json(line.clone()) {
+                            Ok(e) => {
+                                self.pending_events.push(WarhorseEvent::Error { code: e.code, message: e.message });
+                            }
+                            Err(e) => error!("Failed to parse error: {:?}", e),
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_FRIENDS => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match json_to_vec::<Friend>(first.clone()) {
+                            Ok(friends) => {
+                                self.friends_cache.set_friends(&friends);
+                                self.pending_events.push(WarhorseEvent::FriendsList(friends));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse friends list: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_BLOCKED_USERS => handle_blocked_users(payload, &self.pending_events, &self.friends_cache),
+            EVENT_RECEIVE_FRIEND_REQUESTS => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match json_to_vec::<Friend>(first.clone()) {
+                            Ok(friend_requests) => {
+                                self.friends_cache.set_received_requests(&friend_requests);
+                                self.pending_events.push(WarhorseEvent::FriendRequests(friend_requests));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse friend requests: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_FRIEND_REQUEST_NEW => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match Friend::from_json(first.clone()) {
+                            Ok(friend_request) => {
+                                self.friends_cache.update(&friend_request);
+                                self.pending_events.push(WarhorseEvent::FriendRequestReceived(friend_request));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse friend request: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_FRIEND_REQUEST_ACCEPTED => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match FriendRequestAccepted::from_json(first.clone()) {
+                            Ok(accepted) => {
+                                self.friends_cache.update(&accepted.friend);
+                                self.pending_events.push(WarhorseEvent::FriendRequestAccepted(accepted.friend));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse friend request accepted: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_CHAT_HISTORY => handle_chat_history(payload, &self.pending_events),
+            EVENT_RECEIVE_TYPING => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match UserTyping::from_json(first.clone()) {
+                            Ok(typing) => {
+                                self.pending_events.push(WarhorseEvent::Typing {
+                                    display_name: typing.display_name,
+                                    channel: typing.channel,
+                                    is_typing: typing.is_typing,
+                                });
+                            }
+                            Err(e) => {
+                                error!("Failed to parse typing indicator: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_ROOM_JOINED => {
+                if let Some(room_id) = room_id_from_payload(payload) {
+                    self.pending_events.push(WarhorseEvent::RoomJoined(room_id));
+                }
+            }
+            EVENT_RECEIVE_ROOM_LEFT => {
+                if let Some(room_id) = room_id_from_payload(payload) {
+                    self.pending_events.push(WarhorseEvent::RoomLeft(room_id));
+                }
+            }
+            EVENT_RECEIVE_ROOM_MEMBERS => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match RoomMembers::from_json(first.clone()) {
+                            Ok(room_members) => {
+                                self.pending_events.push(WarhorseEvent::RoomMembers {
+                                    room_id: room_members.room_id,
+                                    members: room_members.members,
+                                });
+                            }
+                            Err(e) => {
+                                error!("Failed to parse room members: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_PARTY_INVITE => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match PartyInviteReceived::from_json(first.clone()) {
+                            Ok(invite) => {
+                                self.pending_events.push(WarhorseEvent::PartyInviteReceived {
+                                    party_id: invite.party_id,
+                                    friend: invite.friend,
+                                });
+                            }
+                            Err(e) => {
+                                error!("Failed to parse party invite: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_FRIENDS_PAGE => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match FriendsPage::from_json(first.clone()) {
+                            Ok(page) => {
+                                self.friends_cache.add_page(page.offset, &page.friends);
+                                self.pending_events.push(WarhorseEvent::FriendsPage {
+                                    offset: page.offset,
+                                    total: page.total,
+                                    friends: page.friends,
+                                });
+                            }
+                            Err(e) => {
+                                error!("Failed to parse friends page: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_PARTY_UPDATE => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match PartyUpdate::from_json(first.clone()) {
+                            Ok(update) => {
+                                self.pending_events.push(WarhorseEvent::PartyUpdate {
+                                    party_id: update.party_id,
+                                    members: update.members,
+                                });
+                            }
+                            Err(e) => {
+                                error!("Failed to parse party update: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_USER_SEARCH_RESULTS => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match json_to_vec::<UserPartial>(first.clone()) {
+                            Ok(users) => {
+                                self.pending_events.push(WarhorseEvent::SearchResults(users));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse user search results: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_PROFILE => match payload {
+                Payload::Text(text) => {
+                    if let Some(first) = text.first() {
+                        match PublicProfile::from_json(first.clone()) {
+                            Ok(profile) => {
+                                self.pending_events.push(WarhorseEvent::Profile(profile));
+                            }
+                            Err(e) => {
+                                error!("Failed to parse profile: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    error!("Unexpected payload: {:?}", payload);
+                }
+            }
+            EVENT_RECEIVE_CHAT_MESSAGE => handle_chat_message(payload, &self.pending_events, &self.message_dedup),
+            _ => {}
+        }
+    }
+}
+
+fn handle_hello(
+    payload: Payload,
+    pending_events: &Arc<EventQueue>,
+    connection_state: &AtomicConnectionState,
+    server_compression: &AtomicBool,
+) {
+    match payload {
+        Payload::Text(text) => {
+            // servers that predate versioning just send a string
+            let hello = text.first().and_then(|first| Hello::from_json(first.clone()).ok());
+            server_compression.store(hello.as_ref().is_some_and(|hello| hello.compression), Ordering::Relaxed);
+            let server_version = hello.and_then(|hello| hello.protocol_version);
+
+            match server_version {
+                Some(server) if server != PROTOCOL_VERSION => {
+                    error!("Server protocol version {} doesn't match ours ({})", server, PROTOCOL_VERSION);
+                    connection_state.store(ConnectionState::Incompatible);
+                    pending_events.push(WarhorseEvent::VersionMismatch {
+                        server,
+                        client: PROTOCOL_VERSION,
+                    });
+                }
+                _ => pending_events.push(WarhorseEvent::Hello),
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+/// Inflates anything the server compressed, so the handlers only ever see plain JSON
+fn decompress_payload(payload: Payload) -> Payload {
+    match payload {
+        Payload::Text(text) => Payload::Text(text.into_iter()
+            .map(|json| match decompress(&json) {
+                Ok(Some(decompressed)) => decompressed,
+                Ok(None) => json,
+                Err(e) => {
+                    error!("Failed to decompress payload: {:?}", e);
+                    json
+                }
+            })
+            .collect()),
+        payload => payload,
+    }
+}
+
+/// Handles a chat message, dropping it if we've already received it
+fn handle_chat_message(payload: Payload, pending_events: &Arc<EventQueue>, message_dedup: &MessageDedup) {
+    match payload {
+        Payload::Text(text) => {
+            if let Some(first) = text.first() {
+                match ChatMessage::from_json(first.clone()) {
+                    Ok(chat_message) => {
+                        if message_dedup.first_sighting(chat_message.id) {
+                            pending_events.push(WarhorseEvent::ChatMessage(chat_message));
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to parse chat message: {:?}", e);
+                    }
+                }
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+/// Parses the room ID sent when we join or leave a room
+fn room_id_from_payload(payload: Payload) -> Option<RoomId> {
+    match payload {
+        Payload::Text(text) => {
+            let room_id = text.first().and_then(|first| first.as_str()).map(RoomId::from);
+            if room_id.is_none() {
+                error!("Failed to parse room ID: {:?}", text);
+            }
+            room_id
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+            None
+        }
+    }
+}
+
+/// Handles a page of chat history we requested
+fn handle_chat_history(payload: Payload, pending_events: &Arc<EventQueue>) {
+    match payload {
+        Payload::Text(text) => {
+            if let Some(first) = text.first() {
+                match json_to_vec::<ChatMessage>(first.clone()) {
+                    Ok(messages) => {
+                        pending_events.push(WarhorseEvent::ChatHistory(messages));
+                    }
+                    Err(e) => {
+                        error!("Failed to parse chat history: {:?}", e);
+                    }
+                }
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+/// Handles the list of users we've blocked
+fn handle_blocked_users(payload: Payload, pending_events: &Arc<EventQueue>, friends_cache: &FriendsCache) {
+    match payload {
+        Payload::Text(text) => {
+            if let Some(first) = text.first() {
+                match json_to_vec::<Friend>(first.clone()) {
+                    Ok(blocked_users) => {
+                        friends_cache.set_blocked(&blocked_users);
+                        pending_events.push(WarhorseEvent::BlockedList(blocked_users));
+                    }
+                    Err(e) => {
+                        error!("Failed to parse blocked users: {:?}", e);
+                    }
+                }
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+fn handle_server_shutdown(payload: Payload, pending_events: &Arc<EventQueue>) {
+    match payload {
+        Payload::Text(text) => {
+            if let Some(first) = text.first() {
+                match ServerShutdown::from_json(first.clone()) {
+                    Ok(shutdown) => {
+                        pending_events.push(WarhorseEvent::ServerShutdown { reason: shutdown.reason });
+                    }
+                    Err(e) => {
+                        error!("Failed to parse server shutdown: {:?}", e);
+                    }
+                }
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+fn handle_send_ack(request_id: RequestId, payload: Payload, pending_events: &Arc<EventQueue>) {
+    match payload {
+        Payload::Text(text) => {
+            if let Some(first) = text.first() {
+                match ChatMessageAck::from_json(first.clone()) {
+                    Ok(ack) => {
+                        let result = match ack.error {
+                            Some(e) => Err(e),
+                            None => Ok(()),
+                        };
+                        pending_events.push(WarhorseEvent::SendAck { request_id, result });
+                    }
+                    Err(e) => {
+                        error!("Failed to parse acknowledgement: {:?}", e);
+                    }
+                }
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+fn handle_banned(payload: Payload, pending_events: &Arc<EventQueue>) {
+    match payload {
+        Payload::Text(text) => {
+            if let Some(first) = text.first() {
+                match Banned::from_json(first.clone()) {
+                    Ok(banned) => {
+                        pending_events.push(WarhorseEvent::Banned { reason: banned.reason });
+                    }
+                    Err(e) => {
+                        error!("Failed to parse ban: {:?}", e);
+                    }
+                }
+            }
+        }
+        _ => {
+            error!("Unexpected payload: {:?}", payload);
+        }
+    }
+}
+
+/// Reconnects with exponential backoff until it succeeds.
+/// Messages queued in the meantime are moved into `backlog` so they can be sent once we're back.
+/// Returns `None` if the client was dropped while reconnecting.
+fn reconnect<C: Connection>(
+    config: &ClientConfig,
+    pending_events: &Arc<EventQueue>,
+    connection_state: &Arc<AtomicConnectionState>,
+    receiver: &Receiver<Outgoing>,
+    backlog: &mut VecDeque<Outgoing>,
+    connect: impl Fn() -> Result<C, ClientError>,
+) -> Option<C> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        connection_state.store(ConnectionState::Reconnecting);
+        warn!("Connection lost, reconnecting (attempt {})", attempt);
+        pending_events.push(WarhorseEvent::Reconnecting { attempt });
+
+        // wait on the channel rather than sleeping so a dropped client doesn't have to sit out the backoff
+        let retry_at = Instant::now() + config.reconnect_delay(attempt);
+        loop {
+            match receiver.recv_timeout(retry_at.saturating_duration_since(Instant::now())) {
+                Ok(message) => backlog.push_back(message),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+
+        match connect() {
+            Ok(socket_io) => {
+                connection_state.transition(ConnectionState::Reconnecting, ConnectionState::Connected);
+                info!("Reconnected after {} attempt(s)", attempt);
+                pending_events.push(WarhorseEvent::Reconnected);
+                return Some(socket_io);
+            }
+            Err(e) => {
+                error!("Failed to reconnect: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+
+    fn user(id: &str) -> UserPartial {
+        UserPartial {
+            id: UserId::from(id),
+            display_name_lower: "test user".to_string(),
+            display_name: "Test User".to_string(),
+            account_name_lower: Some("test".to_string()),
+            account_name: Some("test".to_string()),
+            email: Some("test@example.com".to_string()),
+            language: Language::English,
+            activity: None,
+            is_admin: false,
+            created_at: Timestamp(1),
+            avatar_url: None,
+        }
+    }
+
+    #[test]
+    fn test_event_kinds_are_stable() {
+        let friend = Friend {
+            id: UserId::from("1"),
+            display_name: "Test User".to_string(),
+            status: FriendStatus::Online,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        let room = RoomId::from("general");
+        let events = [
+            (WarhorseEvent::Hello, WarhorseEventKind::Hello, "hello"),
+            (WarhorseEvent::LoggedIn, WarhorseEventKind::LoggedIn, "logged_in"),
+            (WarhorseEvent::LoggedOut, WarhorseEventKind::LoggedOut, "logged_out"),
+            (WarhorseEvent::CurrentUser(user("0")), WarhorseEventKind::CurrentUser, "current_user"),
+            (WarhorseEvent::Error { code: ErrorCode::Unknown, message: String::new() }, WarhorseEventKind::Error, "error"),
+            (WarhorseEvent::FriendsList(vec![]), WarhorseEventKind::FriendsList, "friends_list"),
+            (WarhorseEvent::FriendsPage { offset: 0, total: 0, friends: vec![] }, WarhorseEventKind::FriendsPage, "friends_page"),
+            (WarhorseEvent::BlockedList(vec![]), WarhorseEventKind::BlockedList, "blocked_list"),
+            (WarhorseEvent::FriendRequests(vec![]), WarhorseEventKind::FriendRequests, "friend_requests"),
+            (WarhorseEvent::FriendRequestReceived(friend.clone()), WarhorseEventKind::FriendRequestReceived, "friend_request_received"),
+            (WarhorseEvent::FriendRequestAccepted(friend.clone()), WarhorseEventKind::FriendRequestAccepted, "friend_request_accepted"),
+            (
+                WarhorseEvent::ChatMessage(ChatMessage {
+                    id: Uuid::nil(),
+                    display_name: friend.display_name.clone(),
+                    channel: ChatChannel::Room(room.clone()),
+                    message: "hello".to_string(),
+                    time: Timestamp(1),
+                }),
+                WarhorseEventKind::ChatMessage,
+                "chat_message",
+            ),
+            (WarhorseEvent::ChatHistory(vec![]), WarhorseEventKind::ChatHistory, "chat_history"),
+            (
+                WarhorseEvent::Typing { display_name: friend.display_name.clone(), channel: ChatChannel::Room(room.clone()), is_typing: true },
+                WarhorseEventKind::Typing,
+                "typing",
+            ),
+            (WarhorseEvent::SearchResults(vec![]), WarhorseEventKind::SearchResults, "search_results"),
+            (
+                WarhorseEvent::Profile(PublicProfile {
+                    id: friend.id.clone(),
+                    display_name: friend.display_name.clone(),
+                    status: FriendStatus::Online,
+                    activity: None,
+                    member_since: None,
+                    mutual_friends: 0,
+                    avatar_url: None,
+                }),
+                WarhorseEventKind::Profile,
+                "profile",
+            ),
+            (WarhorseEvent::RoomJoined(room.clone()), WarhorseEventKind::RoomJoined, "room_joined"),
+            (WarhorseEvent::RoomLeft(room.clone()), WarhorseEventKind::RoomLeft, "room_left"),
+            (WarhorseEvent::RoomMembers { room_id: room, members: vec![] }, WarhorseEventKind::RoomMembers, "room_members"),
+            (
+                WarhorseEvent::PartyInviteReceived { party_id: "1".to_string(), friend },
+                WarhorseEventKind::PartyInviteReceived,
+                "party_invite_received",
+            ),
+            (WarhorseEvent::PartyUpdate { party_id: "1".to_string(), members: vec![] }, WarhorseEventKind::PartyUpdate, "party_update"),
+            (WarhorseEvent::Reconnecting { attempt: 1 }, WarhorseEventKind::Reconnecting, "reconnecting"),
+            (WarhorseEvent::Reconnected, WarhorseEventKind::Reconnected, "reconnected"),
+            (WarhorseEvent::ConnectionLost, WarhorseEventKind::ConnectionLost, "connection_lost"),
+            (WarhorseEvent::HandshakeTimeout, WarhorseEventKind::HandshakeTimeout, "handshake_timeout"),
+            (WarhorseEvent::EventsDropped { count: 1 }, WarhorseEventKind::EventsDropped, "events_dropped"),
+            (WarhorseEvent::VersionMismatch { server: 2, client: 1 }, WarhorseEventKind::VersionMismatch, "version_mismatch"),
+            (WarhorseEvent::AccountDeleted, WarhorseEventKind::AccountDeleted, "account_deleted"),
+            (WarhorseEvent::ServerShutdown { reason: None }, WarhorseEventKind::ServerShutdown, "server_shutdown"),
+            (WarhorseEvent::Banned { reason: None }, WarhorseEventKind::Banned, "banned"),
+            (WarhorseEvent::SendAck { request_id: Uuid::nil(), result: Ok(()) }, WarhorseEventKind::SendAck, "send_ack"),
+        ];
+
+        for (event, kind, name) in events {
+            assert_eq!(event.kind(), kind);
+            assert_eq!(event.to_string(), name);
+            assert_eq!(kind.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_connection_state_round_trips_through_u8() {
+        for state in [
+            ConnectionState::Connecting,
+            ConnectionState::Connected,
+            ConnectionState::Disconnected,
+            ConnectionState::Reconnecting,
+        ] {
+            assert_eq!(ConnectionState::from(state as u8), state);
+        }
+    }
+
+    #[test]
+    fn test_connection_state_transition_only_from_expected_state() {
+        let state = AtomicConnectionState::new(ConnectionState::Connecting);
+        assert!(state.transition(ConnectionState::Connecting, ConnectionState::Connected));
+        assert_eq!(state.load(), ConnectionState::Connected);
+
+        // a close callback won the race, so we must not flip back to connected
+        state.store(ConnectionState::Disconnected);
+        assert!(!state.transition(ConnectionState::Reconnecting, ConnectionState::Connected));
+        assert_eq!(state.load(), ConnectionState::Disconnected);
+    }
+
+    #[test]
+    fn test_callbacks_fire_on_hello() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        pending_events.subscribe(Arc::new(move |event| {
+            let _ = sender.send(matches!(event, WarhorseEvent::Hello));
+        }));
+
+        let connection_state = AtomicConnectionState::new(ConnectionState::Connected);
+        handle_hello(Payload::Text(vec![serde_json::json!("hello")]), &pending_events, &connection_state, &AtomicBool::new(false));
+
+        assert_eq!(receiver.try_recv(), Ok(true));
+        // the event is still queued for pump
+        assert_eq!(pending_events.drain().len(), 1);
+    }
+
+    fn hello_payload(protocol_version: u32) -> Payload {
+        let hello = Hello {
+            message: "hello".to_string(),
+            protocol_version: Some(protocol_version),
+            compression: false,
+        };
+        Payload::Text(vec![hello.to_json().unwrap()])
+    }
+
+    #[test]
+    fn test_hello_with_matching_version() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let connection_state = AtomicConnectionState::new(ConnectionState::Connected);
+
+        handle_hello(hello_payload(PROTOCOL_VERSION), &pending_events, &connection_state, &AtomicBool::new(false));
+
+        assert!(matches!(pending_events.drain().as_slice(), [WarhorseEvent::Hello]));
+        assert_eq!(connection_state.load(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn test_hello_with_mismatching_version() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let connection_state = AtomicConnectionState::new(ConnectionState::Connected);
+
+        handle_hello(hello_payload(PROTOCOL_VERSION + 1), &pending_events, &connection_state, &AtomicBool::new(false));
+
+        match pending_events.drain().as_slice() {
+            [WarhorseEvent::VersionMismatch { server, client }] => {
+                assert_eq!(*server, PROTOCOL_VERSION + 1);
+                assert_eq!(*client, PROTOCOL_VERSION);
+            }
+            events => panic!("expected a VersionMismatch event, got {:?}", events),
+        }
+        assert_eq!(connection_state.load(), ConnectionState::Incompatible);
+    }
+
+    #[test]
+    fn test_handle_chat_history_pushes_messages_in_order() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let messages: Vec<ChatMessage> = (1..=3)
+            .map(|time| ChatMessage {
+                id: Uuid::new_v4(),
+                display_name: "Test User".to_string(),
+                channel: ChatChannel::Room(RoomId::from("general")),
+                message: time.to_string(),
+                time: Timestamp(time),
+            })
+            .collect();
+        let payload = Payload::Text(vec![vec_to_json(messages.clone()).unwrap()]);
+
+        handle_chat_history(payload, &pending_events);
+
+        match pending_events.drain().pop() {
+            Some(WarhorseEvent::ChatHistory(history)) => assert_eq!(history, messages),
+            _ => panic!("expected a ChatHistory event"),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_chat_message_is_dropped() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let message_dedup = MessageDedup::new(ClientConfig::default().dedup_window);
+        let message = ChatMessage {
+            id: Uuid::new_v4(),
+            display_name: "Test User".to_string(),
+            channel: ChatChannel::Room(RoomId::from("general")),
+            message: "hello".to_string(),
+            time: Timestamp(1),
+        };
+
+        for _ in 0..2 {
+            let payload = Payload::Text(vec![message.to_json().unwrap()]);
+            handle_chat_message(payload, &pending_events, &message_dedup);
+        }
+
+        let events = pending_events.drain();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], WarhorseEvent::ChatMessage(received) if *received == message));
+    }
+
+    #[test]
+    fn test_handle_blocked_users_pushes_blocked_list() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let blocked = Friend {
+            id: UserId::from("1"),
+            display_name: "Test User 2".to_string(),
+            status: FriendStatus::Blocked,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        let payload = Payload::Text(vec![vec_to_json(vec![blocked.clone()]).unwrap()]);
+
+        handle_blocked_users(payload, &pending_events, &FriendsCache::new());
+
+        let mut events = pending_events.drain();
+        assert_eq!(events.len(), 1);
+        match events.pop() {
+            Some(WarhorseEvent::BlockedList(blocked_users)) => assert_eq!(blocked_users, vec![blocked]),
+            _ => panic!("expected a BlockedList event"),
+        }
+    }
+
+    #[test]
+    fn test_handle_blocked_users_ignores_malformed_payload() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let payload = Payload::Text(vec![serde_json::json!({ "not": "a list" })]);
+
+        handle_blocked_users(payload, &pending_events, &FriendsCache::new());
+
+        assert!(pending_events.drain().is_empty());
+    }
+
+    #[test]
+    fn test_handle_server_shutdown_pushes_reason() {
+        let pending_events = Arc::new(EventQueue::new(ClientConfig::default().max_queue_len));
+        let shutdown = ServerShutdown { reason: Some("Server going down for maintenance".to_string()) };
+        let payload = Payload::Text(vec![shutdown.to_json().unwrap()]);
+
+        handle_server_shutdown(payload, &pending_events);
+
+        match pending_events.drain().as_slice() {
+            [WarhorseEvent::ServerShutdown { reason }] => {
+                assert_eq!(reason.as_deref(), Some("Server going down for maintenance"));
+            }
+            events => panic!("expected a ServerShutdown event, got {:?}", events),
+        }
+    }
+
+    /// What a server says once it's ready for requests.
+    fn say_hello(mock: &MockTransport) {
+        let hello = Hello { message: "hi".to_string(), protocol_version: Some(PROTOCOL_VERSION), compression: false };
+        mock.receive(EVENT_RECEIVE_HELLO, hello.to_json().unwrap());
+    }
+
+    fn mock_client(language: Language) -> (WarhorseClient, MockTransport) {
+        mock_client_with_config(language, ClientConfig::default())
+    }
+
+    fn mock_client_with_config(language: Language, config: ClientConfig) -> (WarhorseClient, MockTransport) {
+        let mock = MockTransport::new();
+        let connection_state = Arc::new(AtomicConnectionState::new(ConnectionState::Connected));
+        let pending_events = Arc::new(EventQueue::new(config.max_queue_len));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let emit_thread = {
+            let mock = mock.clone();
+            let connection_state = connection_state.clone();
+            let pending_events = pending_events.clone();
+            let watchdog = Watchdog::new(config.heartbeat_timeout);
+            let handshake = HandshakeTimer::new(config.handshake_timeout);
+            std::thread::spawn(move || emit_loop(mock, &receiver, &connection_state, &watchdog, &handshake, &pending_events, |_| None))
+        };
+        let client = WarhorseClient {
+            language,
+            config,
+            connection_state,
+            pending_receives: pending_events,
+            pending_sends: sender,
+            emit_thread: Some(emit_thread),
+            last_activity_ping: std::sync::Mutex::new(None),
+            current_user: Arc::new(std::sync::Mutex::new(None)),
+            friends_cache: Arc::new(FriendsCache::new()),
+            server_compression: Arc::new(AtomicBool::new(false)),
+        };
+        (client, mock)
+    }
+
+    #[test]
+    fn test_dropping_client_disconnects_and_stops_emit_thread() {
+        let (client, mock) = mock_client(Language::English);
+
+        client.join_room(RoomId::from("lobby")).unwrap();
+        // drop joins the emit thread, so everything below has already happened
+        drop(client);
+
+        let emitted = mock.emitted();
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(emitted[0].0, EVENT_SEND_ROOM_JOIN);
+        assert!(mock.is_disconnected());
+    }
+
+    #[test]
+    fn test_requests_are_sent_in_the_client_language() {
+        let (client, mock) = mock_client(Language::English);
+        let client = client.with_language(Language::Spanish);
+
+        client.send_user_registration_request(
+            "test".to_string(),
+            "pass".to_string(),
+            "Test User".to_string(),
+            "test@example.com".to_string(),
+        ).unwrap();
+        drop(client);
+
+        let emitted = mock.emitted();
+        let registration = UserRegistration::from_json(emitted[0].1.clone()).unwrap();
+        assert_eq!(registration.language, Language::Spanish);
+    }
+
+    #[test]
+    fn test_silent_server_is_treated_as_lost_connection() {
+        let config = ClientConfig {
+            heartbeat_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (client, mock) = mock_client_with_config(Language::English, config);
+
+        // the stale socket is torn down last
+        let started = Instant::now();
+        while !mock.is_disconnected() {
+            assert!(started.elapsed() < Duration::from_secs(5), "the watchdog never fired");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert!(matches!(client.pump().as_slice(), [WarhorseEvent::ConnectionLost]));
+    }
+
+    #[test]
+    fn test_rejected_chat_message_is_acknowledged_with_the_error() {
+        let (client, mock) = mock_client(Language::English);
+        let error = RequestError { code: ErrorCode::RateLimited, message: "slow down".to_string() };
+        mock.set_ack(Some(ChatMessageAck { error: Some(error.clone()) }.to_json().unwrap()));
+
+        let request_id = client.send_room_message(RoomId::from("general"), "hello".to_string()).unwrap();
+        // drop joins the emit thread, so the acknowledgement is already queued
+        let events = client.pending_receives.clone();
+        drop(client);
+
+        match events.drain().as_slice() {
+            [WarhorseEvent::SendAck { request_id: acked_id, result: Err(e) }] => {
+                assert_eq!(*acked_id, request_id);
+                assert_eq!(*e, error);
+            }
+            events => panic!("expected a failed SendAck, got {:?}", events),
+        }
+    }
+
+    #[test]
+    fn test_sends_tell_the_server_we_are_active_at_most_once_per_interval() {
+        let (client, mock) = mock_client(Language::English);
+
+        client.send_friend_request(UserId::from("friend")).unwrap();
+        client.send_typing(ChatChannel::Room(RoomId::from("general")), true).unwrap();
+        client.notify_activity().unwrap();
+        drop(client);
+
+        let events: Vec<String> = mock.emitted().into_iter().map(|(event, _)| event).collect();
+        assert_eq!(events, vec![EVENT_SEND_ACTIVITY_PING, EVENT_SEND_FRIEND_REQUEST, EVENT_SEND_TYPING]);
+    }
+
+    #[test]
+    fn test_server_that_never_says_hello_times_out_and_is_retried() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::builder("http://localhost:3000")
+            .handshake_timeout(Duration::from_millis(50))
+            .reconnect_backoff(Duration::from_millis(10), Duration::from_millis(10))
+            .connect_with_transport(mock.clone())
+            .unwrap();
+
+        let started = Instant::now();
+        let mut events = Vec::new();
+        while !events.iter().any(|event| matches!(event, WarhorseEvent::Reconnecting { .. })) {
+            assert!(started.elapsed() < Duration::from_secs(5), "the client never retried, got {:?}", events);
+            events.extend(client.pump());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(events[0], WarhorseEvent::HandshakeTimeout));
+    }
+
+    #[test]
+    fn test_hello_stops_the_handshake_timeout() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::builder("http://localhost:3000")
+            .handshake_timeout(Duration::from_millis(50))
+            .connect_with_transport(mock.clone())
+            .unwrap();
+
+        let hello = Hello { message: "hi".to_string(), protocol_version: Some(PROTOCOL_VERSION), compression: false };
+        mock.receive(EVENT_RECEIVE_HELLO, hello.to_json().unwrap());
+        std::thread::sleep(Duration::from_millis(200));
+
+        assert!(matches!(client.pump().as_slice(), [WarhorseEvent::Hello]));
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn test_requests_wait_for_hello() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+
+        client.send_user_login_request("test".to_string(), "password".to_string()).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(mock.emitted().is_empty());
+
+        say_hello(&mock);
+        std::thread::sleep(Duration::from_millis(200));
+        drop(client);
+        assert_eq!(mock.emitted().iter().map(|(event, _)| event.as_str()).collect::<Vec<_>>(), vec![EVENT_SEND_USER_LOGIN]);
+    }
+
+    #[test]
+    fn test_nothing_is_sent_to_an_incompatible_server() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+
+        client.send_user_login_request("test".to_string(), "password".to_string()).unwrap();
+        let hello = Hello { message: "hi".to_string(), protocol_version: Some(PROTOCOL_VERSION + 1), compression: false };
+        mock.receive(EVENT_RECEIVE_HELLO, hello.to_json().unwrap());
+        std::thread::sleep(Duration::from_millis(200));
+        drop(client);
+        assert!(mock.emitted().is_empty());
+    }
+
+    #[test]
+    fn test_events_from_the_transport_come_out_of_pump() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+
+        let friend = Friend {
+            id: UserId::from("1"),
+            display_name: "Test User".to_string(),
+            status: FriendStatus::Online,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        mock.receive(EVENT_RECEIVE_FRIENDS, vec_to_json(vec![friend]).unwrap());
+
+        match client.pump().as_slice() {
+            [WarhorseEvent::FriendsList(friends)] => {
+                assert_eq!(friends.len(), 1);
+                assert_eq!(friends[0].id, "1");
+            }
+            events => panic!("expected a FriendsList event, got {:?}", events),
+        }
+    }
+
+    #[test]
+    fn test_client_reconnects_through_its_transport() {
+        let mock = MockTransport::new();
+        let config = ClientConfig {
+            reconnect_base_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), config).unwrap();
+
+        mock.close();
+        let started = Instant::now();
+        while !client.pump().iter().any(|event| matches!(event, WarhorseEvent::Reconnected)) {
+            assert!(started.elapsed() < Duration::from_secs(5), "the client never reconnected");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        say_hello(&mock);
+        client.join_room(RoomId::from("lobby")).unwrap();
+        drop(client);
+        assert_eq!(mock.emitted()[0].0, EVENT_SEND_ROOM_JOIN);
+    }
+
+    #[test]
+    fn test_current_user_is_known_after_login() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+        assert!(client.current_user().is_none());
+
+        mock.receive(EVENT_RECEIVE_CURRENT_USER, user("7").to_json().unwrap());
+        mock.receive(EVENT_RECEIVE_USER_LOGIN, serde_json::json!({}));
+
+        match client.pump().as_slice() {
+            [WarhorseEvent::CurrentUser(current), WarhorseEvent::LoggedIn] => assert_eq!(current.id, "7"),
+            events => panic!("expected CurrentUser then LoggedIn, got {:?}", events),
+        }
+        let current = client.current_user().unwrap();
+        assert_eq!(current.id, "7");
+        assert_eq!(current.account_name.as_deref(), Some("test"));
+
+        mock.receive(EVENT_RECEIVE_ACCOUNT_DELETED, serde_json::json!({}));
+        assert!(client.current_user().is_none());
+    }
+
+    #[test]
+    fn test_logging_out_forgets_who_we_were() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+        say_hello(&mock);
+        mock.receive(EVENT_RECEIVE_CURRENT_USER, user("7").to_json().unwrap());
+        mock.receive(EVENT_RECEIVE_USER_LOGIN, serde_json::json!({}));
+        let friend = Friend {
+            id: UserId::from("8"),
+            display_name: "Friend".to_string(),
+            status: FriendStatus::Online,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        mock.receive(EVENT_RECEIVE_FRIENDS, vec_to_json(vec![friend]).unwrap());
+        client.pump();
+
+        client.send_logout_request().unwrap();
+        mock.receive(EVENT_RECEIVE_USER_LOGOUT, serde_json::json!({}));
+
+        assert!(matches!(client.pump().as_slice(), [WarhorseEvent::LoggedOut]));
+        assert!(client.current_user().is_none());
+        assert!(client.friends().is_empty());
+        drop(client);
+        assert_eq!(mock.emitted()[0].0, EVENT_SEND_USER_LOGOUT);
+    }
+
+    #[test]
+    fn test_chat_messages_the_server_would_reject_are_not_sent() {
+        let (client, mock) = mock_client(Language::English);
+
+        assert!(client.send_room_message(RoomId::from("general"), " ".to_string()).is_err());
+        assert!(client.send_room_message(RoomId::from("general"), "m".repeat(CHAT_MESSAGE_MAX_LENGTH + 1)).is_err());
+        client.send_room_message(RoomId::from("general"), "hello".to_string()).unwrap();
+        drop(client);
+
+        let chat_messages: Vec<_> = mock.emitted().into_iter().filter(|(event, _)| event == EVENT_SEND_CHAT_MESSAGE).collect();
+        assert_eq!(chat_messages.len(), 1);
+    }
+
+    #[test]
+    fn test_send_to_channel_queues_the_channel_it_was_given() {
+        let (client, mock) = mock_client(Language::English);
+
+        client.send_to_channel(ChatChannel::Room(RoomId::from("lobby")), "hi all".to_string()).unwrap();
+        client.send_to_channel(ChatChannel::PrivateMessage(UserId::from("friend")), "hi you".to_string()).unwrap();
+        drop(client);
+
+        let sent: Vec<_> = mock.emitted().into_iter()
+            .filter(|(event, _)| event == EVENT_SEND_CHAT_MESSAGE)
+            .map(|(_, json)| SendChatMessage::from_json(json).unwrap())
+            .map(|message| (message.channel, message.message))
+            .collect();
+        assert_eq!(sent, vec![
+            (ChatChannel::Room(RoomId::from("lobby")), "hi all".to_string()),
+            (ChatChannel::PrivateMessage(UserId::from("friend")), "hi you".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_errors_say_what_went_wrong() {
+        let (mut client, _mock) = mock_client(Language::English);
+
+        let e = client.send_room_message(RoomId::from("general"), " ".to_string()).unwrap_err();
+        assert!(matches!(e, ClientError::Invalid(validation::ValidationError::ChatMessageEmpty)), "{:?}", e);
+        let e = client.set_avatar(Some("not a url".to_string())).unwrap_err();
+        assert!(matches!(e, ClientError::Invalid(validation::ValidationError::InvalidAvatarUrl)), "{:?}", e);
+
+        // what dropping the client does to the queue
+        let (closed, _) = std::sync::mpsc::channel();
+        client.pending_sends = closed;
+        let e = client.send_logout_request().unwrap_err();
+        assert!(matches!(e, ClientError::QueueClosed("logout request")), "{:?}", e);
+        assert_eq!(e.to_string(), "Failed to queue logout request: the client has shut down");
+
+        // nothing listens on port 1
+        let e = WarhorseClient::new(Language::English, "http://127.0.0.1:1").err().unwrap();
+        assert!(matches!(e, ClientError::Connect(_)), "{:?}", e);
+        assert!(e.to_string().starts_with("Failed to connect: "));
+    }
+
+    #[test]
+    fn test_relationship_follows_the_friends_list() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+        let friend = |id: &str, status| Friend {
+            id: UserId::from(id),
+            display_name: id.to_string(),
+            status,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+
+        mock.receive(EVENT_RECEIVE_FRIENDS, vec_to_json(vec![
+            friend("1", FriendStatus::Online),
+            friend("2", FriendStatus::FriendRequestSent),
+            friend("3", FriendStatus::Blocked),
+        ]).unwrap());
+
+        assert_eq!(client.relationship("1"), Some(FriendStatus::Online));
+        assert_eq!(client.relationship("2"), Some(FriendStatus::FriendRequestSent));
+        assert_eq!(client.relationship("3"), Some(FriendStatus::Blocked));
+        assert_eq!(client.relationship("4"), None);
+
+        mock.receive(EVENT_RECEIVE_BLOCKED_USERS, vec_to_json(Vec::<Friend>::new()).unwrap());
+        assert_eq!(client.relationship("3"), None);
+        assert_eq!(client.relationship("1"), Some(FriendStatus::Online));
+    }
+
+    #[test]
+    fn test_friends_cache_follows_the_latest_events() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+        let friend = |id: &str, status| Friend {
+            id: UserId::from(id),
+            display_name: id.to_string(),
+            status,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        let ids = |friends: Vec<Friend>| friends.into_iter().map(|friend| friend.id).collect::<Vec<_>>();
+
+        mock.receive(EVENT_RECEIVE_FRIENDS, vec_to_json(vec![
+            friend("1", FriendStatus::Online),
+            friend("2", FriendStatus::FriendRequestSent),
+        ]).unwrap());
+        mock.receive(EVENT_RECEIVE_FRIEND_REQUEST_ACCEPTED, FriendRequestAccepted { friend: friend("2", FriendStatus::Offline) }.to_json().unwrap());
+        mock.receive(EVENT_RECEIVE_BLOCKED_USERS, vec_to_json(vec![friend("3", FriendStatus::Blocked)]).unwrap());
+
+        assert_eq!(ids(client.friends_by_status(FriendStatus::Online)), vec!["1"]);
+        assert_eq!(ids(client.friends_by_status(FriendStatus::Offline)), vec!["2"]);
+        assert_eq!(ids(client.friends_by_status(FriendStatus::Blocked)), vec!["3"]);
+        assert!(client.friends_by_status(FriendStatus::FriendRequestSent).is_empty());
+
+        // a fresh list replaces everything
+        mock.receive(EVENT_RECEIVE_FRIENDS, vec_to_json(vec![friend("1", FriendStatus::Away)]).unwrap());
+        let friends = client.friends();
+        assert_eq!(friends.len(), 1);
+        assert_eq!(ids(friends[&FriendStatus::Away].clone()), vec!["1"]);
+
+        // everything still comes out of pump
+        assert_eq!(client.pump().len(), 4);
+    }
+
+    #[test]
+    fn test_new_friend_requests_are_told_apart_from_the_full_list() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::new_with_transport(Language::English, mock.clone(), ClientConfig::default()).unwrap();
+        let friend_request = |id: &str| Friend {
+            id: UserId::from(id),
+            display_name: id.to_string(),
+            status: FriendStatus::FriendRequestReceived,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+
+        mock.receive(EVENT_RECEIVE_FRIEND_REQUESTS, vec_to_json(vec![friend_request("1"), friend_request("2")]).unwrap());
+        mock.receive(EVENT_RECEIVE_FRIEND_REQUEST_NEW, friend_request("3").to_json().unwrap());
+
+        let events = client.pump();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], WarhorseEvent::FriendRequests(friend_requests) if friend_requests.len() == 2));
+        assert!(matches!(&events[1], WarhorseEvent::FriendRequestReceived(friend_request) if friend_request.id == "3"));
+        assert_eq!(client.friends_by_status(FriendStatus::FriendRequestReceived).len(), 3);
+    }
+
+    #[test]
+    fn test_compression_is_negotiated_and_undone_transparently() {
+        let mock = MockTransport::new();
+        let client = WarhorseClient::builder("")
+            .compression(true)
+            .connect_with_transport(mock.clone())
+            .unwrap();
+        let hello = Hello { message: "hi".to_string(), protocol_version: Some(PROTOCOL_VERSION), compression: true };
+        mock.receive(EVENT_RECEIVE_HELLO, hello.to_json().unwrap());
+
+        client.send_user_login_request("test".to_string(), "password".to_string()).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let (_, login) = mock.emitted().into_iter().find(|(event, _)| event == EVENT_SEND_USER_LOGIN).unwrap();
+        assert!(UserLogin::from_json(login).unwrap().compression);
+
+        let friends: Vec<Friend> = (0..2000)
+            .map(|i| Friend {
+                id: UserId::from(i.to_string()),
+                display_name: format!("Friend {}", i),
+                status: FriendStatus::Online,
+                activity: None,
+                last_seen: None,
+                avatar_url: None,
+            })
+            .collect();
+        let compressed = compression::compress_if_large(&vec_to_json(friends.clone()).unwrap()).unwrap().unwrap();
+        mock.receive(EVENT_RECEIVE_FRIENDS, compressed);
+
+        let events = client.pump();
+        assert!(events.iter().any(|event| matches!(event, WarhorseEvent::FriendsList(received) if *received == friends)));
+    }
+}
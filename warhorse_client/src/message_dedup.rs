@@ -0,0 +1,65 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use warhorse_protocol::MessageId;
+
+/// Remembers the IDs of the most recent chat messages, so one we've already seen can be dropped.
+pub(crate) struct MessageDedup {
+    capacity: usize,
+    inner: Mutex<MessageDedupInner>,
+}
+
+#[derive(Default)]
+struct MessageDedupInner {
+    // oldest first, so we know which ID to forget next
+    order: VecDeque<MessageId>,
+    seen: HashSet<MessageId>,
+}
+
+impl MessageDedup {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(MessageDedupInner::default()),
+        }
+    }
+
+    /// Records the ID, returns false if it was already one of the last `capacity` IDs seen.
+    pub fn first_sighting(&self, id: MessageId) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.seen.insert(id) {
+            return false;
+        }
+
+        inner.order.push_back(id);
+        if inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warhorse_protocol::Uuid;
+
+    #[test]
+    fn test_forgets_ids_outside_the_window() {
+        let dedup = MessageDedup::new(2);
+        let ids: Vec<MessageId> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        assert!(dedup.first_sighting(ids[0]));
+        assert!(!dedup.first_sighting(ids[0]));
+        assert!(dedup.first_sighting(ids[1]));
+        assert!(dedup.first_sighting(ids[2]));
+
+        // pushed out by the other two
+        assert!(dedup.first_sighting(ids[0]));
+    }
+}
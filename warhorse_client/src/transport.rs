@@ -0,0 +1,250 @@
+use rust_socketio::client::Client;
+use rust_socketio::{ClientBuilder, Event, TransportType};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::error::ClientError;
+use crate::EventHandlers;
+
+pub use rust_socketio::Payload;
+
+// how long the server has to acknowledge a message, after which no `SendAck` is reported for it
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens connections to the server, so the client can run over something other than socket.io, e.g. `MockTransport` in tests.
+/// `connect` is called for the initial connection and again for every reconnection attempt.
+pub trait Transport: Send + 'static {
+    type Connection: Connection;
+
+    /// Connects, passing everything the server sends to `handlers`.
+    fn connect(&self, handlers: EventHandlers) -> Result<Self::Connection, ClientError>;
+}
+
+/// An open connection to the server, owned by the client's emit thread.
+pub trait Connection: Send + 'static {
+    fn emit(&self, event: String, json: serde_json::Value) -> Result<(), ClientError>;
+    /// Emits and asks the server to acknowledge, `on_ack` gets the acknowledgement's payload.
+    fn emit_with_ack(
+        &self,
+        event: String,
+        json: serde_json::Value,
+        on_ack: Box<dyn FnMut(Payload) + Send + Sync>,
+    ) -> Result<(), ClientError>;
+    fn disconnect(&self) -> Result<(), ClientError>;
+}
+
+/// How the socket.io connection talks to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SocketIoTransportType {
+    /// Starts out long-polling and upgrades to a websocket once it can, the default.
+    #[default]
+    WebsocketUpgrade,
+    /// Websocket only, the lowest latency but it fails outright where websockets are blocked.
+    Websocket,
+    /// Long-polling only, for networks that don't let websockets through.
+    Polling,
+}
+
+impl From<SocketIoTransportType> for TransportType {
+    fn from(transport_type: SocketIoTransportType) -> Self {
+        match transport_type {
+            SocketIoTransportType::WebsocketUpgrade => TransportType::WebsocketUpgrade,
+            SocketIoTransportType::Websocket => TransportType::Websocket,
+            SocketIoTransportType::Polling => TransportType::Polling,
+        }
+    }
+}
+
+/// Connects to a Warhorse server over socket.io.
+pub struct SocketIoTransport {
+    connection_string: String,
+    namespace: String,
+    transport_type: SocketIoTransportType,
+}
+
+impl SocketIoTransport {
+    pub fn new(connection_string: &str, namespace: &str) -> Self {
+        Self {
+            connection_string: connection_string.to_string(),
+            namespace: namespace.to_string(),
+            transport_type: SocketIoTransportType::default(),
+        }
+    }
+
+    pub fn with_transport_type(mut self, transport_type: SocketIoTransportType) -> Self {
+        self.transport_type = transport_type;
+        self
+    }
+}
+
+impl Transport for SocketIoTransport {
+    type Connection = Client;
+
+    fn connect(&self, handlers: EventHandlers) -> Result<Client, ClientError> {
+        ClientBuilder::new(self.connection_string.as_str())
+            .namespace(self.namespace.as_str())
+            .transport_type(self.transport_type.into())
+            // we handle reconnection ourselves so the handlers can be re-registered
+            .reconnect(false)
+            .on(Event::Connect, {
+                let handlers = handlers.clone();
+                move |_payload, _socket| handlers.connected()
+            })
+            .on(Event::Close, {
+                let handlers = handlers.clone();
+                move |_payload, _socket| handlers.closed()
+            })
+            .on_any(move |event, payload, _socket| handlers.handle(event.as_str(), payload))
+            .connect()
+            .map_err(|e| ClientError::Connect(format!("{:?}", e)))
+    }
+}
+
+/// socket.io hands over an acknowledgement as a single array of everything the server acked with,
+/// unwrapped here so it looks like any other payload.
+fn ack_arguments(payload: Payload) -> Payload {
+    match payload {
+        Payload::Text(text) => match <[serde_json::Value; 1]>::try_from(text) {
+            Ok([serde_json::Value::Array(arguments)]) => Payload::Text(arguments),
+            Ok([argument]) => Payload::Text(vec![argument]),
+            Err(text) => Payload::Text(text),
+        },
+        payload => payload,
+    }
+}
+
+impl Connection for Client {
+    fn emit(&self, event: String, json: serde_json::Value) -> Result<(), ClientError> {
+        Client::emit(self, event, json).map_err(|e| ClientError::Emit(format!("{:?}", e)))
+    }
+
+    fn emit_with_ack(
+        &self,
+        event: String,
+        json: serde_json::Value,
+        mut on_ack: Box<dyn FnMut(Payload) + Send + Sync>,
+    ) -> Result<(), ClientError> {
+        Client::emit_with_ack(self, event, json, ACK_TIMEOUT, move |payload, _socket| on_ack(ack_arguments(payload)))
+            .map_err(|e| ClientError::Emit(format!("{:?}", e)))
+    }
+
+    fn disconnect(&self) -> Result<(), ClientError> {
+        Client::disconnect(self).map_err(|e| ClientError::Disconnect(format!("{:?}", e)))
+    }
+}
+
+/// A transport with no server behind it, for testing code built on `WarhorseClient`.
+/// `receive` delivers events as if the server sent them and `emitted` shows what the client sent.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    handlers: Option<EventHandlers>,
+    emitted: Vec<(String, serde_json::Value)>,
+    // what the "server" answers to messages that ask for an acknowledgement
+    ack: Option<serde_json::Value>,
+    disconnected: bool,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delivers an event to the client as if the server sent it.
+    pub fn receive(&self, event: &str, json: serde_json::Value) {
+        // not holding the lock while handling, `on_event` callbacks are free to send
+        let handlers = self.state.lock().unwrap().handlers.clone();
+        match handlers {
+            Some(handlers) => handlers.handle(event, Payload::Text(vec![json])),
+            None => warn!("Dropping {}, the client hasn't connected yet", event),
+        }
+    }
+
+    /// Drops the connection as if the server went away, the client will reconnect.
+    pub fn close(&self) {
+        let handlers = self.state.lock().unwrap().handlers.clone();
+        if let Some(handlers) = handlers {
+            handlers.closed();
+        }
+    }
+
+    /// Everything the client has sent, oldest first.
+    pub fn emitted(&self) -> Vec<(String, serde_json::Value)> {
+        self.state.lock().unwrap().emitted.clone()
+    }
+
+    /// Sets what messages that ask for an acknowledgement are answered with, `None` leaves them unanswered.
+    pub fn set_ack(&self, ack: Option<serde_json::Value>) {
+        self.state.lock().unwrap().ack = ack;
+    }
+
+    /// Whether the client has disconnected and not connected again since.
+    pub fn is_disconnected(&self) -> bool {
+        self.state.lock().unwrap().disconnected
+    }
+}
+
+impl Transport for MockTransport {
+    type Connection = MockTransport;
+
+    fn connect(&self, handlers: EventHandlers) -> Result<MockTransport, ClientError> {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.handlers = Some(handlers.clone());
+            state.disconnected = false;
+        }
+        handlers.connected();
+        Ok(self.clone())
+    }
+}
+
+impl Connection for MockTransport {
+    fn emit(&self, event: String, json: serde_json::Value) -> Result<(), ClientError> {
+        self.state.lock().unwrap().emitted.push((event, json));
+        Ok(())
+    }
+
+    fn emit_with_ack(
+        &self,
+        event: String,
+        json: serde_json::Value,
+        mut on_ack: Box<dyn FnMut(Payload) + Send + Sync>,
+    ) -> Result<(), ClientError> {
+        let ack = {
+            let mut state = self.state.lock().unwrap();
+            state.emitted.push((event, json));
+            state.ack.clone()
+        };
+        if let Some(ack) = ack {
+            on_ack(Payload::Text(vec![ack]));
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self) -> Result<(), ClientError> {
+        self.state.lock().unwrap().disconnected = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_acknowledgements_are_unwrapped_from_their_argument_array() {
+        let unwrapped = |payload| match ack_arguments(payload) {
+            Payload::Text(text) => text,
+            _ => panic!("expected a text payload"),
+        };
+
+        assert_eq!(unwrapped(Payload::Text(vec![json!([{ "error": null }])])), vec![json!({ "error": null })]);
+        assert_eq!(unwrapped(Payload::Text(vec![json!({ "error": null })])), vec![json!({ "error": null })]);
+    }
+}
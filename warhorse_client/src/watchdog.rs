@@ -0,0 +1,121 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{AtomicConnectionState, ConnectionState};
+
+/// Notices a half-open connection by how long it's been since the server last sent anything.
+/// The server pings every client periodically, so silence means the connection is gone.
+pub(crate) struct Watchdog {
+    last_traffic: Mutex<Instant>,
+    timeout: Duration,
+}
+
+impl Watchdog {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            last_traffic: Mutex::new(Instant::now()),
+            timeout,
+        }
+    }
+
+    /// Records that the server just sent us something.
+    pub fn feed(&self) {
+        *self.last_traffic.lock().unwrap() = Instant::now();
+    }
+
+    /// Marks a connected client as disconnected if the server has been silent for too long.
+    /// Returns true if it did, so the caller can tear down the stale socket.
+    pub fn check(&self, connection_state: &AtomicConnectionState) -> bool {
+        let silent_for = self.last_traffic.lock().unwrap().elapsed();
+        silent_for > self.timeout
+            && connection_state.transition(ConnectionState::Connected, ConnectionState::Disconnected)
+    }
+}
+
+/// Notices a server that accepts the connection but never says hello, e.g. the wrong URL or a proxy swallowing it.
+pub(crate) struct HandshakeTimer {
+    started: Mutex<Option<Instant>>,
+    timeout: Duration,
+}
+
+impl HandshakeTimer {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            started: Mutex::new(None),
+            timeout,
+        }
+    }
+
+    /// Starts waiting for the server's hello, called right before every connection attempt.
+    pub fn start(&self) {
+        *self.started.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Stops waiting, the server said hello.
+    pub fn finish(&self) {
+        *self.started.lock().unwrap() = None;
+    }
+
+    /// Whether we're still waiting for the server's hello.
+    pub fn waiting(&self) -> bool {
+        self.started.lock().unwrap().is_some()
+    }
+
+    /// Marks a connected client as disconnected if the server hasn't said hello in time.
+    /// Returns true if it did, so the caller can tear down the socket and try again.
+    pub fn check(&self, connection_state: &AtomicConnectionState) -> bool {
+        let mut started = self.started.lock().unwrap();
+        let timed_out = started.is_some_and(|started| started.elapsed() > self.timeout)
+            && connection_state.transition(ConnectionState::Connected, ConnectionState::Disconnected);
+        if timed_out {
+            *started = None;
+        }
+        timed_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_a_silent_connected_client_is_disconnected() {
+        let watchdog = Watchdog::new(Duration::from_millis(10));
+        let connection_state = AtomicConnectionState::new(ConnectionState::Connected);
+        assert!(!watchdog.check(&connection_state));
+
+        std::thread::sleep(Duration::from_millis(20));
+        let reconnecting = AtomicConnectionState::new(ConnectionState::Reconnecting);
+        assert!(!watchdog.check(&reconnecting));
+        assert_eq!(reconnecting.load(), ConnectionState::Reconnecting);
+
+        assert!(watchdog.check(&connection_state));
+        assert_eq!(connection_state.load(), ConnectionState::Disconnected);
+
+        watchdog.feed();
+        connection_state.store(ConnectionState::Connected);
+        assert!(!watchdog.check(&connection_state));
+    }
+
+    #[test]
+    fn test_handshake_times_out_only_while_waiting_for_hello() {
+        let handshake = HandshakeTimer::new(Duration::from_millis(10));
+        let connection_state = AtomicConnectionState::new(ConnectionState::Connected);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!handshake.check(&connection_state));
+
+        handshake.start();
+        handshake.finish();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!handshake.check(&connection_state));
+
+        handshake.start();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(handshake.check(&connection_state));
+        assert_eq!(connection_state.load(), ConnectionState::Disconnected);
+
+        // it only fires once per attempt
+        connection_state.store(ConnectionState::Connected);
+        assert!(!handshake.check(&connection_state));
+    }
+}
@@ -1,26 +1,104 @@
-use std::ffi::{c_char, CStr, CString};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::sync::{Arc, Mutex};
 use serde_json::Value;
 use tracing::{error, info};
+use warhorse_client::error::ClientError;
 use warhorse_client::{WarhorseClient, WarhorseEvent};
 use warhorse_client::warhorse_protocol::*;
 
-struct WarhorseClientImpl(Box<WarhorseClient>);
+struct WarhorseClientImpl {
+    client: Box<WarhorseClient>,
+    // shared with the client's event subscription, so it can be swapped without subscribing again
+    event_callback: Arc<Mutex<Option<EventCallback>>>,
+}
+
+/// Called with each event as soon as it arrives, see `client_set_event_callback`.
+pub type WarhorseEventCallback = Option<extern "C" fn(WarhorseEventData, *mut c_void)>;
+
+struct EventCallback {
+    callback: extern "C" fn(WarhorseEventData, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// the user data is only ever handed back to the callback, making that thread-safe is the caller's side of the contract
+unsafe impl Send for EventCallback {}
+
+impl EventCallback {
+    fn invoke(&self, event: &WarhorseEvent) {
+        let mut event_data = WarhorseEventData::empty();
+        fill_event_data(event.clone(), &mut event_data);
+        (self.callback)(event_data, self.user_data);
+    }
+}
 
 #[repr(C)]
 pub struct WarhorseClientHandle {
     _private: u8
 }
 
+#[repr(C)]
+pub enum WarhorseLanguage {
+    English,
+    Spanish,
+    French,
+    German,
+    BrazilianPortuguese,
+}
+
+impl From<WarhorseLanguage> for Language {
+    fn from(language: WarhorseLanguage) -> Self {
+        match language {
+            WarhorseLanguage::English => Language::English,
+            WarhorseLanguage::Spanish => Language::Spanish,
+            WarhorseLanguage::French => Language::French,
+            WarhorseLanguage::German => Language::German,
+            WarhorseLanguage::BrazilianPortuguese => Language::BrazilianPortuguese,
+        }
+    }
+}
+
+/// Who a chat message goes to, the target is a room ID or a friend's user ID respectively.
+#[repr(C)]
+pub enum WarhorseChatChannelKind {
+    Room,
+    PrivateMessage,
+}
+
 #[repr(C)]
 pub enum WarhorseEventType {
     Hello,
     LoggedIn,
     Error,
     FriendRequests,
+    FriendRequestReceived,
     FriendsList,
+    FriendsPage,
     BlockedList,
     FriendRequestAccepted,
     ChatMessage,
+    ChatHistory,
+    Typing,
+    SearchResults,
+    Profile,
+    RoomJoined,
+    RoomLeft,
+    PartyInviteReceived,
+    PartyUpdate,
+    Reconnecting,
+    Reconnected,
+    EventsDropped,
+    VersionMismatch,
+    AccountDeleted,
+    ServerShutdown,
+    ConnectionLost,
+    HandshakeTimeout,
+    Banned,
+    SendAck,
+    CurrentUser,
+    RoomMembers,
+    LoggedOut,
+    /// An event this version of the bindings doesn't know yet, `message` holds its name.
+    Unknown,
 }
 
 #[repr(C)]
@@ -29,13 +107,25 @@ pub struct WarhorseEventData {
     pub message: *mut c_char,  // Will contain JSON string for complex data
 }
 
+impl WarhorseEventData {
+    fn empty() -> Self {
+        Self {
+            event_type: WarhorseEventType::Hello,
+            message: std::ptr::null_mut(),
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn use_log() {
     tracing_subscriber::fmt::init();
 }
 
 #[no_mangle]
-pub extern "C" fn client_new(connection_string: *const c_char) -> *mut WarhorseClientHandle {
+pub extern "C" fn client_new(
+    language: WarhorseLanguage,
+    connection_string: *const c_char
+) -> *mut WarhorseClientHandle {
     let connection_str = unsafe {
         match CStr::from_ptr(connection_string).to_str() {
             Ok(s) => s,
@@ -43,9 +133,22 @@ pub extern "C" fn client_new(connection_string: *const c_char) -> *mut WarhorseC
         }
     };
 
-    match WarhorseClient::new(connection_str) {
+    match WarhorseClient::new(language.into(), connection_str) {
         Ok(client) => {
-            let impl_handle = Box::new(WarhorseClientImpl(Box::new(client)));
+            let event_callback: Arc<Mutex<Option<EventCallback>>> = Arc::new(Mutex::new(None));
+            client.on_event({
+                let event_callback = event_callback.clone();
+                move |event| {
+                    if let Some(callback) = event_callback.lock().unwrap().as_ref() {
+                        callback.invoke(event);
+                    }
+                }
+            });
+
+            let impl_handle = Box::new(WarhorseClientImpl {
+                client: Box::new(client),
+                event_callback,
+            });
             Box::into_raw(impl_handle) as *mut WarhorseClientHandle
         }
         Err(_) => std::ptr::null_mut()
@@ -86,13 +189,7 @@ pub extern "C" fn client_login_with_username(
         }
     };
 
-    let login = UserLogin {
-        language: Language::English,
-        identity: LoginUserIdentity::AccountName(username_str.to_string()),
-        password: password_str.to_string(),
-    };
-
-    match handle.0.send_user_login_request(login) {
+    match handle.client.send_user_login_request(username_str.to_string(), password_str.to_string()) {
         Ok(_) => {
             linfo("Attempting to login to Warhorse");
             true
@@ -104,6 +201,157 @@ pub extern "C" fn client_login_with_username(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn client_logout(handle: *mut WarhorseClientHandle) -> bool {
+    let Some(handle) = (unsafe { client_arg(handle, "logout") }) else { return false };
+    sent(handle.client.send_logout_request(), "logout request")
+}
+
+#[no_mangle]
+pub extern "C" fn client_register(
+    handle: *mut WarhorseClientHandle,
+    account_name: *const c_char,
+    password: *const c_char,
+    display_name: *const c_char,
+    email: *const c_char
+) -> bool {
+    let Some(handle) = (unsafe { client_arg(handle, "register") }) else { return false };
+    let Some(account_name) = (unsafe { str_arg(account_name, "account name") }) else { return false };
+    let Some(password) = (unsafe { str_arg(password, "password") }) else { return false };
+    let Some(display_name) = (unsafe { str_arg(display_name, "display name") }) else { return false };
+    let Some(email) = (unsafe { str_arg(email, "email") }) else { return false };
+
+    sent(
+        handle.client.send_user_registration_request(account_name, password, display_name, email),
+        "registration request"
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn client_send_friend_request(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "friend request", WarhorseClient::send_friend_request) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_accept_friend_request(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "friend request accept", WarhorseClient::send_accept_friend_request) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_reject_friend_request(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "friend request reject", WarhorseClient::send_reject_friend_request) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_cancel_friend_request(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "friend request cancel", WarhorseClient::send_cancel_friend_request) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_remove_friend(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "friend removal", WarhorseClient::send_remove_friend) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_block_friend(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "block", WarhorseClient::send_block_friend) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_unblock_friend(handle: *mut WarhorseClientHandle, friend_id: *const c_char) -> bool {
+    unsafe { send_friend_action(handle, friend_id, "unblock", WarhorseClient::send_unblock_friend) }
+}
+
+#[no_mangle]
+pub extern "C" fn client_send_chat_message(
+    handle: *mut WarhorseClientHandle,
+    channel_kind: WarhorseChatChannelKind,
+    target: *const c_char,
+    message: *const c_char
+) -> bool {
+    let Some(handle) = (unsafe { client_arg(handle, "send chat message") }) else { return false };
+    let Some(target) = (unsafe { str_arg(target, "chat target") }) else { return false };
+    let Some(message) = (unsafe { str_arg(message, "chat message") }) else { return false };
+
+    let result = match channel_kind {
+        WarhorseChatChannelKind::Room => handle.client.send_room_message(target.into(), message),
+        WarhorseChatChannelKind::PrivateMessage => handle.client.send_whisper_message(target.into(), message),
+    };
+    sent(result, "chat message")
+}
+
+/// # Safety
+/// Same as `client_arg` for `handle` and `str_arg` for `friend_id`.
+unsafe fn send_friend_action(
+    handle: *mut WarhorseClientHandle,
+    friend_id: *const c_char,
+    action: &str,
+    send: impl FnOnce(&WarhorseClient, UserId) -> Result<(), ClientError>
+) -> bool {
+    let Some(handle) = (unsafe { client_arg(handle, action) }) else { return false };
+    let Some(friend_id) = (unsafe { str_arg(friend_id, "friend id") }) else { return false };
+    sent(send(&handle.client, friend_id.into()), action)
+}
+
+/// # Safety
+/// `handle` must be null or a handle from `client_new` that hasn't been passed to `client_free`,
+/// and it must stay that way for as long as the returned reference is used.
+unsafe fn client_arg<'a>(handle: *mut WarhorseClientHandle, action: &str) -> Option<&'a WarhorseClientImpl> {
+    if handle.is_null() {
+        lerror(&format!("Null handle passed to {}", action));
+        return None;
+    }
+    Some(unsafe { &*(handle as *mut WarhorseClientImpl) })
+}
+
+/// # Safety
+/// `ptr` must be null or point to a nul-terminated string that stays valid and unchanged for the call.
+unsafe fn str_arg(ptr: *const c_char, name: &str) -> Option<String> {
+    if ptr.is_null() {
+        lerror(&format!("Null {} passed", name));
+        return None;
+    }
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Some(s.to_string()),
+        Err(e) => {
+            lerror(&format!("Error converting {} to string {}", name, e));
+            None
+        }
+    }
+}
+
+fn sent<T>(result: Result<T, ClientError>, what: &str) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            lerror(&format!("Error sending {}: {}", what, e));
+            false
+        }
+    }
+}
+
+/// Sets a callback that's invoked with every event as soon as it arrives, replacing any previous one.
+/// Pass a null callback to stop receiving them.
+///
+/// Threading: the callback runs on one of the client's background threads, never the caller's,
+/// so it must be thread-safe and return quickly. It must not call back into this function.
+/// Memory: the callback owns `message` and must free it with `free_string`.
+/// `user_data` is passed through untouched and has to outlive the client or the next call here.
+/// Events are still queued for `client_pump`, so polling keeps working alongside the callback.
+#[no_mangle]
+pub extern "C" fn client_set_event_callback(
+    handle: *mut WarhorseClientHandle,
+    callback: WarhorseEventCallback,
+    user_data: *mut c_void
+) -> bool {
+    let Some(handle) = (unsafe { client_arg(handle, "set event callback") }) else { return false };
+    *handle.event_callback.lock().unwrap() = callback.map(|callback| EventCallback { callback, user_data });
+    true
+}
+
+/// Fills `events` with up to `max_events` received events and returns how many were written.
+/// Ownership of every `message` is transferred to the caller, free them all with `free_event_array`
+/// (or each one with `free_string`) once they've been read.
 #[no_mangle]
 pub extern "C" fn client_pump(
     handle: *mut WarhorseClientHandle,
@@ -115,107 +363,374 @@ pub extern "C" fn client_pump(
         &*(handle as *mut WarhorseClientImpl)
     };
 
-    let rust_events = handle.0.pump();
+    let rust_events = handle.client.pump();
     let mut count = 0;
 
     for (i, event) in rust_events.into_iter().take(max_events).enumerate() {
         let event_data = unsafe {
             &mut *events.add(i)
         };
+        fill_event_data(event, event_data);
+        count += 1;
+    }
+    count
+}
 
-        match event {
-            WarhorseEvent::Hello => {
-                linfo("Received hello event");
-                event_data.event_type = WarhorseEventType::Hello;
-                match to_json_as_cstring(&Value::Null) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing hello message: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+fn fill_event_data(event: WarhorseEvent, event_data: &mut WarhorseEventData) {
+    match event {
+        WarhorseEvent::Hello => {
+            linfo("Received hello event");
+            event_data.event_type = WarhorseEventType::Hello;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing hello message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::LoggedIn => {
-                linfo("Received logged in event");
-                event_data.event_type = WarhorseEventType::LoggedIn;
-                match to_json_as_cstring(&Value::Null) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing logged in message: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::LoggedIn => {
+            linfo("Received logged in event");
+            event_data.event_type = WarhorseEventType::LoggedIn;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing logged in message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::Error(msg) => {
-                linfo(&format!("Received error event: {:?}", msg).as_str());
-                event_data.event_type = WarhorseEventType::Error;
-                match to_json_as_cstring(&msg) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing error message: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::LoggedOut => {
+            linfo("Received logged out event");
+            event_data.event_type = WarhorseEventType::LoggedOut;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing logged out message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::FriendRequests(friends) => {
-                linfo(&format!("Received friend requests event: {:?}", friends).as_str());
-                event_data.event_type = WarhorseEventType::FriendRequests;
-                match to_json_as_cstring(&friends) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing friend requests: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::CurrentUser(user) => {
+            linfo(&format!("Received current user event: {}", user.id).as_str());
+            event_data.event_type = WarhorseEventType::CurrentUser;
+            match to_json_as_cstring(&user) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing current user: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::FriendsList(friends) => {
-                linfo(&format!("Received friends list event: {:?}", friends).as_str());
-                event_data.event_type = WarhorseEventType::FriendsList;
-                match to_json_as_cstring(&friends) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing friends list: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::Error { code, message } => {
+            linfo(&format!("Received error event: {:?} {:?}", code, message).as_str());
+            event_data.event_type = WarhorseEventType::Error;
+            match to_json_as_cstring(&serde_json::json!({ "code": code, "message": message })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing error message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::BlockedList(friends) => {
-                linfo(&format!("Received blocked list event: {:?}", friends).as_str());
-                event_data.event_type = WarhorseEventType::BlockedList;
-                match to_json_as_cstring(&friends) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing blocked list: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::FriendRequests(friends) => {
+            linfo(&format!("Received friend requests event: {:?}", friends).as_str());
+            event_data.event_type = WarhorseEventType::FriendRequests;
+            match to_json_as_cstring(&friends) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing friend requests: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::FriendRequestAccepted(friend) => {
-                linfo(&format!("Received friend request accepted event: {:?}", friend).as_str());
-                event_data.event_type = WarhorseEventType::FriendRequestAccepted;
-                match to_json_as_cstring(&friend) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing friend request accepted: {}", e));
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::FriendsList(friends) => {
+            linfo(&format!("Received friends list event: {:?}", friends).as_str());
+            event_data.event_type = WarhorseEventType::FriendsList;
+            match to_json_as_cstring(&friends) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing friends list: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
-            WarhorseEvent::ChatMessage(msg) => {
-                linfo(&format!("Received chat message event: {:?}", msg).as_str());
-                event_data.event_type = WarhorseEventType::ChatMessage;
-                match to_json_as_cstring(&msg) {
-                    Ok(cstr) => event_data.message = cstr.into_raw(),
-                    Err(e) => {
-                        lerror(&format!("Error serializing chat message: {}", e).as_str());
-                        event_data.message = std::ptr::null_mut()
-                    },
-                }
+        }
+        WarhorseEvent::FriendsPage { offset, total, friends } => {
+            linfo(&format!("Received friends page event: {} friends from {} of {}", friends.len(), offset, total));
+            event_data.event_type = WarhorseEventType::FriendsPage;
+            match to_json_as_cstring(&FriendsPage { offset, total, friends }) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing friends page: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::BlockedList(friends) => {
+            linfo(&format!("Received blocked list event: {:?}", friends).as_str());
+            event_data.event_type = WarhorseEventType::BlockedList;
+            match to_json_as_cstring(&friends) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing blocked list: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::FriendRequestReceived(friend) => {
+            linfo(&format!("Received friend request received event: {:?}", friend).as_str());
+            event_data.event_type = WarhorseEventType::FriendRequestReceived;
+            match to_json_as_cstring(&friend) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing friend request received: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::FriendRequestAccepted(friend) => {
+            linfo(&format!("Received friend request accepted event: {:?}", friend).as_str());
+            event_data.event_type = WarhorseEventType::FriendRequestAccepted;
+            match to_json_as_cstring(&friend) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing friend request accepted: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::ChatMessage(msg) => {
+            linfo(&format!("Received chat message event: {:?}", msg).as_str());
+            event_data.event_type = WarhorseEventType::ChatMessage;
+            match to_json_as_cstring(&msg) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing chat message: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::ChatHistory(messages) => {
+            linfo(&format!("Received chat history event: {} messages", messages.len()).as_str());
+            event_data.event_type = WarhorseEventType::ChatHistory;
+            match to_json_as_cstring(&messages) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing chat history: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::Typing { display_name, channel, is_typing } => {
+            event_data.event_type = WarhorseEventType::Typing;
+            let typing = UserTyping { display_name, channel, is_typing };
+            match to_json_as_cstring(&typing) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing typing indicator: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::SearchResults(users) => {
+            linfo(&format!("Received search results event: {} users", users.len()).as_str());
+            event_data.event_type = WarhorseEventType::SearchResults;
+            match to_json_as_cstring(&users) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing search results: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::Profile(profile) => {
+            linfo(&format!("Received profile event: {}", profile.id).as_str());
+            event_data.event_type = WarhorseEventType::Profile;
+            match to_json_as_cstring(&profile) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing profile: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::RoomJoined(room_id) => {
+            linfo(&format!("Received room joined event: {}", room_id).as_str());
+            event_data.event_type = WarhorseEventType::RoomJoined;
+            match to_json_as_cstring(&room_id) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing room joined: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::RoomLeft(room_id) => {
+            linfo(&format!("Received room left event: {}", room_id).as_str());
+            event_data.event_type = WarhorseEventType::RoomLeft;
+            match to_json_as_cstring(&room_id) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing room left: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::RoomMembers { room_id, members } => {
+            linfo(&format!("Received room members event: {} has {} members", room_id, members.len()).as_str());
+            event_data.event_type = WarhorseEventType::RoomMembers;
+            match to_json_as_cstring(&RoomMembers { room_id, members }) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing room members: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::PartyInviteReceived { party_id, friend } => {
+            linfo(&format!("Received party invite event: {} from {}", party_id, friend.display_name).as_str());
+            event_data.event_type = WarhorseEventType::PartyInviteReceived;
+            match to_json_as_cstring(&PartyInviteReceived { party_id, friend }) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing party invite: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::PartyUpdate { party_id, members } => {
+            linfo(&format!("Received party update event: {} has {} members", party_id, members.len()).as_str());
+            event_data.event_type = WarhorseEventType::PartyUpdate;
+            match to_json_as_cstring(&PartyUpdate { party_id, members }) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing party update: {}", e).as_str());
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::Reconnecting { attempt } => {
+            linfo(&format!("Received reconnecting event: attempt {}", attempt).as_str());
+            event_data.event_type = WarhorseEventType::Reconnecting;
+            match to_json_as_cstring(&serde_json::json!({ "attempt": attempt })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing reconnecting message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::Reconnected => {
+            linfo("Received reconnected event");
+            event_data.event_type = WarhorseEventType::Reconnected;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing reconnected message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::EventsDropped { count } => {
+            lerror(&format!("Dropped {} events that were never pumped", count));
+            event_data.event_type = WarhorseEventType::EventsDropped;
+            match to_json_as_cstring(&serde_json::json!({ "count": count })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing events dropped message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::VersionMismatch { server, client } => {
+            lerror(&format!("Server protocol version {} doesn't match ours ({})", server, client));
+            event_data.event_type = WarhorseEventType::VersionMismatch;
+            match to_json_as_cstring(&serde_json::json!({ "server": server, "client": client })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing version mismatch message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::AccountDeleted => {
+            linfo("Received account deleted event");
+            event_data.event_type = WarhorseEventType::AccountDeleted;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing account deleted message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::ConnectionLost => {
+            linfo("Received connection lost event");
+            event_data.event_type = WarhorseEventType::ConnectionLost;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing connection lost message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::HandshakeTimeout => {
+            linfo("Received handshake timeout event");
+            event_data.event_type = WarhorseEventType::HandshakeTimeout;
+            match to_json_as_cstring(&Value::Null) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing handshake timeout message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::ServerShutdown { reason } => {
+            linfo("Received server shutdown event");
+            event_data.event_type = WarhorseEventType::ServerShutdown;
+            match to_json_as_cstring(&serde_json::json!({ "reason": reason })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing server shutdown message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::Banned { reason } => {
+            linfo("Received banned event");
+            event_data.event_type = WarhorseEventType::Banned;
+            match to_json_as_cstring(&serde_json::json!({ "reason": reason })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing banned message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        WarhorseEvent::SendAck { request_id, result } => {
+            linfo(&format!("Received send ack event: {} {:?}", request_id, result).as_str());
+            event_data.event_type = WarhorseEventType::SendAck;
+            match to_json_as_cstring(&serde_json::json!({ "request_id": request_id, "error": result.err() })) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing send ack message: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
+            }
+        }
+        event => {
+            linfo(&format!("Received unknown event: {}", event).as_str());
+            event_data.event_type = WarhorseEventType::Unknown;
+            match to_json_as_cstring(&event.kind().to_string()) {
+                Ok(cstr) => event_data.message = cstr.into_raw(),
+                Err(e) => {
+                    lerror(&format!("Error serializing unknown event: {}", e));
+                    event_data.message = std::ptr::null_mut()
+                },
             }
         }
-        count += 1;
     }
-    count
 }
 
 fn to_json_as_cstring<T: serde::Serialize>(value: &T) -> Result<CString, String> {
@@ -291,4 +806,225 @@ pub extern "C" fn free_string(ptr: *mut c_char) {
             let _ = CString::from_raw(ptr);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Frees every `message` in the first `count` events filled by `client_pump`.
+/// The array itself belongs to the caller, only the strings are freed.
+#[no_mangle]
+pub extern "C" fn free_event_array(events: *mut WarhorseEventData, count: usize) {
+    if events.is_null() {
+        return;
+    }
+
+    for i in 0..count {
+        let event_data = unsafe { &mut *events.add(i) };
+        free_string(event_data.message);
+        event_data.message = std::ptr::null_mut();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+    // counts the bytes this thread has live, other test threads don't disturb it
+    struct CountingAllocator;
+
+    thread_local! {
+        static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn live_bytes() -> isize {
+        LIVE_BYTES.with(|live| live.get())
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = LIVE_BYTES.try_with(|live| live.set(live.get() + layout.size() as isize));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = LIVE_BYTES.try_with(|live| live.set(live.get() - layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_free_event_array_frees_every_message() {
+        let rust_events = vec![
+            WarhorseEvent::Hello,
+            WarhorseEvent::RoomJoined(RoomId::from("lobby")),
+            WarhorseEvent::Reconnecting { attempt: 2 },
+        ];
+        let mut events: Vec<WarhorseEventData> = (0..rust_events.len()).map(|_| WarhorseEventData::empty()).collect();
+        for (event, event_data) in rust_events.into_iter().zip(events.iter_mut()) {
+            fill_event_data(event, event_data);
+        }
+
+        // each message is a boxed C string, so it owns exactly its length plus the nul
+        let message_bytes: isize = events.iter()
+            .map(|event| unsafe { CStr::from_ptr(event.message) }.to_bytes_with_nul().len() as isize)
+            .sum();
+        assert!(message_bytes > 0);
+
+        let before = live_bytes();
+        free_event_array(events.as_mut_ptr(), events.len());
+        assert_eq!(before - live_bytes(), message_bytes);
+        assert!(events.iter().all(|event| event.message.is_null()));
+
+        // freeing again, or a null array, is harmless
+        free_event_array(events.as_mut_ptr(), events.len());
+        free_event_array(std::ptr::null_mut(), 3);
+        assert_eq!(before - live_bytes(), message_bytes);
+    }
+
+    #[test]
+    fn test_friend_request_received_maps_to_its_event_type() {
+        let friend = Friend {
+            id: UserId::from("1"),
+            display_name: "Test User".to_string(),
+            status: FriendStatus::FriendRequestReceived,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        let mut event_data = WarhorseEventData::empty();
+
+        fill_event_data(WarhorseEvent::FriendRequestReceived(friend.clone()), &mut event_data);
+
+        assert!(matches!(event_data.event_type, WarhorseEventType::FriendRequestReceived));
+        let json = unsafe { CStr::from_ptr(event_data.message) }.to_str().unwrap();
+        assert_eq!(serde_json::from_str::<Friend>(json).unwrap(), friend);
+        free_event_array(&mut event_data, 1);
+    }
+
+    fn c_string(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    // starts a server on an ephemeral port and connects a client to it
+    fn connected_handle() -> *mut WarhorseClientHandle {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+                warhorse_server::serve(listener, app, server, std::future::pending()).await.unwrap();
+            });
+        });
+
+        let connection_string = c_string(&format!("http://{}", receiver.recv().unwrap()));
+        let handle = client_new(WarhorseLanguage::English, connection_string.as_ptr());
+        assert!(!handle.is_null());
+        handle
+    }
+
+    // every wrapper that takes a single friend ID
+    const FRIEND_ACTIONS: [extern "C" fn(*mut WarhorseClientHandle, *const c_char) -> bool; 7] = [
+        client_send_friend_request,
+        client_accept_friend_request,
+        client_reject_friend_request,
+        client_cancel_friend_request,
+        client_remove_friend,
+        client_block_friend,
+        client_unblock_friend,
+    ];
+
+    #[test]
+    fn test_sends_reject_null_handle() {
+        let text = c_string("text");
+        let handle = std::ptr::null_mut();
+
+        assert!(!client_register(handle, text.as_ptr(), text.as_ptr(), text.as_ptr(), text.as_ptr()));
+        for action in FRIEND_ACTIONS {
+            assert!(!action(handle, text.as_ptr()));
+        }
+        assert!(!client_send_chat_message(handle, WarhorseChatChannelKind::Room, text.as_ptr(), text.as_ptr()));
+    }
+
+    #[test]
+    fn test_sends_reject_null_and_invalid_strings() {
+        let handle = connected_handle();
+        let text = c_string("text");
+        let null = std::ptr::null();
+        let invalid_utf8 = [0xffu8, 0];
+        let invalid = invalid_utf8.as_ptr() as *const c_char;
+
+        for bad in [null, invalid] {
+            assert!(!client_register(handle, bad, text.as_ptr(), text.as_ptr(), text.as_ptr()));
+            assert!(!client_register(handle, text.as_ptr(), text.as_ptr(), text.as_ptr(), bad));
+            for action in FRIEND_ACTIONS {
+                assert!(!action(handle, bad));
+            }
+            assert!(!client_send_chat_message(handle, WarhorseChatChannelKind::Room, bad, text.as_ptr()));
+            assert!(!client_send_chat_message(handle, WarhorseChatChannelKind::PrivateMessage, text.as_ptr(), bad));
+        }
+
+        client_free(handle);
+    }
+
+    #[test]
+    fn test_sends_are_queued_with_valid_arguments() {
+        let handle = connected_handle();
+        let account_name = c_string("test");
+        let password = c_string("password");
+        let display_name = c_string("Test User");
+        let email = c_string("test@example.com");
+        let friend_id = c_string("1");
+        let message = c_string("hello");
+
+        assert!(client_register(handle, account_name.as_ptr(), password.as_ptr(), display_name.as_ptr(), email.as_ptr()));
+        for action in FRIEND_ACTIONS {
+            assert!(action(handle, friend_id.as_ptr()));
+        }
+        assert!(client_send_chat_message(handle, WarhorseChatChannelKind::Room, c_string("general").as_ptr(), message.as_ptr()));
+        assert!(client_send_chat_message(handle, WarhorseChatChannelKind::PrivateMessage, friend_id.as_ptr(), message.as_ptr()));
+
+        client_free(handle);
+    }
+
+    // records what the callback was handed, through its user data
+    extern "C" fn record_event(event_data: WarhorseEventData, user_data: *mut c_void) {
+        let received = unsafe { &*(user_data as *const Mutex<Vec<String>>) };
+        let message = unsafe { CStr::from_ptr(event_data.message) }.to_str().unwrap().to_string();
+        received.lock().unwrap().push(message);
+        free_string(event_data.message);
+    }
+
+    #[test]
+    fn test_event_callback_fires_with_user_data() {
+        let received: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let callback = EventCallback {
+            callback: record_event,
+            user_data: &received as *const _ as *mut c_void,
+        };
+
+        callback.invoke(&WarhorseEvent::RoomJoined(RoomId::from("lobby")));
+
+        assert_eq!(*received.lock().unwrap(), vec!["\"lobby\"".to_string()]);
+    }
+
+    #[test]
+    fn test_set_event_callback() {
+        assert!(!client_set_event_callback(std::ptr::null_mut(), Some(record_event), std::ptr::null_mut()));
+
+        let handle = connected_handle();
+        let received: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        assert!(client_set_event_callback(handle, Some(record_event), &received as *const _ as *mut c_void));
+        let client = unsafe { &*(handle as *mut WarhorseClientImpl) };
+        assert!(client.event_callback.lock().unwrap().is_some());
+
+        assert!(client_set_event_callback(handle, None, std::ptr::null_mut()));
+        assert!(client.event_callback.lock().unwrap().is_none());
+        client_free(handle);
+    }
+}
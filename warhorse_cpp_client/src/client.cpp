@@ -3,7 +3,7 @@
 #include <iostream>
 #include <thread>
 
-WarhorseClient::WarhorseClient(const std::string& connection_string) {
+WarhorseClient::WarhorseClient(warhorse::WarhorseLanguage language, const std::string& connection_string) {
 
     // State
     logged_in = false;
@@ -15,12 +15,15 @@ WarhorseClient::WarhorseClient(const std::string& connection_string) {
     on_logged_in = nullptr;
     on_error = nullptr;
     on_friend_requests = nullptr;
+    on_friend_request_received = nullptr;
     on_friends_list = nullptr;
     on_blocked_list = nullptr;
     on_friend_request_accepted = nullptr;
     on_chat_message = nullptr;
+    on_reconnecting = nullptr;
+    on_reconnected = nullptr;
 
-    handle = warhorse::client_new(connection_string.c_str());
+    handle = warhorse::client_new(language, connection_string.c_str());
 }
 
 WarhorseClient::~WarhorseClient() {
@@ -38,6 +41,54 @@ bool WarhorseClient::login(const std::string& username, const std::string& passw
     return false;
 }
 
+bool WarhorseClient::logout() {
+    return warhorse::client_logout(handle);
+}
+
+bool WarhorseClient::register_user(const std::string& account_name, const std::string& password, const std::string& display_name, const std::string& email) {
+    return warhorse::client_register(handle, account_name.c_str(), password.c_str(), display_name.c_str(), email.c_str());
+}
+
+bool WarhorseClient::send_friend_request(const std::string& friend_id) {
+    return warhorse::client_send_friend_request(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::accept_friend_request(const std::string& friend_id) {
+    return warhorse::client_accept_friend_request(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::reject_friend_request(const std::string& friend_id) {
+    return warhorse::client_reject_friend_request(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::cancel_friend_request(const std::string& friend_id) {
+    return warhorse::client_cancel_friend_request(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::remove_friend(const std::string& friend_id) {
+    return warhorse::client_remove_friend(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::block_friend(const std::string& friend_id) {
+    return warhorse::client_block_friend(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::unblock_friend(const std::string& friend_id) {
+    return warhorse::client_unblock_friend(handle, friend_id.c_str());
+}
+
+bool WarhorseClient::send_room_message(const std::string& room_id, const std::string& message) {
+    return warhorse::client_send_chat_message(handle, warhorse::WarhorseChatChannelKind::Room, room_id.c_str(), message.c_str());
+}
+
+bool WarhorseClient::send_whisper_message(const std::string& friend_id, const std::string& message) {
+    return warhorse::client_send_chat_message(handle, warhorse::WarhorseChatChannelKind::PrivateMessage, friend_id.c_str(), message.c_str());
+}
+
+bool WarhorseClient::set_event_callback(warhorse::WarhorseEventCallback callback, void* user_data) {
+    return warhorse::client_set_event_callback(handle, callback, user_data);
+}
+
 bool WarhorseClient::pump_messages(std::vector<Message>& messages) {
 
     constexpr size_t MAX_EVENTS = 32;
@@ -60,9 +111,15 @@ bool WarhorseClient::pump_messages(std::vector<Message>& messages) {
             case warhorse::WarhorseEventType::FriendRequests:
                 message.type = FRIEND_REQUESTS;
                 break;
+            case warhorse::WarhorseEventType::FriendRequestReceived:
+                message.type = FRIEND_REQUEST_RECEIVED;
+                break;
             case warhorse::WarhorseEventType::FriendsList:
                 message.type = FRIENDS_LIST;
                 break;
+            case warhorse::WarhorseEventType::FriendsPage:
+                message.type = FRIENDS_PAGE;
+                break;
             case warhorse::WarhorseEventType::BlockedList:
                 message.type = BLOCKED_LIST;
                 break;
@@ -72,11 +129,83 @@ bool WarhorseClient::pump_messages(std::vector<Message>& messages) {
             case warhorse::WarhorseEventType::ChatMessage:
                 message.type = CHAT_MESSAGE;
                 break;
+            case warhorse::WarhorseEventType::ChatHistory:
+                message.type = CHAT_HISTORY;
+                break;
+            case warhorse::WarhorseEventType::Typing:
+                message.type = TYPING;
+                break;
+            case warhorse::WarhorseEventType::SearchResults:
+                message.type = SEARCH_RESULTS;
+                break;
+            case warhorse::WarhorseEventType::Profile:
+                message.type = PROFILE;
+                break;
+            case warhorse::WarhorseEventType::RoomJoined:
+                message.type = ROOM_JOINED;
+                break;
+            case warhorse::WarhorseEventType::RoomLeft:
+                message.type = ROOM_LEFT;
+                break;
+            case warhorse::WarhorseEventType::PartyInviteReceived:
+                message.type = PARTY_INVITE_RECEIVED;
+                break;
+            case warhorse::WarhorseEventType::PartyUpdate:
+                message.type = PARTY_UPDATE;
+                break;
+            case warhorse::WarhorseEventType::Reconnecting:
+                message.type = RECONNECTING;
+                break;
+            case warhorse::WarhorseEventType::Reconnected:
+                message.type = RECONNECTED;
+                break;
+            case warhorse::WarhorseEventType::EventsDropped:
+                message.type = EVENTS_DROPPED;
+                break;
+            case warhorse::WarhorseEventType::VersionMismatch:
+                message.type = VERSION_MISMATCH;
+                break;
+            case warhorse::WarhorseEventType::AccountDeleted:
+                message.type = ACCOUNT_DELETED;
+                logged_in = false;
+                break;
+            case warhorse::WarhorseEventType::ServerShutdown:
+                message.type = SERVER_SHUTDOWN;
+                break;
+            case warhorse::WarhorseEventType::ConnectionLost:
+                message.type = CONNECTION_LOST;
+                break;
+            case warhorse::WarhorseEventType::HandshakeTimeout:
+                message.type = HANDSHAKE_TIMEOUT;
+                break;
+            case warhorse::WarhorseEventType::Banned:
+                message.type = BANNED;
+                logged_in = false;
+                break;
+            case warhorse::WarhorseEventType::SendAck:
+                message.type = SEND_ACK;
+                break;
+            case warhorse::WarhorseEventType::CurrentUser:
+                message.type = CURRENT_USER;
+                break;
+            case warhorse::WarhorseEventType::RoomMembers:
+                message.type = ROOM_MEMBERS;
+                break;
+            case warhorse::WarhorseEventType::LoggedOut:
+                message.type = LOGGED_OUT;
+                // still connected, so we can log in again without another hello
+                logged_in = false;
+                sent_login_request = false;
+                break;
+            case warhorse::WarhorseEventType::Unknown:
+                // newer than this wrapper, there's no callback to hand it to
+                continue;
         }
 
         message.message = events[i].message;
         messages.push_back(message);
     }
+    warhorse::free_event_array(events, event_count);
 
     return event_count > 0;
 }
@@ -88,7 +217,7 @@ bool WarhorseClient::is_ready_for_login() const {
 int test_warhorse_client() {
     warhorse::use_log();
 
-    auto client = std::make_shared<WarhorseClient>("http://localhost:3000");
+    auto client = std::make_shared<WarhorseClient>(warhorse::WarhorseLanguage::English, "http://localhost:3000");
 
     std::weak_ptr<WarhorseClient> weak_client = client;
 
@@ -148,9 +277,15 @@ int test_warhorse_client() {
                     case FRIEND_REQUESTS:
                         if (client->on_friend_requests) client->on_friend_requests(message.message.c_str());
                         break;
+                    case FRIEND_REQUEST_RECEIVED:
+                        if (client->on_friend_request_received) client->on_friend_request_received(message.message.c_str());
+                        break;
                     case FRIENDS_LIST:
                         if (client->on_friends_list) client->on_friends_list(message.message.c_str());
                         break;
+                    case FRIENDS_PAGE:
+                        if (client->on_friends_page) client->on_friends_page(message.message.c_str());
+                        break;
                     case BLOCKED_LIST:
                         if (client->on_blocked_list) client->on_blocked_list(message.message.c_str());
                         break;
@@ -160,6 +295,69 @@ int test_warhorse_client() {
                     case CHAT_MESSAGE:
                         if (client->on_chat_message) client->on_chat_message(message.message.c_str());
                         break;
+                    case CHAT_HISTORY:
+                        if (client->on_chat_history) client->on_chat_history(message.message.c_str());
+                        break;
+                    case TYPING:
+                        if (client->on_typing) client->on_typing(message.message.c_str());
+                        break;
+                    case SEARCH_RESULTS:
+                        if (client->on_search_results) client->on_search_results(message.message.c_str());
+                        break;
+                    case PROFILE:
+                        if (client->on_profile) client->on_profile(message.message.c_str());
+                        break;
+                    case ROOM_JOINED:
+                        if (client->on_room_joined) client->on_room_joined(message.message.c_str());
+                        break;
+                    case ROOM_LEFT:
+                        if (client->on_room_left) client->on_room_left(message.message.c_str());
+                        break;
+                    case PARTY_INVITE_RECEIVED:
+                        if (client->on_party_invite_received) client->on_party_invite_received(message.message.c_str());
+                        break;
+                    case PARTY_UPDATE:
+                        if (client->on_party_update) client->on_party_update(message.message.c_str());
+                        break;
+                    case RECONNECTING:
+                        if (client->on_reconnecting) client->on_reconnecting(message.message.c_str());
+                        break;
+                    case RECONNECTED:
+                        if (client->on_reconnected) client->on_reconnected(message.message.c_str());
+                        break;
+                    case EVENTS_DROPPED:
+                        if (client->on_events_dropped) client->on_events_dropped(message.message.c_str());
+                        break;
+                    case VERSION_MISMATCH:
+                        if (client->on_version_mismatch) client->on_version_mismatch(message.message.c_str());
+                        break;
+                    case ACCOUNT_DELETED:
+                        if (client->on_account_deleted) client->on_account_deleted(message.message.c_str());
+                        break;
+                    case SERVER_SHUTDOWN:
+                        if (client->on_server_shutdown) client->on_server_shutdown(message.message.c_str());
+                        break;
+                    case CONNECTION_LOST:
+                        if (client->on_connection_lost) client->on_connection_lost(message.message.c_str());
+                        break;
+                    case HANDSHAKE_TIMEOUT:
+                        if (client->on_handshake_timeout) client->on_handshake_timeout(message.message.c_str());
+                        break;
+                    case BANNED:
+                        if (client->on_banned) client->on_banned(message.message.c_str());
+                        break;
+                    case SEND_ACK:
+                        if (client->on_send_ack) client->on_send_ack(message.message.c_str());
+                        break;
+                    case CURRENT_USER:
+                        if (client->on_current_user) client->on_current_user(message.message.c_str());
+                        break;
+                    case ROOM_MEMBERS:
+                        if (client->on_room_members) client->on_room_members(message.message.c_str());
+                        break;
+                    case LOGGED_OUT:
+                        if (client->on_logged_out) client->on_logged_out(message.message.c_str());
+                        break;
                 }
             }
         }
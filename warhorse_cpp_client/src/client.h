@@ -10,10 +10,33 @@ enum MessageType {
     LOGGED_IN,
     ERROR,
     FRIEND_REQUESTS,
+    FRIEND_REQUEST_RECEIVED,
     FRIENDS_LIST,
+    FRIENDS_PAGE,
     BLOCKED_LIST,
     FRIEND_REQUEST_ACCEPTED,
-    CHAT_MESSAGE
+    CHAT_MESSAGE,
+    CHAT_HISTORY,
+    TYPING,
+    SEARCH_RESULTS,
+    PROFILE,
+    ROOM_JOINED,
+    ROOM_LEFT,
+    PARTY_INVITE_RECEIVED,
+    PARTY_UPDATE,
+    RECONNECTING,
+    RECONNECTED,
+    EVENTS_DROPPED,
+    VERSION_MISMATCH,
+    ACCOUNT_DELETED,
+    SERVER_SHUTDOWN,
+    CONNECTION_LOST,
+    HANDSHAKE_TIMEOUT,
+    BANNED,
+    SEND_ACK,
+    CURRENT_USER,
+    ROOM_MEMBERS,
+    LOGGED_OUT
 };
 
 struct Message {
@@ -27,10 +50,23 @@ static void log(const std::string& message);
 
 class WarhorseClient {
 public:
-    WarhorseClient(const std::string& connection_string);
+    WarhorseClient(warhorse::WarhorseLanguage language, const std::string& connection_string);
     ~WarhorseClient();
     bool login(const std::string& username, const std::string& password);
+    bool logout();
+    bool register_user(const std::string& account_name, const std::string& password, const std::string& display_name, const std::string& email);
+    bool send_friend_request(const std::string& friend_id);
+    bool accept_friend_request(const std::string& friend_id);
+    bool reject_friend_request(const std::string& friend_id);
+    bool cancel_friend_request(const std::string& friend_id);
+    bool remove_friend(const std::string& friend_id);
+    bool block_friend(const std::string& friend_id);
+    bool unblock_friend(const std::string& friend_id);
+    bool send_room_message(const std::string& room_id, const std::string& message);
+    bool send_whisper_message(const std::string& friend_id, const std::string& message);
     bool pump_messages(std::vector<Message>& messages);
+    // Invoked on a Warhorse background thread for each event, the callback must free the message with warhorse::free_string
+    bool set_event_callback(warhorse::WarhorseEventCallback callback, void* user_data);
     bool is_ready_for_login() const;
 
     // Callbacks
@@ -38,20 +74,66 @@ public:
     WarhorseCallback on_logged_in;
     WarhorseCallback on_error;
     WarhorseCallback on_friend_requests;
+    WarhorseCallback on_friend_request_received;
     WarhorseCallback on_friends_list;
+    WarhorseCallback on_friends_page;
     WarhorseCallback on_blocked_list;
     WarhorseCallback on_friend_request_accepted;
     WarhorseCallback on_chat_message;
+    WarhorseCallback on_chat_history;
+    WarhorseCallback on_typing;
+    WarhorseCallback on_search_results;
+    WarhorseCallback on_profile;
+    WarhorseCallback on_room_joined;
+    WarhorseCallback on_room_left;
+    WarhorseCallback on_party_invite_received;
+    WarhorseCallback on_party_update;
+    WarhorseCallback on_reconnecting;
+    WarhorseCallback on_reconnected;
+    WarhorseCallback on_events_dropped;
+    WarhorseCallback on_version_mismatch;
+    WarhorseCallback on_account_deleted;
+    WarhorseCallback on_server_shutdown;
+    WarhorseCallback on_connection_lost;
+    WarhorseCallback on_handshake_timeout;
+    WarhorseCallback on_banned;
+    WarhorseCallback on_send_ack;
+    WarhorseCallback on_current_user;
+    WarhorseCallback on_room_members;
+    WarhorseCallback on_logged_out;
 
     // Binds to callbacks
     void bind_on_hello(WarhorseCallback cb) { on_hello = cb; }
     void bind_on_logged_in(WarhorseCallback cb) { on_logged_in = cb; }
     void bind_on_error(WarhorseCallback cb) { on_error = cb; }
     void bind_on_friend_requests(WarhorseCallback cb) { on_friend_requests = cb; }
+    void bind_on_friend_request_received(WarhorseCallback cb) { on_friend_request_received = cb; }
     void bind_on_friends_list(WarhorseCallback cb) { on_friends_list = cb; }
+    void bind_on_friends_page(WarhorseCallback cb) { on_friends_page = cb; }
     void bind_on_blocked_list(WarhorseCallback cb) { on_blocked_list = cb; }
     void bind_on_friend_request_accepted(WarhorseCallback cb) { on_friend_request_accepted = cb; }
     void bind_on_chat_message(WarhorseCallback cb) { on_chat_message = cb; }
+    void bind_on_chat_history(WarhorseCallback cb) { on_chat_history = cb; }
+    void bind_on_typing(WarhorseCallback cb) { on_typing = cb; }
+    void bind_on_search_results(WarhorseCallback cb) { on_search_results = cb; }
+    void bind_on_profile(WarhorseCallback cb) { on_profile = cb; }
+    void bind_on_room_joined(WarhorseCallback cb) { on_room_joined = cb; }
+    void bind_on_room_left(WarhorseCallback cb) { on_room_left = cb; }
+    void bind_on_party_invite_received(WarhorseCallback cb) { on_party_invite_received = cb; }
+    void bind_on_party_update(WarhorseCallback cb) { on_party_update = cb; }
+    void bind_on_reconnecting(WarhorseCallback cb) { on_reconnecting = cb; }
+    void bind_on_reconnected(WarhorseCallback cb) { on_reconnected = cb; }
+    void bind_on_events_dropped(WarhorseCallback cb) { on_events_dropped = cb; }
+    void bind_on_version_mismatch(WarhorseCallback cb) { on_version_mismatch = cb; }
+    void bind_on_account_deleted(WarhorseCallback cb) { on_account_deleted = cb; }
+    void bind_on_server_shutdown(WarhorseCallback cb) { on_server_shutdown = cb; }
+    void bind_on_connection_lost(WarhorseCallback cb) { on_connection_lost = cb; }
+    void bind_on_handshake_timeout(WarhorseCallback cb) { on_handshake_timeout = cb; }
+    void bind_on_banned(WarhorseCallback cb) { on_banned = cb; }
+    void bind_on_send_ack(WarhorseCallback cb) { on_send_ack = cb; }
+    void bind_on_current_user(WarhorseCallback cb) { on_current_user = cb; }
+    void bind_on_room_members(WarhorseCallback cb) { on_room_members = cb; }
+    void bind_on_logged_out(WarhorseCallback cb) { on_logged_out = cb; }
 private:
     bool logged_in;
     bool received_hello;
@@ -6,15 +6,54 @@
 
 namespace warhorse {
 
+/// Who a chat message goes to, the target is a room ID or a friend's user ID respectively.
+enum class WarhorseChatChannelKind {
+  Room,
+  PrivateMessage,
+};
+
 enum class WarhorseEventType {
   Hello,
   LoggedIn,
   Error,
   FriendRequests,
+  FriendRequestReceived,
   FriendsList,
+  FriendsPage,
   BlockedList,
   FriendRequestAccepted,
   ChatMessage,
+  ChatHistory,
+  Typing,
+  SearchResults,
+  Profile,
+  RoomJoined,
+  RoomLeft,
+  PartyInviteReceived,
+  PartyUpdate,
+  Reconnecting,
+  Reconnected,
+  EventsDropped,
+  VersionMismatch,
+  AccountDeleted,
+  ServerShutdown,
+  ConnectionLost,
+  HandshakeTimeout,
+  Banned,
+  SendAck,
+  CurrentUser,
+  RoomMembers,
+  LoggedOut,
+  /// An event this version of the bindings doesn't know yet, `message` holds its name.
+  Unknown,
+};
+
+enum class WarhorseLanguage {
+  English,
+  Spanish,
+  French,
+  German,
+  BrazilianPortuguese,
 };
 
 struct WarhorseClientHandle {
@@ -26,16 +65,61 @@ struct WarhorseEventData {
   char *message;
 };
 
+/// Called with each event as soon as it arrives, see `client_set_event_callback`.
+using WarhorseEventCallback = void(*)(WarhorseEventData, void*);
+
 extern "C" {
 
 void use_log();
 
-WarhorseClientHandle *client_new(const char *connection_string);
+WarhorseClientHandle *client_new(WarhorseLanguage language, const char *connection_string);
 
 bool client_login_with_username(WarhorseClientHandle *handle,
                                 const char *username,
                                 const char *password);
 
+bool client_logout(WarhorseClientHandle *handle);
+
+bool client_register(WarhorseClientHandle *handle,
+                     const char *account_name,
+                     const char *password,
+                     const char *display_name,
+                     const char *email);
+
+bool client_send_friend_request(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_accept_friend_request(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_reject_friend_request(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_cancel_friend_request(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_remove_friend(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_block_friend(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_unblock_friend(WarhorseClientHandle *handle, const char *friend_id);
+
+bool client_send_chat_message(WarhorseClientHandle *handle,
+                              WarhorseChatChannelKind channel_kind,
+                              const char *target,
+                              const char *message);
+
+/// Sets a callback that's invoked with every event as soon as it arrives, replacing any previous one.
+/// Pass a null callback to stop receiving them.
+///
+/// Threading: the callback runs on one of the client's background threads, never the caller's,
+/// so it must be thread-safe and return quickly. It must not call back into this function.
+/// Memory: the callback owns `message` and must free it with `free_string`.
+/// `user_data` is passed through untouched and has to outlive the client or the next call here.
+/// Events are still queued for `client_pump`, so polling keeps working alongside the callback.
+bool client_set_event_callback(WarhorseClientHandle *handle,
+                               WarhorseEventCallback callback,
+                               void *user_data);
+
+/// Fills `events` with up to `max_events` received events and returns how many were written.
+/// Ownership of every `message` is transferred to the caller, free them all with `free_event_array`
+/// (or each one with `free_string`) once they've been read.
 uintptr_t client_pump(WarhorseClientHandle *handle,
                       WarhorseEventData *events,
                       uintptr_t max_events);
@@ -48,6 +132,10 @@ void client_free(WarhorseClientHandle *handle);
 
 void free_string(char *ptr);
 
+/// Frees every `message` in the first `count` events filled by `client_pump`.
+/// The array itself belongs to the caller, only the strings are freed.
+void free_event_array(WarhorseEventData *events, uintptr_t count);
+
 }  // extern "C"
 
 }  // namespace warhorse
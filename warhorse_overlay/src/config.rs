@@ -0,0 +1,181 @@
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    VIRTUAL_KEY, VK_CONTROL, VK_DELETE, VK_END, VK_F, VK_F1, VK_HOME, VK_INSERT, VK_MENU, VK_OEM_3,
+    VK_SHIFT, VK_SPACE, VK_TAB,
+};
+
+// followed by the keys that toggle the overlay, e.g. `--toggle-keys ctrl+shift+o`
+const TOGGLE_KEYS_ARG: &str = "--toggle-keys";
+
+// followed by part of the title of the window to attach to
+const WINDOW_TITLE_ARG: &str = "--window-title";
+
+// followed by the file name of the executable whose window to attach to, e.g. `game.exe`
+const WINDOW_EXECUTABLE_ARG: &str = "--window-exe";
+
+// followed by the id of the process whose window to attach to
+const WINDOW_PROCESS_ID_ARG: &str = "--window-pid";
+
+const DEFAULT_WINDOW_TITLE: &str = "Visual Studio Code";
+
+/// Settings the overlay reads once at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayConfig {
+    /// Keys that all have to be held to show or hide the overlay, Shift+F by default.
+    pub toggle_keys: Vec<VIRTUAL_KEY>,
+    /// The game window the overlay attaches to.
+    pub target_window: TargetWindow,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            toggle_keys: vec![VK_SHIFT, VK_F],
+            target_window: TargetWindow::Title(DEFAULT_WINDOW_TITLE.to_string()),
+        }
+    }
+}
+
+impl OverlayConfig {
+    /// Reads the settings from command line arguments, anything not given keeps its default.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                TOGGLE_KEYS_ARG => config.toggle_keys = parse_key_combination(&value()?)?,
+                WINDOW_TITLE_ARG => config.target_window = TargetWindow::Title(value()?),
+                WINDOW_EXECUTABLE_ARG => config.target_window = TargetWindow::Executable(value()?),
+                WINDOW_PROCESS_ID_ARG => {
+                    let process_id = value()?;
+                    let process_id = process_id
+                        .parse()
+                        .map_err(|_| format!("{:?} is not a process id", process_id))?;
+                    config.target_window = TargetWindow::ProcessId(process_id);
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Which window the overlay attaches to, the first one that matches wins.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetWindow {
+    /// A window whose title contains this, ignoring case.
+    Title(String),
+    /// A window belonging to the executable with this file name, ignoring case.
+    Executable(String),
+    /// A window belonging to this process, which can't be confused with another window of the same name.
+    ProcessId(u32),
+}
+
+/// What's known about a window while looking for the one to attach to.
+#[derive(Debug, Clone, Copy)]
+pub struct WindowInfo<'a> {
+    pub title: &'a str,
+    /// Full path of the executable, only looked up when matching by executable.
+    pub executable: Option<&'a str>,
+    pub process_id: u32,
+}
+
+impl TargetWindow {
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        match self {
+            TargetWindow::Title(title) => {
+                !title.is_empty() && window.title.to_lowercase().contains(&title.to_lowercase())
+            }
+            TargetWindow::Executable(executable) => window
+                .executable
+                .and_then(|path| path.rsplit(['\\', '/']).next())
+                .is_some_and(|file_name| file_name.eq_ignore_ascii_case(executable)),
+            TargetWindow::ProcessId(process_id) => window.process_id == *process_id,
+        }
+    }
+}
+
+/// Parses keys joined with `+`, like `ctrl+shift+o`. Names are case insensitive.
+pub fn parse_key_combination(keys: &str) -> Result<Vec<VIRTUAL_KEY>, String> {
+    keys.split('+').map(|key| parse_key(key.trim())).collect()
+}
+
+fn parse_key(key: &str) -> Result<VIRTUAL_KEY, String> {
+    let key = key.to_lowercase();
+    let virtual_key = match key.as_str() {
+        "shift" => VK_SHIFT,
+        "ctrl" | "control" => VK_CONTROL,
+        "alt" => VK_MENU,
+        "tab" => VK_TAB,
+        "space" => VK_SPACE,
+        "home" => VK_HOME,
+        "end" => VK_END,
+        "insert" => VK_INSERT,
+        "delete" => VK_DELETE,
+        "`" | "tilde" => VK_OEM_3,
+        // letters and digits have the same codes as their uppercase ASCII
+        _ if key.len() == 1 && key.as_bytes()[0].is_ascii_alphanumeric() => {
+            VIRTUAL_KEY(key.as_bytes()[0].to_ascii_uppercase() as u16)
+        }
+        _ => match key.strip_prefix('f').and_then(|number| number.parse::<u16>().ok()) {
+            Some(number @ 1..=24) => VIRTUAL_KEY(VK_F1.0 + number - 1),
+            _ => return Err(format!("Unknown key {:?}", key)),
+        },
+    };
+    Ok(virtual_key)
+}
+
+/// Whether every key in the combination is held, an empty combination never is.
+pub fn combination_pressed(keys: &[VIRTUAL_KEY], is_down: impl Fn(VIRTUAL_KEY) -> bool) -> bool {
+    !keys.is_empty() && keys.iter().all(|key| is_down(*key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{VK_F12, VK_O};
+
+    #[test]
+    fn test_parse_key_combination() {
+        assert_eq!(parse_key_combination("Ctrl+Shift+O").unwrap(), vec![VK_CONTROL, VK_SHIFT, VK_O]);
+        assert_eq!(parse_key_combination("f12").unwrap(), vec![VK_F12]);
+        assert!(parse_key_combination("shift+hyper").is_err());
+        assert!(parse_key_combination("shift+").is_err());
+
+        let args = ["--toggle-keys", "alt+`"].map(String::from);
+        assert_eq!(OverlayConfig::from_args(args).unwrap().toggle_keys, vec![VK_MENU, VK_OEM_3]);
+        assert_eq!(OverlayConfig::from_args(Vec::new()).unwrap(), OverlayConfig::default());
+    }
+
+    #[test]
+    fn test_target_window_matches() {
+        let window = WindowInfo {
+            title: "main.rs - warhorse - Visual Studio Code",
+            executable: Some("C:\\Program Files\\Microsoft VS Code\\Code.exe"),
+            process_id: 42,
+        };
+
+        assert!(TargetWindow::Title("visual studio code".to_string()).matches(&window));
+        assert!(!TargetWindow::Title("Notepad".to_string()).matches(&window));
+        assert!(!TargetWindow::Title(String::new()).matches(&window));
+        assert!(TargetWindow::Executable("code.exe".to_string()).matches(&window));
+        assert!(!TargetWindow::Executable("Code".to_string()).matches(&window));
+        assert!(!TargetWindow::Executable("code.exe".to_string()).matches(&WindowInfo { executable: None, ..window }));
+        assert!(TargetWindow::ProcessId(42).matches(&window));
+        assert!(!TargetWindow::ProcessId(7).matches(&window));
+
+        let args = ["--window-pid", "42"].map(String::from);
+        assert_eq!(OverlayConfig::from_args(args).unwrap().target_window, TargetWindow::ProcessId(42));
+        assert!(OverlayConfig::from_args(["--window-pid", "game"].map(String::from)).is_err());
+        assert!(OverlayConfig::from_args(["--window-exe"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_combination_pressed() {
+        let keys = [VK_SHIFT, VK_F];
+        assert!(combination_pressed(&keys, |key| key == VK_SHIFT || key == VK_F || key == VK_TAB));
+        assert!(!combination_pressed(&keys, |key| key == VK_SHIFT));
+        assert!(!combination_pressed(&keys, |_| false));
+        assert!(!combination_pressed(&[], |_| true));
+    }
+}
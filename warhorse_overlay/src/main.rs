@@ -1,3 +1,5 @@
+mod config;
+mod toggle;
 mod ui;
 
 use std::sync::{Arc, Mutex};
@@ -7,34 +9,41 @@ use dioxus::desktop::tao::keyboard::KeyCode;
 use dioxus::desktop::WindowEvent;
 use dioxus::desktop::{tao::platform::windows::WindowBuilderExtWindows, Config, WindowBuilder};
 use tracing::{error, info};
+use warhorse_client::warhorse_protocol::Language;
 use warhorse_client::WarhorseClient;
-use windows::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_F, VK_SHIFT};
+use crate::config::{combination_pressed, OverlayConfig, TargetWindow, WindowInfo};
+use crate::toggle::OverlayToggle;
+use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
 use windows::Win32::UI::WindowsAndMessaging::{ShowWindow, SW_HIDE, SW_SHOW};
+use windows::core::PWSTR;
 use windows::Win32::{
-    Foundation::{BOOL, HWND, LPARAM},
+    Foundation::{CloseHandle, BOOL, HWND, LPARAM},
+    System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    },
     UI::{
         Input::KeyboardAndMouse::{EnableWindow, SetFocus},
         WindowsAndMessaging::{
-            EnumWindows, FindWindowExW, GetWindowLongPtrW, GetWindowTextW, SetWindowLongPtrW,
+            EnumWindows, FindWindowExW, GetWindowLongPtrW, GetWindowTextW,
+            GetWindowThreadProcessId, SetWindowLongPtrW,
             GWL_EXSTYLE, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_NOREDIRECTIONBITMAP,
             WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT,
         },
     },
 };
 
-const MAIN_WINDOW_TITLE: &str = "Visual Studio Code";
 const OVERLAY_WINDOW_TITLE: &str = "Warhorse Game Overlay";
-static mut OVERLAY_VISIBLE: bool = true;
-static mut HOTKEY_WAS_PRESSED: bool = false;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
     let overlay_mode = false; // Toggle this for overlay vs normal window
+    let config = OverlayConfig::from_args(std::env::args().skip(1))?;
 
-    match WarhorseClient::new("http://localhost:3000") {
+    match WarhorseClient::new(Language::English, "http://localhost:3000") {
         Ok(client) => {
             if overlay_mode {
-                start_overlay(client);
+                start_overlay(client, config);
             } else {
                 start_normal(client);
             }
@@ -57,9 +66,10 @@ fn start_normal(client: WarhorseClient) {
         .launch(ui::components::app);
 }
 
-fn start_overlay(client: WarhorseClient) {
-    if let Ok(hwnd) = find_game_window() {
+fn start_overlay(client: WarhorseClient, config: OverlayConfig) {
+    if let Ok(hwnd) = find_game_window(&config.target_window) {
         info!("Game window found: {:?}", hwnd);
+        let toggle = Mutex::new(OverlayToggle::new(true));
         dioxus::LaunchBuilder::desktop()
             .with_context(Arc::new(Mutex::new(client)))
             .with_cfg(
@@ -74,16 +84,13 @@ fn start_overlay(client: WarhorseClient) {
                             .with_title(OVERLAY_WINDOW_TITLE),
                     )
                     .with_custom_event_handler(move |event, _window| match event {
-                        Event::MainEventsCleared => unsafe {
-                            let hotkey_pressed = GetAsyncKeyState(VK_F.0 as i32) < 0
-                                && GetAsyncKeyState(VK_SHIFT.0 as i32) < 0;
-
-                            if hotkey_pressed && !HOTKEY_WAS_PRESSED {
-                                OVERLAY_VISIBLE = !OVERLAY_VISIBLE;
-                            }
-                            HOTKEY_WAS_PRESSED = hotkey_pressed;
+                        Event::MainEventsCleared => {
+                            let hotkey_pressed = combination_pressed(&config.toggle_keys, |key| unsafe {
+                                GetAsyncKeyState(key.0 as i32) < 0
+                            });
+                            let visible = toggle.lock().unwrap().update(hotkey_pressed);
 
-                            if OVERLAY_VISIBLE {
+                            if visible {
                                 show_overlay(hwnd);
                             } else {
                                 hide_overlay(hwnd);
@@ -161,34 +168,75 @@ fn restore_game_focus(game_hwnd: HWND) {
     }
 }
 
-fn find_game_window() -> Result<HWND, Box<dyn std::error::Error>> {
-    let mut overlay_hwnd = HWND(std::ptr::null_mut());
+// what `enum_windows` is looking for, and where it puts the window once it finds it
+struct WindowSearch<'a> {
+    target: &'a TargetWindow,
+    found: HWND,
+}
+
+fn find_game_window(target: &TargetWindow) -> Result<HWND, Box<dyn std::error::Error>> {
+    let mut search = WindowSearch {
+        target,
+        found: HWND(std::ptr::null_mut()),
+    };
 
     unsafe {
         let _ = EnumWindows(
             Some(enum_windows),
-            LPARAM(&mut overlay_hwnd as *mut HWND as isize),
+            LPARAM(&mut search as *mut WindowSearch as isize),
         );
     }
 
-    if overlay_hwnd.0 != std::ptr::null_mut() {
-        Ok(overlay_hwnd)
+    if !search.found.0.is_null() {
+        Ok(search.found)
     } else {
-        Err("Overlay window not found".into())
+        Err(format!("No window matches {:?}", target).into())
     }
 }
 
 unsafe extern "system" fn enum_windows(window: HWND, param: LPARAM) -> BOOL {
+    let search = &mut *(param.0 as *mut WindowSearch);
+
     let mut title: [u16; 512] = [0; 512];
     GetWindowTextW(window, &mut title);
-
     let window_text = String::from_utf16_lossy(&title)
         .trim_end_matches('\0')
         .to_string();
 
-    if window_text.contains(MAIN_WINDOW_TITLE) {
-        *(param.0 as *mut HWND) = window;
+    let mut process_id = 0;
+    GetWindowThreadProcessId(window, Some(&mut process_id as *mut u32));
+
+    // opening every process is slow, so only when it's needed
+    let executable = match search.target {
+        TargetWindow::Executable(_) => executable_path(process_id),
+        _ => None,
+    };
+
+    let info = WindowInfo {
+        title: &window_text,
+        executable: executable.as_deref(),
+        process_id,
+    };
+    if search.target.matches(&info) {
+        search.found = window;
         return BOOL(0); // Return FALSE to stop enumeration
     }
     BOOL(1) // Continue enumeration
 }
+
+fn executable_path(process_id: u32) -> Option<String> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+        let mut path = [0u16; 1024];
+        let mut len = path.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(path.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        result.ok()?;
+        Some(String::from_utf16_lossy(&path[..len as usize]))
+    }
+}
@@ -0,0 +1,45 @@
+/// Whether the overlay is showing, flipped each time the hotkey goes down.
+#[derive(Debug)]
+pub struct OverlayToggle {
+    visible: bool,
+    // so holding the hotkey down only toggles once
+    was_pressed: bool,
+}
+
+impl OverlayToggle {
+    pub fn new(visible: bool) -> Self {
+        Self {
+            visible,
+            was_pressed: false,
+        }
+    }
+
+    /// Takes whether the hotkey is held right now and returns whether the overlay should be visible.
+    pub fn update(&mut self, pressed: bool) -> bool {
+        if pressed && !self.was_pressed {
+            self.visible = !self.visible;
+        }
+        self.was_pressed = pressed;
+        self.visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggles_once_per_press() {
+        let mut toggle = OverlayToggle::new(true);
+        assert!(toggle.update(false));
+
+        // held down across several frames
+        assert!(!toggle.update(true));
+        assert!(!toggle.update(true));
+        assert!(!toggle.update(false));
+
+        assert!(toggle.update(true));
+        assert!(toggle.update(false));
+        assert!(toggle.update(false));
+    }
+}
@@ -5,10 +5,10 @@ use std::{
 };
 
 use dioxus::prelude::*;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use super::signals::*;
-use warhorse_client::{warhorse_protocol::*, WarhorseClient, WarhorseEvent};
+use warhorse_client::{warhorse_protocol::*, ConnectionState, WarhorseClient, WarhorseEvent};
 
 const FAVICON: Asset = asset!("/assets/favicon.ico");
 const MAIN_CSS: Asset = asset!("/assets/main.css");
@@ -21,17 +21,23 @@ pub fn app() -> Element {
 
     let mut notifications = use_signal(|| Notifications(Vec::new()));
 
+    let mut connection_status = use_signal(|| ConnectionStatus(ConnectionState::Connecting));
     let mut received_hello = use_signal(|| ReceivedHello(false));
+    let mut server_unreachable = use_signal(|| ServerUnreachable(false));
     let mut received_logged_in = use_signal(|| ReceivedLoggedIn(false));
     let mut friends_list = use_signal(|| FriendsList(HashMap::new()));
     let mut chat_messages = use_signal(|| ChatMessages(vec![]));
+    let mut search_results = use_signal(|| SearchResults(vec![]));
     let interactive_state = use_signal(|| InteractiveState::Nothing);
 
     provide_context(wh.clone());
+    provide_context(connection_status);
     provide_context(received_hello);
+    provide_context(server_unreachable);
     provide_context(received_logged_in);
     provide_context(friends_list);
     provide_context(chat_messages);
+    provide_context(search_results);
     provide_context(interactive_state);
     provide_context(notifications);
 
@@ -43,12 +49,21 @@ pub fn app() -> Element {
             loop {
                 interval.tick().await;
 
-                let events = wh_cloned.lock().unwrap().pump();
+                let (state, events) = {
+                    let wh = wh_cloned.lock().unwrap();
+                    (wh.connection_state(), wh.pump())
+                };
+
+                if connection_status.read().0 != state {
+                    connection_status.write().0 = state;
+                }
+
                 for event in events {
                     match event {
                         WarhorseEvent::Hello => {
                             info!("Received Hello event");
                             received_hello.write().0 = true;
+                            server_unreachable.write().0 = false;
                         }
                         WarhorseEvent::LoggedIn => {
                             info!("Received LoggedIn event");
@@ -59,13 +74,36 @@ pub fn app() -> Element {
                                 notification_type: NotificationType::Generic,
                             });
                         }
-                        WarhorseEvent::Error(error) => {
-                            info!("Received Error event: {:?}", error);
+                        WarhorseEvent::Error { code, message } => {
+                            info!("Received Error event: {:?} {:?}", code, message);
                         }
                         WarhorseEvent::FriendsList(friends) => {
                             info!("Received FriendsList event");
                             friends_list.write().0 = categorize_friends(friends);
                         }
+                        WarhorseEvent::FriendsPage { offset, total, friends } => {
+                            info!("Received FriendsPage event");
+                            let received = offset as usize + friends.len();
+                            {
+                                let mut list = friends_list.write();
+                                if offset == 0 {
+                                    list.0.clear();
+                                }
+                                for (status, mut page) in categorize_friends(friends) {
+                                    list.0.entry(status).or_default().append(&mut page);
+                                }
+                            }
+                            // keep asking until we have the whole list
+                            if received < total as usize {
+                                if let Err(e) = wh_cloned.lock().unwrap().request_friends_page(received as u32, 100) {
+                                    error!("Failed to request friends page: {:?}", e);
+                                }
+                            }
+                        }
+                        WarhorseEvent::BlockedList(blocked_users) => {
+                            info!("Received BlockedList event");
+                            friends_list.write().0.insert(FriendStatus::Blocked, blocked_users);
+                        }
                         WarhorseEvent::FriendRequestReceived(friend) => {
                             info!("Received FriendRequestReceived event");
                             notifications.write().0.push(Notification {
@@ -90,7 +128,134 @@ pub fn app() -> Element {
                         }
                         WarhorseEvent::ChatMessage(message) => {
                             info!("Received ChatMessage event");
-                            chat_messages.write().0.push(message);
+                            chat_messages.write().receive(message);
+                        }
+                        WarhorseEvent::ChatHistory(history) => {
+                            info!("Received ChatHistory event");
+                            chat_messages.write().prepend_history(history);
+                        }
+                        WarhorseEvent::Typing { .. } => {
+                            // typing indicators aren't shown yet
+                        }
+                        WarhorseEvent::SearchResults(users) => {
+                            info!("Received SearchResults event");
+                            search_results.write().0 = users;
+                        }
+                        WarhorseEvent::Profile(profile) => {
+                            info!("Received profile for {}", profile.display_name);
+                        }
+                        WarhorseEvent::RoomJoined(room_id) => {
+                            info!("Joined room {}", room_id);
+                        }
+                        WarhorseEvent::RoomLeft(room_id) => {
+                            info!("Left room {}", room_id);
+                        }
+                        WarhorseEvent::PartyInviteReceived { party_id, friend } => {
+                            info!("Received PartyInviteReceived event for {}", party_id);
+                            notifications.write().0.push(Notification {
+                                message: format!(
+                                    "{} has invited you to their party",
+                                    friend.display_name
+                                ),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::PartyUpdate { party_id, members } => {
+                            info!("Party {} now has {} members", party_id, members.len());
+                        }
+                        WarhorseEvent::Reconnecting { attempt } => {
+                            info!("Received Reconnecting event");
+                            notifications.write().0.push(Notification {
+                                message: format!(
+                                    "Connection lost, reconnecting (attempt {})",
+                                    attempt
+                                ),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::Reconnected => {
+                            info!("Received Reconnected event");
+                            notifications.write().0.push(Notification {
+                                message: "Reconnected to Warhorse".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::ConnectionLost => {
+                            // a Reconnecting notification follows right after
+                            warn!("The server went silent, reconnecting");
+                        }
+                        WarhorseEvent::HandshakeTimeout => {
+                            warn!("The server never said hello, retrying");
+                            server_unreachable.write().0 = true;
+                        }
+                        WarhorseEvent::EventsDropped { count } => {
+                            warn!("Dropped {} events that were never pumped", count);
+                        }
+                        WarhorseEvent::VersionMismatch { server, client } => {
+                            error!("Server protocol version {} doesn't match ours ({})", server, client);
+                            notifications.write().0.push(Notification {
+                                message: "This version is out of date, please update to keep playing online".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::AccountDeleted => {
+                            info!("Received AccountDeleted event");
+                            received_logged_in.write().0 = false;
+                            notifications.write().0.push(Notification {
+                                message: "Your account has been deleted".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::ServerShutdown { reason } => {
+                            info!("Received ServerShutdown event: {:?}", reason);
+                            notifications.write().0.push(Notification {
+                                message: reason.unwrap_or_else(|| "The server is shutting down".to_string()),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::SendAck { request_id, result } => {
+                            chat_messages.write().acknowledge(request_id, result.is_ok());
+                            if let Err(e) = result {
+                                warn!("Chat message {} was not sent: {:?}", request_id, e);
+                                notifications.write().0.push(Notification {
+                                    message: e.message,
+                                    timestamp: Instant::now(),
+                                    notification_type: NotificationType::Generic,
+                                });
+                            }
+                        }
+                        WarhorseEvent::LoggedOut => {
+                            info!("Received LoggedOut event");
+                            received_logged_in.write().0 = false;
+                            friends_list.write().0.clear();
+                            chat_messages.write().0.clear();
+                            search_results.write().0.clear();
+                            notifications.write().0.push(Notification {
+                                message: "You have logged out".to_string(),
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        WarhorseEvent::Banned { reason } => {
+                            info!("Received Banned event: {:?}", reason);
+                            received_logged_in.write().0 = false;
+                            notifications.write().0.push(Notification {
+                                message: match reason {
+                                    Some(reason) => format!("You have been banned: {}", reason),
+                                    None => "You have been banned".to_string(),
+                                },
+                                timestamp: Instant::now(),
+                                notification_type: NotificationType::Generic,
+                            });
+                        }
+                        event => {
+                            info!("Ignoring {} event", event);
                         }
                     }
                 }
@@ -175,12 +340,14 @@ fn wh_notification(notification: Notification) -> Element {
 
 #[component]
 fn wh_login() -> Element {
-    let received_hello = use_context::<Signal<ReceivedHello>>();
+    let connection_status = use_context::<Signal<ConnectionStatus>>();
+    let server_unreachable = use_context::<Signal<ServerUnreachable>>();
     let wh_cloned = use_context::<Arc<Mutex<WarhorseClient>>>();
     let wh_cloned2 = wh_cloned.clone();
+    let mut registration_error = use_signal(|| None::<String>);
 
     rsx! {
-        if received_hello.read().0 {
+        if connection_status.read().0 == ConnectionState::Connected {
             header { class: "container mx-auto px-4",
                 h1 { "Warhorse" }
             }
@@ -227,15 +394,27 @@ fn wh_login() -> Element {
                     class: "login-form",
                     onsubmit: move |e| {
                         e.prevent_default();
+                        let value = |name: &str| e.values().get(name).unwrap_or(&FormValue(vec![])).as_value();
+                        let account_name = value("account_name");
+                        let password = value("password");
+                        let display_name = value("display_name");
+                        let email = value("email");
+
+                        // catch what we can before the round-trip, the server checks it all again anyway
+                        let validated = validation::validate_account_name(&account_name)
+                            .and_then(|_| validation::validate_display_name(&display_name))
+                            .and_then(|_| validation::validate_email(&email))
+                            .and_then(|_| validation::validate_password(&password));
+                        if let Err(e) = validated {
+                            registration_error.set(Some(e.to_string()));
+                            return;
+                        }
+                        registration_error.set(None);
+
                         if let Err(e) = wh_cloned2
                             .lock()
                             .unwrap()
-                            .send_user_registration_request(
-                                e.values().get("account_name").unwrap_or(&FormValue(vec![])).as_value(),
-                                e.values().get("password").unwrap_or(&FormValue(vec![])).as_value(),
-                                e.values().get("display_name").unwrap_or(&FormValue(vec![])).as_value(),
-                                e.values().get("email").unwrap_or(&FormValue(vec![])).as_value(),
-                            )
+                            .send_user_registration_request(account_name, password, display_name, email)
                         {
                             error!("Failed to send registration request: {:?}", e);
                         }
@@ -272,6 +451,9 @@ fn wh_login() -> Element {
                             placeholder: "Password",
                         }
                     }
+                    if let Some(error) = registration_error.read().as_ref() {
+                        p { class: "form-error", "{error}" }
+                    }
                     button { class: "login-button", r#type: "submit", "INITIALIZE ACCOUNT" }
                 }
             }
@@ -280,7 +462,15 @@ fn wh_login() -> Element {
                 div { class: "loading-box",
                     h2 { class: "loading-text",
                         span { class: "loading-cursor", ">" }
-                        "ESTABLISHING CONNECTION..."
+                        if server_unreachable.read().0 {
+                            "CAN'T REACH SERVER, RETRYING..."
+                        } else if connection_status.read().0 == ConnectionState::Reconnecting {
+                            "RE-ESTABLISHING CONNECTION..."
+                        } else if connection_status.read().0 == ConnectionState::Incompatible {
+                            "CLIENT OUT OF DATE, PLEASE UPDATE"
+                        } else {
+                            "ESTABLISHING CONNECTION..."
+                        }
                     }
                 }
             }
@@ -337,6 +527,7 @@ fn wh_title_header() -> Element {
 
 #[component]
 fn wh_sidebar() -> Element {
+    let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
     let friends_list = use_context::<Signal<FriendsList>>();
     let mut interactive_state = use_context::<Signal<InteractiveState>>();
     rsx! {
@@ -354,6 +545,9 @@ fn wh_sidebar() -> Element {
                                 id: i.to_string(),
                                 display_name: format!("Friend {}", i),
                                 status: FriendStatus::Online,
+                                activity: None,
+                                last_seen: None,
+                                avatar_url: None,
                             };
                             friends.push(friend);
                         }
@@ -361,38 +555,12 @@ fn wh_sidebar() -> Element {
                     },
                 }
 
-                if let Some(friends) = friends_list.read().0.get(&FriendStatus::FriendRequestReceived) {
-                    wh_friend_category {
-                        status: FriendStatus::FriendRequestReceived,
-                        friends: friends.clone(),
-                    }
-                }
-
-                if let Some(friends) = friends_list.read().0.get(&FriendStatus::Online) {
-                    wh_friend_category {
-                        status: FriendStatus::Online,
-                        friends: friends.clone(),
-                    }
-                }
-
-                if let Some(friends) = friends_list.read().0.get(&FriendStatus::Offline) {
-                    wh_friend_category {
-                        status: FriendStatus::Offline,
-                        friends: friends.clone(),
-                    }
-                }
-
-                if let Some(friends) = friends_list.read().0.get(&FriendStatus::FriendRequestSent) {
-                    wh_friend_category {
-                        status: FriendStatus::FriendRequestSent,
-                        friends: friends.clone(),
-                    }
-                }
-
-                if let Some(friends) = friends_list.read().0.get(&FriendStatus::Blocked) {
-                    wh_friend_category {
-                        status: FriendStatus::Blocked,
-                        friends: friends.clone(),
+                for status in FriendStatus::all_ordered() {
+                    if let Some(friends) = friends_list.read().0.get(&status) {
+                        wh_friend_category {
+                            status,
+                            friends: friends.clone(),
+                        }
                     }
                 }
             }
@@ -401,6 +569,15 @@ fn wh_sidebar() -> Element {
                 onclick: move |_| *interactive_state.write() = InteractiveState::AddFriendModal,
                 "Add Friend"
             }
+            button {
+                class: "log-out",
+                onclick: move |_| {
+                    if let Err(e) = wh.lock().unwrap().send_logout_request() {
+                        error!("Failed to log out: {:?}", e);
+                    }
+                },
+                "Log Out"
+            }
         }
     }
 }
@@ -408,7 +585,7 @@ fn wh_sidebar() -> Element {
 #[component]
 fn wh_chat() -> Element {
     let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
-    let chat_messages = use_context::<Signal<ChatMessages>>();
+    let mut chat_messages = use_context::<Signal<ChatMessages>>();
 
     let mut message_input = use_signal(|| String::new());
 
@@ -422,12 +599,14 @@ fn wh_chat() -> Element {
                         display_name: "Warhorse".to_string(),
                         time: "12:00".to_string(),
                         message: "Welcome to Warhorse!".to_string(),
+                        delivery: Delivery::Sent,
                     }
-                    for message in chat_messages.read().0.iter() {
+                    for shown in chat_messages.read().0.iter() {
                         wh_chat_message {
-                            display_name: message.display_name.clone(),
-                            time: message.time.to_string(),
-                            message: message.message.clone(),
+                            display_name: shown.message.display_name.clone(),
+                            time: shown.message.time.to_string(),
+                            message: shown.message.message.clone(),
+                            delivery: shown.delivery,
                         }
                     }
                 }
@@ -436,8 +615,16 @@ fn wh_chat() -> Element {
                     onsubmit: move |e| {
                         e.prevent_default();
                         let message = message_input.to_string();
-                        if let Err(e) = wh.lock().unwrap().send_room_message("general".into(), message) {
-                            error!("Failed to send room message: {:?}", e);
+                        let wh = wh.lock().unwrap();
+                        match wh.send_room_message("general".into(), message.clone()) {
+                            Ok(request_id) => {
+                                // shown right away, the server's echo replaces it
+                                let display_name = wh.current_user().map(|user| user.display_name).unwrap_or_default();
+                                chat_messages.write().push_pending(request_id, display_name, ChatChannel::Room("general".into()), message);
+                            }
+                            Err(e) => {
+                                error!("Failed to send room message: {:?}", e);
+                            }
                         }
                         message_input.set(String::new());
                     },
@@ -462,6 +649,8 @@ fn wh_chat() -> Element {
 fn wh_friend_category(status: FriendStatus, friends: Vec<Friend>) -> Element {
     let status = match status {
         FriendStatus::Online => "Online",
+        FriendStatus::Away => "Away",
+        FriendStatus::Busy => "Busy",
         FriendStatus::Offline => "Offline",
         FriendStatus::FriendRequestSent => "Friend Requests Sent",
         FriendStatus::FriendRequestReceived => "Friend Requests Received",
@@ -491,6 +680,9 @@ fn wh_friend(friend: Friend) -> Element {
                 );
             },
             span { class: "friend-name", "{friend.display_name}" }
+            if let Some(activity) = &friend.activity {
+                span { class: "friend-activity", "{activity}" }
+            }
             span { class: "friend-menu", "⋮" }
         }
         if match &*interactive_state.read() {
@@ -514,7 +706,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
     rsx! {
         div { class: "friend-context-menu",
 
-            if friend.status == FriendStatus::Online {
+            if friend.status.can_whisper() {
                 button {
                     onclick: move |e| {
                         e.stop_propagation();
@@ -526,7 +718,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
                 }
             }
 
-            if friend.status != FriendStatus::Blocked {
+            if friend.status.can_block() {
                 button {
                     class: "secondary",
                     onclick: move |e| {
@@ -539,7 +731,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
                 }
             }
 
-            if friend.status == FriendStatus::Blocked {
+            if friend.status.is_blocked() {
                 button {
                     class: "secondary",
                     onclick: move |e| {
@@ -552,7 +744,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
                 }
             }
 
-            if friend.status == FriendStatus::FriendRequestReceived {
+            if friend.status.is_request_received() {
                 button {
                     class: "secondary",
                     onclick: move |e| {
@@ -597,6 +789,7 @@ fn wh_friend_context_menu(friend: Friend) -> Element {
 fn wh_add_friend_modal() -> Element {
     let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
     let mut interactive_state = use_context::<Signal<InteractiveState>>();
+    let mut search_results = use_context::<Signal<SearchResults>>();
     rsx! {
         div { class: "modal",
             div { class: "modal-content",
@@ -605,29 +798,36 @@ fn wh_add_friend_modal() -> Element {
                     class: "add-friend-form",
                     onsubmit: move |e| {
                         e.prevent_default();
-                        *interactive_state.write() = InteractiveState::Nothing;
                         if let Err(e) = wh
                             .lock()
                             .unwrap()
-                            .send_friend_request(
-                                e.values().get("friend_id").unwrap_or(&FormValue(vec![])).as_value(),
+                            .send_user_search(
+                                e.values().get("query").unwrap_or(&FormValue(vec![])).as_value(),
                             )
                         {
-                            error!("Failed to send friend request: {:?}", e);
+                            error!("Failed to search for users: {:?}", e);
                         }
                     },
                     input {
                         r#type: "text",
-                        name: "friend_id",
-                        placeholder: "Friend ID",
+                        name: "query",
+                        placeholder: "Display name",
+                    }
+                    button { r#type: "submit", "Search" }
+                }
+                div { class: "search-results",
+                    for user in search_results.read().0.iter() {
+                        wh_search_result { user: user.clone() }
                     }
-                    button { r#type: "submit", "Request" }
                 }
             }
             div { class: "modal-buttons",
                 button {
                     class: "secondary",
-                    onclick: move |_| *interactive_state.write() = InteractiveState::Nothing,
+                    onclick: move |_| {
+                        search_results.write().0.clear();
+                        *interactive_state.write() = InteractiveState::Nothing;
+                    },
                     "Close"
                 }
             }
@@ -635,6 +835,29 @@ fn wh_add_friend_modal() -> Element {
     }
 }
 
+#[component]
+fn wh_search_result(user: UserPartial) -> Element {
+    let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
+    let mut interactive_state = use_context::<Signal<InteractiveState>>();
+    let mut search_results = use_context::<Signal<SearchResults>>();
+    rsx! {
+        div { class: "search-result",
+            span { "{user.display_name}" }
+            button {
+                class: "secondary",
+                onclick: move |_| {
+                    if let Err(e) = wh.lock().unwrap().send_friend_request(user.id.clone()) {
+                        error!("Failed to send friend request: {:?}", e);
+                    }
+                    search_results.write().0.clear();
+                    *interactive_state.write() = InteractiveState::Nothing;
+                },
+                "Request"
+            }
+        }
+    }
+}
+
 #[component]
 fn wh_block_friend_modal(friend: Friend) -> Element {
     let wh = use_context::<Arc<Mutex<WarhorseClient>>>();
@@ -836,9 +1059,14 @@ fn wh_whisper_friend_modal(friend: Friend) -> Element {
 }
 
 #[component]
-fn wh_chat_message(display_name: String, time: String, message: String) -> Element {
+fn wh_chat_message(display_name: String, time: String, message: String, delivery: Delivery) -> Element {
+    let class = match delivery {
+        Delivery::Pending => "chat-message pending",
+        Delivery::Sent => "chat-message",
+        Delivery::Failed => "chat-message failed",
+    };
     rsx! {
-        div { class: "chat-message",
+        div { class,
             div { class: "chat-message-author", "{display_name}" }
             div { class: "chat-message-time", "{time}" }
             div { class: "chat-message-content", "{message}" }
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, time::Instant};
 
 use warhorse_client::warhorse_protocol::*;
+use warhorse_client::ConnectionState;
 
 #[derive(PartialEq, Eq)]
 pub enum InteractiveState {
@@ -12,16 +13,98 @@ pub enum InteractiveState {
     UnblockFriendModal(Friend),
     AcceptFriendRequestModal(Friend),
     RejectFriendRequestModal(Friend),
-    FriendContextMenu(String),
+    FriendContextMenu(UserId),
 }
 
+pub struct ConnectionStatus(pub ConnectionState);
+
 pub struct ReceivedHello(pub bool);
 
+/// Set when the server doesn't say hello in time, until it finally does.
+pub struct ServerUnreachable(pub bool);
+
 pub struct ReceivedLoggedIn(pub bool);
 
 pub struct FriendsList(pub HashMap<FriendStatus, Vec<Friend>>);
 
-pub struct ChatMessages(pub Vec<ChatMessage>);
+/// Whether the server has seen a message we sent, messages from anyone else are always `Sent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Shown as soon as it's sent, until the server echoes it back.
+    Pending,
+    Sent,
+    /// The server said no, it never reached anyone.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShownChatMessage {
+    pub message: ChatMessage,
+    pub delivery: Delivery,
+}
+
+pub struct ChatMessages(pub Vec<ShownChatMessage>);
+
+impl ChatMessages {
+    /// Shows a message we just sent before the server gets back to us.
+    /// Until then it goes by the request id, since the server hasn't given it an id of its own.
+    pub fn push_pending(&mut self, request_id: RequestId, display_name: String, channel: ChatChannel, message: String) {
+        self.0.push(ShownChatMessage {
+            message: ChatMessage {
+                id: request_id,
+                display_name,
+                channel,
+                message,
+                time: Timestamp::now(),
+            },
+            delivery: Delivery::Pending,
+        });
+    }
+
+    /// Adds a message from the server. The echo of one of ours takes the place of the oldest pending one it matches,
+    /// the acknowledgement doesn't say which id the server gave it so it's matched by who sent what where.
+    pub fn receive(&mut self, message: ChatMessage) {
+        if self.0.iter().any(|shown| shown.message.id == message.id) {
+            return;
+        }
+
+        let pending = self.0.iter_mut().find(|shown| {
+            shown.delivery == Delivery::Pending
+                && shown.message.display_name == message.display_name
+                && shown.message.channel == message.channel
+                && shown.message.message == message.message
+        });
+        match pending {
+            Some(shown) => {
+                shown.message = message;
+                shown.delivery = Delivery::Sent;
+            }
+            None => self.0.push(ShownChatMessage { message, delivery: Delivery::Sent }),
+        }
+    }
+
+    /// History is older than anything we've already got.
+    pub fn prepend_history(&mut self, history: Vec<ChatMessage>) {
+        let history = history.into_iter().map(|message| ShownChatMessage { message, delivery: Delivery::Sent });
+        self.0.splice(0..0, history);
+    }
+
+    /// Marks a message we sent as failed if the server rejected it, accepted ones wait for their echo.
+    pub fn acknowledge(&mut self, request_id: RequestId, accepted: bool) {
+        if accepted {
+            return;
+        }
+        if let Some(shown) = self
+            .0
+            .iter_mut()
+            .find(|shown| shown.delivery == Delivery::Pending && shown.message.id == request_id)
+        {
+            shown.delivery = Delivery::Failed;
+        }
+    }
+}
+
+pub struct SearchResults(pub Vec<UserPartial>);
 
 #[derive(Clone, PartialEq)]
 pub struct Notification {
@@ -38,3 +121,56 @@ pub enum NotificationType {
 }
 
 pub struct Notifications(pub Vec<Notification>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn general() -> ChatChannel {
+        ChatChannel::Room(RoomId::from("general"))
+    }
+
+    fn echo(message: &str) -> ChatMessage {
+        ChatMessage {
+            id: Uuid::new_v4(),
+            display_name: "me".to_string(),
+            channel: general(),
+            message: message.to_string(),
+            time: Timestamp::now(),
+        }
+    }
+
+    #[test]
+    fn test_sent_messages_show_before_the_server_echoes_them() {
+        let mut messages = ChatMessages(Vec::new());
+        let request_id = Uuid::new_v4();
+        messages.push_pending(request_id, "me".to_string(), general(), "hello".to_string());
+
+        assert_eq!(messages.0.len(), 1);
+        assert_eq!(messages.0[0].message.message, "hello");
+        assert_eq!(messages.0[0].delivery, Delivery::Pending);
+
+        let echoed = echo("hello");
+        messages.acknowledge(request_id, true);
+        messages.receive(echoed.clone());
+        messages.receive(echoed.clone());
+        assert_eq!(messages.0, vec![ShownChatMessage { message: echoed, delivery: Delivery::Sent }]);
+    }
+
+    #[test]
+    fn test_rejected_messages_are_marked_failed() {
+        let mut messages = ChatMessages(Vec::new());
+        let rejected_id = Uuid::new_v4();
+        messages.push_pending(rejected_id, "me".to_string(), general(), "spam".to_string());
+        messages.push_pending(Uuid::new_v4(), "me".to_string(), general(), "spam".to_string());
+
+        messages.acknowledge(rejected_id, false);
+        assert_eq!(messages.0[0].delivery, Delivery::Failed);
+        assert_eq!(messages.0[1].delivery, Delivery::Pending);
+
+        // the one that did get through is the one the echo settles
+        messages.receive(echo("spam"));
+        assert_eq!(messages.0[0].delivery, Delivery::Failed);
+        assert_eq!(messages.0[1].delivery, Delivery::Sent);
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+
+/// Payloads smaller than this many bytes of JSON are sent as they are, compressing them isn't worth it.
+pub const COMPRESSION_THRESHOLD: usize = 16 * 1024;
+
+/// Sent in place of a compressed payload: its JSON, deflated and base64 encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompressedPayload {
+    #[serde(rename = "$deflate")]
+    pub deflate: String,
+}
+
+/// Compresses a payload that's at least `COMPRESSION_THRESHOLD` bytes of JSON, `None` when it's smaller.
+pub fn compress_if_large(json: &Value) -> Result<Option<Value>, Error> {
+    let bytes = serde_json::to_vec(json)?;
+    if bytes.len() < COMPRESSION_THRESHOLD {
+        return Ok(None);
+    }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&bytes).map_err(|e| Error::new(e.to_string()))?;
+    let deflated = encoder.finish().map_err(|e| Error::new(e.to_string()))?;
+    let payload = CompressedPayload { deflate: STANDARD.encode(deflated) };
+    Ok(Some(serde_json::to_value(payload)?))
+}
+
+/// Undoes `compress_if_large`, `None` when the payload wasn't compressed.
+pub fn decompress(json: &Value) -> Result<Option<Value>, Error> {
+    let is_compressed = json.as_object().is_some_and(|object| object.len() == 1 && object.contains_key("$deflate"));
+    if !is_compressed {
+        return Ok(None);
+    }
+
+    let payload = CompressedPayload::deserialize(json)?;
+    let deflated = STANDARD.decode(payload.deflate).map_err(|e| Error::new(e.to_string()))?;
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(deflated.as_slice())
+        .read_to_end(&mut inflated)
+        .map_err(|e| Error::new(e.to_string()))?;
+    Ok(Some(serde_json::from_slice(&inflated)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json_to_vec, vec_to_json, Friend, FriendStatus, UserId};
+
+    #[test]
+    fn test_large_friends_list_round_trips_through_compression() {
+        let friends: Vec<Friend> = (0..2000)
+            .map(|i| Friend {
+                id: UserId::from(i.to_string()),
+                display_name: format!("Friend {}", i),
+                status: FriendStatus::Online,
+                activity: Some("Playing Ranked - Level 4".to_string()),
+                last_seen: None,
+                avatar_url: None,
+            })
+            .collect();
+        let json = vec_to_json(friends.clone()).unwrap();
+
+        let compressed = compress_if_large(&json).unwrap().unwrap();
+        assert!(serde_json::to_vec(&compressed).unwrap().len() < serde_json::to_vec(&json).unwrap().len());
+
+        let decompressed = decompress(&compressed).unwrap().unwrap();
+        assert_eq!(serde_json::to_vec(&decompressed).unwrap(), serde_json::to_vec(&json).unwrap());
+        assert_eq!(json_to_vec::<Friend>(decompressed).unwrap(), friends);
+    }
+
+    #[test]
+    fn test_small_payloads_are_left_alone() {
+        let json = serde_json::json!([{ "id": "1" }]);
+        assert!(compress_if_large(&json).unwrap().is_none());
+        assert!(decompress(&json).unwrap().is_none());
+    }
+}
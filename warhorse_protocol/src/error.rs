@@ -1,19 +1,63 @@
 use std::fmt;
 
+/// Why something couldn't be encoded or decoded, keeping the error that caused it when there is one.
 #[derive(Debug)]
-pub struct Error(pub String);
+pub struct Error {
+    message: String,
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), source: None }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn std::error::Error + 'static))
     }
 }
 
-impl std::error::Error for Error {}
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self { message: e.to_string(), source: Some(Box::new(e)) }
+    }
+}
 
 impl From<Box<dyn std::error::Error>> for Error {
     fn from(e: Box<dyn std::error::Error>) -> Self {
-        Error(e.to_string())
+        Error::new(e.to_string())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_serde_errors_are_kept_as_the_source() {
+        let e: Error = serde_json::from_str::<u32>("\"not a number\"").unwrap_err().into();
+        assert!(e.message().starts_with("invalid type"));
+        assert!(e.source().unwrap().is::<serde_json::Error>());
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> = Box::new(e);
+        assert!(boxed.to_string().starts_with("invalid type"));
+        assert!(boxed.source().is_some());
+
+        let e = Error::new("plain");
+        assert_eq!(format!("{}", e), "plain");
+        assert!(e.source().is_none());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod compression;
 pub mod error;
+pub mod validation;
 
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -9,8 +11,96 @@ use serde::de::DeserializeOwned;
 use serde_json::Value;
 use crate::error::Error;
 
-pub type UserId = String;
-pub type RoomId = String;
+// re-exports
+pub use uuid::Uuid;
+
+/// Defines an id that's a string on the wire but its own type in code, so one kind can't be passed as another.
+macro_rules! string_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl std::ops::Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::borrow::Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+string_id!(
+    /// Identifies a user, a plain string on the wire.
+    UserId
+);
+string_id!(
+    /// Identifies a chat room, a plain string on the wire.
+    RoomId
+);
+pub type PartyId = String;
+pub type MessageId = Uuid;
+/// Generated by the client to match a server acknowledgement to what was sent.
+pub type RequestId = Uuid;
+
+/// Bumped whenever a change to the protocol would break older clients or servers.
+pub const PROTOCOL_VERSION: u32 = 1;
 
 // For validation on both backend and frontend
 pub const ACCOUNT_NAME_MAX_LENGTH: usize = 20;
@@ -18,6 +108,9 @@ pub const ACCOUNT_NAME_MIN_LENGTH: usize = 3;
 pub const DISPLAY_NAME_MAX_LENGTH: usize = 20;
 pub const DISPLAY_NAME_MIN_LENGTH: usize = 3;
 pub const PASSWORD_MIN_LENGTH: usize = 8;
+/// In characters, not bytes.
+pub const CHAT_MESSAGE_MAX_LENGTH: usize = 500;
+pub const AVATAR_URL_MAX_LENGTH: usize = 2048;
 
 // Socket.IO Events, named from the client's perspective.
 
@@ -39,6 +132,27 @@ pub const EVENT_SEND_USER_BLOCK: &str = "/user/block";
 /// Event for sending a user unblock to the server.
 pub const EVENT_SEND_USER_UNBLOCK: &str = "/user/unblock";
 
+/// Event for deleting your account, which needs your password again.
+pub const EVENT_SEND_ACCOUNT_DELETE: &str = "/user/delete";
+
+/// Event for setting how you appear to your friends.
+pub const EVENT_SEND_PRESENCE: &str = "/user/presence";
+
+/// Event for setting what you're currently doing, shown to your friends.
+pub const EVENT_SEND_ACTIVITY: &str = "/user/activity";
+
+/// Event for telling the server the player is at the keyboard, so they aren't marked away for being idle.
+pub const EVENT_SEND_ACTIVITY_PING: &str = "/user/activity/ping";
+
+/// Event for setting or clearing the URL of your avatar.
+pub const EVENT_SEND_SET_AVATAR: &str = "/user/avatar";
+
+/// Event for searching for users by display name.
+pub const EVENT_SEND_USER_SEARCH: &str = "/user/search";
+
+/// Event for requesting another user's profile.
+pub const EVENT_SEND_GET_PROFILE: &str = "/user/profile";
+
 /// Event for sending a friend request to the server.
 pub const EVENT_SEND_FRIEND_REQUEST: &str = "/friend/request";
 
@@ -48,74 +162,197 @@ pub const EVENT_SEND_FRIEND_REQUEST_ACCEPT: &str = "/friend/request/accept";
 /// Event for sending a friend request reject to the server.
 pub const EVENT_SEND_FRIEND_REQUEST_REJECT: &str = "/friend/request/reject";
 
+/// Event for taking back a friend request you sent.
+pub const EVENT_SEND_FRIEND_REQUEST_CANCEL: &str = "/friend/request/cancel";
+
 /// Event for sending a friend remove to the server.
 pub const EVENT_SEND_FRIEND_REMOVE: &str = "/friend/remove";
 
+/// Event for requesting a page of your friends list from the server.
+pub const EVENT_SEND_FRIENDS_PAGE: &str = "/friends/page";
+
 /// Event for sending a chat message to the server.
 pub const EVENT_SEND_CHAT_MESSAGE: &str = "/chat/send";
 
+/// Event for requesting a page of chat history from the server.
+pub const EVENT_SEND_CHAT_HISTORY: &str = "/chat/history";
+
+/// Event for telling a channel you've started or stopped typing.
+pub const EVENT_SEND_TYPING: &str = "/chat/typing";
+
+/// Event for joining a chat room.
+pub const EVENT_SEND_ROOM_JOIN: &str = "/room/join";
+
+/// Event for leaving a chat room.
+pub const EVENT_SEND_ROOM_LEAVE: &str = "/room/leave";
+
+/// Event for requesting who's in a chat room you're in.
+pub const EVENT_SEND_ROOM_MEMBERS: &str = "/room/members";
+
+/// Event for inviting a friend to a party.
+pub const EVENT_SEND_PARTY_INVITE: &str = "/party/invite";
+
+/// Event for joining a party you've been invited to.
+pub const EVENT_SEND_PARTY_JOIN: &str = "/party/join";
+
+/// Event for leaving a party.
+pub const EVENT_SEND_PARTY_LEAVE: &str = "/party/leave";
+
+/// Event for an admin disconnecting a user.
+pub const EVENT_SEND_ADMIN_KICK: &str = "/admin/kick";
+
+/// Event for an admin banning a user, which also disconnects them.
+pub const EVENT_SEND_ADMIN_BAN: &str = "/admin/ban";
+
 /// Event for receiving a successful user login response, received from the server.
 pub const EVENT_RECEIVE_USER_LOGIN: &str = "/user/login";
 
+/// Event for receiving that you've logged out, received from the server.
+/// The connection stays open, so you can log in again without reconnecting.
+pub const EVENT_RECEIVE_USER_LOGOUT: &str = "/user/logout/receive";
+
+/// Event for receiving your own account, account name and email included, sent right before the login response.
+/// Received from the server.
+pub const EVENT_RECEIVE_CURRENT_USER: &str = "/user/current/receive";
+
+/// Event for receiving that your account was deleted, received from the server right before it disconnects you.
+pub const EVENT_RECEIVE_ACCOUNT_DELETED: &str = "/user/deleted";
+
+/// Event for being told you're banned, received from the server when banned and when logging in while banned.
+pub const EVENT_RECEIVE_BANNED: &str = "/user/banned";
+
+/// Event for being told the server is going down, received from the server.
+pub const EVENT_RECEIVE_SERVER_SHUTDOWN: &str = "/server/shutdown";
+
+/// Event for the server's periodic heartbeat, so clients notice when it goes silent. Received from the server.
+pub const EVENT_RECEIVE_PING: &str = "/ping";
+
 /// Event for receiving an error response, received from the server.
 pub const EVENT_RECEIVE_ERROR: &str = "/error";
 
+/// Event for receiving the users matching a search, received from the server.
+pub const EVENT_RECEIVE_USER_SEARCH_RESULTS: &str = "/user/search/receive";
+
+/// Event for receiving a user's profile, received from the server.
+pub const EVENT_RECEIVE_PROFILE: &str = "/user/profile/receive";
+
 /// Event for receiving your friend list, received from the server.
 pub const EVENT_RECEIVE_FRIENDS: &str = "/friends/receive";
 
+/// Event for receiving a page of your friend list, received from the server.
+pub const EVENT_RECEIVE_FRIENDS_PAGE: &str = "/friends/page/receive";
+
 /// Event for receiving a blocked list of users, received from the server.
 pub const EVENT_RECEIVE_BLOCKED_USERS: &str = "/blocked_users/receive";
 
-/// Event for receiving a friend request, invoked by a user, but ultimately received from the server.
+/// Event for receiving all of your pending friend requests, received from the server after logging in.
 pub const EVENT_RECEIVE_FRIEND_REQUESTS: &str = "/friend_requests/receive";
 
+/// Event for receiving a friend request that just arrived, invoked by a user, but ultimately received from the server.
+pub const EVENT_RECEIVE_FRIEND_REQUEST_NEW: &str = "/friend_request/new";
+
 /// Event for receiving a friend request response, received from the server.
 pub const EVENT_RECEIVE_FRIEND_REQUEST_ACCEPTED: &str = "/friend_request/accepted";
 
 /// Event for receiving a chat message, invoked by a user, but ultimately received from the server.
 pub const EVENT_RECEIVE_CHAT_MESSAGE: &str = "/chat/receive";
 
+/// Event for receiving a page of chat history, received from the server.
+pub const EVENT_RECEIVE_CHAT_HISTORY: &str = "/chat/history/receive";
+
+/// Event for receiving that someone started or stopped typing, invoked by a user, but ultimately received from the server.
+pub const EVENT_RECEIVE_TYPING: &str = "/chat/typing/receive";
+
+/// Event for receiving the ID of a room you've joined, received from the server.
+pub const EVENT_RECEIVE_ROOM_JOINED: &str = "/room/joined";
+
+/// Event for receiving the ID of a room you've left, received from the server.
+pub const EVENT_RECEIVE_ROOM_LEFT: &str = "/room/left";
+
+/// Event for receiving who's in a room, received from the server.
+pub const EVENT_RECEIVE_ROOM_MEMBERS: &str = "/room/members/receive";
+
+/// Event for receiving a party invite, invoked by a friend, but ultimately received from the server.
+pub const EVENT_RECEIVE_PARTY_INVITE: &str = "/party/invite/receive";
+
+/// Event for receiving a party's members whenever they change, received from the server.
+pub const EVENT_RECEIVE_PARTY_UPDATE: &str = "/party/update";
+
 /// Base trait for all protocol types.
 pub trait ProtoType: Send + Sync + Serialize + DeserializeOwned {
     fn to_json(&self) -> Result<Value, Error> {
         serde_json::to_value(self)
-            .map_err(|e| Error(e.to_string()))
+            .map_err(Error::from)
     }
 
     fn from_json(json: Value) -> Result<Self, Error>
     where
         Self: Sized {
         serde_json::from_value(json)
-            .map_err(|e| Error(e.to_string()))
+            .map_err(Error::from)
     }
 }
 
 /// Serialize a vector of messages to JSON.
 pub fn vec_to_json<T: ProtoType>(messages: Vec<T>) -> Result<Value, Error> {
     serde_json::to_value(messages)
-        .map_err(|e| Error(e.to_string()))
+        .map_err(Error::from)
 }
 
 /// Deserialize a vector of messages from JSON.
 pub fn json_to_vec<T: ProtoType>(json: Value) -> Result<Vec<T>, Error> {
     serde_json::from_value(json)
-        .map_err(|e| Error(e.to_string()))
+        .map_err(Error::from)
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Language {
     English,
     Spanish,
     French,
+    German,
+    BrazilianPortuguese,
 }
 
 impl ProtoType for Language {}
 
 
+/// Sent by the server when a client connects.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hello {
+    pub message: String,
+    /// Missing from servers that predate versioning.
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// Whether the server can compress large payloads, for clients that ask for it when logging in.
+    #[serde(default)]
+    pub compression: bool,
+}
+
+impl ProtoType for Hello {}
+
+/// Sent by the server to every client right before it shuts down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerShutdown {
+    /// Something to show the user, like "Server going down for maintenance".
+    pub reason: Option<String>,
+}
+
+impl ProtoType for ServerShutdown {}
+
+/// Sent by the server to a banned user before it disconnects them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Banned {
+    /// Why the admin banned them, if they said.
+    pub reason: Option<String>,
+}
+
+impl ProtoType for Banned {}
+
 /// Represents a user in the system, but with sensitive information removed.
 /// And options to reduce the amount of data/sensitive info sent depending on the context.
 /// Regardless, we never include the password
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserPartial {
     pub id: UserId,
     pub display_name_lower: String,
@@ -124,6 +361,17 @@ pub struct UserPartial {
     pub account_name: Option<String>,
     pub email: Option<String>,
     pub language: Language,
+    /// What the user is currently doing, only known while they're connected.
+    pub activity: Option<String>,
+    /// Whether the user can kick and ban other users.
+    #[serde(default)]
+    pub is_admin: bool,
+    /// When the account was created.
+    #[serde(default)]
+    pub created_at: Timestamp,
+    /// Where to load the user's avatar from, if they've set one.
+    #[serde(default)]
+    pub avatar_url: Option<String>,
 }
 
 impl ProtoType for UserPartial {}
@@ -143,10 +391,43 @@ pub struct UserLogin {
     pub language: Language,
     pub identity: LoginUserIdentity,
     pub password: String,
+    /// Asks for large payloads to be compressed, only when the server's `Hello` offered it.
+    /// Registering logs in without compression.
+    #[serde(default)]
+    pub compression: bool,
 }
 
 impl ProtoType for UserLogin {}
 
+/// Request to delete the logged in user's account
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccount {
+    pub language: Language,
+    pub password: String,
+}
+
+impl ProtoType for DeleteAccount {}
+
+/// Request from an admin to disconnect a user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminKick {
+    pub language: Language,
+    pub user_id: UserId,
+}
+
+impl ProtoType for AdminKick {}
+
+/// Request from an admin to ban a user, they're disconnected and can't log in again
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminBan {
+    pub language: Language,
+    pub user_id: UserId,
+    /// Shown to the banned user.
+    pub reason: Option<String>,
+}
+
+impl ProtoType for AdminBan {}
+
 /// Request to register a new user
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserRegistration {
@@ -159,15 +440,82 @@ pub struct UserRegistration {
 
 impl ProtoType for UserRegistration {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RequestError(pub String);
+/// Why a request failed, stable across languages so clients can branch on it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorCode {
+    InvalidLogin,
+    AccountNameTaken,
+    EmailTaken,
+    InvalidEmail,
+    PasswordTooShort,
+    InvalidAccountName,
+    InvalidDisplayName,
+    IncorrectPassword,
+    AlreadyFriends,
+    CannotFriendSelf,
+    FriendRequestAlreadyReceived,
+    UserBlocked,
+    RateLimited,
+    ChatHistoryRoomsOnly,
+    InvalidRoomId,
+    InvalidPartyId,
+    ActivityTooLong,
+    NotAdmin,
+    Banned,
+    MessageRejected,
+    EmailNotVerified,
+    InvalidVerificationToken,
+    ChatMessageEmpty,
+    ChatMessageTooLong,
+    FriendLimitReached,
+    InvalidAvatarUrl,
+    /// Accepting a friend request that was never sent, or was taken back.
+    NoFriendRequest,
+    /// Anything without a more specific code, including codes added by newer servers.
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl ProtoType for ErrorCode {}
+
+/// Sent by the server when a request fails, the message is in the request's language.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RequestErrorRepr")]
+pub struct RequestError {
+    pub code: ErrorCode,
+    pub message: String,
+}
 
 impl ProtoType for RequestError {}
 
+// servers that predate error codes send just the message
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RequestErrorRepr {
+    Coded {
+        #[serde(default)]
+        code: ErrorCode,
+        message: String,
+    },
+    Message(String),
+}
+
+impl From<RequestErrorRepr> for RequestError {
+    fn from(repr: RequestErrorRepr) -> Self {
+        match repr {
+            RequestErrorRepr::Coded { code, message } => RequestError { code, message },
+            RequestErrorRepr::Message(message) => RequestError { code: ErrorCode::Unknown, message },
+        }
+    }
+}
+
 /// The online status of a friend
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum FriendStatus {
     Online,
+    Away,
+    Busy,
     Offline,
     FriendRequestSent,
     FriendRequestReceived,
@@ -178,6 +526,8 @@ impl std::fmt::Display for FriendStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             FriendStatus::Online => write!(f, "Online"),
+            FriendStatus::Away => write!(f, "Away"),
+            FriendStatus::Busy => write!(f, "Busy"),
             FriendStatus::Offline => write!(f, "Offline"),
             FriendStatus::FriendRequestSent => write!(f, "Invite Sent"),
             FriendStatus::FriendRequestReceived => write!(f, "Pending Request"),
@@ -186,26 +536,203 @@ impl std::fmt::Display for FriendStatus {
     }
 }
 
+/// What a UI can offer for a friend in each status, so every UI agrees.
+impl FriendStatus {
+    /// Connected and visible, whatever they set their presence to.
+    pub fn is_online(self) -> bool {
+        matches!(self, FriendStatus::Online | FriendStatus::Away | FriendStatus::Busy)
+    }
+
+    /// An accepted friend, online or not.
+    pub fn is_friend(self) -> bool {
+        self.is_online() || self == FriendStatus::Offline
+    }
+
+    pub fn is_request_sent(self) -> bool {
+        self == FriendStatus::FriendRequestSent
+    }
+
+    /// A request waiting for us to accept or reject it.
+    pub fn is_request_received(self) -> bool {
+        self == FriendStatus::FriendRequestReceived
+    }
+
+    pub fn is_blocked(self) -> bool {
+        self == FriendStatus::Blocked
+    }
+
+    pub fn can_whisper(self) -> bool {
+        self.is_online()
+    }
+
+    pub fn can_block(self) -> bool {
+        !self.is_blocked()
+    }
+
+    /// Every status in the order UIs show their sections, requests waiting on us first and blocked users last.
+    pub fn all_ordered() -> [FriendStatus; 7] {
+        [
+            FriendStatus::FriendRequestReceived,
+            FriendStatus::Online,
+            FriendStatus::Away,
+            FriendStatus::Busy,
+            FriendStatus::Offline,
+            FriendStatus::FriendRequestSent,
+            FriendStatus::Blocked,
+        ]
+    }
+
+    fn section_index(self) -> usize {
+        match self {
+            FriendStatus::FriendRequestReceived => 0,
+            FriendStatus::Online => 1,
+            FriendStatus::Away => 2,
+            FriendStatus::Busy => 3,
+            FriendStatus::Offline => 4,
+            FriendStatus::FriendRequestSent => 5,
+            FriendStatus::Blocked => 6,
+        }
+    }
+}
+
+/// Orders statuses the way `FriendStatus::all_ordered` lists them.
+impl Ord for FriendStatus {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.section_index().cmp(&other.section_index())
+    }
+}
+
+impl PartialOrd for FriendStatus {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Hash for FriendStatus {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.to_string().hash(state);
     }
 }
 
+/// How a connected user chooses to appear to their friends.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum PresenceKind {
+    #[default]
+    Online,
+    Away,
+    Busy,
+    /// Appear offline to friends while still connected.
+    Invisible,
+}
+
+impl ProtoType for PresenceKind {}
+
+/// Request to set how you appear to your friends.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetPresence {
+    pub language: Language,
+    pub status: PresenceKind,
+}
+
+impl ProtoType for SetPresence {}
+
+/// Request to set or clear what you're currently doing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetActivity {
+    pub language: Language,
+    pub activity: Option<String>,
+}
+
+impl ProtoType for SetActivity {}
+
+/// Request to set your avatar, or clear it with `None`.
+/// The URL must be http(s) and at most `AVATAR_URL_MAX_LENGTH` characters long.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAvatar {
+    pub language: Language,
+    pub avatar_url: Option<String>,
+}
+
+impl ProtoType for SetAvatar {}
+
 /// A friend of a user
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Friend {
-    pub id: String,
+    pub id: UserId,
     pub display_name: String,
     pub status: FriendStatus,
+    /// What the friend is currently doing, e.g. "Playing Ranked - Level 4".
+    pub activity: Option<String>,
+    /// When the friend last disconnected, only sent while they're offline.
+    #[serde(default)]
+    pub last_seen: Option<Timestamp>,
+    /// Where to load the friend's avatar from, if they've set one.
+    #[serde(default)]
+    pub avatar_url: Option<String>,
 }
 
 impl ProtoType for Friend {}
 
+/// Request for a page of the friends list, which is sorted by status and then display name.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetFriends {
+    pub language: Language,
+    pub offset: u32,
+    pub limit: u32,
+}
+
+impl ProtoType for GetFriends {}
+
+/// A page of the friends list starting at `offset`, out of `total` friends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriendsPage {
+    pub offset: u32,
+    pub total: u32,
+    pub friends: Vec<Friend>,
+}
+
+impl ProtoType for FriendsPage {}
+
+/// Request to search for users whose display name starts with the query, ignoring case.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchUsers {
+    pub language: Language,
+    pub query: String,
+}
+
+impl ProtoType for SearchUsers {}
+
+/// Request for another user's profile.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetProfile {
+    pub language: Language,
+    pub user_id: UserId,
+}
+
+impl ProtoType for GetProfile {}
+
+/// What other users can see about a user.
+/// When either of them has blocked the other only the id and display name are filled in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicProfile {
+    pub id: UserId,
+    pub display_name: String,
+    pub status: FriendStatus,
+    pub activity: Option<String>,
+    /// When the account was created.
+    pub member_since: Option<Timestamp>,
+    /// How many friends the two users have in common.
+    pub mutual_friends: u32,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+}
+
+impl ProtoType for PublicProfile {}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FriendRequest {
     pub language: Language,
-    pub friend_id: String,
+    pub friend_id: UserId,
 }
 
 impl ProtoType for FriendRequest {}
@@ -228,6 +755,15 @@ pub struct RejectFriendRequest {
 
 impl ProtoType for RejectFriendRequest {}
 
+/// Take back a friend request we sent
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelFriendRequest {
+    pub language: Language,
+    pub friend_id: UserId,
+}
+
+impl ProtoType for CancelFriendRequest {}
+
 /// A friend request response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FriendRequestAccepted {
@@ -264,7 +800,7 @@ pub struct UnblockUserRequest {
 impl ProtoType for UnblockUserRequest {}
 
 /// A chat channel can either be a room or a private message to another user.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ChatChannel {
     Room(RoomId),
     PrivateMessage(UserId),
@@ -272,6 +808,88 @@ pub enum ChatChannel {
 
 impl ProtoType for ChatChannel {}
 
+/// Request to join a chat room, rooms are created when someone first joins them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinRoom {
+    pub language: Language,
+    pub room_id: RoomId,
+}
+
+impl ProtoType for JoinRoom {}
+
+/// Request to leave a chat room.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LeaveRoom {
+    pub language: Language,
+    pub room_id: RoomId,
+}
+
+impl ProtoType for LeaveRoom {}
+
+/// Request for who's in a chat room, only answered for rooms you're in.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetRoomMembers {
+    pub language: Language,
+    pub room_id: RoomId,
+}
+
+impl ProtoType for GetRoomMembers {}
+
+/// Who's in a chat room, leaving out anyone on either side of a block with you.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomMembers {
+    pub room_id: RoomId,
+    pub members: Vec<Friend>,
+}
+
+impl ProtoType for RoomMembers {}
+
+/// Request to invite a friend to a party, the party is created if it doesn't exist yet.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartyInvite {
+    pub language: Language,
+    pub party_id: PartyId,
+    pub friend_id: UserId,
+}
+
+impl ProtoType for PartyInvite {}
+
+/// Request to join a party you've been invited to, leaving any party you're already in.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartyJoin {
+    pub language: Language,
+    pub party_id: PartyId,
+}
+
+impl ProtoType for PartyJoin {}
+
+/// Request to leave a party.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartyLeave {
+    pub language: Language,
+    pub party_id: PartyId,
+}
+
+impl ProtoType for PartyLeave {}
+
+/// An invite to a friend's party.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartyInviteReceived {
+    pub party_id: PartyId,
+    pub friend: Friend,
+}
+
+impl ProtoType for PartyInviteReceived {}
+
+/// The members of a party, empty if you've left it or it dissolved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartyUpdate {
+    pub party_id: PartyId,
+    pub members: Vec<Friend>,
+}
+
+impl ProtoType for PartyUpdate {}
+
 /// Request to send a chat message.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendChatMessage {
@@ -282,23 +900,260 @@ pub struct SendChatMessage {
 
 impl ProtoType for SendChatMessage {}
 
+/// The server's acknowledgement of a `SendChatMessage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessageAck {
+    /// Why the message wasn't sent, or `None` if it was.
+    pub error: Option<RequestError>,
+}
+
+impl ProtoType for ChatMessageAck {}
+
+/// Seconds since the unix epoch, sent as a plain integer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(chrono::Utc::now().timestamp())
+    }
+
+    /// The time in UTC, `None` if it's too far out for chrono to represent.
+    pub fn to_datetime(self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(self.0, 0)
+    }
+}
+
+impl std::fmt::Display for Timestamp {
+    /// RFC 3339 in UTC, e.g. `2025-01-02T03:04:05+00:00`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_datetime() {
+            Some(time) => write!(f, "{}", time.to_rfc3339()),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
 /// A chat message.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ChatMessage {
+    /// Assigned by the server, so clients can tell when they've been sent the same message twice.
+    pub id: MessageId,
     pub display_name: String,
     pub channel: ChatChannel,
     pub message: String,
-    pub time: u32,
+    pub time: Timestamp,
 }
 
 impl ProtoType for ChatMessage {}
 
+/// Tells a channel that you've started or stopped typing, these are never stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TypingIndicator {
+    pub language: Language,
+    pub channel: ChatChannel,
+    pub is_typing: bool,
+}
+
+impl ProtoType for TypingIndicator {}
+
+/// Someone started or stopped typing in a channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserTyping {
+    pub display_name: String,
+    pub channel: ChatChannel,
+    pub is_typing: bool,
+}
+
+impl ProtoType for UserTyping {}
+
+/// Request for the chat messages sent to a channel before a point in time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetChatHistory {
+    pub language: Language,
+    pub channel: ChatChannel,
+    /// Only messages sent strictly before this time, or the most recent messages if `None`.
+    pub before: Option<Timestamp>,
+    pub limit: u32,
+}
+
+impl ProtoType for GetChatHistory {}
+
+/// Groups friends by status, each group sorted by display name regardless of case and then by id,
+/// so a list doesn't reshuffle when it's refreshed in a different order.
 pub fn categorize_friends(friends: Vec<Friend>) -> HashMap<FriendStatus, Vec<Friend>> {
-    let mut categorized = HashMap::new();
+    let mut categorized: HashMap<FriendStatus, Vec<Friend>> = HashMap::new();
     for friend in friends {
         let status = friend.status;
-        let list = categorized.entry(status).or_insert_with(Vec::new);
+        let list = categorized.entry(status).or_default();
         list.push(friend);
     }
+    for list in categorized.values_mut() {
+        list.sort_by_cached_key(|friend| (friend.display_name.to_lowercase(), friend.id.clone()));
+    }
     categorized
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_message_room_channel_round_trip() {
+        let message = ChatMessage {
+            id: Uuid::new_v4(),
+            display_name: "Test User".to_string(),
+            channel: ChatChannel::Room(RoomId::from("general")),
+            message: "hello room".to_string(),
+            time: Timestamp(1),
+        };
+
+        let json = message.to_json().unwrap();
+        let decoded = ChatMessage::from_json(json).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(decoded.channel, ChatChannel::Room(RoomId::from("general")));
+    }
+
+    #[test]
+    fn test_chat_message_private_channel_round_trip() {
+        let message = ChatMessage {
+            id: Uuid::new_v4(),
+            display_name: "Test User".to_string(),
+            channel: ChatChannel::PrivateMessage(UserId::from("1")),
+            message: "hello friend".to_string(),
+            time: Timestamp(1),
+        };
+
+        let json = message.to_json().unwrap();
+        let decoded = ChatMessage::from_json(json).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(decoded.channel, ChatChannel::PrivateMessage(UserId::from("1")));
+    }
+
+    #[test]
+    fn test_categorized_friends_are_sorted_the_same_every_time() {
+        let friend = |id: &str, display_name: &str, status| Friend {
+            id: UserId::from(id),
+            display_name: display_name.to_string(),
+            status,
+            activity: None,
+            last_seen: None,
+            avatar_url: None,
+        };
+        let friends = vec![
+            friend("4", "bob", FriendStatus::Online),
+            friend("1", "Charlie", FriendStatus::Offline),
+            friend("3", "alice", FriendStatus::Online),
+            friend("2", "Bob", FriendStatus::Online),
+            friend("5", "anna", FriendStatus::Offline),
+        ];
+
+        let ids = |categorized: &HashMap<FriendStatus, Vec<Friend>>, status| {
+            categorized[&status].iter().map(|friend| friend.id.to_string()).collect::<Vec<_>>()
+        };
+        let categorized = categorize_friends(friends.clone());
+        assert_eq!(ids(&categorized, FriendStatus::Online), ["3", "2", "4"]);
+        assert_eq!(ids(&categorized, FriendStatus::Offline), ["5", "1"]);
+
+        let reversed = categorize_friends(friends.into_iter().rev().collect());
+        assert_eq!(reversed, categorized);
+    }
+
+    #[test]
+    fn test_ids_are_plain_strings_on_the_wire() {
+        assert_eq!(serde_json::to_value(UserId::from("1")).unwrap(), serde_json::json!("1"));
+        assert_eq!(serde_json::to_value(RoomId::from("general")).unwrap(), serde_json::json!("general"));
+        assert_eq!(serde_json::from_value::<UserId>(serde_json::json!("1")).unwrap(), UserId::from("1"));
+
+        let channel = ChatChannel::PrivateMessage(UserId::from("1"));
+        assert_eq!(serde_json::to_value(&channel).unwrap(), serde_json::json!({ "PrivateMessage": "1" }));
+    }
+
+    #[test]
+    fn test_timestamp_is_a_plain_integer_on_the_wire() {
+        assert_eq!(serde_json::to_value(Timestamp(1_700_000_000)).unwrap(), serde_json::json!(1_700_000_000));
+        assert_eq!(serde_json::from_value::<Timestamp>(serde_json::json!(1_700_000_000)).unwrap(), Timestamp(1_700_000_000));
+
+        // messages from before the newtype still decode
+        let old = serde_json::json!({
+            "id": Uuid::nil(),
+            "display_name": "Test User",
+            "channel": { "Room": "general" },
+            "message": "hello",
+            "time": 5,
+        });
+        assert_eq!(ChatMessage::from_json(old).unwrap().time, Timestamp(5));
+    }
+
+    #[test]
+    fn test_timestamp_display() {
+        assert_eq!(Timestamp(0).to_string(), "1970-01-01T00:00:00+00:00");
+        assert_eq!(Timestamp(1_700_000_000).to_string(), "2023-11-14T22:13:20+00:00");
+    }
+
+    #[test]
+    fn test_friend_status_helpers() {
+        use FriendStatus::*;
+        // status, is_online, is_friend, is_request_sent, is_request_received, is_blocked, can_whisper, can_block
+        let matrix = [
+            (Online, true, true, false, false, false, true, true),
+            (Away, true, true, false, false, false, true, true),
+            (Busy, true, true, false, false, false, true, true),
+            (Offline, false, true, false, false, false, false, true),
+            (FriendRequestSent, false, false, true, false, false, false, true),
+            (FriendRequestReceived, false, false, false, true, false, false, true),
+            (Blocked, false, false, false, false, true, false, false),
+        ];
+
+        for (status, online, friend, sent, received, blocked, whisper, block) in matrix {
+            assert_eq!(status.is_online(), online, "{status:?}");
+            assert_eq!(status.is_friend(), friend, "{status:?}");
+            assert_eq!(status.is_request_sent(), sent, "{status:?}");
+            assert_eq!(status.is_request_received(), received, "{status:?}");
+            assert_eq!(status.is_blocked(), blocked, "{status:?}");
+            assert_eq!(status.can_whisper(), whisper, "{status:?}");
+            assert_eq!(status.can_block(), block, "{status:?}");
+        }
+    }
+
+    #[test]
+    fn test_friend_status_order() {
+        use FriendStatus::*;
+        assert!(FriendRequestReceived < Online);
+        assert!(Online < Away && Away < Busy && Busy < Offline);
+        assert!(Offline < FriendRequestSent);
+        assert!(FriendRequestSent < Blocked);
+
+        let ordered = FriendStatus::all_ordered();
+        assert!(ordered.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let mut shuffled = [Blocked, Offline, FriendRequestSent, Busy, Online, FriendRequestReceived, Away];
+        shuffled.sort();
+        assert_eq!(shuffled, ordered);
+
+        // a new status has to be given a place in the list
+        for status in ordered {
+            match status {
+                Online | Away | Busy | Offline | FriendRequestSent | FriendRequestReceived | Blocked => {}
+            }
+        }
+        let unique: std::collections::HashSet<_> = ordered.into_iter().collect();
+        assert_eq!(unique.len(), ordered.len());
+    }
+
+    #[test]
+    fn test_request_error_decoding() {
+        let error = RequestError { code: ErrorCode::EmailTaken, message: "Email already exists".to_string() };
+        assert_eq!(RequestError::from_json(error.to_json().unwrap()).unwrap(), error);
+
+        let missing_code = RequestError::from_json(serde_json::json!({ "message": "Oops" })).unwrap();
+        assert_eq!(missing_code.code, ErrorCode::Unknown);
+
+        let legacy = RequestError::from_json(serde_json::json!("Invalid login")).unwrap();
+        assert_eq!(legacy, RequestError { code: ErrorCode::Unknown, message: "Invalid login".to_string() });
+
+        let newer = RequestError::from_json(serde_json::json!({ "code": "SomethingNew", "message": "Oops" })).unwrap();
+        assert_eq!(newer.code, ErrorCode::Unknown);
+    }
+}
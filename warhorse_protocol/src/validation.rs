@@ -0,0 +1,124 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{ACCOUNT_NAME_MAX_LENGTH, AVATAR_URL_MAX_LENGTH, ACCOUNT_NAME_MIN_LENGTH, CHAT_MESSAGE_MAX_LENGTH, DISPLAY_NAME_MAX_LENGTH, DISPLAY_NAME_MIN_LENGTH, PASSWORD_MIN_LENGTH};
+
+const EMAIL_MAX_LENGTH: usize = 254;
+
+/// Why user input was rejected, so the client can check it before sending and the server can localize it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    PasswordTooShort,
+    AccountNameLength,
+    DisplayNameLength,
+    InvalidEmail,
+    ChatMessageEmpty,
+    ChatMessageTooLong,
+    InvalidAvatarUrl,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::PasswordTooShort => write!(f, "Passwords must be at least {} characters long", PASSWORD_MIN_LENGTH),
+            ValidationError::AccountNameLength => write!(f, "Account names must be between {} and {} characters long", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH),
+            ValidationError::DisplayNameLength => write!(f, "Display names must be between {} and {} characters long", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH),
+            ValidationError::InvalidEmail => write!(f, "Invalid email"),
+            ValidationError::ChatMessageEmpty => write!(f, "Chat messages can't be empty"),
+            ValidationError::ChatMessageTooLong => write!(f, "Chat messages can be at most {} characters long", CHAT_MESSAGE_MAX_LENGTH),
+            ValidationError::InvalidAvatarUrl => write!(f, "Avatars must be an http(s) URL of at most {} characters", AVATAR_URL_MAX_LENGTH),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    if password.len() < PASSWORD_MIN_LENGTH {
+        return Err(ValidationError::PasswordTooShort);
+    }
+    Ok(())
+}
+
+pub fn validate_account_name(account_name: &str) -> Result<(), ValidationError> {
+    if account_name.len() < ACCOUNT_NAME_MIN_LENGTH || account_name.len() > ACCOUNT_NAME_MAX_LENGTH {
+        return Err(ValidationError::AccountNameLength);
+    }
+    Ok(())
+}
+
+pub fn validate_display_name(display_name: &str) -> Result<(), ValidationError> {
+    if display_name.len() < DISPLAY_NAME_MIN_LENGTH || display_name.len() > DISPLAY_NAME_MAX_LENGTH {
+        return Err(ValidationError::DisplayNameLength);
+    }
+    Ok(())
+}
+
+pub fn validate_email(email: &str) -> Result<(), ValidationError> {
+    static EMAIL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    // thanks AI!
+    let email_regex = EMAIL_REGEX.get_or_init(|| {
+        Regex::new(r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$").unwrap()
+    });
+
+    if email.len() > EMAIL_MAX_LENGTH || !email_regex.is_match(email) {
+        return Err(ValidationError::InvalidEmail);
+    }
+    Ok(())
+}
+
+/// Chat messages need something besides whitespace in them.
+pub fn validate_chat_message(message: &str) -> Result<(), ValidationError> {
+    if message.trim().is_empty() {
+        return Err(ValidationError::ChatMessageEmpty);
+    }
+    if message.chars().count() > CHAT_MESSAGE_MAX_LENGTH {
+        return Err(ValidationError::ChatMessageTooLong);
+    }
+    Ok(())
+}
+
+/// Avatars have to be an absolute http(s) URL with a host, since clients load them straight into an image.
+pub fn validate_avatar_url(avatar_url: &str) -> Result<(), ValidationError> {
+    static AVATAR_URL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    let avatar_url_regex = AVATAR_URL_REGEX.get_or_init(|| {
+        Regex::new(r"^https?://[a-zA-Z0-9](?:[a-zA-Z0-9.-]*[a-zA-Z0-9])?(?::[0-9]{1,5})?(?:[/?#][^\s]*)?$").unwrap()
+    });
+
+    if avatar_url.chars().count() > AVATAR_URL_MAX_LENGTH || !avatar_url_regex.is_match(avatar_url) {
+        return Err(ValidationError::InvalidAvatarUrl);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_errors() {
+        assert_eq!(validate_password("pass"), Err(ValidationError::PasswordTooShort));
+        assert_eq!(validate_account_name("ac"), Err(ValidationError::AccountNameLength));
+        assert_eq!(validate_display_name(&"d".repeat(DISPLAY_NAME_MAX_LENGTH + 1)), Err(ValidationError::DisplayNameLength));
+        assert_eq!(validate_email("test@"), Err(ValidationError::InvalidEmail));
+        assert_eq!(validate_chat_message(" \n"), Err(ValidationError::ChatMessageEmpty));
+        assert_eq!(validate_chat_message(&"m".repeat(CHAT_MESSAGE_MAX_LENGTH + 1)), Err(ValidationError::ChatMessageTooLong));
+        assert_eq!(validate_avatar_url("javascript:alert(1)"), Err(ValidationError::InvalidAvatarUrl));
+        assert_eq!(validate_avatar_url("https://"), Err(ValidationError::InvalidAvatarUrl));
+        assert_eq!(validate_avatar_url("https://example.com/my avatar.png"), Err(ValidationError::InvalidAvatarUrl));
+        assert_eq!(validate_avatar_url(&format!("https://example.com/{}", "a".repeat(AVATAR_URL_MAX_LENGTH))), Err(ValidationError::InvalidAvatarUrl));
+
+        assert!(validate_password("password").is_ok());
+        assert!(validate_account_name("account_name").is_ok());
+        assert!(validate_display_name("display_name").is_ok());
+        assert!(validate_email("test@example.com").is_ok());
+        assert!(validate_avatar_url("https://cdn.example.com/avatars/1.png?size=64").is_ok());
+        assert!(validate_avatar_url("http://localhost:8080/avatar.png").is_ok());
+        // counted in characters, so this fits even though it's more bytes than that
+        assert!(validate_chat_message(&"é".repeat(CHAT_MESSAGE_MAX_LENGTH)).is_ok());
+    }
+}
@@ -0,0 +1,183 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use socketioxide::SocketIo;
+use warhorse_protocol::RoomId;
+
+use crate::database::Database;
+use crate::filter::{MessageFilter, NoFilter};
+use crate::rate_limit::RateLimit;
+use crate::verification::{EmailVerificationSender, LogVerificationToken};
+use crate::server::WarhorseServer;
+
+// the room everyone joins when they log in, unless configured otherwise
+pub(crate) const DEFAULT_ROOM: &str = "general";
+
+/// Settings for a `WarhorseServer`, the defaults match what the server has always done.
+#[derive(Debug, Clone)]
+pub struct WarhorseServerConfig {
+    /// Address the HTTP server listens on.
+    pub bind_address: String,
+    /// The socket.io namespace clients connect to, they have to be configured with the same one.
+    pub namespace: String,
+    /// Rooms every user joins when they log in.
+    pub default_rooms: Vec<RoomId>,
+    /// How many chat messages each socket may send.
+    pub chat_rate_limit: RateLimit,
+    /// Checks chat messages before they're sent to a room.
+    pub message_filter: Arc<dyn MessageFilter>,
+    /// Runs private messages through `message_filter` too, they're exempt by default.
+    pub filter_private_messages: bool,
+    /// How many private messages are kept for a user while they're offline, older ones are dropped.
+    pub max_offline_messages: usize,
+    /// How many friends a user can have, requests and accepts past it are refused.
+    pub max_friends: usize,
+    /// How often every client is pinged, clients treat a long silence as a lost connection.
+    pub heartbeat_interval: Duration,
+    /// How long an online user can go without sending an activity ping before they're shown as away.
+    pub idle_timeout: Duration,
+    /// Gets new users their email verification token.
+    pub email_verification: Arc<dyn EmailVerificationSender>,
+    /// Only lets users with a verified email send friend requests.
+    pub require_verified_email: bool,
+    /// How many sockets can be connected at once, across every client.
+    pub max_connections: usize,
+    /// How many sockets a single IP address can have connected at once.
+    /// Only enforced when the peer address is known, which `warhorse_server::serve` takes care of.
+    pub max_connections_per_ip: usize,
+    /// Passed to the database, ignored by the in-memory one.
+    pub database_connection_string: String,
+    /// Registers the `test`, `test2` and `test3` users with password `password` on startup, for local development only.
+    pub seed_test_users: bool,
+}
+
+impl Default for WarhorseServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:3000".to_string(),
+            namespace: "/".to_string(),
+            default_rooms: vec![RoomId::from(DEFAULT_ROOM)],
+            chat_rate_limit: RateLimit::default(),
+            message_filter: Arc::new(NoFilter),
+            filter_private_messages: false,
+            max_offline_messages: 100,
+            max_friends: 250,
+            heartbeat_interval: Duration::from_secs(15),
+            idle_timeout: Duration::from_secs(5 * 60),
+            email_verification: Arc::new(LogVerificationToken),
+            require_verified_email: false,
+            max_connections: 10_000,
+            max_connections_per_ip: 32,
+            database_connection_string: String::new(),
+            seed_test_users: false,
+        }
+    }
+}
+
+/// Builds a `WarhorseServer` from a `WarhorseServerConfig`, see `WarhorseServer::builder`.
+pub struct WarhorseServerBuilder<T> {
+    config: WarhorseServerConfig,
+    database: PhantomData<T>,
+}
+
+impl<T> WarhorseServerBuilder<T>
+where T: Database + Send + Sync + 'static
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            config: WarhorseServerConfig::default(),
+            database: PhantomData,
+        }
+    }
+
+    /// Replaces every setting at once.
+    pub fn config(mut self, config: WarhorseServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn bind_address(mut self, bind_address: impl Into<String>) -> Self {
+        self.config.bind_address = bind_address.into();
+        self
+    }
+
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.config.namespace = namespace.into();
+        self
+    }
+
+    pub fn default_rooms(mut self, default_rooms: Vec<RoomId>) -> Self {
+        self.config.default_rooms = default_rooms;
+        self
+    }
+
+    pub fn chat_rate_limit(mut self, chat_rate_limit: RateLimit) -> Self {
+        self.config.chat_rate_limit = chat_rate_limit;
+        self
+    }
+
+    pub fn message_filter(mut self, message_filter: impl MessageFilter + 'static) -> Self {
+        self.config.message_filter = Arc::new(message_filter);
+        self
+    }
+
+    pub fn filter_private_messages(mut self, filter_private_messages: bool) -> Self {
+        self.config.filter_private_messages = filter_private_messages;
+        self
+    }
+
+    pub fn max_offline_messages(mut self, max_offline_messages: usize) -> Self {
+        self.config.max_offline_messages = max_offline_messages;
+        self
+    }
+
+    pub fn max_friends(mut self, max_friends: usize) -> Self {
+        self.config.max_friends = max_friends;
+        self
+    }
+
+    pub fn heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.config.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.config.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn email_verification(mut self, email_verification: impl EmailVerificationSender + 'static) -> Self {
+        self.config.email_verification = Arc::new(email_verification);
+        self
+    }
+
+    pub fn require_verified_email(mut self, require_verified_email: bool) -> Self {
+        self.config.require_verified_email = require_verified_email;
+        self
+    }
+
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.config.max_connections = max_connections;
+        self
+    }
+
+    pub fn max_connections_per_ip(mut self, max_connections_per_ip: usize) -> Self {
+        self.config.max_connections_per_ip = max_connections_per_ip;
+        self
+    }
+
+    pub fn database_connection_string(mut self, database_connection_string: impl Into<String>) -> Self {
+        self.config.database_connection_string = database_connection_string.into();
+        self
+    }
+
+    pub fn seed_test_users(mut self, seed_test_users: bool) -> Self {
+        self.config.seed_test_users = seed_test_users;
+        self
+    }
+
+    pub fn build(self, io: SocketIo) -> WarhorseServer<T> {
+        WarhorseServer::with_config(io, self.config)
+    }
+}
@@ -1,4 +1,6 @@
-use warhorse_protocol::{Friend, UserId, UserRegistration, UserPartial, FriendStatus};
+use std::collections::{HashMap, HashSet};
+
+use warhorse_protocol::{Banned, ChatChannel, ChatMessage, Friend, Timestamp, UserId, UserRegistration, UserPartial, FriendStatus};
 use crate::database::Database;
 
 /// DataAccess is a struct that provides a high-level interface to the database.
@@ -21,14 +23,44 @@ impl<T> DataAccess<T>
         self.database.user_exists(user_id)
     }
 
-    pub fn users_insert(&mut self, user: UserRegistration) -> UserId {
-        self.database.users_insert(user)
+    pub fn users_insert(&mut self, user: UserRegistration, password_hash: String) -> UserId {
+        self.database.users_insert(user, password_hash)
     }
 
     pub fn user_get_pending_friend_requests_for_user(&self, user_id: UserId) -> Vec<Friend> {
         self.database.user_get_pending_friend_requests_for_user(user_id)
     }
 
+    pub fn user_get_friend_request_invites_sent_for_user(&self, user_id: UserId) -> Vec<Friend> {
+        self.database.user_get_friend_request_invites_sent_for_user(user_id)
+    }
+
+    pub fn offline_messages_push(&mut self, user_id: UserId, message: ChatMessage, max_len: usize) {
+        self.database.offline_messages_push(user_id, message, max_len)
+    }
+
+    pub fn offline_messages_drain(&mut self, user_id: UserId) -> Vec<ChatMessage> {
+        self.database.offline_messages_drain(user_id)
+    }
+
+    pub fn chat_history_insert(&mut self, message: ChatMessage) {
+        self.database.chat_history_insert(message)
+    }
+
+    pub fn chat_history_get(&self, channel: ChatChannel, before: Option<Timestamp>, limit: usize) -> Vec<ChatMessage> {
+        self.database.chat_history_get(channel, before, limit)
+    }
+
+    /// Whether the friendship exists, pending friend requests don't count.
+    pub fn are_friends(&self, user_id: UserId, friend_id: UserId) -> bool {
+        self.database.friends_get(user_id).iter().any(|f| f.id == friend_id)
+    }
+
+    /// How many friends the user has, pending friend requests and blocks don't count.
+    pub fn friends_count(&self, user_id: UserId) -> usize {
+        self.database.friends_get(user_id).len()
+    }
+
     pub fn friends_get(&self, user_id: UserId) -> Vec<Friend> {
         let friends = self.database.friends_get(user_id.clone());
 
@@ -45,6 +77,24 @@ impl<T> DataAccess<T>
             .collect()
     }
 
+    /// Friends both users have, leaving out anyone either of them has blocked.
+    pub fn mutual_friends(&self, user_a: UserId, user_b: UserId) -> Vec<Friend> {
+        let blocked: HashSet<UserId> = self.database.user_blocks_get_blocks_for_user(user_a.clone())
+            .into_iter()
+            .chain(self.database.user_blocks_get_blocks_for_user(user_b.clone()))
+            .map(|user| user.id)
+            .collect();
+        let friends_of_a: HashSet<UserId> = self.database.friends_get(user_a)
+            .into_iter()
+            .map(|friend| friend.id)
+            .collect();
+
+        self.database.friends_get(user_b)
+            .into_iter()
+            .filter(|friend| friends_of_a.contains(&friend.id) && !blocked.contains(&friend.id))
+            .collect()
+    }
+
     pub fn friends_add(&mut self, user_id: UserId, friend_id: UserId) {
         self.database.friends_add(user_id, friend_id);
     }
@@ -67,6 +117,10 @@ impl<T> DataAccess<T>
         self.database.users_get(user_id)
     }
 
+    pub fn users_get_many(&self, user_ids: &[UserId]) -> HashMap<UserId, UserPartial> {
+        self.database.users_get_many(user_ids)
+    }
+
     pub fn users_get_by_account_name(&self, account_name: &str) -> Option<UserPartial> {
         self.database.users_get_by_account_name(account_name)
     }
@@ -75,19 +129,115 @@ impl<T> DataAccess<T>
         self.database.users_get_by_email(email)
     }
 
+    pub fn users_search_by_display_name(&self, prefix: &str, limit: usize) -> Vec<UserPartial> {
+        self.database.users_search_by_display_name(prefix, limit)
+    }
+
+    pub fn users_get_password_hash(&self, user_id: UserId) -> Option<String> {
+        self.database.users_get_password_hash(user_id)
+    }
+
+    pub fn users_delete(&mut self, user_id: UserId) {
+        self.database.users_delete(user_id);
+    }
+
+    pub fn users_set_admin(&mut self, user_id: UserId, is_admin: bool) {
+        self.database.users_set_admin(user_id, is_admin);
+    }
+
+    pub fn users_set_avatar(&mut self, user_id: UserId, avatar_url: Option<String>) {
+        self.database.users_set_avatar(user_id, avatar_url);
+    }
+
+    pub fn users_set_last_seen(&mut self, user_id: UserId, last_seen: Timestamp) {
+        self.database.users_set_last_seen(user_id, last_seen);
+    }
+
+    pub fn users_set_email_verification_token(&mut self, user_id: UserId, token: String) {
+        self.database.users_set_email_verification_token(user_id, token);
+    }
+
+    pub fn users_verify_email(&mut self, token: &str) -> Option<UserId> {
+        self.database.users_verify_email(token)
+    }
+
+    pub fn users_is_email_verified(&self, user_id: UserId) -> bool {
+        self.database.users_is_email_verified(user_id)
+    }
+
+    pub fn bans_insert(&mut self, user_id: UserId, reason: Option<String>) {
+        self.database.bans_insert(user_id, reason);
+    }
+
+    pub fn bans_get(&self, user_id: UserId) -> Option<Banned> {
+        self.database.bans_get(user_id)
+    }
+
     pub fn user_blocks_insert(&mut self, user_id: UserId, blocked_id: UserId) {
         self.database.user_blocks_insert(user_id.clone(), blocked_id.clone());
         self.friends_remove(user_id.clone(), blocked_id.clone());
-        self.friends_remove(blocked_id.clone(), user_id.clone());
-        self.friend_requests_remove(user_id.clone(), blocked_id.clone());
-        self.friend_requests_remove(user_id.clone(), blocked_id.clone());
     }
 
     pub fn user_blocks_remove(&mut self, user_id: UserId, blocked_id: UserId) {
         self.database.user_blocks_remove(user_id, blocked_id);
     }
 
+    pub fn user_blocks_get_blocks_for_user(&self, user_id: UserId) -> Vec<Friend> {
+        self.database.user_blocks_get_blocks_for_user(user_id)
+    }
+
     pub fn user_is_blocked(&self, user_id: UserId, blocked_id: UserId) -> bool {
         self.database.user_is_blocked(user_id, blocked_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_in_memory::InMemoryDatabase;
+    use warhorse_protocol::Language;
+
+    fn mutual_ids(data_access: &DataAccess<InMemoryDatabase>, user_a: &UserId, user_b: &UserId) -> Vec<UserId> {
+        let mut ids: Vec<UserId> = data_access.mutual_friends(user_a.clone(), user_b.clone())
+            .into_iter()
+            .map(|friend| friend.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_mutual_friends() {
+        let mut database = InMemoryDatabase::new("");
+        let mut ids = HashMap::new();
+        for name in ["a", "b", "c", "x", "y", "z"] {
+            let registration = UserRegistration {
+                language: Language::English,
+                account_name: name.to_string(),
+                email: format!("{}@example.com", name),
+                display_name: name.to_string(),
+                password: "password".to_string(),
+            };
+            ids.insert(name, database.users_insert(registration, String::new()));
+        }
+        for (user, friend) in [("a", "x"), ("a", "y"), ("a", "z"), ("b", "x"), ("b", "y"), ("c", "z")] {
+            database.friends_add(ids[user].clone(), ids[friend].clone());
+        }
+        let mut data_access = DataAccess::new(database);
+        let mutual = |data_access: &DataAccess<InMemoryDatabase>, user_a, user_b| mutual_ids(data_access, &ids[user_a], &ids[user_b]);
+        let expected = |names: &[&str]| {
+            let mut expected: Vec<UserId> = names.iter().map(|name| ids[name].clone()).collect();
+            expected.sort();
+            expected
+        };
+
+        assert_eq!(mutual(&data_access, "a", "b"), expected(&["x", "y"]));
+        assert_eq!(mutual(&data_access, "b", "a"), expected(&["x", "y"]));
+        assert!(mutual(&data_access, "b", "c").is_empty());
+
+        // blocking hides the mutual whichever side did it
+        data_access.user_blocks_insert(ids["b"].clone(), ids["x"].clone());
+        assert_eq!(mutual(&data_access, "a", "b"), expected(&["y"]));
+        assert_eq!(mutual(&data_access, "b", "a"), expected(&["y"]));
+    }
+}
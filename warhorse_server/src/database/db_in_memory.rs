@@ -1,25 +1,59 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use warhorse_protocol::{Friend, FriendStatus, UserPartial, UserId, UserRegistration};
+use warhorse_protocol::{Banned, ChatChannel, ChatMessage, Friend, FriendStatus, Timestamp, UserPartial, UserId, UserRegistration};
 
 use super::Database;
 
 pub struct InMemoryDatabase {
     users: HashMap<UserId, UserPartial>,
+    password_hashes: HashMap<UserId, String>,
     friendships: HashMap<UserId, Vec<UserId>>,
     friend_requests: HashMap<UserId, Vec<UserId>>,
     user_blocks: Vec<(UserId, UserId)>,
+    bans: HashMap<UserId, Banned>,
+    last_seen: HashMap<UserId, Timestamp>,
+    // keyed by token, a user only ever has one outstanding
+    email_verification_tokens: HashMap<String, UserId>,
+    verified_emails: HashSet<UserId>,
+    offline_messages: HashMap<UserId, VecDeque<ChatMessage>>,
+    // messages are in the order they were sent, so they're sorted by time
+    chat_history: HashMap<ChatChannel, Vec<ChatMessage>>,
     next_user_id: usize,
 }
 
+impl InMemoryDatabase {
+    /// Looks up all of `user_ids` in one go, in the same order and skipping any that no longer exist.
+    fn friends_from(&self, user_ids: Vec<UserId>, status: FriendStatus) -> Vec<Friend> {
+        let users = self.users_get_many(&user_ids);
+        user_ids.iter()
+            .filter_map(|user_id| users.get(user_id))
+            .map(|user| Friend {
+                id: user.id.clone(),
+                display_name: user.display_name.clone(),
+                status,
+                activity: None,
+                last_seen: None,
+                avatar_url: user.avatar_url.clone(),
+            })
+            .collect()
+    }
+}
+
 impl Database for InMemoryDatabase {
 
     fn new(_connection_string: &str) -> Self {
         InMemoryDatabase {
             users: HashMap::new(),
+            password_hashes: HashMap::new(),
             friendships: HashMap::new(),
             friend_requests: HashMap::new(),
             user_blocks: Vec::new(),
+            bans: HashMap::new(),
+            last_seen: HashMap::new(),
+            email_verification_tokens: HashMap::new(),
+            verified_emails: HashSet::new(),
+            offline_messages: HashMap::new(),
+            chat_history: HashMap::new(),
             next_user_id: 0,
         }
     }
@@ -28,8 +62,8 @@ impl Database for InMemoryDatabase {
         self.users.contains_key(&user_id)
     }
 
-    fn users_insert(&mut self, user: UserRegistration) -> UserId {
-        let new_user_id = self.next_user_id.to_string();
+    fn users_insert(&mut self, user: UserRegistration, password_hash: String) -> UserId {
+        let new_user_id = UserId::from(self.next_user_id.to_string());
         self.next_user_id += 1;
         let user = UserPartial {
             id: new_user_id.clone(),
@@ -39,15 +73,45 @@ impl Database for InMemoryDatabase {
             account_name_lower: Some(user.account_name.to_lowercase()),
             account_name: Some(user.account_name),
             email: Some(user.email),
+            activity: None,
+            is_admin: false,
+            created_at: Timestamp::now(),
+            avatar_url: None,
         };
         self.users.insert(new_user_id.clone(), user);
+        self.password_hashes.insert(new_user_id.clone(), password_hash);
         new_user_id
     }
 
+    fn users_delete(&mut self, user_id: UserId) {
+        self.users.remove(&user_id);
+        self.password_hashes.remove(&user_id);
+        self.offline_messages.remove(&user_id);
+
+        self.friendships.remove(&user_id);
+        self.friendships.values_mut().for_each(|friends| friends.retain(|id| id != &user_id));
+
+        self.friend_requests.remove(&user_id);
+        self.friend_requests.values_mut().for_each(|friend_requests| friend_requests.retain(|id| id != &user_id));
+
+        self.user_blocks.retain(|(id, blocked)| id != &user_id && blocked != &user_id);
+        self.bans.remove(&user_id);
+        self.last_seen.remove(&user_id);
+        self.email_verification_tokens.retain(|_, id| id != &user_id);
+        self.verified_emails.remove(&user_id);
+    }
+
     fn users_get(&self, user_id: UserId) -> Option<UserPartial> {
         self.users.get(&user_id).cloned()
     }
 
+    fn users_get_many(&self, user_ids: &[UserId]) -> HashMap<UserId, UserPartial> {
+        user_ids.iter()
+            .filter_map(|user_id| self.users.get(user_id))
+            .map(|user| (user.id.clone(), user.clone()))
+            .collect()
+    }
+
     fn users_get_by_account_name(&self, account_name: &str) -> Option<UserPartial> {
         self.users.values().find(|user| {
             if let Some(user_account_name) = &user.account_name {
@@ -68,6 +132,53 @@ impl Database for InMemoryDatabase {
         }).cloned()
     }
 
+    fn users_get_password_hash(&self, user_id: UserId) -> Option<String> {
+        self.password_hashes.get(&user_id).cloned()
+    }
+
+    fn users_search_by_display_name(&self, prefix: &str, limit: usize) -> Vec<UserPartial> {
+        let mut users: Vec<UserPartial> = self.users.values()
+            .filter(|user| user.display_name_lower.starts_with(prefix))
+            .cloned()
+            .collect();
+
+        // the map has no order, so sort to keep results stable
+        users.sort_by(|a, b| a.display_name_lower.cmp(&b.display_name_lower));
+        users.truncate(limit);
+        users
+    }
+
+    fn users_set_admin(&mut self, user_id: UserId, is_admin: bool) {
+        if let Some(user) = self.users.get_mut(&user_id) {
+            user.is_admin = is_admin;
+        }
+    }
+
+    fn users_set_avatar(&mut self, user_id: UserId, avatar_url: Option<String>) {
+        if let Some(user) = self.users.get_mut(&user_id) {
+            user.avatar_url = avatar_url;
+        }
+    }
+
+    fn users_set_last_seen(&mut self, user_id: UserId, last_seen: Timestamp) {
+        self.last_seen.insert(user_id, last_seen);
+    }
+
+    fn users_set_email_verification_token(&mut self, user_id: UserId, token: String) {
+        self.email_verification_tokens.retain(|_, id| id != &user_id);
+        self.email_verification_tokens.insert(token, user_id);
+    }
+
+    fn users_verify_email(&mut self, token: &str) -> Option<UserId> {
+        let user_id = self.email_verification_tokens.remove(token)?;
+        self.verified_emails.insert(user_id.clone());
+        Some(user_id)
+    }
+
+    fn users_is_email_verified(&self, user_id: UserId) -> bool {
+        self.verified_emails.contains(&user_id)
+    }
+
     fn user_blocks_insert(&mut self, user_id: UserId, blocked_id: UserId) {
         self.user_blocks.push((user_id, blocked_id));
     }
@@ -77,60 +188,32 @@ impl Database for InMemoryDatabase {
     }
 
     fn user_blocks_get_blocks_for_user(&self, user_id: UserId) -> Vec<Friend> {
-        self.user_blocks.iter()
-            .filter_map(|(id, blocked_id)| {
-                if id == &user_id {
-                    self.users_get(blocked_id.clone())
-                } else {
-                    None
-                }
-            })
-            .map(|user| Friend {
-                id: user.id,
-                display_name: user.display_name,
-                status: FriendStatus::Blocked,
-            })
-            .collect()
+        let blocked_ids = self.user_blocks.iter()
+            .filter(|(id, _)| id == &user_id)
+            .map(|(_, blocked_id)| blocked_id.clone())
+            .collect();
+        self.friends_from(blocked_ids, FriendStatus::Blocked)
+    }
+
+    fn bans_insert(&mut self, user_id: UserId, reason: Option<String>) {
+        self.bans.insert(user_id, Banned { reason });
+    }
+
+    fn bans_get(&self, user_id: UserId) -> Option<Banned> {
+        self.bans.get(&user_id).cloned()
     }
 
     fn user_get_pending_friend_requests_for_user(&self, user_id: UserId) -> Vec<Friend> {
-        self.friend_requests.iter()
-            .filter_map(|(id, friend_requests)| {
-                if friend_requests.contains(&user_id) {
-                    self.users_get(id.clone())
-                } else {
-                    None
-                }
-            })
-            .map(|user| Friend {
-                id: user.id,
-                display_name: user.display_name,
-                status: FriendStatus::FriendRequestReceived,
-            })
-            .collect()
+        let requester_ids = self.friend_requests.iter()
+            .filter(|(_, friend_requests)| friend_requests.contains(&user_id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        self.friends_from(requester_ids, FriendStatus::FriendRequestReceived)
     }
 
     fn user_get_friend_request_invites_sent_for_user(&self, user_id: UserId) -> Vec<Friend> {
-        // we need to do a deep search to find all the friend requests that the user has sent.
-        self.friend_requests.iter()
-            .filter_map(|(id, friend_requests)| {
-                if id == &user_id {
-                    Some(friend_requests)
-                } else {
-                    None
-                }
-            })
-            .flat_map(|friend_requests| {
-                friend_requests.iter()
-                    .filter_map(|id| self.users_get(id.clone()))
-                    .map(|user| Friend {
-                        id: user.id,
-                        display_name: user.display_name,
-                        status: FriendStatus::FriendRequestSent,
-                    })
-                    .collect::<Vec<Friend>>()
-            })
-            .collect()
+        let requested_ids = self.friend_requests.get(&user_id).cloned().unwrap_or_default();
+        self.friends_from(requested_ids, FriendStatus::FriendRequestSent)
     }
 
     fn user_is_blocked(&self, user_id: UserId, blocked_id: UserId) -> bool {
@@ -138,32 +221,30 @@ impl Database for InMemoryDatabase {
     }
 
     fn friend_requests_insert(&mut self, user_id: UserId, friend_id: UserId) {
-        if let Some(friend_requests) = self.friend_requests.get_mut(&user_id) {
+        let friend_requests = self.friend_requests.entry(user_id).or_default();
+        // the same request twice is a no-op, like the postgres `ON CONFLICT DO NOTHING`.
+        if !friend_requests.contains(&friend_id) {
             friend_requests.push(friend_id);
-        } else {
-            self.friend_requests.insert(user_id, vec![friend_id]);
         }
     }
 
     fn friend_requests_remove(&mut self, user_id: UserId, friend_id: UserId) {
+        // a request between the two users can be in either direction, like the postgres version.
         if let Some(friend_requests) = self.friend_requests.get_mut(&user_id) {
-            
-            // remove the friend request from the user.
             friend_requests.retain(|id| id != &friend_id);
-
-            // need to do a deep search to remove the friend request from the other user.
-            self.friend_requests.iter_mut()
-                .for_each(|(_, friend_requests)| {
-                    friend_requests.retain(|id| id != &user_id);
-                });
+        }
+        if let Some(friend_requests) = self.friend_requests.get_mut(&friend_id) {
+            friend_requests.retain(|id| id != &user_id);
         }
     }
 
     fn friends_add(&mut self, user_id: UserId, friend_id: UserId) {
-        if let Some(friends) = self.friendships.get_mut(&user_id) {
-            friends.push(friend_id);
-        } else {
-            self.friendships.insert(user_id, vec![friend_id]);
+        for (user_id, friend_id) in [(user_id.clone(), friend_id.clone()), (friend_id, user_id)] {
+            let friends = self.friendships.entry(user_id).or_default();
+            // adding a friend twice is a no-op, like the postgres `ON CONFLICT DO NOTHING`.
+            if !friends.contains(&friend_id) {
+                friends.push(friend_id);
+            }
         }
     }
 
@@ -171,18 +252,198 @@ impl Database for InMemoryDatabase {
         if let Some(friends) = self.friendships.get_mut(&user_id) {
             friends.retain(|id| id != &friend_id);
         }
+        if let Some(friends) = self.friendships.get_mut(&friend_id) {
+            friends.retain(|id| id != &user_id);
+        }
     }
 
     fn friends_get(&self, user_id: UserId) -> Vec<Friend> {
-        self.friendships.get(&user_id).cloned().unwrap_or_default()
-            .iter()
-            .filter_map(|id| {
-                self.users_get(id.clone()).map(|user| Friend {
-                    id: user.id.clone(),
-                    display_name: user.display_name.clone(),
-                    status: FriendStatus::Offline, // it is up to the caller to figure out the status, so we default to offline.
-                })
-            })
+        let friend_ids = self.friendships.get(&user_id).cloned().unwrap_or_default();
+        // it is up to the caller to figure out the status, so we default to offline.
+        self.friends_from(friend_ids, FriendStatus::Offline)
+            .into_iter()
+            .map(|friend| Friend { last_seen: self.last_seen.get(&friend.id).copied(), ..friend })
             .collect()
     }
+
+    fn offline_messages_push(&mut self, user_id: UserId, message: ChatMessage, max_len: usize) {
+        let messages = self.offline_messages.entry(user_id).or_default();
+        messages.push_back(message);
+        while messages.len() > max_len {
+            messages.pop_front();
+        }
+    }
+
+    fn offline_messages_drain(&mut self, user_id: UserId) -> Vec<ChatMessage> {
+        self.offline_messages.remove(&user_id)
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+
+    fn chat_history_insert(&mut self, message: ChatMessage) {
+        self.chat_history.entry(message.channel.clone()).or_default().push(message);
+    }
+
+    fn chat_history_get(&self, channel: ChatChannel, before: Option<Timestamp>, limit: usize) -> Vec<ChatMessage> {
+        let messages = match self.chat_history.get(&channel) {
+            Some(messages) => messages,
+            None => return Vec::new(),
+        };
+
+        let end = match before {
+            Some(before) => messages.partition_point(|message| message.time < before),
+            None => messages.len(),
+        };
+        messages[end.saturating_sub(limit)..end].to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warhorse_protocol::{Language, RoomId, Uuid};
+
+    fn database_with_history() -> InMemoryDatabase {
+        let mut database = InMemoryDatabase::new("");
+        for time in 1..=5 {
+            database.chat_history_insert(message(time));
+        }
+        database
+    }
+
+    fn message(time: i64) -> ChatMessage {
+        ChatMessage {
+            id: Uuid::new_v4(),
+            display_name: "Test User".to_string(),
+            channel: general(),
+            message: time.to_string(),
+            time: Timestamp(time),
+        }
+    }
+
+    fn general() -> ChatChannel {
+        ChatChannel::Room(RoomId::from("general"))
+    }
+
+    fn times(messages: Vec<ChatMessage>) -> Vec<i64> {
+        messages.iter().map(|message| message.time.0).collect()
+    }
+
+    #[test]
+    fn test_chat_history_latest_page() {
+        let database = database_with_history();
+        assert_eq!(times(database.chat_history_get(general(), None, 2)), vec![4, 5]);
+        assert_eq!(times(database.chat_history_get(general(), None, 10)), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_chat_history_before_is_exclusive() {
+        let database = database_with_history();
+        assert_eq!(times(database.chat_history_get(general(), Some(Timestamp(4)), 2)), vec![2, 3]);
+        assert_eq!(times(database.chat_history_get(general(), Some(Timestamp(3)), 10)), vec![1, 2]);
+        assert_eq!(times(database.chat_history_get(general(), Some(Timestamp(100)), 1)), vec![5]);
+    }
+
+    #[test]
+    fn test_chat_history_empty_pages() {
+        let database = database_with_history();
+        assert!(database.chat_history_get(general(), Some(Timestamp(1)), 10).is_empty());
+        assert!(database.chat_history_get(general(), None, 0).is_empty());
+        assert!(database.chat_history_get(ChatChannel::Room(RoomId::from("other")), None, 10).is_empty());
+    }
+
+    fn insert_user(database: &mut InMemoryDatabase, account_name: &str) -> UserId {
+        database.users_insert(
+            UserRegistration {
+                language: Language::English,
+                account_name: account_name.to_string(),
+                email: format!("{}@example.com", account_name),
+                display_name: account_name.to_string(),
+                password: "password".to_string(),
+            },
+            "hash".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_duplicate_friend_request_is_ignored() {
+        let mut database = InMemoryDatabase::new("");
+        let user_id = insert_user(&mut database, "test");
+        let friend_id = insert_user(&mut database, "test2");
+
+        database.friend_requests_insert(user_id.clone(), friend_id.clone());
+        database.friend_requests_insert(user_id.clone(), friend_id.clone());
+
+        assert_eq!(database.user_get_pending_friend_requests_for_user(friend_id).len(), 1);
+        assert_eq!(database.user_get_friend_request_invites_sent_for_user(user_id).len(), 1);
+    }
+
+    #[test]
+    fn test_users_get_many_returns_the_users_that_exist() {
+        let mut database = InMemoryDatabase::new("");
+        let user_id = insert_user(&mut database, "test");
+        let other_id = insert_user(&mut database, "test2");
+        insert_user(&mut database, "test3");
+
+        let users = database.users_get_many(&[user_id.clone(), UserId::from("missing"), other_id.clone(), user_id.clone()]);
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[&user_id].account_name.as_deref(), Some("test"));
+        assert_eq!(users[&other_id].account_name.as_deref(), Some("test2"));
+        assert!(database.users_get_many(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_friendships_go_both_ways() {
+        let mut database = InMemoryDatabase::new("");
+        let user_id = insert_user(&mut database, "test");
+        let friend_id = insert_user(&mut database, "test2");
+
+        database.friends_add(user_id.clone(), friend_id.clone());
+        assert_eq!(database.friends_get(user_id.clone()).iter().map(|friend| friend.id.clone()).collect::<Vec<_>>(), vec![friend_id.clone()]);
+        assert_eq!(database.friends_get(friend_id.clone()).iter().map(|friend| friend.id.clone()).collect::<Vec<_>>(), vec![user_id.clone()]);
+
+        database.friends_remove(friend_id.clone(), user_id.clone());
+        assert!(database.friends_get(user_id).is_empty());
+        assert!(database.friends_get(friend_id).is_empty());
+    }
+
+    #[test]
+    fn test_users_delete_removes_related_rows() {
+        let mut database = InMemoryDatabase::new("");
+        let user_id = insert_user(&mut database, "test");
+        let friend_id = insert_user(&mut database, "test2");
+        let other_id = insert_user(&mut database, "test3");
+
+        database.friends_add(user_id.clone(), friend_id.clone());
+        database.friend_requests_insert(other_id.clone(), user_id.clone());
+        database.friend_requests_insert(user_id.clone(), other_id.clone());
+        database.user_blocks_insert(other_id.clone(), user_id.clone());
+        database.offline_messages_push(user_id.clone(), message(1), 10);
+
+        database.users_delete(user_id.clone());
+
+        assert!(!database.user_exists(user_id.clone()));
+        assert!(database.users_get_password_hash(user_id.clone()).is_none());
+        assert!(database.friends_get(user_id.clone()).is_empty());
+        assert!(database.friends_get(friend_id).is_empty());
+        assert!(database.user_get_pending_friend_requests_for_user(other_id.clone()).is_empty());
+        assert!(database.user_get_friend_request_invites_sent_for_user(other_id.clone()).is_empty());
+        assert!(!database.user_is_blocked(other_id, user_id.clone()));
+        assert!(database.offline_messages_drain(user_id).is_empty());
+    }
+
+    #[test]
+    fn test_email_verification_tokens() {
+        let mut database = InMemoryDatabase::new("");
+        let user_id = insert_user(&mut database, "test");
+        assert!(!database.users_is_email_verified(user_id.clone()));
+
+        database.users_set_email_verification_token(user_id.clone(), "old".to_string());
+        database.users_set_email_verification_token(user_id.clone(), "new".to_string());
+        // only the latest token works, and only once
+        assert_eq!(database.users_verify_email("old"), None);
+        assert_eq!(database.users_verify_email("new"), Some(user_id.clone()));
+        assert_eq!(database.users_verify_email("new"), None);
+        assert!(database.users_is_email_verified(user_id));
+    }
 }
@@ -1,77 +1,781 @@
-use warhorse_protocol::{Friend, UserPartial, UserId, UserRegistration};
+use std::collections::HashMap;
+use std::future::Future;
 
-use super::Database;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use tokio::runtime::Handle;
+use tracing::error;
+use warhorse_protocol::{Banned, ChatChannel, ChatMessage, Friend, FriendStatus, Language, Timestamp, UserPartial, UserId, UserRegistration};
 
-pub struct PostgresDatabase {}
+use super::{migrations, Database};
+
+const MAX_CONNECTIONS: u32 = 10;
+
+// id, display_name_lower, display_name, account_name_lower, account_name, email, language, is_admin, created_at, avatar_url
+type UserRow = (String, String, String, Option<String>, Option<String>, Option<String>, String, bool, i64, Option<String>);
+
+const SELECT_USER: &str = "SELECT id::TEXT, display_name_lower, display_name, account_name_lower, account_name, email, language, is_admin, EXTRACT(EPOCH FROM created_at)::BIGINT, avatar_url FROM users";
+
+pub struct PostgresDatabase {
+    pool: PgPool,
+    runtime: Handle,
+}
+
+impl PostgresDatabase {
+    /// The `Database` trait is synchronous, so we block on the query from within the tokio runtime.
+    /// This requires the multi-threaded runtime.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        tokio::task::block_in_place(|| self.runtime.block_on(future))
+    }
+
+    fn get_user(&self, query: &str, value: &str) -> Option<UserPartial> {
+        let result = self.block_on(
+            sqlx::query_as::<_, UserRow>(query)
+                .bind(value)
+                .fetch_optional(&self.pool)
+        );
+
+        match result {
+            Ok(row) => row.map(user_from_row),
+            Err(e) => {
+                error!("Failed to get user: {}", e);
+                None
+            }
+        }
+    }
+
+    fn get_friends(&self, query: &str, user_id: UserId, status: FriendStatus) -> Vec<Friend> {
+        let result = self.block_on(
+            sqlx::query_as::<_, (String, String, Option<String>, Option<i64>)>(query)
+                .bind(user_id.0)
+                .fetch_all(&self.pool)
+        );
+
+        match result {
+            Ok(rows) => rows.into_iter()
+                .map(|(id, display_name, avatar_url, last_seen)| Friend {
+                    id: UserId(id),
+                    display_name,
+                    status,
+                    activity: None,
+                    last_seen: last_seen.map(Timestamp),
+                    avatar_url,
+                })
+                .collect(),
+            Err(e) => {
+                error!("Failed to get friends: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn execute(&self, query: &str, user_id: UserId, other_id: UserId) {
+        let result = self.block_on(
+            sqlx::query(query)
+                .bind(user_id.0)
+                .bind(other_id.0)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to execute query: {}", e);
+        }
+    }
+}
 
 impl Database for PostgresDatabase {
 
-    fn new(_connection_string: &str) -> Self {
-        unimplemented!();
+    fn new(connection_string: &str) -> Self {
+        let database = PostgresDatabase {
+            pool: PgPoolOptions::new()
+                .max_connections(MAX_CONNECTIONS)
+                .connect_lazy(connection_string)
+                .expect("Invalid postgres connection string"),
+            runtime: Handle::current(),
+        };
+
+        database.block_on(migrations::run(&database.pool))
+            .expect("Failed to run postgres migrations");
+
+        database
     }
 
     fn user_exists(&self, user_id: UserId) -> bool {
-        unimplemented!();
+        let result = self.block_on(
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1::BIGINT)")
+                .bind(user_id.0)
+                .fetch_one(&self.pool)
+        );
+
+        result.unwrap_or_else(|e| {
+            error!("Failed to check if user exists: {}", e);
+            false
+        })
     }
 
-    fn users_insert(&mut self, user: UserRegistration) -> UserId {
-        unimplemented!();
+    fn users_insert(&mut self, user: UserRegistration, password_hash: String) -> UserId {
+        let result = self.block_on(
+            sqlx::query_scalar::<_, String>(
+                "INSERT INTO users (account_name, account_name_lower, email, display_name, display_name_lower, password_hash, language)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING id::TEXT"
+            )
+                .bind(&user.account_name)
+                .bind(user.account_name.to_lowercase())
+                .bind(&user.email)
+                .bind(&user.display_name)
+                .bind(user.display_name.to_lowercase())
+                .bind(password_hash)
+                .bind(language_to_db(user.language))
+                .fetch_one(&self.pool)
+        );
+
+        result.map(UserId).unwrap_or_else(|e| {
+            error!("Failed to insert user: {}", e);
+            UserId::default()
+        })
     }
 
     fn users_get(&self, user_id: UserId) -> Option<UserPartial> {
-        unimplemented!();
+        self.get_user(&format!("{} WHERE id = $1::BIGINT", SELECT_USER), &user_id)
+    }
+
+    fn users_get_many(&self, user_ids: &[UserId]) -> HashMap<UserId, UserPartial> {
+        let ids: Vec<String> = user_ids.iter().map(|user_id| user_id.0.clone()).collect();
+        let result = self.block_on(
+            sqlx::query_as::<_, UserRow>(&format!("{} WHERE id = ANY($1::BIGINT[])", SELECT_USER))
+                .bind(ids)
+                .fetch_all(&self.pool)
+        );
+
+        match result {
+            Ok(rows) => rows.into_iter().map(user_from_row).map(|user| (user.id.clone(), user)).collect(),
+            Err(e) => {
+                error!("Failed to get users: {}", e);
+                HashMap::new()
+            }
+        }
     }
 
     fn users_get_by_account_name(&self, account_name: &str) -> Option<UserPartial> {
-        unimplemented!();
+        self.get_user(&format!("{} WHERE account_name = $1", SELECT_USER), account_name)
     }
 
     fn users_get_by_email(&self, email: &str) -> Option<UserPartial> {
-        unimplemented!();
+        self.get_user(&format!("{} WHERE email = $1", SELECT_USER), email)
+    }
+
+    fn users_get_password_hash(&self, user_id: UserId) -> Option<String> {
+        let result = self.block_on(
+            sqlx::query_scalar::<_, String>("SELECT password_hash FROM users WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .fetch_optional(&self.pool)
+        );
+
+        result.unwrap_or_else(|e| {
+            error!("Failed to get password hash: {}", e);
+            None
+        })
+    }
+
+    fn users_delete(&mut self, user_id: UserId) {
+        // friendships, friend requests, blocks, bans and offline messages are removed by `ON DELETE CASCADE`.
+        let result = self.block_on(
+            sqlx::query("DELETE FROM users WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to delete user: {}", e);
+        }
+    }
+
+    fn users_search_by_display_name(&self, prefix: &str, limit: usize) -> Vec<UserPartial> {
+        // escape LIKE wildcards so they match literally
+        let pattern = format!("{}%", prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let result = self.block_on(
+            sqlx::query_as::<_, UserRow>(&format!("{} WHERE display_name_lower LIKE $1 ORDER BY display_name_lower LIMIT $2", SELECT_USER))
+                .bind(pattern)
+                .bind(limit as i64)
+                .fetch_all(&self.pool)
+        );
+
+        match result {
+            Ok(rows) => rows.into_iter().map(user_from_row).collect(),
+            Err(e) => {
+                error!("Failed to search users: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn users_set_admin(&mut self, user_id: UserId, is_admin: bool) {
+        let result = self.block_on(
+            sqlx::query("UPDATE users SET is_admin = $2 WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .bind(is_admin)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to set admin: {}", e);
+        }
+    }
+
+    fn users_set_last_seen(&mut self, user_id: UserId, last_seen: Timestamp) {
+        let result = self.block_on(
+            sqlx::query("UPDATE users SET last_seen = $2 WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .bind(last_seen.0)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to set last seen: {}", e);
+        }
+    }
+
+    fn users_set_avatar(&mut self, user_id: UserId, avatar_url: Option<String>) {
+        let result = self.block_on(
+            sqlx::query("UPDATE users SET avatar_url = $2 WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .bind(avatar_url)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to set avatar: {}", e);
+        }
+    }
+
+    fn users_set_email_verification_token(&mut self, user_id: UserId, token: String) {
+        let result = self.block_on(
+            sqlx::query("UPDATE users SET email_verification_token = $2 WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .bind(token)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to set email verification token: {}", e);
+        }
+    }
+
+    fn users_verify_email(&mut self, token: &str) -> Option<UserId> {
+        let result = self.block_on(
+            sqlx::query_scalar::<_, String>(
+                "UPDATE users SET email_verified = TRUE, email_verification_token = NULL WHERE email_verification_token = $1 RETURNING id::TEXT"
+            )
+                .bind(token)
+                .fetch_optional(&self.pool)
+        );
+
+        result.map(|user_id| user_id.map(UserId)).unwrap_or_else(|e| {
+            error!("Failed to verify email: {}", e);
+            None
+        })
+    }
+
+    fn users_is_email_verified(&self, user_id: UserId) -> bool {
+        let result = self.block_on(
+            sqlx::query_scalar::<_, bool>("SELECT email_verified FROM users WHERE id = $1::BIGINT")
+                .bind(user_id.0)
+                .fetch_optional(&self.pool)
+        );
+
+        match result {
+            Ok(verified) => verified.unwrap_or(false),
+            Err(e) => {
+                error!("Failed to check email verification: {}", e);
+                false
+            }
+        }
     }
 
     fn user_blocks_insert(&mut self, user_id: UserId, blocked_id: UserId) {
-        unimplemented!();
+        self.execute(
+            "INSERT INTO user_blocks (user_id, blocked_id) VALUES ($1::BIGINT, $2::BIGINT) ON CONFLICT DO NOTHING",
+            user_id,
+            blocked_id,
+        );
     }
 
     fn user_blocks_remove(&mut self, user_id: UserId, blocked_id: UserId) {
-        unimplemented!();
+        self.execute(
+            "DELETE FROM user_blocks WHERE user_id = $1::BIGINT AND blocked_id = $2::BIGINT",
+            user_id,
+            blocked_id,
+        );
     }
 
     fn user_blocks_get_blocks_for_user(&self, user_id: UserId) -> Vec<Friend> {
-        unimplemented!();
+        self.get_friends(
+            "SELECT users.id::TEXT, users.display_name, users.avatar_url, NULL::BIGINT FROM user_blocks
+            JOIN users ON users.id = user_blocks.blocked_id
+            WHERE user_blocks.user_id = $1::BIGINT",
+            user_id,
+            FriendStatus::Blocked,
+        )
+    }
+
+    fn bans_insert(&mut self, user_id: UserId, reason: Option<String>) {
+        let result = self.block_on(
+            sqlx::query(
+                "INSERT INTO bans (user_id, reason) VALUES ($1::BIGINT, $2)
+                ON CONFLICT (user_id) DO UPDATE SET reason = EXCLUDED.reason"
+            )
+                .bind(user_id.0)
+                .bind(reason)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to ban user: {}", e);
+        }
+    }
+
+    fn bans_get(&self, user_id: UserId) -> Option<Banned> {
+        let result = self.block_on(
+            sqlx::query_scalar::<_, Option<String>>("SELECT reason FROM bans WHERE user_id = $1::BIGINT")
+                .bind(user_id.0)
+                .fetch_optional(&self.pool)
+        );
+
+        match result {
+            Ok(row) => row.map(|reason| Banned { reason }),
+            Err(e) => {
+                error!("Failed to get ban: {}", e);
+                None
+            }
+        }
     }
 
     fn user_get_pending_friend_requests_for_user(&self, user_id: UserId) -> Vec<Friend> {
-        unimplemented!();
+        self.get_friends(
+            "SELECT users.id::TEXT, users.display_name, users.avatar_url, NULL::BIGINT FROM friend_requests
+            JOIN users ON users.id = friend_requests.user_id
+            WHERE friend_requests.friend_id = $1::BIGINT",
+            user_id,
+            FriendStatus::FriendRequestReceived,
+        )
     }
 
     fn user_get_friend_request_invites_sent_for_user(&self, user_id: UserId) -> Vec<Friend> {
-        unimplemented!();
+        self.get_friends(
+            "SELECT users.id::TEXT, users.display_name, users.avatar_url, NULL::BIGINT FROM friend_requests
+            JOIN users ON users.id = friend_requests.friend_id
+            WHERE friend_requests.user_id = $1::BIGINT",
+            user_id,
+            FriendStatus::FriendRequestSent,
+        )
     }
 
     fn user_is_blocked(&self, user_id: UserId, blocked_id: UserId) -> bool {
-        unimplemented!();
+        let result = self.block_on(
+            sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM user_blocks WHERE user_id = $1::BIGINT AND blocked_id = $2::BIGINT)"
+            )
+                .bind(user_id.0)
+                .bind(blocked_id.0)
+                .fetch_one(&self.pool)
+        );
+
+        result.unwrap_or_else(|e| {
+            error!("Failed to check if user is blocked: {}", e);
+            false
+        })
     }
 
     fn friend_requests_insert(&mut self, user_id: UserId, friend_id: UserId) {
-        unimplemented!();
+        self.execute(
+            "INSERT INTO friend_requests (user_id, friend_id) VALUES ($1::BIGINT, $2::BIGINT) ON CONFLICT DO NOTHING",
+            user_id,
+            friend_id,
+        );
     }
 
     fn friend_requests_remove(&mut self, user_id: UserId, friend_id: UserId) {
-        unimplemented!();
+        // a request between the two users can be in either direction.
+        self.execute(
+            "DELETE FROM friend_requests
+            WHERE (user_id = $1::BIGINT AND friend_id = $2::BIGINT) OR (user_id = $2::BIGINT AND friend_id = $1::BIGINT)",
+            user_id,
+            friend_id,
+        );
     }
 
     fn friends_add(&mut self, user_id: UserId, friend_id: UserId) {
-        unimplemented!();
+        self.execute(
+            // both rows in one statement, so there's never only one of them.
+            "INSERT INTO friendships (user_id, friend_id) VALUES ($1::BIGINT, $2::BIGINT), ($2::BIGINT, $1::BIGINT) ON CONFLICT DO NOTHING",
+            user_id,
+            friend_id,
+        );
     }
 
     fn friends_remove(&mut self, user_id: UserId, friend_id: UserId) {
-        unimplemented!();
+        self.execute(
+            "DELETE FROM friendships
+            WHERE (user_id = $1::BIGINT AND friend_id = $2::BIGINT) OR (user_id = $2::BIGINT AND friend_id = $1::BIGINT)",
+            user_id,
+            friend_id,
+        );
     }
 
     fn friends_get(&self, user_id: UserId) -> Vec<Friend> {
-        unimplemented!();
+        // it is up to the caller to figure out the status, so we default to offline.
+        self.get_friends(
+            "SELECT users.id::TEXT, users.display_name, users.avatar_url, users.last_seen FROM friendships
+            JOIN users ON users.id = friendships.friend_id
+            WHERE friendships.user_id = $1::BIGINT",
+            user_id,
+            FriendStatus::Offline,
+        )
     }
+
+    fn offline_messages_push(&mut self, user_id: UserId, message: ChatMessage, max_len: usize) {
+        let message = match serde_json::to_string(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Failed to serialize offline message: {}", e);
+                return;
+            }
+        };
+
+        let result = self.block_on(async {
+            let mut transaction = self.pool.begin().await?;
+            sqlx::query("INSERT INTO offline_messages (user_id, message) VALUES ($1::BIGINT, $2)")
+                .bind(&user_id.0)
+                .bind(message)
+                .execute(&mut *transaction)
+                .await?;
+            sqlx::query(
+                "DELETE FROM offline_messages WHERE user_id = $1::BIGINT AND id NOT IN (
+                    SELECT id FROM offline_messages WHERE user_id = $1::BIGINT ORDER BY id DESC LIMIT $2
+                )"
+            )
+                .bind(&user_id.0)
+                .bind(max_len as i64)
+                .execute(&mut *transaction)
+                .await?;
+            transaction.commit().await
+        });
+
+        if let Err(e) = result {
+            error!("Failed to store offline message: {}", e);
+        }
+    }
+
+    fn offline_messages_drain(&mut self, user_id: UserId) -> Vec<ChatMessage> {
+        let result = self.block_on(
+            sqlx::query_as::<_, (i64, String)>("DELETE FROM offline_messages WHERE user_id = $1::BIGINT RETURNING id, message")
+                .bind(user_id.0)
+                .fetch_all(&self.pool)
+        );
+
+        match result {
+            Ok(mut rows) => {
+                // DELETE doesn't return rows in any particular order
+                rows.sort_by_key(|(id, _)| *id);
+                rows.into_iter()
+                    .filter_map(|(_, message)| serde_json::from_str(&message).ok())
+                    .collect()
+            }
+            Err(e) => {
+                error!("Failed to drain offline messages: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn chat_history_insert(&mut self, message: ChatMessage) {
+        let (channel, serialized_message) = match (serde_json::to_string(&message.channel), serde_json::to_string(&message)) {
+            (Ok(channel), Ok(serialized_message)) => (channel, serialized_message),
+            _ => {
+                error!("Failed to serialize chat message");
+                return;
+            }
+        };
+
+        let result = self.block_on(
+            sqlx::query("INSERT INTO chat_history (channel, time, message) VALUES ($1, $2, $3)")
+                .bind(channel)
+                .bind(message.time.0)
+                .bind(serialized_message)
+                .execute(&self.pool)
+        );
+
+        if let Err(e) = result {
+            error!("Failed to insert chat history: {}", e);
+        }
+    }
+
+    fn chat_history_get(&self, channel: ChatChannel, before: Option<Timestamp>, limit: usize) -> Vec<ChatMessage> {
+        let channel = match serde_json::to_string(&channel) {
+            Ok(channel) => channel,
+            Err(e) => {
+                error!("Failed to serialize chat channel: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let result = self.block_on(
+            sqlx::query_scalar::<_, String>(
+                "SELECT message FROM chat_history
+                WHERE channel = $1 AND ($2::BIGINT IS NULL OR time < $2)
+                ORDER BY time DESC, id DESC
+                LIMIT $3"
+            )
+                .bind(channel)
+                .bind(before.map(|before| before.0))
+                .bind(limit as i64)
+                .fetch_all(&self.pool)
+        );
+
+        match result {
+            // we fetched newest first, but the page is returned oldest first
+            Ok(rows) => rows.iter()
+                .rev()
+                .filter_map(|message| serde_json::from_str(message).ok())
+                .collect(),
+            Err(e) => {
+                error!("Failed to get chat history: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn user_from_row(row: UserRow) -> UserPartial {
+    let (id, display_name_lower, display_name, account_name_lower, account_name, email, language, is_admin, created_at, avatar_url) = row;
+    UserPartial {
+        id: UserId(id),
+        display_name_lower,
+        display_name,
+        account_name_lower,
+        account_name,
+        email,
+        language: language_from_db(&language),
+        activity: None,
+        is_admin,
+        created_at: Timestamp(created_at),
+        avatar_url,
+    }
+}
+
+fn language_to_db(language: Language) -> String {
+    serde_json::to_value(language)
+        .ok()
+        .and_then(|value| value.as_str().map(|s| s.to_string()))
+        .unwrap_or_default()
 }
 
+fn language_from_db(language: &str) -> Language {
+    serde_json::from_value(serde_json::Value::String(language.to_string()))
+        .unwrap_or(Language::English)
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod tests {
+    use super::*;
+    use warhorse_protocol::{ChatChannel, RoomId, Uuid};
+    use testcontainers_modules::postgres::Postgres;
+    use testcontainers_modules::testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::testcontainers::ContainerAsync;
+
+    async fn start_database() -> (ContainerAsync<Postgres>, PostgresDatabase) {
+        let container = Postgres::default().start().await.unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap(),
+        );
+        let database = PostgresDatabase::new(&connection_string);
+        (container, database)
+    }
+
+    fn registration(account_name: &str) -> UserRegistration {
+        UserRegistration {
+            language: Language::Spanish,
+            account_name: account_name.to_string(),
+            email: format!("{}@example.com", account_name),
+            display_name: format!("{} display", account_name),
+            password: "password".to_string(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_users_insert_and_get() {
+        let (_container, mut database) = start_database().await;
+
+        let user_id = database.users_insert(registration("Test"), "hash".to_string());
+        assert!(database.user_exists(user_id.clone()));
+
+        let user = database.users_get(user_id.clone()).unwrap();
+        assert_eq!(user.account_name_lower.as_deref(), Some("test"));
+        assert!(matches!(user.language, Language::Spanish));
+
+        assert_eq!(database.users_get_by_account_name("Test").unwrap().id, user_id);
+        assert_eq!(database.users_get_by_email("Test@example.com").unwrap().id, user_id);
+        assert!(database.users_get_by_account_name("missing").is_none());
+        assert_eq!(database.users_get_password_hash(user_id).as_deref(), Some("hash"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_users_search_by_display_name() {
+        let (_container, mut database) = start_database().await;
+        database.users_insert(registration("test"), "hash".to_string());
+        database.users_insert(registration("test2"), "hash".to_string());
+        database.users_insert(registration("other"), "hash".to_string());
+
+        let names = |users: Vec<UserPartial>| users.into_iter().map(|u| u.display_name).collect::<Vec<_>>();
+        assert_eq!(names(database.users_search_by_display_name("test", 10)), vec!["test display", "test2 display"]);
+        assert_eq!(names(database.users_search_by_display_name("test", 1)), vec!["test display"]);
+        // wildcards match literally
+        assert!(database.users_search_by_display_name("%", 10).is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_users_get_many() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        let other_id = database.users_insert(registration("test2"), "hash".to_string());
+        database.users_insert(registration("test3"), "hash".to_string());
+
+        let users = database.users_get_many(&[user_id.clone(), UserId::from("0"), other_id.clone()]);
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[&user_id].account_name.as_deref(), Some("test"));
+        assert_eq!(users[&other_id].account_name.as_deref(), Some("test2"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_friend_requests_and_friends() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        let friend_id = database.users_insert(registration("test2"), "hash".to_string());
+
+        database.friend_requests_insert(user_id.clone(), friend_id.clone());
+        assert_eq!(database.user_get_friend_request_invites_sent_for_user(user_id.clone())[0].id, friend_id);
+        assert_eq!(database.user_get_pending_friend_requests_for_user(friend_id.clone())[0].id, user_id);
+
+        database.friend_requests_remove(friend_id.clone(), user_id.clone());
+        assert!(database.user_get_pending_friend_requests_for_user(friend_id.clone()).is_empty());
+
+        database.friends_add(user_id.clone(), friend_id.clone());
+        let friends = database.friends_get(user_id.clone());
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].status, FriendStatus::Offline);
+        assert_eq!(database.friends_get(friend_id.clone())[0].id, user_id);
+
+        // from the other side ends it for both
+        database.friends_remove(friend_id.clone(), user_id.clone());
+        assert!(database.friends_get(user_id).is_empty());
+        assert!(database.friends_get(friend_id).is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_user_blocks() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        let blocked_id = database.users_insert(registration("test2"), "hash".to_string());
+
+        database.user_blocks_insert(user_id.clone(), blocked_id.clone());
+        assert!(database.user_is_blocked(user_id.clone(), blocked_id.clone()));
+        assert!(!database.user_is_blocked(blocked_id.clone(), user_id.clone()));
+        assert_eq!(database.user_blocks_get_blocks_for_user(user_id.clone())[0].status, FriendStatus::Blocked);
+
+        database.user_blocks_remove(user_id.clone(), blocked_id.clone());
+        assert!(!database.user_is_blocked(user_id, blocked_id));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_admins_and_bans() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        assert!(!database.users_get(user_id.clone()).unwrap().is_admin);
+        assert!(database.bans_get(user_id.clone()).is_none());
+
+        database.users_set_admin(user_id.clone(), true);
+        assert!(database.users_get(user_id.clone()).unwrap().is_admin);
+
+        database.bans_insert(user_id.clone(), None);
+        database.bans_insert(user_id.clone(), Some("spam".to_string()));
+        assert_eq!(database.bans_get(user_id).unwrap().reason.as_deref(), Some("spam"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_email_verification() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        assert!(!database.users_is_email_verified(user_id.clone()));
+
+        database.users_set_email_verification_token(user_id.clone(), "token".to_string());
+        assert_eq!(database.users_verify_email("wrong"), None);
+        assert_eq!(database.users_verify_email("token"), Some(user_id.clone()));
+        assert_eq!(database.users_verify_email("token"), None);
+        assert!(database.users_is_email_verified(user_id));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_users_delete_cascades() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        let friend_id = database.users_insert(registration("test2"), "hash".to_string());
+        let other_id = database.users_insert(registration("test3"), "hash".to_string());
+
+        database.friends_add(user_id.clone(), friend_id.clone());
+        database.friend_requests_insert(other_id.clone(), user_id.clone());
+        database.user_blocks_insert(other_id.clone(), user_id.clone());
+
+        database.users_delete(user_id.clone());
+
+        assert!(!database.user_exists(user_id.clone()));
+        assert!(database.users_get_password_hash(user_id.clone()).is_none());
+        assert!(database.friends_get(friend_id).is_empty());
+        assert!(database.user_get_friend_request_invites_sent_for_user(other_id.clone()).is_empty());
+        assert!(!database.user_is_blocked(other_id, user_id));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_offline_messages() {
+        let (_container, mut database) = start_database().await;
+        let user_id = database.users_insert(registration("test"), "hash".to_string());
+        let messages: Vec<ChatMessage> = (0..3)
+            .map(|i| ChatMessage {
+                id: Uuid::new_v4(),
+                display_name: "test2".to_string(),
+                channel: ChatChannel::PrivateMessage(user_id.clone()),
+                message: i.to_string(),
+                time: Timestamp(0),
+            })
+            .collect();
+
+        for message in messages.iter() {
+            database.offline_messages_push(user_id.clone(), message.clone(), 2);
+        }
+
+        assert_eq!(database.offline_messages_drain(user_id.clone()), messages[1..].to_vec());
+        assert!(database.offline_messages_drain(user_id).is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_chat_history_pages() {
+        let (_container, mut database) = start_database().await;
+        let general = ChatChannel::Room(RoomId::from("general"));
+        for time in 1..=5 {
+            database.chat_history_insert(ChatMessage {
+                id: Uuid::new_v4(),
+                display_name: "test".to_string(),
+                channel: general.clone(),
+                message: time.to_string(),
+                time: Timestamp(time),
+            });
+        }
+
+        let times = |messages: Vec<ChatMessage>| messages.iter().map(|m| m.time.0).collect::<Vec<_>>();
+        assert_eq!(times(database.chat_history_get(general.clone(), None, 2)), vec![4, 5]);
+        assert_eq!(times(database.chat_history_get(general.clone(), Some(Timestamp(4)), 2)), vec![2, 3]);
+        assert!(database.chat_history_get(general, Some(Timestamp(1)), 10).is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use sqlx::PgPool;
+
+/// Schema for the postgres database, applied in order when the database is created.
+/// Every statement must be safe to run against a database that's already up to date.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS users (
+        id BIGSERIAL PRIMARY KEY,
+        account_name TEXT,
+        account_name_lower TEXT UNIQUE,
+        email TEXT UNIQUE,
+        display_name TEXT NOT NULL,
+        display_name_lower TEXT NOT NULL,
+        password_hash TEXT NOT NULL,
+        language TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS friendships (
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        friend_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        PRIMARY KEY (user_id, friend_id)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS friend_requests (
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        friend_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        PRIMARY KEY (user_id, friend_id)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS user_blocks (
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        blocked_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        PRIMARY KEY (user_id, blocked_id)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS offline_messages (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        message TEXT NOT NULL
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS chat_history (
+        id BIGSERIAL PRIMARY KEY,
+        channel TEXT NOT NULL,
+        time BIGINT NOT NULL,
+        message TEXT NOT NULL
+    )
+    "#,
+    "CREATE INDEX IF NOT EXISTS chat_history_channel_time ON chat_history (channel, time)",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE",
+    r#"
+    CREATE TABLE IF NOT EXISTS bans (
+        user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+        reason TEXT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    )
+    "#,
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS last_seen BIGINT",
+    // accounts from before verification existed count as verified, new ones don't
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT TRUE",
+    "ALTER TABLE users ALTER COLUMN email_verified SET DEFAULT FALSE",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verification_token TEXT",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_url TEXT",
+];
+
+pub async fn run(pool: &PgPool) -> Result<(), sqlx::Error> {
+    for migration in MIGRATIONS {
+        sqlx::query(migration).execute(pool).await?;
+    }
+    Ok(())
+}
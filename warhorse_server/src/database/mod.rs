@@ -1,17 +1,39 @@
-use warhorse_protocol::{Friend, UserPartial, UserId, UserRegistration};
+use std::collections::HashMap;
+
+use warhorse_protocol::{Banned, ChatChannel, ChatMessage, Friend, Timestamp, UserPartial, UserId, UserRegistration};
 
 pub mod db_in_memory;
 pub mod db_postgres;
+mod migrations;
 
 pub trait Database {
     fn new(connection_string: &str) -> Self;
 
     // Users
     fn user_exists(&self, user_id: UserId) -> bool;
-    fn users_insert(&mut self, user: UserRegistration) -> UserId;
+    /// Inserts a new user, `user.password` is the plain text password and must not be stored.
+    fn users_insert(&mut self, user: UserRegistration, password_hash: String) -> UserId;
     fn users_get(&self, user_id: UserId) -> Option<UserPartial>;
+    /// Gets many users with one lookup, ids that don't exist are left out.
+    fn users_get_many(&self, user_ids: &[UserId]) -> HashMap<UserId, UserPartial>;
     fn users_get_by_account_name(&self, account_name: &str) -> Option<UserPartial>;
     fn users_get_by_email(&self, email: &str) -> Option<UserPartial>;
+    fn users_get_password_hash(&self, user_id: UserId) -> Option<String>;
+    /// Deletes a user along with their friendships, friend requests, blocks and offline messages, in both directions.
+    fn users_delete(&mut self, user_id: UserId);
+    /// Gets up to `limit` users whose lowercase display name starts with `prefix`, which must already be lowercase.
+    fn users_search_by_display_name(&self, prefix: &str, limit: usize) -> Vec<UserPartial>;
+    fn users_set_admin(&mut self, user_id: UserId, is_admin: bool);
+    /// Records when a user last disconnected, `friends_get` hands it back on each friend.
+    fn users_set_last_seen(&mut self, user_id: UserId, last_seen: Timestamp);
+    /// Sets or clears the user's avatar, handed back on the user and on each `Friend` made from them.
+    fn users_set_avatar(&mut self, user_id: UserId, avatar_url: Option<String>);
+    /// Stores the token that verifies a user's email, replacing any earlier one.
+    fn users_set_email_verification_token(&mut self, user_id: UserId, token: String);
+    /// Marks the email of the user the token was issued to as verified and forgets the token.
+    fn users_verify_email(&mut self, token: &str) -> Option<UserId>;
+    /// New users start out unverified.
+    fn users_is_email_verified(&self, user_id: UserId) -> bool;
     fn user_blocks_insert(&mut self, user_id: UserId, blocked_id: UserId);
     fn user_blocks_remove(&mut self, user_id: UserId, blocked_id: UserId);
     fn user_blocks_get_blocks_for_user(&self, user_id: UserId) -> Vec<Friend>;
@@ -19,10 +41,30 @@ pub trait Database {
     fn user_get_friend_request_invites_sent_for_user(&self, user_id: UserId) -> Vec<Friend>;
     fn user_is_blocked(&self, user_id: UserId, blocked_id: UserId) -> bool;
 
+    // Bans
+    /// Bans a user, replacing the reason if they're already banned.
+    fn bans_insert(&mut self, user_id: UserId, reason: Option<String>);
+    /// Gets the user's ban, if they're banned.
+    fn bans_get(&self, user_id: UserId) -> Option<Banned>;
+
     // Friends
     fn friend_requests_insert(&mut self, user_id: UserId, friend_id: UserId);
     fn friend_requests_remove(&mut self, user_id: UserId, friend_id: UserId);
+    /// Makes the two users friends of each other. Friendships always go both ways,
+    /// so after this each of them is in the other's `friends_get`.
     fn friends_add(&mut self, user_id: UserId, friend_id: UserId);
+    /// Ends the friendship for both users, whichever way round they're given.
     fn friends_remove(&mut self, user_id: UserId, friend_id: UserId);
     fn friends_get(&self, user_id: UserId) -> Vec<Friend>;
+
+    // Offline messages
+    /// Stores a message for a user who isn't connected, dropping the oldest once there are more than `max_len`.
+    fn offline_messages_push(&mut self, user_id: UserId, message: ChatMessage, max_len: usize);
+    /// Removes and returns a user's stored messages, oldest first.
+    fn offline_messages_drain(&mut self, user_id: UserId) -> Vec<ChatMessage>;
+
+    // Chat history
+    fn chat_history_insert(&mut self, message: ChatMessage);
+    /// Gets up to `limit` of the most recent messages sent to the channel strictly before `before`, oldest first.
+    fn chat_history_get(&self, channel: ChatChannel, before: Option<Timestamp>, limit: usize) -> Vec<ChatMessage>;
 }
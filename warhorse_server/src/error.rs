@@ -1,48 +1,92 @@
 use std::fmt::Display;
 use socketioxide::{BroadcastError, SendError};
 use warhorse_protocol::error::Error;
+use warhorse_protocol::{ErrorCode, RequestError};
 
 #[derive(Debug)]
-pub struct ServerError(pub String);
+pub struct ServerError {
+    pub code: ErrorCode,
+    pub message: String,
+    // what went wrong underneath, never sent to clients
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl ServerError {
+    pub fn new(code: ErrorCode, message: String) -> Self {
+        Self { code, message, source: None }
+    }
+}
 
 impl Display for ServerError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<ServerError> for RequestError {
+    fn from(e: ServerError) -> Self {
+        RequestError { code: e.code, message: e.message }
     }
 }
 
 impl From<Error> for ServerError {
     fn from(e: Error) -> Self {
-        ServerError(e.0)
+        Self { code: ErrorCode::Unknown, message: e.to_string(), source: Some(Box::new(e)) }
     }
 }
 
 impl From<SendError> for ServerError {
     fn from(e: SendError) -> Self {
-        ServerError(e.to_string())
+        ServerError::new(ErrorCode::Unknown, e.to_string())
     }
 }
 
 impl From<BroadcastError> for ServerError {
     fn from(e: BroadcastError) -> Self {
-        ServerError(e.to_string())
+        ServerError::new(ErrorCode::Unknown, e.to_string())
     }
 }
 
 impl From<Box<dyn std::error::Error>> for ServerError {
     fn from(e: Box<dyn std::error::Error>) -> Self {
-        ServerError(e.to_string())
+        ServerError::new(ErrorCode::Unknown, e.to_string())
     }
 }
 
 impl From<String> for ServerError {
     fn from(e: String) -> Self {
-        ServerError(e)
+        ServerError::new(ErrorCode::Unknown, e)
     }
 }
 
 impl From<&str> for ServerError {
     fn from(e: &str) -> Self {
-        ServerError(e.to_string())
+        ServerError::new(ErrorCode::Unknown, e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_protocol_errors_are_kept_as_the_source() {
+        let protocol_error: Error = serde_json::from_str::<u32>("\"not a number\"").unwrap_err().into();
+        let e = ServerError::from(protocol_error);
+        assert_eq!(e.code, ErrorCode::Unknown);
+        assert!(e.source().unwrap().is::<Error>());
+        // down to the serde error that started it
+        assert!(e.source().and_then(|e| e.source()).unwrap().is::<serde_json::Error>());
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> = Box::new(ServerError::from("went wrong"));
+        assert_eq!(boxed.to_string(), "went wrong");
+        assert!(boxed.source().is_none());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,112 @@
+use std::fmt::Debug;
+
+/// What to do with a chat message after it's been checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterResult {
+    /// Send it as is.
+    Allow,
+    /// Send this instead.
+    Replace(String),
+    /// Don't send it, the sender gets an error.
+    Reject,
+}
+
+/// Checks chat messages before they're sent, e.g. to keep slurs out of public rooms.
+pub trait MessageFilter: Debug + Send + Sync {
+    fn filter(&self, message: &str) -> FilterResult;
+}
+
+/// Allows everything, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoFilter;
+
+impl MessageFilter for NoFilter {
+    fn filter(&self, _message: &str) -> FilterResult {
+        FilterResult::Allow
+    }
+}
+
+/// Catches whole words from a list, ignoring case.
+#[derive(Debug, Clone)]
+pub struct WordListFilter {
+    words: Vec<String>,
+    reject: bool,
+}
+
+impl WordListFilter {
+    /// Replaces each listed word with asterisks.
+    pub fn masking(words: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::new(words, false)
+    }
+
+    /// Rejects any message containing a listed word.
+    pub fn rejecting(words: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::new(words, true)
+    }
+
+    fn new(words: impl IntoIterator<Item = impl Into<String>>, reject: bool) -> Self {
+        Self {
+            words: words.into_iter().map(|word| word.into().to_lowercase()).collect(),
+            reject,
+        }
+    }
+
+    fn is_listed(&self, word: &str) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+}
+
+impl MessageFilter for WordListFilter {
+    fn filter(&self, message: &str) -> FilterResult {
+        let mut filtered = String::with_capacity(message.len());
+        let mut caught = false;
+
+        // split on anything that isn't part of a word, keeping the separators as they were
+        let mut rest = message;
+        while !rest.is_empty() {
+            let word_len = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
+            let (word, after) = rest.split_at(word_len);
+            if !word.is_empty() && self.is_listed(word) {
+                caught = true;
+                filtered.extend(std::iter::repeat_n('*', word.chars().count()));
+            } else {
+                filtered.push_str(word);
+            }
+
+            let separator_len = after.find(char::is_alphanumeric).unwrap_or(after.len());
+            let (separator, after) = after.split_at(separator_len);
+            filtered.push_str(separator);
+            rest = after;
+        }
+
+        match (caught, self.reject) {
+            (false, _) => FilterResult::Allow,
+            (true, true) => FilterResult::Reject,
+            (true, false) => FilterResult::Replace(filtered),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_filter_allows_everything() {
+        assert_eq!(NoFilter.filter("anything at all"), FilterResult::Allow);
+    }
+
+    #[test]
+    fn test_word_list_only_catches_whole_words() {
+        let filter = WordListFilter::masking(["darn"]);
+        assert_eq!(filter.filter("darnation"), FilterResult::Allow);
+        assert_eq!(filter.filter("Darn it, DARN!"), FilterResult::Replace("**** it, ****!".to_string()));
+    }
+
+    #[test]
+    fn test_rejecting_word_list() {
+        let filter = WordListFilter::rejecting(["darn"]);
+        assert_eq!(filter.filter("well darn"), FilterResult::Reject);
+        assert_eq!(filter.filter("well done"), FilterResult::Allow);
+    }
+}
@@ -1,5 +1,5 @@
-use warhorse_protocol::{Language, UserId};
-use warhorse_protocol::{ACCOUNT_NAME_MAX_LENGTH, ACCOUNT_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH, DISPLAY_NAME_MIN_LENGTH, PASSWORD_MIN_LENGTH};
+use warhorse_protocol::{ErrorCode, Language, UserId};
+use warhorse_protocol::{ACCOUNT_NAME_MAX_LENGTH, ACCOUNT_NAME_MIN_LENGTH, CHAT_MESSAGE_MAX_LENGTH, DISPLAY_NAME_MAX_LENGTH, DISPLAY_NAME_MIN_LENGTH, PASSWORD_MIN_LENGTH};
 use crate::error::ServerError;
 
 pub fn hello_message(lang: Language) -> String {
@@ -7,77 +7,295 @@ pub fn hello_message(lang: Language) -> String {
         Language::English => "You are now connected to the Warhorse server".into(),
         Language::Spanish => "Ahora estás conectado al servidor de Warhorse".into(),
         Language::French => "Vous êtes maintenant connecté au serveur Warhorse".into(),
+        Language::German => "Du bist jetzt mit dem Warhorse-Server verbunden".into(),
+        Language::BrazilianPortuguese => "Você agora está conectado ao servidor Warhorse".into(),
     }
 }
 
 pub fn invalid_login(lang: Language) -> ServerError {
-    match lang {
+    ServerError::new(ErrorCode::InvalidLogin, match lang {
         Language::English => "Invalid login, please ensure the information is correct".into(),
         Language::Spanish => "Inicio de sesión inválido, asegúrese de que la información sea correcta".into(),
         Language::French => "Connexion invalide, veuillez vous assurer que les informations sont correctes".into(),
-    }
+        Language::German => "Ungültige Anmeldung, bitte überprüfe deine Angaben".into(),
+        Language::BrazilianPortuguese => "Login inválido, verifique se as informações estão corretas".into(),
+    })
 }
 
 pub fn account_name_already_exists(lang: Language) -> ServerError {
-    match lang {
+    ServerError::new(ErrorCode::AccountNameTaken, match lang {
         Language::English => "Account name already exists".into(),
         Language::Spanish => "El nombre de la cuenta ya existe".into(),
         Language::French => "Le nom du compte existe déjà".into(),
-    }
+        Language::German => "Der Kontoname ist bereits vergeben".into(),
+        Language::BrazilianPortuguese => "O nome da conta já existe".into(),
+    })
 }
 
 pub fn email_already_exists(lang: Language) -> ServerError {
-    match lang {
+    ServerError::new(ErrorCode::EmailTaken, match lang {
         Language::English => "Email already exists".into(),
         Language::Spanish => "El correo electrónico ya existe".into(),
         Language::French => "L'email existe déjà".into(),
-    }
+        Language::German => "Die E-Mail-Adresse ist bereits vergeben".into(),
+        Language::BrazilianPortuguese => "O e-mail já existe".into(),
+    })
 }
 
 pub fn invalid_email(lang: Language) -> ServerError {
-    match lang {
+    ServerError::new(ErrorCode::InvalidEmail, match lang {
         Language::English => "Invalid email".into(),
         Language::Spanish => "Correo electrónico inválido".into(),
         Language::French => "Email invalide".into(),
-    }
+        Language::German => "Ungültige E-Mail-Adresse".into(),
+        Language::BrazilianPortuguese => "E-mail inválido".into(),
+    })
 }
 
 pub fn invalid_password(lang: Language) -> ServerError {
-    match lang {
-        Language::English => format!("Passwords must be at least {} characters long", PASSWORD_MIN_LENGTH).into(),
-        Language::Spanish => format!("Las contraseñas deben tener al menos {} caracteres", PASSWORD_MIN_LENGTH).into(),
-        Language::French => format!("Les mots de passe doivent comporter au moins {} caractères", PASSWORD_MIN_LENGTH).into(),
-    }
+    ServerError::new(ErrorCode::PasswordTooShort, match lang {
+        Language::English => format!("Passwords must be at least {} characters long", PASSWORD_MIN_LENGTH),
+        Language::Spanish => format!("Las contraseñas deben tener al menos {} caracteres", PASSWORD_MIN_LENGTH),
+        Language::French => format!("Les mots de passe doivent comporter au moins {} caractères", PASSWORD_MIN_LENGTH),
+        Language::German => format!("Passwörter müssen mindestens {} Zeichen lang sein", PASSWORD_MIN_LENGTH),
+        Language::BrazilianPortuguese => format!("As senhas devem ter pelo menos {} caracteres", PASSWORD_MIN_LENGTH),
+    })
 }
 
 pub fn invalid_account_name(lang: Language) -> ServerError {
-    match lang {
-        Language::English => format!("Account names must be between {} and {} characters long", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH).into(),
-        Language::Spanish => format!("Los nombres de cuenta deben tener entre {} y {} caracteres", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH).into(),
-        Language::French => format!("Les noms de compte doivent comporter entre {} et {} caractères", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH).into(),
-    }
+    ServerError::new(ErrorCode::InvalidAccountName, match lang {
+        Language::English => format!("Account names must be between {} and {} characters long", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH),
+        Language::Spanish => format!("Los nombres de cuenta deben tener entre {} y {} caracteres", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH),
+        Language::French => format!("Les noms de compte doivent comporter entre {} et {} caractères", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH),
+        Language::German => format!("Kontonamen müssen zwischen {} und {} Zeichen lang sein", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH),
+        Language::BrazilianPortuguese => format!("Os nomes de conta devem ter entre {} e {} caracteres", ACCOUNT_NAME_MIN_LENGTH, ACCOUNT_NAME_MAX_LENGTH),
+    })
 }
 
 pub fn invalid_display_name(lang: Language) -> ServerError {
-    match lang {
-        Language::English => format!("Display names must be between {} and {} characters long", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH).into(),
-        Language::Spanish => format!("Los nombres de visualización deben tener entre {} y {} caracteres", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH).into(),
-        Language::French => format!("Les noms d'affichage doivent comporter entre {} et {} caractères", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH).into(),
-    }
+    ServerError::new(ErrorCode::InvalidDisplayName, match lang {
+        Language::English => format!("Display names must be between {} and {} characters long", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH),
+        Language::Spanish => format!("Los nombres de visualización deben tener entre {} y {} caracteres", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH),
+        Language::French => format!("Les noms d'affichage doivent comporter entre {} et {} caractères", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH),
+        Language::German => format!("Anzeigenamen müssen zwischen {} und {} Zeichen lang sein", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH),
+        Language::BrazilianPortuguese => format!("Os nomes de exibição devem ter entre {} e {} caracteres", DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH),
+    })
 }
 
 pub fn already_friends(lang: Language) -> ServerError {
-    match lang {
+    ServerError::new(ErrorCode::AlreadyFriends, match lang {
         Language::English => "You are already friends".into(),
         Language::Spanish => "Ya son amigos".into(),
         Language::French => "Vous êtes déjà amis".into(),
-    }
+        Language::German => "Ihr seid bereits befreundet".into(),
+        Language::BrazilianPortuguese => "Vocês já são amigos".into(),
+    })
+}
+
+pub fn no_friend_request(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::NoFriendRequest, match lang {
+        Language::English => "There is no friend request to accept".into(),
+        Language::Spanish => "No hay ninguna solicitud de amistad para aceptar".into(),
+        Language::French => "Il n'y a aucune demande d'ami à accepter".into(),
+        Language::German => "Es gibt keine Freundschaftsanfrage zum Annehmen".into(),
+        Language::BrazilianPortuguese => "Não há nenhum pedido de amizade para aceitar".into(),
+    })
+}
+
+pub fn chat_history_rooms_only(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::ChatHistoryRoomsOnly, match lang {
+        Language::English => "Chat history is only available for rooms".into(),
+        Language::Spanish => "El historial de chat solo está disponible para salas".into(),
+        Language::French => "L'historique du chat n'est disponible que pour les salons".into(),
+        Language::German => "Der Chatverlauf ist nur für Räume verfügbar".into(),
+        Language::BrazilianPortuguese => "O histórico de chat só está disponível para salas".into(),
+    })
 }
 
 pub fn user_is_blocked(lang: Language) -> ServerError {
-    match lang {
+    ServerError::new(ErrorCode::UserBlocked, match lang {
         Language::English => "User is blocked".into(),
         Language::Spanish => "Usuario bloqueado".into(),
         Language::French => "L'utilisateur est bloqué".into(),
+        Language::German => "Der Benutzer ist blockiert".into(),
+        Language::BrazilianPortuguese => "Usuário bloqueado".into(),
+    })
+}
+
+pub fn rate_limited(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::RateLimited, match lang {
+        Language::English => "You are sending messages too quickly, please slow down".into(),
+        Language::Spanish => "Estás enviando mensajes demasiado rápido, por favor ve más despacio".into(),
+        Language::French => "Vous envoyez des messages trop rapidement, veuillez ralentir".into(),
+        Language::German => "Du sendest Nachrichten zu schnell, bitte mach langsamer".into(),
+        Language::BrazilianPortuguese => "Você está enviando mensagens rápido demais, por favor diminua o ritmo".into(),
+    })
+}
+
+pub fn invalid_room_id(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::InvalidRoomId, match lang {
+        Language::English => "Invalid room".into(),
+        Language::Spanish => "Sala inválida".into(),
+        Language::French => "Salon invalide".into(),
+        Language::German => "Ungültiger Raum".into(),
+        Language::BrazilianPortuguese => "Sala inválida".into(),
+    })
+}
+
+pub fn activity_too_long(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::ActivityTooLong, match lang {
+        Language::English => "Activity is too long".into(),
+        Language::Spanish => "La actividad es demasiado larga".into(),
+        Language::French => "L'activité est trop longue".into(),
+        Language::German => "Die Aktivität ist zu lang".into(),
+        Language::BrazilianPortuguese => "A atividade é longa demais".into(),
+    })
+}
+
+pub fn invalid_avatar_url(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::InvalidAvatarUrl, match lang {
+        Language::English => "Invalid avatar URL".into(),
+        Language::Spanish => "URL de avatar inválida".into(),
+        Language::French => "URL d'avatar invalide".into(),
+        Language::German => "Ungültige Avatar-URL".into(),
+        Language::BrazilianPortuguese => "URL de avatar inválida".into(),
+    })
+}
+
+pub fn invalid_party_id(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::InvalidPartyId, match lang {
+        Language::English => "Invalid party".into(),
+        Language::Spanish => "Grupo inválido".into(),
+        Language::French => "Groupe invalide".into(),
+        Language::German => "Ungültige Gruppe".into(),
+        Language::BrazilianPortuguese => "Grupo inválido".into(),
+    })
+}
+
+pub fn cannot_friend_self(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::CannotFriendSelf, match lang {
+        Language::English => "You can't send a friend request to yourself".into(),
+        Language::Spanish => "No puedes enviarte una solicitud de amistad a ti mismo".into(),
+        Language::French => "Vous ne pouvez pas vous envoyer une demande d'ami".into(),
+        Language::German => "Du kannst dir selbst keine Freundschaftsanfrage senden".into(),
+        Language::BrazilianPortuguese => "Você não pode enviar um pedido de amizade para si mesmo".into(),
+    })
+}
+
+pub fn friend_request_already_received(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::FriendRequestAlreadyReceived, match lang {
+        Language::English => "This user has already sent you a friend request".into(),
+        Language::Spanish => "Este usuario ya te ha enviado una solicitud de amistad".into(),
+        Language::French => "Cet utilisateur vous a déjà envoyé une demande d'ami".into(),
+        Language::German => "Dieser Benutzer hat dir bereits eine Freundschaftsanfrage gesendet".into(),
+        Language::BrazilianPortuguese => "Este usuário já enviou um pedido de amizade para você".into(),
+    })
+}
+
+pub fn incorrect_password(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::IncorrectPassword, match lang {
+        Language::English => "Incorrect password".into(),
+        Language::Spanish => "Contraseña incorrecta".into(),
+        Language::French => "Mot de passe incorrect".into(),
+        Language::German => "Falsches Passwort".into(),
+        Language::BrazilianPortuguese => "Senha incorreta".into(),
+    })
+}
+
+pub fn not_admin(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::NotAdmin, match lang {
+        Language::English => "Only admins can do that".into(),
+        Language::Spanish => "Solo los administradores pueden hacer eso".into(),
+        Language::French => "Seuls les administrateurs peuvent faire cela".into(),
+        Language::German => "Nur Administratoren können das tun".into(),
+        Language::BrazilianPortuguese => "Apenas administradores podem fazer isso".into(),
+    })
+}
+
+pub fn banned(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::Banned, match lang {
+        Language::English => "You are banned from this server".into(),
+        Language::Spanish => "Estás vetado de este servidor".into(),
+        Language::French => "Vous êtes banni de ce serveur".into(),
+        Language::German => "Du bist von diesem Server gesperrt".into(),
+        Language::BrazilianPortuguese => "Você está banido deste servidor".into(),
+    })
+}
+
+pub fn message_rejected(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::MessageRejected, match lang {
+        Language::English => "Your message wasn't sent because it contains inappropriate language".into(),
+        Language::Spanish => "Tu mensaje no se envió porque contiene lenguaje inapropiado".into(),
+        Language::French => "Votre message n'a pas été envoyé car il contient des propos inappropriés".into(),
+        Language::German => "Deine Nachricht wurde nicht gesendet, weil sie unangemessene Sprache enthält".into(),
+        Language::BrazilianPortuguese => "Sua mensagem não foi enviada porque contém linguagem inapropriada".into(),
+    })
+}
+
+pub fn email_not_verified(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::EmailNotVerified, match lang {
+        Language::English => "You need to verify your email first".into(),
+        Language::Spanish => "Primero tienes que verificar tu correo electrónico".into(),
+        Language::French => "Vous devez d'abord vérifier votre email".into(),
+        Language::German => "Du musst zuerst deine E-Mail-Adresse bestätigen".into(),
+        Language::BrazilianPortuguese => "Você precisa verificar seu e-mail primeiro".into(),
+    })
+}
+
+pub fn invalid_verification_token(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::InvalidVerificationToken, match lang {
+        Language::English => "Invalid or expired verification link".into(),
+        Language::Spanish => "Enlace de verificación no válido o caducado".into(),
+        Language::French => "Lien de vérification invalide ou expiré".into(),
+        Language::German => "Ungültiger oder abgelaufener Bestätigungslink".into(),
+        Language::BrazilianPortuguese => "Link de verificação inválido ou expirado".into(),
+    })
+}
+
+pub fn chat_message_empty(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::ChatMessageEmpty, match lang {
+        Language::English => "You can't send an empty message".into(),
+        Language::Spanish => "No puedes enviar un mensaje vacío".into(),
+        Language::French => "Vous ne pouvez pas envoyer un message vide".into(),
+        Language::German => "Du kannst keine leere Nachricht senden".into(),
+        Language::BrazilianPortuguese => "Você não pode enviar uma mensagem vazia".into(),
+    })
+}
+
+pub fn chat_message_too_long(lang: Language) -> ServerError {
+    ServerError::new(ErrorCode::ChatMessageTooLong, match lang {
+        Language::English => format!("Messages can be at most {} characters long", CHAT_MESSAGE_MAX_LENGTH),
+        Language::Spanish => format!("Los mensajes pueden tener como máximo {} caracteres", CHAT_MESSAGE_MAX_LENGTH),
+        Language::French => format!("Les messages peuvent contenir au maximum {} caractères", CHAT_MESSAGE_MAX_LENGTH),
+        Language::German => format!("Nachrichten dürfen höchstens {} Zeichen lang sein", CHAT_MESSAGE_MAX_LENGTH),
+        Language::BrazilianPortuguese => format!("As mensagens podem ter no máximo {} caracteres", CHAT_MESSAGE_MAX_LENGTH),
+    })
+}
+
+pub fn friend_limit_reached(lang: Language, max_friends: usize) -> ServerError {
+    ServerError::new(ErrorCode::FriendLimitReached, match lang {
+        Language::English => format!("Friend limit reached, nobody can have more than {} friends", max_friends),
+        Language::Spanish => format!("Límite de amigos alcanzado, nadie puede tener más de {} amigos", max_friends),
+        Language::French => format!("Limite d'amis atteinte, personne ne peut avoir plus de {} amis", max_friends),
+        Language::German => format!("Freundeslimit erreicht, niemand kann mehr als {} Freunde haben", max_friends),
+        Language::BrazilianPortuguese => format!("Limite de amigos atingido, ninguém pode ter mais de {} amigos", max_friends),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hello_message_in_every_language() {
+        for lang in [
+            Language::English,
+            Language::Spanish,
+            Language::French,
+            Language::German,
+            Language::BrazilianPortuguese,
+        ] {
+            assert!(!hello_message(lang).is_empty());
+        }
     }
 }
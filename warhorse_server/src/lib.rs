@@ -0,0 +1,104 @@
+pub mod server;
+pub mod database;
+mod data_access;
+mod utils;
+pub mod error;
+pub mod config;
+mod i18n;
+pub mod rate_limit;
+pub mod filter;
+pub mod verification;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::extract::Query;
+use axum::routing::get;
+use serde::Deserialize;
+use serde_json::Value;
+use socketioxide::extract::{Data, SocketRef};
+use socketioxide::SocketIo;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tracing::error;
+use warhorse_protocol::Language;
+use crate::config::WarhorseServerConfig;
+use crate::database::Database;
+use crate::error::ServerError;
+use crate::server::WarhorseServer;
+
+/// Creates a Warhorse server with the default settings and the router that serves it.
+pub fn app<T>(database_connection_string: &str) -> (axum::Router, Arc<Mutex<WarhorseServer<T>>>)
+where T: Database + Send + Sync + 'static
+{
+    app_with_config(WarhorseServerConfig {
+        database_connection_string: database_connection_string.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Creates a Warhorse server and the router that serves it.
+pub fn app_with_config<T>(config: WarhorseServerConfig) -> (axum::Router, Arc<Mutex<WarhorseServer<T>>>)
+where T: Database + Send + Sync + 'static
+{
+    let (layer, io) = SocketIo::new_layer();
+    let namespace = config.namespace.clone();
+    let server = Arc::new(Mutex::new(
+        WarhorseServer::<T>::builder().config(config).build(io.clone())
+    ));
+
+    {
+        let server = server.clone();
+        io.ns(namespace, move |socket: SocketRef, Data::<Value>(data)| {
+            let server = server.clone();
+            Box::pin(async move {
+                server::handle_connection(socket, data, server).await;
+            })
+        });
+    }
+
+    server::spawn_presence_refresh_task(server.clone());
+    server::spawn_heartbeat_task(server.clone());
+    server::spawn_idle_check_task(server.clone());
+
+    let app = axum::Router::new()
+        .route("/", get(|| async { "Hello, World!" }))
+        .route("/verify-email", get({
+            let server = server.clone();
+            move |Query(query): Query<VerifyEmailQuery>| async move {
+                match server.lock().await.verify_email(&query.token, Language::English) {
+                    Ok(_) => "Your email has been verified".to_string(),
+                    Err(e) => e.to_string(),
+                }
+            }
+        }))
+        .layer(layer);
+
+    (app, server)
+}
+
+#[derive(Deserialize)]
+struct VerifyEmailQuery {
+    token: String,
+}
+
+/// Serves the app until `shutdown` resolves with an optional reason, which every client is told before the connections drain.
+/// Each connection's address is passed along so the per-IP connection cap can be enforced.
+pub async fn serve<T>(
+    listener: TcpListener,
+    app: axum::Router,
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    shutdown: impl Future<Output = Option<String>> + Send + 'static,
+) -> Result<(), ServerError>
+where T: Database + Send + Sync + 'static
+{
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            let reason = shutdown.await;
+            if let Err(e) = server.lock().await.shutdown(reason) {
+                error!(?e, "Failed to notify clients of shutdown");
+            }
+        })
+        .await
+        .map_err(|e| ServerError::from(e.to_string()))
+}
@@ -1,96 +1,63 @@
-pub mod server;
-mod database;
-mod data_access;
-mod utils;
-mod error;
-mod i18n;
-
-use std::sync::Arc;
-use axum::routing::get;
-use serde_json::Value;
-use socketioxide::extract::{Data, SocketRef};
-use socketioxide::SocketIo;
-use tokio::sync::Mutex;
 use tracing::{error, info};
 use tracing_subscriber::FmtSubscriber;
-use warhorse_protocol::UserRegistration;
-use crate::error::ServerError;
-use crate::server::WarhorseServer;
+use warhorse_server::config::WarhorseServerConfig;
+use warhorse_server::database::Database;
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+use warhorse_server::database::db_postgres::PostgresDatabase;
+use warhorse_server::error::ServerError;
+
+// when set, users are stored in this postgres database instead of in memory
+const DATABASE_URL_ENV: &str = "WARHORSE_DATABASE_URL";
+
+// when set, the server listens here instead of the default address
+const BIND_ADDRESS_ENV: &str = "WARHORSE_BIND_ADDRESS";
+
+// when set to `1` or `true`, the test users the examples log in with are created on startup
+const SEED_TEST_USERS_ENV: &str = "WARHORSE_SEED_TEST_USERS";
 
 #[tokio::main]
 async fn main() -> Result<(), ServerError> {
     tracing::subscriber::set_global_default(FmtSubscriber::default())
-        .map_err(|e| ServerError(e.to_string()))?;
-
-    let (layer, io) = SocketIo::new_layer();
-    let server = Arc::new(Mutex::new(
-        WarhorseServer::<database::db_in_memory::InMemoryDatabase>::new(io, "")
-    ));
+        .map_err(|e| ServerError::from(e.to_string()))?;
 
-    let server_clone = server.clone();
-    {
-        let server = server_clone.clone();
-        let io = server.lock().await.io().clone();
-        io.ns("/", move |socket: SocketRef, Data::<Value>(data)| {
-            let server = server.clone();
-            Box::pin(async move {
-                server::handle_connection(socket, data, server).await;
-            })
-        });
+    let mut config = WarhorseServerConfig::default();
+    if let Ok(bind_address) = std::env::var(BIND_ADDRESS_ENV) {
+        config.bind_address = bind_address;
     }
-
-    // add fake user data
-    {
-        let mut server = server.lock().await;
-
-        server.register_user(
-            UserRegistration {
-                account_name: "test".to_string(),
-                email: "test@example.com".to_string(),
-                display_name: "Test User".to_string(),
-                password: "password".to_string(),
-                language: warhorse_protocol::Language::English,
-            },
-        None
-        ).await?;
-        info!("Created test user with account name `test` and password `password`");
-
-        server.register_user(
-            UserRegistration {
-                account_name: "test2".to_string(),
-                email: "test2@example.com".to_string(),
-                display_name: "Test User 2".to_string(),
-                password: "password".to_string(),
-                language: warhorse_protocol::Language::English,
-            },
-            None
-        ).await?;
-        info!("Created test user with account name `test2` and password `password`");
-
-        server.register_user(
-            UserRegistration {
-                account_name: "test3".to_string(),
-                email: "test3@example.com".to_string(),
-                display_name: "Test User 3".to_string(),
-                password: "password".to_string(),
-                language: warhorse_protocol::Language::English,
-            },
-            None
-        ).await?;
-        info!("Created test user with account name `test3` and password `password`");
+    config.seed_test_users = std::env::var(SEED_TEST_USERS_ENV).is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"));
+
+    match std::env::var(DATABASE_URL_ENV) {
+        Ok(database_url) => {
+            info!("Using postgres database");
+            config.database_connection_string = database_url;
+            run::<PostgresDatabase>(config).await
+        }
+        Err(_) => {
+            info!("Using in-memory database, set {} to use postgres", DATABASE_URL_ENV);
+            run::<InMemoryDatabase>(config).await
+        }
     }
+}
 
-    let app = axum::Router::new()
-        .route("/", get(|| async { "Hello, World!" }))
-        .layer(layer);
+async fn run<T>(config: WarhorseServerConfig) -> Result<(), ServerError>
+where T: Database + Send + Sync + 'static
+{
+    let bind_address = config.bind_address.clone();
+    let (app, server) = warhorse_server::app_with_config::<T>(config);
 
-    info!("Starting server");
+    info!("Starting server on {}", bind_address);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await
-        .map_err(|e| ServerError(e.to_string()))?;
+    let listener = tokio::net::TcpListener::bind(&bind_address).await
+        .map_err(|e| ServerError::from(e.to_string()))?;
 
-    axum::serve(listener, app).await
-        .map_err(|e| ServerError(e.to_string()))?;
+    warhorse_server::serve(listener, app, server, shutdown_signal()).await
+}
 
-    Ok(())
-}
\ No newline at end of file
+async fn shutdown_signal() -> Option<String> {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!(?e, "Failed to listen for ctrl-c");
+        std::future::pending::<()>().await;
+    }
+    info!("Received ctrl-c, shutting down");
+    Some("Server going down for maintenance".to_string())
+}
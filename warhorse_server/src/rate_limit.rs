@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// How many messages may be sent within a window of time.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub max_messages: u32,
+    pub window: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_messages: 10,
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// A token bucket per key, each holding up to `max_messages` tokens and refilling all of them over `window`.
+pub(crate) struct RateLimiter<K> {
+    limit: RateLimit,
+    buckets: HashMap<K, Bucket>,
+}
+
+impl<K> RateLimiter<K>
+where K: Eq + Hash
+{
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token from the key's bucket, returns false if it's empty.
+    pub fn try_acquire(&mut self, key: K) -> bool {
+        self.try_acquire_at(key, Instant::now())
+    }
+
+    fn try_acquire_at(&mut self, key: K, now: Instant) -> bool {
+        let capacity = self.limit.max_messages as f64;
+        let window = self.limit.window;
+        let bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+
+        let refill = if window.is_zero() {
+            capacity
+        } else {
+            now.saturating_duration_since(bucket.last_refill).as_secs_f64() / window.as_secs_f64() * capacity
+        };
+        bucket.tokens = (bucket.tokens + refill).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets the key's bucket, e.g. when its socket disconnects.
+    pub fn remove(&mut self, key: &K) {
+        self.buckets.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_over_the_window() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            max_messages: 2,
+            window: Duration::from_secs(10),
+        });
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at("a", start));
+        assert!(limiter.try_acquire_at("a", start));
+        assert!(!limiter.try_acquire_at("a", start));
+
+        // other keys have their own bucket
+        assert!(limiter.try_acquire_at("b", start));
+
+        // half the window gives back one of the two tokens
+        assert!(limiter.try_acquire_at("a", start + Duration::from_secs(5)));
+        assert!(!limiter.try_acquire_at("a", start + Duration::from_secs(5)));
+    }
+}
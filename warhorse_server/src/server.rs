@@ -1,40 +1,163 @@
 use std::{sync::Arc, time::Instant};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use axum::extract::ConnectInfo;
 use tokio::sync::Mutex;
 use serde_json::Value;
 use socketioxide::{
-    extract::{Data, SocketRef},
+    extract::{AckSender, Data, SocketRef},
     SocketIo,
 };
 use socketioxide::operators::BroadcastOperators;
 use socketioxide::socket::Sid;
 use warhorse_protocol::*;
-use tracing::{error, info};
+use warhorse_protocol::compression::compress_if_large;
+use tracing::{error, field, info, info_span, Instrument, Span};
 use tracing::log::warn;
 use crate::data_access::DataAccess;
 use crate::database::Database;
 use crate::error::ServerError;
-use crate::utils::{is_valid_email, validate_account_name, validate_display_name, validate_password};
+use crate::config::{WarhorseServerBuilder, WarhorseServerConfig};
+use crate::filter::FilterResult;
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::utils::{generate_verification_token, hash_password, is_valid_email, validate_account_name, validate_avatar_url, validate_chat_message, validate_display_name, validate_password, verify_password};
 
 type SocketId = Sid;
 
+// how often online users are checked for having gone idle
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// how often queued presence refreshes are sent, so a wave of logins doesn't refresh every friends list once per login
+const PRESENCE_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+// the most chat history messages a client can get in a single request
+const MAX_CHAT_HISTORY_LIMIT: u32 = 100;
+
+// the longest activity a user can set, in characters
+const MAX_ACTIVITY_LENGTH: usize = 64;
+
+// the most users returned by a single search
+const USER_SEARCH_LIMIT: usize = 20;
+
+// the most friends a client can get in a single page
+const MAX_FRIENDS_PAGE_LIMIT: u32 = 100;
+
+// friends lists longer than this are pushed a page at a time, the client asks for the rest
+const FRIENDS_LIST_PUSH_LIMIT: usize = 500;
+
+/// A logged in user's connection
+struct ConnectedUser {
+    socket_id: SocketId,
+    presence: PresenceKind,
+    activity: Option<String>,
+    // when they last sent an activity ping, or logged in
+    last_input: Instant,
+    // whether they're only away because they went idle, so they come back online on their own
+    idle: bool,
+    // who they've blocked, cached so room messages don't hit the database once per member
+    blocked: HashSet<UserId>,
+    // whether they asked for large payloads to be compressed when logging in
+    compression: bool,
+}
+
+impl ConnectedUser {
+    fn new(socket_id: SocketId) -> Self {
+        Self {
+            socket_id,
+            presence: PresenceKind::default(),
+            activity: None,
+            last_input: Instant::now(),
+            idle: false,
+            blocked: HashSet::new(),
+            compression: false,
+        }
+    }
+}
+
 pub struct WarhorseServer<T>
 where T: Database + Send + Sync + 'static
 {
     data_service: DataAccess<T>,
-    user_sockets: HashMap<UserId, SocketId>,
+    user_sockets: HashMap<UserId, ConnectedUser>,
+    // every open socket, logged in or not, and the address it came from when that's known
+    connections: HashMap<SocketId, Option<IpAddr>>,
+    connections_per_ip: HashMap<IpAddr, usize>,
+    // online users whose friends list is stale because a friend logged in or out
+    presence_refreshes: HashSet<UserId>,
+    room_members: HashMap<RoomId, HashSet<UserId>>,
+    parties: HashMap<PartyId, Vec<UserId>>,
+    // users who've been invited to a party but haven't joined yet
+    party_invites: HashMap<PartyId, HashSet<UserId>>,
+    config: WarhorseServerConfig,
+    chat_rate_limiter: RateLimiter<SocketId>,
     io: SocketIo,
 }
 
 impl<T> WarhorseServer<T>
 where T: Database + Send + Sync + 'static
 {
-    pub fn new(io: SocketIo, database_connection_string: &str) -> Self {
-        Self {
+    /// Creates a server, each socket may send at most `chat_rate_limit` chat messages.
+    pub fn new(io: SocketIo, database_connection_string: &str, chat_rate_limit: RateLimit) -> Self {
+        Self::builder()
+            .database_connection_string(database_connection_string)
+            .chat_rate_limit(chat_rate_limit)
+            .build(io)
+    }
+
+    /// Starts configuring a server, anything left unset keeps its default.
+    pub fn builder() -> WarhorseServerBuilder<T> {
+        WarhorseServerBuilder::new()
+    }
+
+    pub(crate) fn with_config(io: SocketIo, config: WarhorseServerConfig) -> Self {
+        let mut server = Self {
             io,
             user_sockets: HashMap::new(),
-            data_service: DataAccess::new(T::new(database_connection_string)),
+            connections: HashMap::new(),
+            connections_per_ip: HashMap::new(),
+            presence_refreshes: HashSet::new(),
+            room_members: HashMap::new(),
+            parties: HashMap::new(),
+            party_invites: HashMap::new(),
+            chat_rate_limiter: RateLimiter::new(config.chat_rate_limit),
+            data_service: DataAccess::new(T::new(&config.database_connection_string)),
+            config,
+        };
+
+        if server.config.seed_test_users {
+            warn!("Seeding the test, test2 and test3 users with password `password`, never do this on a server anyone else can reach");
+            if let Err(e) = server.seed_test_users() {
+                error!(?e, "Failed to seed test users");
+            }
+        }
+        server
+    }
+
+    /// Gets the settings the server was built with
+    pub fn config(&self) -> &WarhorseServerConfig {
+        &self.config
+    }
+
+    /// Registers the test users, a persistent database will already have them after the first run
+    fn seed_test_users(&mut self) -> Result<(), ServerError> {
+        for (account_name, display_name) in [("test", "Test User"), ("test2", "Test User 2"), ("test3", "Test User 3")] {
+            if self.data_service.users_get_by_account_name(account_name).is_some() {
+                continue;
+            }
+
+            let registration = UserRegistration {
+                account_name: account_name.to_string(),
+                email: format!("{}@example.com", account_name),
+                display_name: display_name.to_string(),
+                password: "password".to_string(),
+                language: Language::English,
+            };
+            let password_hash = hash_password(&registration.password)?;
+            self.data_service.users_insert(registration, password_hash);
+            info!("Created test user with account name `{}` and password `password`", account_name);
         }
+        Ok(())
     }
 
     /// Gets the Socket.IO instance
@@ -42,29 +165,132 @@ where T: Database + Send + Sync + 'static
         &self.io
     }
 
+    /// Sets how many private messages are kept for a user while they're offline, older ones are dropped.
+    pub fn set_max_offline_messages(&mut self, max_offline_messages: usize) {
+        self.config.max_offline_messages = max_offline_messages;
+    }
+
     /// Gets the online status of a user
     fn get_online_status(&self, user_id: UserId) -> FriendStatus {
-        if self.user_sockets.contains_key(&user_id) {
-            FriendStatus::Online
-        } else {
-            FriendStatus::Offline
+        match self.user_sockets.get(&user_id).map(|user| user.presence) {
+            Some(PresenceKind::Online) => FriendStatus::Online,
+            Some(PresenceKind::Away) => FriendStatus::Away,
+            Some(PresenceKind::Busy) => FriendStatus::Busy,
+            Some(PresenceKind::Invisible) | None => FriendStatus::Offline,
+        }
+    }
+
+    /// Gets what a user is doing, hidden along with the user when they're invisible
+    fn get_activity(&self, user_id: UserId) -> Option<String> {
+        self.user_sockets.get(&user_id)
+            .filter(|user| user.presence != PresenceKind::Invisible)
+            .and_then(|user| user.activity.clone())
+    }
+
+    /// Sets or clears what a user is doing, it only lasts until they disconnect
+    fn set_activity(&mut self, user_id: UserId, req: SetActivity) -> Result<(), ServerError> {
+        let activity = req.activity
+            .map(|activity| activity.trim().to_string())
+            .filter(|activity| !activity.is_empty());
+        if activity.as_ref().is_some_and(|activity| activity.chars().count() > MAX_ACTIVITY_LENGTH) {
+            return Err(crate::i18n::activity_too_long(req.language));
+        }
+
+        match self.user_sockets.get_mut(&user_id) {
+            Some(user) => user.activity = activity,
+            None => Err(format!("{} is not connected", user_id))?,
+        }
+        self.queue_presence_refresh(user_id);
+        Ok(())
+    }
+
+    /// Sets or clears a user's avatar, queuing a refresh so their online friends see it
+    fn set_avatar(&mut self, user_id: UserId, req: SetAvatar) -> Result<(), ServerError> {
+        let avatar_url = req.avatar_url
+            .map(|avatar_url| avatar_url.trim().to_string())
+            .filter(|avatar_url| !avatar_url.is_empty());
+        if let Some(avatar_url) = &avatar_url {
+            validate_avatar_url(avatar_url, req.language)?;
+        }
+
+        self.data_service.users_set_avatar(user_id.clone(), avatar_url);
+        self.queue_presence_refresh(user_id);
+        Ok(())
+    }
+
+    /// Sets how a user appears to their friends
+    fn set_presence(&mut self, user_id: UserId, req: SetPresence) -> Result<(), ServerError> {
+        match self.user_sockets.get_mut(&user_id) {
+            Some(user) => {
+                user.presence = req.status;
+                user.idle = false;
+            }
+            None => Err(format!("{} is not connected", user_id))?,
+        }
+        self.queue_presence_refresh(user_id);
+        Ok(())
+    }
+
+    /// Notes that a user is at the keyboard, bringing them back online if they were only away for being idle
+    fn note_activity(&mut self, user_id: UserId) -> Result<(), ServerError> {
+        let was_idle = match self.user_sockets.get_mut(&user_id) {
+            Some(user) => {
+                user.last_input = Instant::now();
+                if user.idle {
+                    user.presence = PresenceKind::Online;
+                    user.idle = false;
+                    true
+                } else {
+                    false
+                }
+            }
+            None => Err(format!("{} is not connected", user_id))?,
+        };
+        if was_idle {
+            self.queue_presence_refresh(user_id);
+        }
+        Ok(())
+    }
+
+    /// Shows online users who haven't been active for the idle timeout as of `now` as away.
+    /// Users who picked a status themselves are left alone.
+    pub fn mark_idle_users_away(&mut self, now: Instant) {
+        let idle_timeout = self.config.idle_timeout;
+        let idle_users: Vec<UserId> = self.user_sockets
+            .iter_mut()
+            .filter(|(_, user)| user.presence == PresenceKind::Online)
+            .filter(|(_, user)| now.saturating_duration_since(user.last_input) >= idle_timeout)
+            .map(|(user_id, user)| {
+                user.presence = PresenceKind::Away;
+                user.idle = true;
+                user_id.clone()
+            })
+            .collect();
+        for user_id in idle_users {
+            info!("{} went idle, showing them as away", user_id);
+            self.queue_presence_refresh(user_id);
         }
     }
 
+    /// Gets everyone connected to the configured namespace, `None` until the namespace has been registered
+    fn get_namespace(&self) -> Option<BroadcastOperators> {
+        self.io.of(self.config.namespace.as_str())
+    }
+
     /// Gets a room by its ID
-    pub fn get_room(&self, room_id: RoomId) -> BroadcastOperators {
-        self.io.to(room_id)
+    pub fn get_room(&self, room_id: RoomId) -> Option<BroadcastOperators> {
+        self.get_namespace().map(|namespace| namespace.to(String::from(room_id)))
     }
 
     /// Gets a socket by its ID
     pub fn get_socket(&self, socket_id: SocketId) -> Option<SocketRef> {
-        self.io.get_socket(socket_id)
+        self.get_namespace()?.get_socket(socket_id)
     }
 
     /// Gets the socket ID associated with a user
     pub fn get_socket_id(&self, user_id: UserId) -> Result<SocketId, ServerError> {
         match self.user_sockets.get(&user_id) {
-            Some(socket_id) => Ok(socket_id.clone()),
+            Some(user) => Ok(user.socket_id),
             None => Err(format!("{} is not connected", user_id))?,
         }
     }
@@ -74,7 +300,7 @@ where T: Database + Send + Sync + 'static
         &mut self,
         req: UserLogin,
         socket_id: SocketId
-    ) -> Result<(), ServerError> {
+    ) -> Result<UserId, ServerError> {
         let user_partial = match req.identity {
             LoginUserIdentity::AccountName(account_name) => {
                 self.data_service.users_get_by_account_name(&account_name)
@@ -85,12 +311,24 @@ where T: Database + Send + Sync + 'static
         };
 
         if let Some(user) = user_partial {
-            // @todo: do actual authentication here
+            let password_hash = self.data_service.users_get_password_hash(user.id.clone());
+            if !password_hash.is_some_and(|hash| verify_password(&req.password, &hash)) {
+                return Err(crate::i18n::invalid_login(req.language));
+            }
+
+            if let Some(ban) = self.data_service.bans_get(user.id.clone()) {
+                info!("Banned user {} tried to log in", user.id);
+                if let Some(socket) = self.get_socket(socket_id) {
+                    socket.emit(EVENT_RECEIVE_BANNED, &ban.to_json()?)?;
+                }
+                return Err(crate::i18n::banned(req.language));
+            }
 
             // Actually log them in
-            self.user_sockets.insert(user.id.clone(), socket_id);
-            self.send_post_login_data(user.id);
-            Ok(())
+            self.connect_user(user.id.clone(), socket_id, req.compression);
+            self.send_post_login_data(user.id.clone());
+            self.queue_presence_refresh(user.id.clone());
+            Ok(user.id)
         } else {
             Err(crate::i18n::invalid_login(req.language))?
         }
@@ -101,7 +339,7 @@ where T: Database + Send + Sync + 'static
         &mut self,
         req: UserRegistration,
         socket_id: Option<SocketId>
-    ) -> Result<(), ServerError> {
+    ) -> Result<UserId, ServerError> {
         validate_password(&req.password, req.language)?;
         validate_account_name(&req.account_name, req.language)?;
         validate_display_name(&req.display_name, req.language)?;
@@ -119,421 +357,1673 @@ where T: Database + Send + Sync + 'static
         }
 
         // insert into the db
-        let new_user_id = self.data_service.users_insert(req);
+        let password_hash = hash_password(&req.password)?;
+        let new_user_id = self.data_service.users_insert(req, password_hash);
         info!("Registered new user: {}", new_user_id);
 
+        let token = generate_verification_token();
+        self.data_service.users_set_email_verification_token(new_user_id.clone(), token.clone());
+        if let Some(user) = self.data_service.users_get(new_user_id.clone()) {
+            self.config.email_verification.send(&user, &token);
+        }
+
         // log them in if there's a socket available
         if let Some(socket_id) = socket_id {
-            self.user_sockets.insert(new_user_id.clone(), socket_id);
-            self.send_post_login_data(new_user_id);
+            self.connect_user(new_user_id.clone(), socket_id, false);
+            self.send_post_login_data(new_user_id.clone());
         }
-        Ok(())
+        Ok(new_user_id)
     }
 
-    /// Removes a user's socket
-    pub async fn remove_user(&mut self, user_id: &str) {
-        self.user_sockets.remove(user_id);
+    /// Verifies the email of the user a token from registration was issued to, the token only works once
+    pub fn verify_email(&mut self, token: &str, language: Language) -> Result<UserId, ServerError> {
+        match self.data_service.users_verify_email(token) {
+            Some(user_id) => {
+                info!("{} verified their email", user_id);
+                Ok(user_id)
+            }
+            None => Err(crate::i18n::invalid_verification_token(language)),
+        }
     }
 
-    /// Sends post login data to the user
-    fn send_post_login_data(&self, user_id: UserId) {
-        self.send_friend_list(user_id.clone());
-        self.send_friend_requests(user_id.clone());
-        self.send_post_login_event(user_id);
+    /// Whether a user has verified their email
+    pub fn is_email_verified(&self, user_id: UserId) -> bool {
+        self.data_service.users_is_email_verified(user_id)
     }
 
-    /// Sends a post login event
-    fn send_post_login_event(&self, user_id: UserId) {
-        match self.get_socket_id(user_id) {
-            Ok(socket_id) => {
-                if let Some(socket) = self.get_socket(socket_id) {
-                    let _= socket.emit(EVENT_RECEIVE_USER_LOGIN, &serde_json::json!({}));
-                }
-            },
-            Err(e) => {
-                info!(?e, "Failed to get socket ID");
+    /// Deletes a user's account once they've confirmed their password, telling them before they're disconnected
+    pub async fn delete_account(&mut self, user_id: UserId, req: DeleteAccount) -> Result<(), ServerError> {
+        let password_hash = self.data_service.users_get_password_hash(user_id.clone());
+        if !password_hash.is_some_and(|hash| verify_password(&req.password, &hash)) {
+            return Err(crate::i18n::incorrect_password(req.language));
+        }
+
+        // everyone the user shows up for, gathered before the rows are gone
+        let related = self.data_service.friends_get(user_id.clone());
+
+        self.emit_to_user(user_id.clone(), EVENT_RECEIVE_ACCOUNT_DELETED, &serde_json::json!({}));
+        self.handle_disconnect(&user_id).await;
+        self.data_service.users_delete(user_id.clone());
+        info!("Deleted user: {}", user_id);
+
+        for friend in related {
+            if self.user_sockets.contains_key(&friend.id) {
+                self.send_friend_list(friend.id);
             }
         }
+        Ok(())
     }
 
-    /// Sends a private message to a specific user
-    fn send_chat_message(&self, sender_id: UserId, message: SendChatMessage) -> Result<(), ServerError> {
+    /// Lets a user kick and ban other users, or takes that away
+    pub fn set_admin(&mut self, user_id: UserId, is_admin: bool) {
+        self.data_service.users_set_admin(user_id, is_admin);
+    }
 
-        let display_name = match self.data_service.users_get(sender_id.clone()) {
-            Some(user) => user.display_name.clone(),
-            None => {
-                error!("User does not exist: {}", sender_id);
-                return Err(format!("{} does not exist", sender_id))?;
-            }
+    /// Disconnects a user on an admin's behalf
+    pub async fn kick_user(&mut self, admin_id: UserId, req: AdminKick) -> Result<(), ServerError> {
+        self.check_admin(admin_id.clone(), req.language)?;
+
+        let socket = self.get_socket_id(req.user_id.clone())
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+        let Some(socket) = socket else {
+            Err(format!("{} is not connected", req.user_id))?
         };
 
-        let serialized_message = ChatMessage {
-            display_name,
-            channel: message.channel.clone(),
-            message: message.message.clone(),
-            time: chrono::Utc::now().timestamp() as u32,
-        }.to_json()?;
+        info!("{} kicked {}", admin_id, req.user_id);
+        self.handle_disconnect(&req.user_id).await;
+        socket.disconnect().map_err(|e| ServerError::from(format!("Failed to disconnect {}: {:?}", req.user_id, e)))?;
+        Ok(())
+    }
 
-        match message.channel {
-            ChatChannel::PrivateMessage(user_id) => {
-                if self.are_friends(sender_id.clone(), user_id.clone()) {
+    /// Bans a user on an admin's behalf, telling them why before they're disconnected
+    pub async fn ban_user(&mut self, admin_id: UserId, req: AdminBan) -> Result<(), ServerError> {
+        self.check_admin(admin_id.clone(), req.language)?;
+        if !self.data_service.user_exists(req.user_id.clone()) {
+            Err(format!("{} does not exist", req.user_id))?;
+        }
 
-                    if self.data_service.user_is_blocked(sender_id.clone(), user_id.clone()) {
-                        warn!("{} has blocked {} but is trying to send a private chat message", sender_id, user_id);
-                        return Err(crate::i18n::user_is_blocked(message.language));
-                    }
+        info!("{} banned {}", admin_id, req.user_id);
+        let ban = Banned { reason: req.reason };
+        self.data_service.bans_insert(req.user_id.clone(), ban.reason.clone());
+
+        // they can be banned while offline, the ban is enforced when they next log in
+        let socket = self.get_socket_id(req.user_id.clone())
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+        if let Some(socket) = socket {
+            socket.emit(EVENT_RECEIVE_BANNED, &ban.to_json()?)?;
+            self.handle_disconnect(&req.user_id).await;
+            socket.disconnect().map_err(|e| ServerError::from(format!("Failed to disconnect {}: {:?}", req.user_id, e)))?;
+        }
+        Ok(())
+    }
 
-                    if self.data_service.user_is_blocked(user_id.clone(), sender_id.clone()) {
-                        warn!("{} has blocked {} but {} is trying to send a private chat message", user_id, sender_id, sender_id);
-                        return Err(crate::i18n::user_is_blocked(message.language));
-                    }
+    /// Errors unless the user is an admin
+    fn check_admin(&self, user_id: UserId, language: Language) -> Result<(), ServerError> {
+        if self.data_service.users_get(user_id.clone()).is_some_and(|user| user.is_admin) {
+            Ok(())
+        } else {
+            warn!("{} is not an admin but is trying to use admin commands", user_id);
+            Err(crate::i18n::not_admin(language))
+        }
+    }
 
-                    let socket_id = self.get_socket_id(user_id.clone())?;
-                    if let Some(socket) = self.get_socket(socket_id) {
-                        socket.emit(EVENT_RECEIVE_CHAT_MESSAGE, &serialized_message)?;
-                    } else {
-                        Err(format!("{} is not connected", user_id))?;
-                    }
-                } else {
-                    Err(format!("{} is not friends with {} but is trying to send a private chat message", sender_id, user_id))?;
-                }
-            },
-            ChatChannel::Room(room_id) => {
-                if self.user_in_room(sender_id.clone(), room_id.clone()) {
-                    self.get_room(room_id)
-                        .emit(EVENT_RECEIVE_CHAT_MESSAGE, &serialized_message)?;
-                } else {
-                    Err(format!("{} is not in room {}", sender_id, room_id))?;
-                }
-            }
+    /// Tells every connected client the server is going down, then disconnects them so the connections can drain
+    pub fn shutdown(&mut self, reason: Option<String>) -> Result<(), ServerError> {
+        info!("Shutting down, notifying {} connected users", self.user_sockets.len());
+        let json = ServerShutdown { reason }.to_json()?;
+        if let Some(namespace) = self.get_namespace() {
+            namespace.emit(EVENT_RECEIVE_SERVER_SHUTDOWN, &json)?;
+        }
+        if let Some(namespace) = self.get_namespace() {
+            namespace.disconnect().map_err(|e| ServerError::from(format!("Failed to disconnect sockets: {:?}", e)))?;
         }
+        Ok(())
+    }
 
+    /// Pings every connected client so they know the connection is still alive
+    pub fn send_heartbeat(&self) -> Result<(), ServerError> {
+        if let Some(namespace) = self.get_namespace() {
+            namespace.emit(EVENT_RECEIVE_PING, &serde_json::json!({}))?;
+        }
         Ok(())
     }
 
-    fn send_friend_requests(&self, user_id: UserId) {
-        match vec_to_json(self.data_service.user_get_pending_friend_requests_for_user(user_id.clone())) {
-            Ok(friend_requests) => {
-                match self.get_socket_id(user_id) {
-                    Ok(socket_id) => {
-                        if let Some(socket) = self.get_socket(socket_id) {
-                            let _= socket.emit(EVENT_RECEIVE_FRIEND_REQUESTS, &friend_requests);
-                        }
-                    },
-                    Err(e) => {
-                        info!(?e, "Failed to get socket ID");
-                    }
-                }
-            },
-            Err(e) => {
-                error!(?e, "Failed to serialize friend requests");
+    /// Counts a new socket against the connection caps, erroring with why if it would go over either of them
+    pub fn open_connection(&mut self, socket_id: SocketId, ip: Option<IpAddr>) -> Result<(), ServerError> {
+        if self.connections.len() >= self.config.max_connections {
+            Err(format!("Already at the limit of {} connections", self.config.max_connections))?;
+        }
+        if let Some(ip) = ip {
+            let count = self.connections_per_ip.entry(ip).or_default();
+            if *count >= self.config.max_connections_per_ip {
+                Err(format!("{} is already at the limit of {} connections", ip, self.config.max_connections_per_ip))?;
             }
+            *count += 1;
         }
+        self.connections.insert(socket_id, ip);
+        Ok(())
     }
 
-    fn send_friend_request(&mut self, sender_id: UserId, req: FriendRequest) -> Result<(), ServerError> {
-        if self.are_friends(sender_id.clone(), req.friend_id.clone()) {
-            warn!("{} is already friends with {} but is trying to send a friend request", sender_id, req.friend_id);
-            return Err(crate::i18n::already_friends(req.language));
+    /// Stops counting a closed socket against the connection caps
+    pub fn close_connection(&mut self, socket_id: SocketId) {
+        let Some(Some(ip)) = self.connections.remove(&socket_id) else {
+            return;
+        };
+        if let Some(count) = self.connections_per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                self.connections_per_ip.remove(&ip);
+            }
         }
+    }
 
-        if self.data_service.user_is_blocked(sender_id.clone(), req.friend_id.clone()) {
-            warn!("{} has blocked {} but is trying to send a friend request", sender_id, req.friend_id);
-            return Err(crate::i18n::user_is_blocked(req.language));
-        }
+    /// Associates a logged in user with their socket
+    fn connect_user(&mut self, user_id: UserId, socket_id: SocketId, compression: bool) {
+        let mut connected_user = ConnectedUser::new(socket_id);
+        connected_user.compression = compression;
+        connected_user.blocked = self.data_service.user_blocks_get_blocks_for_user(user_id.clone())
+            .into_iter()
+            .map(|blocked| blocked.id)
+            .collect();
+        self.user_sockets.insert(user_id, connected_user);
+    }
 
-        if self.data_service.user_is_blocked(req.friend_id.clone(), sender_id.clone()) {
-            warn!("{} has blocked {} but {} is trying to send a friend request", req.friend_id, sender_id, sender_id);
-            return Err(crate::i18n::user_is_blocked(req.language));
+    /// Cleans up after a user disconnects, however it happened. Drops their socket along with their presence and activity,
+    /// takes them out of rooms and parties, records when they were last seen and queues a refresh for their friends.
+    pub async fn handle_disconnect(&mut self, user_id: &UserId) {
+        if let Some(user) = self.user_sockets.remove(user_id) {
+            self.chat_rate_limiter.remove(&user.socket_id);
+            self.room_members.retain(|_, members| {
+                members.remove(user_id);
+                !members.is_empty()
+            });
+            if let Some(party_id) = self.get_party_id(user_id) {
+                self.remove_from_party(user_id.clone(), party_id);
+            }
+            for invited in self.party_invites.values_mut() {
+                invited.remove(user_id);
+            }
+            self.data_service.users_set_last_seen(user_id.clone(), Timestamp::now());
+            self.queue_presence_refresh(user_id.clone());
         }
+    }
 
-        if self.data_service.user_exists(req.friend_id.clone()) {
-            self.data_service.friend_requests_insert(sender_id.clone(), req.friend_id.clone());
+    /// Queues a friends list refresh for each of the user's online friends, so they see the user's new status.
+    fn queue_presence_refresh(&mut self, user_id: UserId) {
+        for friend in self.data_service.friends_get(user_id) {
+            // pending requests and blocks don't get to see presence, only actual friends (listed as offline)
+            if friend.status == FriendStatus::Offline && self.user_sockets.contains_key(&friend.id) {
+                self.presence_refreshes.insert(friend.id);
+            }
+        }
+    }
 
-            // send a friend request to the target user
-            self.send_friend_requests(req.friend_id.clone());
+    /// Sends the queued presence refreshes, each user gets a single friends list no matter how many friends changed.
+    pub fn flush_presence_refreshes(&mut self) {
+        for user_id in std::mem::take(&mut self.presence_refreshes) {
+            self.send_friend_list(user_id);
+        }
+    }
 
-            // refresh the friends list for the sender
-            self.send_friend_list(sender_id);
+    /// Sends post login data to the user
+    fn send_post_login_data(&mut self, user_id: UserId) {
+        self.send_friend_list(user_id.clone());
+        self.send_friend_requests(user_id.clone());
+        self.send_blocked_list(user_id.clone());
+        self.send_current_user(user_id.clone());
+        self.send_post_login_event(user_id.clone());
+        self.send_offline_messages(user_id.clone());
+        for room_id in self.config.default_rooms.clone() {
+            self.add_to_room(user_id.clone(), room_id);
+        }
+    }
 
-            // refresh the friends list for the target user
-            self.send_friend_list(req.friend_id);
-        } else {
-            error!("User does not exist: {}", req.friend_id);
-            return Err(format!("{} does not exist", req.friend_id))?;
+    /// Joins a chat room, creating it if nobody is in it yet
+    fn join_room(&mut self, user_id: UserId, req: JoinRoom) -> Result<(), ServerError> {
+        if req.room_id.trim().is_empty() {
+            return Err(crate::i18n::invalid_room_id(req.language));
         }
 
+        self.add_to_room(user_id, req.room_id);
         Ok(())
     }
 
-    fn accept_friend_request(&mut self, user_id: UserId, req: AcceptFriendRequest) -> Result<(), ServerError> {
-        if self.are_friends(user_id.clone(), req.friend_id.clone()) {
-            info!("{} is already friends with {}", user_id, req.friend_id);
-            return Err(crate::i18n::already_friends(req.language));
+    /// Leaves a chat room
+    fn leave_room(&mut self, user_id: UserId, req: LeaveRoom) -> Result<(), ServerError> {
+        let left = match self.room_members.get_mut(&req.room_id) {
+            Some(members) => members.remove(&user_id),
+            None => false,
+        };
+        if !left {
+            Err(format!("{} is not in room {}", user_id, req.room_id))?;
         }
 
-        if self.data_service.user_is_blocked(user_id.clone(), req.friend_id.clone()) {
-            warn!("{} has blocked {} but is trying to accept a friend request", user_id, req.friend_id);
-            return Err(crate::i18n::user_is_blocked(req.language));
+        if self.room_members.get(&req.room_id).is_some_and(|members| members.is_empty()) {
+            self.room_members.remove(&req.room_id);
         }
 
-        if self.data_service.user_is_blocked(req.friend_id.clone(), user_id.clone()) {
-            warn!("{} has blocked {} but {} is trying to accept a friend request", req.friend_id, user_id, user_id);
-            return Err(crate::i18n::user_is_blocked(req.language));
+        let socket = self.get_socket_id(user_id)
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+        if let Some(socket) = socket {
+            if let Err(e) = socket.leave(String::from(req.room_id.clone())) {
+                error!(?e, "Failed to leave room");
+            }
+            socket.emit(EVENT_RECEIVE_ROOM_LEFT, &req.room_id)?;
         }
+        Ok(())
+    }
 
-        self.data_service.friends_add(user_id.clone(), req.friend_id.clone());
-        let user_socket_id = self.get_socket_id(user_id.clone())?;
-        if let Some(socket) = self.get_socket(user_socket_id) {
-            let user = self.data_service.users_get(req.friend_id.clone());
-            if let Some(user) = user {
-                let friend = Friend {
-                    id: user.id.clone(),
-                    display_name: user.display_name.clone(),
-                    status: self.get_online_status(user.id.clone()),
-                };
-                let friend_request_accepted = FriendRequestAccepted { friend };
-                let serialized_friend_request_accepted = friend_request_accepted.to_json()?;
-                socket.emit(EVENT_RECEIVE_FRIEND_REQUEST_ACCEPTED, &serialized_friend_request_accepted)?;
+    /// Invites a friend to a party, creating the party with the inviter in it if it doesn't exist yet
+    fn invite_to_party(&mut self, user_id: UserId, req: PartyInvite) -> Result<(), ServerError> {
+        if req.party_id.trim().is_empty() {
+            return Err(crate::i18n::invalid_party_id(req.language));
+        }
 
-                // refresh the friends list for both users
-                self.send_friend_list(user_id);
-                self.send_friend_list(req.friend_id);
-            }
+        self.check_not_blocked(user_id.clone(), req.friend_id.clone(), req.language)?;
+        if !self.are_friends(user_id.clone(), req.friend_id.clone()) {
+            Err(format!("{} is not friends with {} but is trying to invite them to a party", user_id, req.friend_id))?;
         }
 
+        match self.parties.get(&req.party_id) {
+            Some(members) => {
+                if !members.contains(&user_id) {
+                    Err(format!("{} is not in party {}", user_id, req.party_id))?;
+                }
+            },
+            None => self.add_to_party(user_id.clone(), req.party_id.clone()),
+        }
+        self.party_invites.entry(req.party_id.clone()).or_default().insert(req.friend_id.clone());
+
+        if let Some(friend) = self.get_member(user_id) {
+            let invite = PartyInviteReceived {
+                party_id: req.party_id,
+                friend,
+            }.to_json()?;
+            self.emit_to_user(req.friend_id, EVENT_RECEIVE_PARTY_INVITE, &invite);
+        }
         Ok(())
     }
 
-    /// Rejects a friend request
-    fn reject_friend_request(&mut self, user_id: UserId, req: RejectFriendRequest) -> Result<(), ServerError> {
-        self.data_service.friend_requests_remove(user_id.clone(), req.friend_id.clone());
+    /// Joins a party the user has been invited to
+    fn join_party(&mut self, user_id: UserId, req: PartyJoin) -> Result<(), ServerError> {
+        let invited = self.party_invites
+            .get_mut(&req.party_id)
+            .is_some_and(|invited| invited.remove(&user_id));
+        if !invited || !self.parties.contains_key(&req.party_id) {
+            Err(format!("{} has not been invited to party {}", user_id, req.party_id))?;
+        }
 
-        // refresh the friends list for both users
-        self.send_friend_list(req.friend_id);
-        self.send_friend_list(user_id);
+        self.add_to_party(user_id, req.party_id);
         Ok(())
     }
 
-    /// Removes a friend
-    fn remove_friend(&mut self, user_id: UserId, req: RemoveFriendRequest) -> Result<(), ServerError> {
-        info!("Removing friend: {:?}", req);
-        self.data_service.friends_remove(user_id.clone(), req.friend_id.clone());
-
-        // We need to refresh both users friends list
-        self.send_friend_list(user_id);
-        self.send_friend_list(req.friend_id);
+    /// Leaves a party
+    fn leave_party(&mut self, user_id: UserId, req: PartyLeave) -> Result<(), ServerError> {
+        if !self.remove_from_party(user_id.clone(), req.party_id.clone()) {
+            Err(format!("{} is not in party {}", user_id, req.party_id))?;
+        }
         Ok(())
     }
 
-    /// Blocks a user
-    fn block_user(&mut self, user_id: UserId, req: BlockUserRequest) -> Result<(), ServerError> {
-        self.data_service.friends_remove(user_id.clone(), req.user_id.clone());
-        self.data_service.user_blocks_insert(user_id.clone(), req.user_id.clone());
+    /// Gets the party a user is in, they can only be in one at a time
+    fn get_party_id(&self, user_id: &str) -> Option<PartyId> {
+        self.parties.iter()
+            .find(|(_, members)| members.iter().any(|member| member == user_id))
+            .map(|(party_id, _)| party_id.clone())
+    }
 
-        // We need to refresh both users friends list
-        self.send_friend_list(user_id.clone());
-        self.send_friend_list(req.user_id);
-        Ok(())
+    /// Adds a user to a party, taking them out of the party they were in
+    fn add_to_party(&mut self, user_id: UserId, party_id: PartyId) {
+        match self.get_party_id(&user_id) {
+            Some(current) if current == party_id => return,
+            Some(current) => {
+                self.remove_from_party(user_id.clone(), current);
+            },
+            None => {},
+        }
+
+        self.parties.entry(party_id.clone()).or_default().push(user_id);
+        self.send_party_update(party_id);
+    }
+
+    /// Removes a user from a party, dissolving it once it's empty. Returns whether they were in it.
+    fn remove_from_party(&mut self, user_id: UserId, party_id: PartyId) -> bool {
+        let Some(members) = self.parties.get_mut(&party_id) else {
+            return false;
+        };
+        let Some(index) = members.iter().position(|member| *member == user_id) else {
+            return false;
+        };
+        members.remove(index);
+
+        if members.is_empty() {
+            self.parties.remove(&party_id);
+            self.party_invites.remove(&party_id);
+        } else {
+            self.send_party_update(party_id.clone());
+        }
+
+        // an empty party tells them they're out of it
+        match (PartyUpdate { party_id, members: Vec::new() }).to_json() {
+            Ok(json) => self.emit_to_user(user_id, EVENT_RECEIVE_PARTY_UPDATE, &json),
+            Err(e) => error!(?e, "Failed to serialize party update"),
+        }
+        true
+    }
+
+    /// Sends a party's members to each of them
+    fn send_party_update(&self, party_id: PartyId) {
+        let member_ids = self.parties.get(&party_id).cloned().unwrap_or_default();
+        let update = PartyUpdate {
+            party_id,
+            members: self.get_members(&member_ids),
+        };
+
+        match update.to_json() {
+            Ok(json) => {
+                for member_id in member_ids {
+                    self.emit_to_user(member_id, EVENT_RECEIVE_PARTY_UPDATE, &json);
+                }
+            },
+            Err(e) => {
+                error!(?e, "Failed to serialize party update");
+            }
+        }
+    }
+
+    /// Gets a user as they're shown to the rest of their party or room
+    fn get_member(&self, user_id: UserId) -> Option<Friend> {
+        self.get_members(&[user_id]).pop()
+    }
+
+    /// Gets users as they're shown to the rest of their party or room, looked up all at once and kept in order
+    fn get_members(&self, user_ids: &[UserId]) -> Vec<Friend> {
+        let users = self.data_service.users_get_many(user_ids);
+        user_ids.iter()
+            .filter_map(|user_id| users.get(user_id))
+            .map(|user| Friend {
+                id: user.id.clone(),
+                display_name: user.display_name.clone(),
+                status: self.get_online_status(user.id.clone()),
+                activity: self.get_activity(user.id.clone()),
+                last_seen: None,
+                avatar_url: user.avatar_url.clone(),
+            })
+            .collect()
+    }
+
+    /// Emits an event to a user if they're connected
+    fn emit_to_user(&self, user_id: UserId, event: &str, json: &Value) {
+        let socket = self.get_socket_id(user_id)
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+        if let Some(socket) = socket {
+            if let Err(e) = socket.emit(event, json) {
+                error!(?e, "Failed to emit {}", event);
+            }
+        }
+    }
+
+    /// Compresses a large payload for users who asked for compression, anything else is sent as it is
+    fn compress_for(&self, user_id: &str, json: Value) -> Value {
+        if !self.user_sockets.get(user_id).is_some_and(|user| user.compression) {
+            return json;
+        }
+
+        match compress_if_large(&json) {
+            Ok(Some(compressed)) => compressed,
+            Ok(None) => json,
+            Err(e) => {
+                error!(?e, "Failed to compress payload");
+                json
+            }
+        }
+    }
+
+    /// Adds a user to a room's members and their socket to the Socket.IO room
+    fn add_to_room(&mut self, user_id: UserId, room_id: RoomId) {
+        self.room_members.entry(room_id.clone()).or_default().insert(user_id.clone());
+
+        let socket = self.get_socket_id(user_id)
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+        if let Some(socket) = socket {
+            if let Err(e) = socket.join(String::from(room_id.clone())) {
+                error!(?e, "Failed to join room");
+            }
+            let _ = socket.emit(EVENT_RECEIVE_ROOM_JOINED, &room_id);
+        }
+    }
+
+    /// Sends the private messages that arrived while the user was offline
+    fn send_offline_messages(&mut self, user_id: UserId) {
+        let socket = self.get_socket_id(user_id.clone())
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+
+        // leave them stored until we can actually deliver them
+        if let Some(socket) = socket {
+            for message in self.data_service.offline_messages_drain(user_id) {
+                match message.to_json() {
+                    Ok(json) => {
+                        let _ = socket.emit(EVENT_RECEIVE_CHAT_MESSAGE, &json);
+                    },
+                    Err(e) => {
+                        error!(?e, "Failed to serialize offline message");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends a user their own account, so clients know who they're logged in as
+    fn send_current_user(&self, user_id: UserId) {
+        let Some(user) = self.data_service.users_get(user_id.clone()) else {
+            error!("User does not exist: {}", user_id);
+            return;
+        };
+
+        match user.to_json() {
+            Ok(json) => self.emit_to_user(user_id, EVENT_RECEIVE_CURRENT_USER, &json),
+            Err(e) => error!(?e, "Failed to serialize current user"),
+        }
+    }
+
+    /// Sends a post login event
+    fn send_post_login_event(&self, user_id: UserId) {
+        match self.get_socket_id(user_id) {
+            Ok(socket_id) => {
+                if let Some(socket) = self.get_socket(socket_id) {
+                    let _= socket.emit(EVENT_RECEIVE_USER_LOGIN, &serde_json::json!({}));
+                }
+            },
+            Err(e) => {
+                info!(?e, "Failed to get socket ID");
+            }
+        }
+    }
+
+    /// Sends a private message to a specific user
+    fn send_chat_message(&mut self, sender_id: UserId, message: SendChatMessage) -> Result<(), ServerError> {
+        validate_chat_message(&message.message, message.language)?;
+
+        if let Ok(socket_id) = self.get_socket_id(sender_id.clone()) {
+            if !self.chat_rate_limiter.try_acquire(socket_id) {
+                warn!("{} is sending chat messages too quickly", sender_id);
+                return Err(crate::i18n::rate_limited(message.language));
+            }
+        }
+
+        let display_name = match self.data_service.users_get(sender_id.clone()) {
+            Some(user) => user.display_name.clone(),
+            None => {
+                error!("User does not exist: {}", sender_id);
+                return Err(format!("{} does not exist", sender_id))?;
+            }
+        };
+
+        let is_room = matches!(message.channel, ChatChannel::Room(_));
+        let text = if is_room || self.config.filter_private_messages {
+            match self.config.message_filter.filter(&message.message) {
+                FilterResult::Allow => message.message.clone(),
+                FilterResult::Replace(text) => text,
+                FilterResult::Reject => {
+                    info!("Rejected a chat message from {}", sender_id);
+                    return Err(crate::i18n::message_rejected(message.language));
+                }
+            }
+        } else {
+            message.message.clone()
+        };
+
+        let chat_message = ChatMessage {
+            id: Uuid::new_v4(),
+            display_name,
+            channel: message.channel.clone(),
+            message: text,
+            time: Timestamp::now(),
+        };
+        let serialized_message = chat_message.to_json()?;
+
+        match message.channel {
+            ChatChannel::PrivateMessage(user_id) => {
+                if self.are_friends(sender_id.clone(), user_id.clone()) {
+                    self.check_not_blocked(sender_id.clone(), user_id.clone(), message.language)?;
+
+                    let socket = self.get_socket_id(user_id.clone())
+                        .ok()
+                        .and_then(|socket_id| self.get_socket(socket_id));
+                    if let Some(socket) = socket {
+                        socket.emit(EVENT_RECEIVE_CHAT_MESSAGE, &serialized_message)?;
+                    } else {
+                        // they'll get it when they next log in
+                        self.data_service.offline_messages_push(user_id, chat_message, self.config.max_offline_messages);
+                    }
+                } else {
+                    Err(format!("{} is not friends with {} but is trying to send a private chat message", sender_id, user_id))?;
+                }
+            },
+            ChatChannel::Room(room_id) => {
+                if self.user_in_room(sender_id.clone(), room_id.clone()) {
+                    // delivered member by member so nobody sees messages from someone on either side of a block,
+                    // the sender is a member too so their UI shows the message once the server has accepted it
+                    let recipients = self.room_members.get(&room_id)
+                        .into_iter()
+                        .flatten()
+                        .filter(|member_id| !self.online_users_blocked(&sender_id, member_id));
+                    for member_id in recipients {
+                        self.emit_to_user(member_id.clone(), EVENT_RECEIVE_CHAT_MESSAGE, &serialized_message);
+                    }
+                    self.data_service.chat_history_insert(chat_message);
+                } else {
+                    Err(format!("{} is not in room {}", sender_id, room_id))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Errors if either user has blocked the other
+    fn check_not_blocked(&self, sender_id: UserId, user_id: UserId, language: Language) -> Result<(), ServerError> {
+        if self.data_service.user_is_blocked(sender_id.clone(), user_id.clone()) {
+            warn!("{} has blocked {} but is trying to send them a private message", sender_id, user_id);
+            return Err(crate::i18n::user_is_blocked(language));
+        }
+
+        if self.data_service.user_is_blocked(user_id.clone(), sender_id.clone()) {
+            warn!("{} has blocked {} but {} is trying to send them a private message", user_id, sender_id, sender_id);
+            return Err(crate::i18n::user_is_blocked(language));
+        }
+
+        Ok(())
+    }
+
+    /// Whether either of two connected users has blocked the other, using the cached blocks
+    fn online_users_blocked(&self, user_id: &str, other_id: &str) -> bool {
+        let has_blocked = |blocker: &str, blocked: &str| self.user_sockets
+            .get(blocker)
+            .is_some_and(|user| user.blocked.contains(blocked));
+        has_blocked(user_id, other_id) || has_blocked(other_id, user_id)
+    }
+
+    /// Sends a typing indicator to the private message recipient or everyone else in the room, it's never stored
+    fn send_typing(&self, sender_id: UserId, indicator: TypingIndicator) -> Result<(), ServerError> {
+        let display_name = match self.data_service.users_get(sender_id.clone()) {
+            Some(user) => user.display_name,
+            None => Err(format!("{} does not exist", sender_id))?,
+        };
+
+        let typing = UserTyping {
+            display_name,
+            channel: indicator.channel.clone(),
+            is_typing: indicator.is_typing,
+        }.to_json()?;
+
+        match indicator.channel {
+            ChatChannel::PrivateMessage(user_id) => {
+                self.check_not_blocked(sender_id.clone(), user_id.clone(), indicator.language)?;
+                if !self.are_friends(sender_id.clone(), user_id.clone()) {
+                    Err(format!("{} is not friends with {} but is trying to send a typing indicator", sender_id, user_id))?;
+                }
+
+                let socket = self.get_socket_id(user_id)
+                    .ok()
+                    .and_then(|socket_id| self.get_socket(socket_id));
+                if let Some(socket) = socket {
+                    socket.emit(EVENT_RECEIVE_TYPING, &typing)?;
+                }
+            },
+            ChatChannel::Room(room_id) => {
+                if !self.user_in_room(sender_id.clone(), room_id.clone()) {
+                    Err(format!("{} is not in room {}", sender_id, room_id))?;
+                }
+
+                // broadcasting from the sender's socket leaves them out
+                if let Some(socket) = self.get_socket(self.get_socket_id(sender_id)?) {
+                    socket.to(String::from(room_id)).emit(EVENT_RECEIVE_TYPING, &typing)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_friend_requests(&self, user_id: UserId) {
+        match vec_to_json(self.data_service.user_get_pending_friend_requests_for_user(user_id.clone())) {
+            Ok(friend_requests) => {
+                match self.get_socket_id(user_id) {
+                    Ok(socket_id) => {
+                        if let Some(socket) = self.get_socket(socket_id) {
+                            let _= socket.emit(EVENT_RECEIVE_FRIEND_REQUESTS, &friend_requests);
+                        }
+                    },
+                    Err(e) => {
+                        info!(?e, "Failed to get socket ID");
+                    }
+                }
+            },
+            Err(e) => {
+                error!(?e, "Failed to serialize friend requests");
+            }
+        }
+    }
+
+    /// Tells a user about the friend request `sender_id` just sent them
+    fn send_new_friend_request(&self, sender_id: UserId, user_id: UserId) {
+        let friend_request = self.data_service.user_get_pending_friend_requests_for_user(user_id.clone())
+            .into_iter()
+            .find(|friend| friend.id == sender_id);
+        let Some(friend_request) = friend_request else {
+            error!("{} has no friend request from {} to be told about", user_id, sender_id);
+            return;
+        };
+
+        match friend_request.to_json() {
+            Ok(json) => self.emit_to_user(user_id, EVENT_RECEIVE_FRIEND_REQUEST_NEW, &json),
+            Err(e) => {
+                error!(?e, "Failed to serialize friend request");
+            }
+        }
+    }
+
+    /// Sends the list of users a user has blocked
+    fn send_blocked_list(&self, user_id: UserId) {
+        match vec_to_json(self.data_service.user_blocks_get_blocks_for_user(user_id.clone())) {
+            Ok(blocked_users) => {
+                match self.get_socket_id(user_id) {
+                    Ok(socket_id) => {
+                        if let Some(socket) = self.get_socket(socket_id) {
+                            let _= socket.emit(EVENT_RECEIVE_BLOCKED_USERS, &blocked_users);
+                        }
+                    },
+                    Err(e) => {
+                        info!(?e, "Failed to get socket ID");
+                    }
+                }
+            },
+            Err(e) => {
+                error!(?e, "Failed to serialize blocked users");
+            }
+        }
+    }
+
+    /// Searches for other users whose display name starts with the query.
+    /// Only public details are returned, the account name and email are stripped.
+    fn search_users(&self, user_id: UserId, req: SearchUsers) -> Vec<UserPartial> {
+        let query = req.query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        // one extra in case the searcher is among the results
+        self.data_service.users_search_by_display_name(&query, USER_SEARCH_LIMIT + 1)
+            .into_iter()
+            .filter(|user| user.id != user_id)
+            .take(USER_SEARCH_LIMIT)
+            .map(|user| UserPartial {
+                account_name: None,
+                account_name_lower: None,
+                email: None,
+                ..user
+            })
+            .collect()
+    }
+
+    /// Builds what a user gets to see of another user's profile, redacted if either has blocked the other
+    fn get_profile(&self, user_id: UserId, req: GetProfile) -> Result<PublicProfile, ServerError> {
+        let user = match self.data_service.users_get(req.user_id.clone()) {
+            Some(user) => user,
+            None => Err(format!("User {} not found", req.user_id))?,
+        };
+
+        if self.data_service.user_is_blocked(user_id.clone(), user.id.clone())
+            || self.data_service.user_is_blocked(user.id.clone(), user_id.clone())
+        {
+            return Ok(PublicProfile {
+                id: user.id,
+                display_name: user.display_name,
+                status: FriendStatus::Offline,
+                activity: None,
+                member_since: None,
+                mutual_friends: 0,
+                avatar_url: None,
+            });
+        }
+
+        let mutual_friends = self.data_service.mutual_friends(user_id, user.id.clone()).len();
+
+        Ok(PublicProfile {
+            status: self.get_online_status(user.id.clone()),
+            activity: self.get_activity(user.id.clone()),
+            id: user.id,
+            display_name: user.display_name,
+            member_since: Some(user.created_at),
+            mutual_friends: mutual_friends as u32,
+            avatar_url: user.avatar_url,
+        })
+    }
+
+    /// Sends a user the profile they asked for
+    fn send_profile(&self, user_id: UserId, req: GetProfile) -> Result<(), ServerError> {
+        let profile = self.get_profile(user_id.clone(), req)?.to_json()?;
+        let socket_id = self.get_socket_id(user_id)?;
+        if let Some(socket) = self.get_socket(socket_id) {
+            socket.emit(EVENT_RECEIVE_PROFILE, &profile)?;
+        }
+        Ok(())
+    }
+
+    /// Sends the results of a user search
+    fn send_user_search_results(&self, user_id: UserId, req: SearchUsers) -> Result<(), ServerError> {
+        let results = vec_to_json(self.search_users(user_id.clone(), req))?;
+        let socket_id = self.get_socket_id(user_id)?;
+        if let Some(socket) = self.get_socket(socket_id) {
+            socket.emit(EVENT_RECEIVE_USER_SEARCH_RESULTS, &results)?;
+        }
+        Ok(())
+    }
+
+    /// Gets a page of a user's friends list
+    fn get_friends_page(&self, user_id: UserId, offset: u32, limit: u32) -> FriendsPage {
+        paginate_friends(self.get_friends_list(user_id), offset, limit.min(MAX_FRIENDS_PAGE_LIMIT))
+    }
+
+    /// Sends a page of a user's friends list to them
+    fn send_friends_page(&self, user_id: UserId, req: GetFriends) -> Result<(), ServerError> {
+        let page = self.get_friends_page(user_id.clone(), req.offset, req.limit).to_json()?;
+        let page = self.compress_for(&user_id, page);
+        let socket_id = self.get_socket_id(user_id)?;
+        if let Some(socket) = self.get_socket(socket_id) {
+            socket.emit(EVENT_RECEIVE_FRIENDS_PAGE, &page)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a page of a room's chat history to a user in that room
+    fn send_chat_history(&self, user_id: UserId, req: GetChatHistory) -> Result<(), ServerError> {
+        let room_id = match &req.channel {
+            ChatChannel::Room(room_id) => room_id.clone(),
+            ChatChannel::PrivateMessage(_) => return Err(crate::i18n::chat_history_rooms_only(req.language)),
+        };
+
+        if !self.user_in_room(user_id.clone(), room_id.clone()) {
+            Err(format!("{} is not in room {}", user_id, room_id))?;
+        }
+
+        let limit = req.limit.min(MAX_CHAT_HISTORY_LIMIT) as usize;
+        let history = vec_to_json(self.data_service.chat_history_get(req.channel, req.before, limit))?;
+        let socket_id = self.get_socket_id(user_id)?;
+        if let Some(socket) = self.get_socket(socket_id) {
+            socket.emit(EVENT_RECEIVE_CHAT_HISTORY, &history)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a user who's in a room they're in, leaving out anyone on either side of a block with them
+    fn send_room_members(&self, user_id: UserId, req: GetRoomMembers) -> Result<(), ServerError> {
+        if !self.user_in_room(user_id.clone(), req.room_id.clone()) {
+            Err(format!("{} is not in room {}", user_id, req.room_id))?;
+        }
+
+        let member_ids: Vec<UserId> = self.room_members.get(&req.room_id)
+            .into_iter()
+            .flatten()
+            .filter(|member_id| !self.online_users_blocked(&user_id, member_id))
+            .cloned()
+            .collect();
+        let mut members = self.get_members(&member_ids);
+        members.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+
+        let room_members = RoomMembers {
+            room_id: req.room_id,
+            members,
+        }.to_json()?;
+        let socket_id = self.get_socket_id(user_id)?;
+        if let Some(socket) = self.get_socket(socket_id) {
+            socket.emit(EVENT_RECEIVE_ROOM_MEMBERS, &room_members)?;
+        }
+        Ok(())
+    }
+
+    pub fn send_friend_request(&mut self, sender_id: UserId, req: FriendRequest) -> Result<(), ServerError> {
+        if sender_id == req.friend_id {
+            info!("{} tried to send a friend request to themselves", sender_id);
+            return Err(crate::i18n::cannot_friend_self(req.language));
+        }
+
+        if self.config.require_verified_email && !self.data_service.users_is_email_verified(sender_id.clone()) {
+            info!("{} tried to send a friend request before verifying their email", sender_id);
+            return Err(crate::i18n::email_not_verified(req.language));
+        }
+
+        if self.at_friend_limit(sender_id.clone()) {
+            info!("{} tried to send a friend request but already has the most friends allowed", sender_id);
+            return Err(crate::i18n::friend_limit_reached(req.language, self.config.max_friends));
+        }
+
+        if self.are_friends(sender_id.clone(), req.friend_id.clone()) {
+            warn!("{} is already friends with {} but is trying to send a friend request", sender_id, req.friend_id);
+            return Err(crate::i18n::already_friends(req.language));
+        }
+
+        if self.data_service.user_is_blocked(sender_id.clone(), req.friend_id.clone()) {
+            warn!("{} has blocked {} but is trying to send a friend request", sender_id, req.friend_id);
+            return Err(crate::i18n::user_is_blocked(req.language));
+        }
+
+        if self.data_service.user_is_blocked(req.friend_id.clone(), sender_id.clone()) {
+            warn!("{} has blocked {} but {} is trying to send a friend request", req.friend_id, sender_id, sender_id);
+            return Err(crate::i18n::user_is_blocked(req.language));
+        }
+
+        let already_received = self.data_service.user_get_pending_friend_requests_for_user(sender_id.clone())
+            .iter()
+            .any(|friend| friend.id == req.friend_id);
+        if already_received {
+            info!("{} already has a friend request from {}", sender_id, req.friend_id);
+            return Err(crate::i18n::friend_request_already_received(req.language));
+        }
+
+        if self.data_service.user_exists(req.friend_id.clone()) {
+            self.data_service.friend_requests_insert(sender_id.clone(), req.friend_id.clone());
+
+            // tell the target user about the request
+            self.send_new_friend_request(sender_id.clone(), req.friend_id.clone());
+
+            // refresh the friends list for the sender
+            self.send_friend_list(sender_id);
+
+            // refresh the friends list for the target user
+            self.send_friend_list(req.friend_id);
+        } else {
+            error!("User does not exist: {}", req.friend_id);
+            return Err(format!("{} does not exist", req.friend_id))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn accept_friend_request(&mut self, user_id: UserId, req: AcceptFriendRequest) -> Result<(), ServerError> {
+        if self.are_friends(user_id.clone(), req.friend_id.clone()) {
+            info!("{} is already friends with {}", user_id, req.friend_id);
+            return Err(crate::i18n::already_friends(req.language));
+        }
+
+        // otherwise anyone could make themselves a friend of anyone else
+        let has_request = self.data_service.user_get_pending_friend_requests_for_user(user_id.clone())
+            .iter()
+            .any(|friend| friend.id == req.friend_id);
+        if !has_request {
+            warn!("{} is trying to accept a friend request {} never sent", user_id, req.friend_id);
+            return Err(crate::i18n::no_friend_request(req.language));
+        }
+
+        if self.data_service.user_is_blocked(user_id.clone(), req.friend_id.clone()) {
+            warn!("{} has blocked {} but is trying to accept a friend request", user_id, req.friend_id);
+            return Err(crate::i18n::user_is_blocked(req.language));
+        }
+
+        if self.data_service.user_is_blocked(req.friend_id.clone(), user_id.clone()) {
+            warn!("{} has blocked {} but {} is trying to accept a friend request", req.friend_id, user_id, user_id);
+            return Err(crate::i18n::user_is_blocked(req.language));
+        }
+
+        // both lists grow, so both have to have room
+        if self.at_friend_limit(user_id.clone()) || self.at_friend_limit(req.friend_id.clone()) {
+            info!("{} tried to accept a friend request from {} but one of them has the most friends allowed", user_id, req.friend_id);
+            return Err(crate::i18n::friend_limit_reached(req.language, self.config.max_friends));
+        }
+
+        // both users end up in each other's lists so they both see each other's presence
+        self.data_service.friends_add(user_id.clone(), req.friend_id.clone());
+        self.data_service.friend_requests_remove(user_id.clone(), req.friend_id.clone());
+
+        let socket = self.get_socket_id(user_id.clone())
+            .ok()
+            .and_then(|socket_id| self.get_socket(socket_id));
+        if let Some(socket) = socket {
+            let user = self.data_service.users_get(req.friend_id.clone());
+            if let Some(user) = user {
+                let friend = Friend {
+                    id: user.id.clone(),
+                    display_name: user.display_name.clone(),
+                    status: self.get_online_status(user.id.clone()),
+                    activity: self.get_activity(user.id.clone()),
+                    last_seen: None,
+                    avatar_url: user.avatar_url.clone(),
+                };
+                let friend_request_accepted = FriendRequestAccepted { friend };
+                let serialized_friend_request_accepted = friend_request_accepted.to_json()?;
+                socket.emit(EVENT_RECEIVE_FRIEND_REQUEST_ACCEPTED, &serialized_friend_request_accepted)?;
+
+                // refresh the friends list for both users
+                self.send_friend_list(user_id);
+                self.send_friend_list(req.friend_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rejects a friend request
+    fn reject_friend_request(&mut self, user_id: UserId, req: RejectFriendRequest) -> Result<(), ServerError> {
+        self.data_service.friend_requests_remove(user_id.clone(), req.friend_id.clone());
+
+        // refresh the friends list for both users
+        self.send_friend_list(req.friend_id);
+        self.send_friend_list(user_id);
+        Ok(())
+    }
+
+    /// Takes back a friend request the user sent, requests they received can only be accepted or rejected
+    fn cancel_friend_request(&mut self, user_id: UserId, req: CancelFriendRequest) -> Result<(), ServerError> {
+        let sent = self.data_service.user_get_friend_request_invites_sent_for_user(user_id.clone())
+            .iter()
+            .any(|friend| friend.id == req.friend_id);
+        if !sent {
+            Err(format!("{} has not sent a friend request to {}", user_id, req.friend_id))?;
+        }
+
+        self.data_service.friend_requests_remove(user_id.clone(), req.friend_id.clone());
+
+        // refresh the friends list for both users
+        self.send_friend_list(req.friend_id);
+        self.send_friend_list(user_id);
+        Ok(())
+    }
+
+    /// Removes a friend
+    fn remove_friend(&mut self, user_id: UserId, req: RemoveFriendRequest) -> Result<(), ServerError> {
+        info!("Removing friend: {:?}", req);
+        self.data_service.friends_remove(user_id.clone(), req.friend_id.clone());
+
+        // We need to refresh both users friends list
+        self.send_friend_list(user_id);
+        self.send_friend_list(req.friend_id);
+        Ok(())
+    }
+
+    /// Blocks a user
+    fn block_user(&mut self, user_id: UserId, req: BlockUserRequest) -> Result<(), ServerError> {
+        self.data_service.friends_remove(user_id.clone(), req.user_id.clone());
+        self.data_service.user_blocks_insert(user_id.clone(), req.user_id.clone());
+        if let Some(user) = self.user_sockets.get_mut(&user_id) {
+            user.blocked.insert(req.user_id.clone());
+        }
+
+        // We need to refresh both users friends list
+        self.send_friend_list(user_id.clone());
+        self.send_friend_list(req.user_id);
+        self.send_blocked_list(user_id);
+        Ok(())
     }
 
     /// Unblocks a user
     fn unblock_user(&mut self, user_id: UserId, req: UnblockUserRequest) -> Result<(), ServerError> {
         self.data_service.user_blocks_remove(user_id.clone(), req.user_id.clone());
+        if let Some(user) = self.user_sockets.get_mut(&user_id) {
+            user.blocked.remove(&req.user_id);
+        }
         
         // We need to refresh both users friends list
         self.send_friend_list(user_id.clone());
         self.send_friend_list(req.user_id);
+        self.send_blocked_list(user_id);
         Ok(())
     }
 
-    /// Whether two users are friends
-    fn are_friends(&self, user_id: UserId, friend_id: UserId) -> bool {
-        self.data_service.friends_get(user_id).iter().any(|f| f.id == friend_id)
-    }
+    /// Whether two users are friends
+    fn are_friends(&self, user_id: UserId, friend_id: UserId) -> bool {
+        self.data_service.are_friends(user_id, friend_id)
+    }
+
+    /// Whether a user has as many friends as `WarhorseServerConfig::max_friends` allows
+    fn at_friend_limit(&self, user_id: UserId) -> bool {
+        self.data_service.friends_count(user_id) >= self.config.max_friends
+    }
+
+    /// Whether a user is in a specific room or not
+    fn user_in_room(&self, user_id: UserId, room_id: RoomId) -> bool {
+        self.room_members
+            .get(&room_id)
+            .is_some_and(|members| members.contains(&user_id))
+    }
+
+    fn send_friend_list(&self, user_id: UserId) {
+        let friends_list = self.get_friends_list(user_id.clone());
+        if friends_list.len() > FRIENDS_LIST_PUSH_LIMIT {
+            let page = paginate_friends(friends_list, 0, MAX_FRIENDS_PAGE_LIMIT);
+            match page.to_json() {
+                Ok(json) => {
+                    let json = self.compress_for(&user_id, json);
+                    self.emit_to_user(user_id, EVENT_RECEIVE_FRIENDS_PAGE, &json)
+                },
+                Err(e) => error!(?e, "Failed to serialize friends page"),
+            }
+            return;
+        }
+
+        match vec_to_json(friends_list) {
+            Ok(friends_list) => {
+                let friends_list = self.compress_for(&user_id, friends_list);
+                match self.get_socket_id(user_id) {
+                    Ok(socket_id) => {
+                        if let Some(socket) = self.get_socket(socket_id) {
+                            let _= socket.emit(EVENT_RECEIVE_FRIENDS, &friends_list);
+                        }
+                    },
+                    Err(e) => {
+                        info!(?e, "Failed to get socket ID");
+                    }
+                }
+            },
+            Err(e) => {
+                error!(?e, "Failed to serialize friends list");
+            }
+        }
+    }
+
+    /// Whether a room exists or not
+    /// Gets the user ID of the logged in user associated with a socket
+    fn get_logged_in_user_id(&self, socket_id: SocketId) -> Option<UserId> {
+        self.user_sockets.iter().find_map(|(user_id, user)| {
+            if user.socket_id == socket_id {
+                Some(user_id.clone())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Gets the friends list of a user and their online status
+    fn get_friends_list(&self, user_id: UserId) -> Vec<Friend> {
+        let mut friends_list = self.data_service.friends_get(user_id);
+        for friend in friends_list.iter_mut() {
+            match friend.status {
+                // established friends, whatever the database defaulted them to, get their live status
+                FriendStatus::Online | FriendStatus::Away | FriendStatus::Busy | FriendStatus::Offline => {
+                    friend.status = self.get_online_status(friend.id.clone());
+                    friend.activity = self.get_activity(friend.id.clone());
+                    if friend.status != FriendStatus::Offline {
+                        friend.last_seen = None;
+                    }
+                }
+                // requests and blocks keep their category and don't get to see presence
+                FriendStatus::FriendRequestSent | FriendStatus::FriendRequestReceived | FriendStatus::Blocked => {
+                    friend.activity = None;
+                    friend.last_seen = None;
+                }
+            }
+        }
+        friends_list
+    }
+}
+
+/// Sorts friends by status and then display name, ignoring case, so pages don't shift around between requests.
+fn paginate_friends(mut friends: Vec<Friend>, offset: u32, limit: u32) -> FriendsPage {
+    friends.sort_by_cached_key(|friend| (friend.status, friend.display_name.to_lowercase(), friend.id.clone()));
+    let total = friends.len() as u32;
+    let friends = friends.into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect();
+    FriendsPage { offset, total, friends }
+}
+
+fn listen_for_chat_messages<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_CHAT_MESSAGE, move |socket: SocketRef, Data::<Value>(data), ack: AckSender| {
+        async move {
+            match SendChatMessage::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        let result = server.lock().await.send_chat_message(logged_in_user_id, data);
+                        if let Err(e) = &result {
+                            info!(?e, "Failed to send chat message");
+                        }
+
+                        // only answered if the client asked for it
+                        let chat_message_ack = ChatMessageAck { error: result.err().map(RequestError::from) };
+                        match chat_message_ack.to_json() {
+                            Ok(json) => {
+                                if let Err(e) = ack.send(&json) {
+                                    error!(?e, "Failed to acknowledge chat message");
+                                }
+                            },
+                            Err(e) => {
+                                error!(?e, "Failed to serialize chat message acknowledgement");
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse chat message");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_typing<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_TYPING, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match TypingIndicator::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.send_typing(logged_in_user_id, data) {
+                            info!(?e, "Failed to send typing indicator");
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse typing indicator");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_chat_history_requests<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_CHAT_HISTORY, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match GetChatHistory::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.send_chat_history(logged_in_user_id, data) {
+                            info!(?e, "Failed to send chat history");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse chat history request");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_friends_page_requests<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_FRIENDS_PAGE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match GetFriends::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.send_friends_page(logged_in_user_id, data) {
+                            info!(?e, "Failed to send friends page");
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse friends page request");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_room_join<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ROOM_JOIN, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match JoinRoom::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.join_room(logged_in_user_id, data) {
+                            info!(?e, "Failed to join room");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse room join request");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_room_leave<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ROOM_LEAVE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match LeaveRoom::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.leave_room(logged_in_user_id, data) {
+                            info!(?e, "Failed to leave room");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse room leave request");
+                }
+            };
+        }.instrument(span)
+    });
+}
 
-    /// Whether a user is in a specific room or not
-    fn user_in_room(&self, user_id: UserId, room_id: RoomId) -> bool {
-        let room_id_clone = room_id.clone();
-        if self.room_exists(room_id) {
-            match self.get_socket_id(user_id) {
-                Ok(id) => {
-                    match self.get_socket(id) {
-                        Some(socket) => {
-                            match socket.rooms() {
-                                Ok(rooms) => {
-                                    let room_id = room_id_clone.as_str();
-                                    rooms.iter().any(|r| r == room_id)
+fn listen_for_room_members<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ROOM_MEMBERS, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match GetRoomMembers::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.send_room_members(logged_in_user_id, data) {
+                            info!(?e, "Failed to send room members");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
                                 }
-                                Err(_) => false,
                             }
-                        },
-                        None => false,
+                        }
                     }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse room members request");
                 }
-                Err(_) => false,
-            }
-        } else {
-            false
-        }
-    }
+            };
+        }.instrument(span)
+    });
+}
 
-    fn send_friend_list(&self, user_id: UserId) {
-        match vec_to_json(self.get_friends_list(user_id.clone())) {
-            Ok(friends_list) => {
-                match self.get_socket_id(user_id) {
-                    Ok(socket_id) => {
-                        if let Some(socket) = self.get_socket(socket_id) {
-                            let _= socket.emit(EVENT_RECEIVE_FRIENDS, &friends_list);
+fn listen_for_presence<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_PRESENCE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match SetPresence::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.set_presence(logged_in_user_id, data) {
+                            info!(?e, "Failed to set presence");
                         }
-                    },
-                    Err(e) => {
-                        info!(?e, "Failed to get socket ID");
                     }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse presence");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_activity_ping<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ACTIVITY_PING, move |socket: SocketRef| {
+        async move {
+            let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+            if let Some(logged_in_user_id) = logged_in_user_id {
+                if let Err(e) = server.lock().await.note_activity(logged_in_user_id) {
+                    info!(?e, "Failed to note activity");
                 }
-            },
-            Err(e) => {
-                error!(?e, "Failed to serialize friends list");
             }
-        }
-    }
+        }.instrument(span)
+    });
+}
 
-    /// Whether a room exists or not
-    fn room_exists(&self, room_id: RoomId) -> bool {
-        let room_id = room_id.as_str();
-        self.io.rooms().iter().flatten().any(|r| r == room_id)
-    }
+fn listen_for_activity<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ACTIVITY, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match SetActivity::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.set_activity(logged_in_user_id, data) {
+                            info!(?e, "Failed to set activity");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse activity");
+                }
+            };
+        }.instrument(span)
+    });
+}
 
-    /// Gets the user ID of the logged in user associated with a socket
-    fn get_logged_in_user_id(&self, socket_id: SocketId) -> Option<UserId> {
-        self.user_sockets.iter().find_map(|(user_id, id)| {
-            if id == &socket_id {
-                Some(user_id.clone())
-            } else {
-                None
-            }
-        })
-    }
+fn listen_for_set_avatar<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_SET_AVATAR, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match SetAvatar::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.set_avatar(logged_in_user_id, data) {
+                            info!(?e, "Failed to set avatar");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse avatar");
+                }
+            };
+        }.instrument(span)
+    });
+}
 
-    /// Gets the friends list of a user and their online status
-    fn get_friends_list(&self, user_id: UserId) -> Vec<Friend> {
-        let mut friends_list = self.data_service.friends_get(user_id);
-        for friend in friends_list.iter_mut() {
-            // get their online status if they are a friend who:
-            // - is not a pending friend request
-            // - is not a friend request invite sent
-            // - is not blocked
-            if friend.status == FriendStatus::Offline {
-                friend.status = self.get_online_status(friend.id.clone());
-            }
-        }
-        friends_list
-    }
+fn listen_for_party_invite<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_PARTY_INVITE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match PartyInvite::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.invite_to_party(logged_in_user_id, data) {
+                            info!(?e, "Failed to invite to party");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse party invite");
+                }
+            };
+        }.instrument(span)
+    });
 }
 
-fn listen_for_chat_messages<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>) {
-    socket_ref.on(EVENT_SEND_CHAT_MESSAGE, move |socket: SocketRef, Data::<Value>(data)| {
+fn listen_for_party_join<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_PARTY_JOIN, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
-            match SendChatMessage::from_json(data) {
+            match PartyJoin::from_json(data) {
                 Ok(data) => {
                     let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
                     if let Some(logged_in_user_id) = logged_in_user_id {
-                        if let Err(e) = server.lock().await.send_chat_message(logged_in_user_id, data) {
-                            info!(ns = socket.ns(), ?socket.id, ?e, "Failed to send chat message");
+                        if let Err(e) = server.lock().await.join_party(logged_in_user_id, data) {
+                            info!(?e, "Failed to join party");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse chat message");
+                    error!(?e, "Failed to parse party join");
                 }
             };
-        }
+        }.instrument(span)
+    });
+}
+
+fn listen_for_party_leave<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_PARTY_LEAVE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match PartyLeave::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.leave_party(logged_in_user_id, data) {
+                            info!(?e, "Failed to leave party");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse party leave");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_user_search<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_USER_SEARCH, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match SearchUsers::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.send_user_search_results(logged_in_user_id, data) {
+                            info!(?e, "Failed to send user search results");
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse user search");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_get_profile<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_GET_PROFILE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match GetProfile::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        if let Err(e) = server.lock().await.send_profile(logged_in_user_id, data) {
+                            info!(?e, "Failed to send profile");
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse profile request");
+                }
+            };
+        }.instrument(span)
     });
 }
 
 fn listen_for_user_login<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_USER_LOGIN, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
             match UserLogin::from_json(data) {
                 Ok(data) => {
-                    match server.lock().await.login_user(data, socket.id).await {
-                        Ok(_) => {
-                            info!(ns = socket.ns(), ?socket.id, "User logged in");
+                    let result = server.lock().await.login_user(data, socket.id).await;
+                    match result {
+                        Ok(user_id) => {
+                            Span::current().record("user_id", user_id.as_str());
+                            info!("User logged in");
+                            handle_user_disconnect(socket.clone(), user_id, server.clone(), Span::current());
                         },
                         Err(e) => {
-                            info!(ns = socket.ns(), ?socket.id, ?e, "Failed to log in user");
-                            match RequestError(e.0).to_json() {
+                            info!(?e, "Failed to log in user");
+                            match RequestError::from(e).to_json() {
                                 Ok(json) => {
                                     match socket.emit(EVENT_RECEIVE_ERROR, &json) {
                                         Ok(_) => {
-                                            info!(ns = socket.ns(), ?socket.id, "Sent error response");
+                                            info!("Sent error response");
                                         },
                                         Err(e) => {
-                                            error!(ns = socket.ns(), ?socket.id, ?e, "Failed to send error response");
+                                            error!(?e, "Failed to send error response");
                                         }
                                     }
                                 },
                                 Err(e) => {
-                                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to serialize error");
+                                    error!(?e, "Failed to serialize error");
                                 }
                             }
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse login data");
+                    error!(?e, "Failed to parse login data");
                 }
             }
-        }
+        }.instrument(span)
+    });
+}
+
+fn listen_for_user_logout<T: Database + Send + Sync + 'static>(
+    socket_ref: &SocketRef,
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
+) {
+    socket_ref.on(EVENT_SEND_USER_LOGOUT, move |socket: SocketRef| {
+        async move {
+            let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+            if let Some(logged_in_user_id) = logged_in_user_id {
+                server.lock().await.handle_disconnect(&logged_in_user_id).await;
+                if let Err(e) = socket.leave_all() {
+                    error!(?e, "Failed to leave rooms");
+                }
+                // the socket stays connected, only its connection slot needs freeing when it goes
+                handle_socket_disconnect(&socket, server.clone(), Span::current());
+                info!("User logged out");
+                let _ = socket.emit(EVENT_RECEIVE_USER_LOGOUT, &serde_json::json!({}));
+            }
+        }.instrument(span)
     });
 }
 
 fn listen_for_user_registration<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_USER_REGISTER, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
             match UserRegistration::from_json(data) {
                 Ok(data) => {
-                    match server.lock().await.register_user(data, Some(socket.id)).await {
-                        Ok(_) => {
-                            info!(ns = socket.ns(), ?socket.id, "User registered");
+                    let result = server.lock().await.register_user(data, Some(socket.id)).await;
+                    match result {
+                        Ok(user_id) => {
+                            Span::current().record("user_id", user_id.as_str());
+                            info!("User registered");
+                            handle_user_disconnect(socket.clone(), user_id, server.clone(), Span::current());
                         },
                         Err(e) => {
-                            info!(ns = socket.ns(), ?socket.id, ?e, "Failed to register user");
-                            match RequestError(e.0).to_json() {
+                            info!(?e, "Failed to register user");
+                            match RequestError::from(e).to_json() {
                                 Ok(json) => {
                                     match socket.emit(EVENT_RECEIVE_ERROR, &json) {
                                         Ok(_) => {
-                                            info!(ns = socket.ns(), ?socket.id, "Sent error response");
+                                            info!("Sent error response");
                                         },
                                         Err(e) => {
-                                            error!(ns = socket.ns(), ?socket.id, ?e, "Failed to send error response");
+                                            error!(?e, "Failed to send error response");
                                         }
                                     }
                                 },
                                 Err(e) => {
-                                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to serialize error");
+                                    error!(?e, "Failed to serialize error");
                                 }
                             }
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse registration data");
+                    error!(?e, "Failed to parse registration data");
                 }
             }
-        }
+        }.instrument(span)
+    });
+}
+
+fn listen_for_account_delete<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ACCOUNT_DELETE, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match DeleteAccount::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        let result = server.lock().await.delete_account(logged_in_user_id, data).await;
+                        match result {
+                            Ok(_) => {
+                                info!("Account deleted");
+                                if let Err(e) = socket.disconnect() {
+                                    error!(?e, "Failed to disconnect deleted account");
+                                }
+                            },
+                            Err(e) => {
+                                info!(?e, "Failed to delete account");
+                                match RequestError::from(e).to_json() {
+                                    Ok(json) => {
+                                        let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                    },
+                                    Err(e) => {
+                                        error!(?e, "Failed to serialize error");
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse account delete request");
+                }
+            };
+        }.instrument(span)
     });
 }
 
 fn listen_for_friend_requests<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     info!("Setting up friend request listener");
     socket_ref.on(EVENT_SEND_FRIEND_REQUEST, move |socket: SocketRef, Data::<Value>(data)| {
@@ -546,34 +2036,29 @@ fn listen_for_friend_requests<T: Database + Send + Sync + 'static>(
                     match server.get_logged_in_user_id(socket.id) {
                         Some(sender_id) => {
                             info!("Found sender ID: {}", sender_id);
-
-                            if sender_id == data.friend_id {
-                                info!(ns = socket.ns(), ?socket.id, "User tried to send a friend request to themselves, it was ignored");
-                                return;
-                            }
-
                             if let Err(e) = server.send_friend_request(sender_id, data) {
-                                info!(ns = socket.ns(), ?socket.id, ?e, "Failed to send friend request");
+                                info!(?e, "Failed to send friend request");
                             } else {
                                 info!("Friend request processed successfully");
                             }
                         },
                         None => {
-                            info!(ns = socket.ns(), ?socket.id, "Failed to get user ID - user might not be logged in");
+                            info!("Failed to get user ID - user might not be logged in");
                         }
                     }
                 }
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse friend request data");
+                    error!(?e, "Failed to parse friend request data");
                 }
             }
-        }
+        }.instrument(span)
     });
 }
 
 fn listen_for_accept_friend_requests<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_FRIEND_REQUEST_ACCEPT, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
@@ -583,25 +2068,26 @@ fn listen_for_accept_friend_requests<T: Database + Send + Sync + 'static>(
                     match logged_in_user_id {
                         Some(user_id) => {
                             if let Err(e) = server.lock().await.accept_friend_request(user_id, data) {
-                                info!(ns = socket.ns(), ?socket.id, ?e, "Failed to accept friend request");
+                                info!(?e, "Failed to accept friend request");
                             }
                         },
                         None => {
-                            info!(ns = socket.ns(), ?socket.id, "Failed to get user ID - user might not be logged in");
+                            info!("Failed to get user ID - user might not be logged in");
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse accept friend request");
+                    error!(?e, "Failed to parse accept friend request");
                 }
             }
-        }
+        }.instrument(span)
     });
 }
 
 fn listen_for_reject_friend_requests<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_FRIEND_REQUEST_REJECT, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
@@ -611,25 +2097,55 @@ fn listen_for_reject_friend_requests<T: Database + Send + Sync + 'static>(
                     match logged_in_user_id {
                         Some(user_id) => {
                             if let Err(e) = server.lock().await.reject_friend_request(user_id, data) {
-                                info!(ns = socket.ns(), ?socket.id, ?e, "Failed to reject friend request");
+                                info!(?e, "Failed to reject friend request");
+                            }
+                        },
+                        None => {
+                            info!("Failed to get user ID - user might not be logged in");
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse reject friend request");
+                }
+            }
+        }.instrument(span)
+    });
+}
+
+fn listen_for_cancel_friend_requests<T: Database + Send + Sync + 'static>(
+    socket_ref: &SocketRef,
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
+) {
+    socket_ref.on(EVENT_SEND_FRIEND_REQUEST_CANCEL, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match CancelFriendRequest::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    match logged_in_user_id {
+                        Some(user_id) => {
+                            if let Err(e) = server.lock().await.cancel_friend_request(user_id, data) {
+                                info!(?e, "Failed to cancel friend request");
                             }
                         },
                         None => {
-                            info!(ns = socket.ns(), ?socket.id, "Failed to get user ID - user might not be logged in");
+                            info!("Failed to get user ID - user might not be logged in");
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse reject friend request");
+                    error!(?e, "Failed to parse cancel friend request");
                 }
             }
-        }
+        }.instrument(span)
     });
 }
 
 fn listen_for_remove_friend<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_FRIEND_REMOVE, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
@@ -639,25 +2155,26 @@ fn listen_for_remove_friend<T: Database + Send + Sync + 'static>(
                     match logged_in_user_id {
                         Some(user_id) => {
                             if let Err(e) = server.lock().await.remove_friend(user_id, data) {
-                                info!(ns = socket.ns(), ?socket.id, ?e, "Failed to remove friend");
+                                info!(?e, "Failed to remove friend");
                             }
                         },
                         None => {
-                            info!(ns = socket.ns(), ?socket.id, "Failed to get user ID - user might not be logged in");
+                            info!("Failed to get user ID - user might not be logged in");
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse remove friend request");
+                    error!(?e, "Failed to parse remove friend request");
                 }
             }
-        }
+        }.instrument(span)
     });
 }
 
 fn listen_for_block_user_requests<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_USER_BLOCK, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
@@ -667,25 +2184,26 @@ fn listen_for_block_user_requests<T: Database + Send + Sync + 'static>(
                     match logged_in_user_id {
                         Some(user_id) => {
                             if let Err(e) = server.lock().await.block_user(user_id, data) {
-                                info!(ns = socket.ns(), ?socket.id, ?e, "Failed to block user");
+                                info!(?e, "Failed to block user");
                             }
                         },
                         None => {
-                            info!(ns = socket.ns(), ?socket.id, "Failed to get user ID - user might not be logged in");
+                            info!("Failed to get user ID - user might not be logged in");
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse block user request");
+                    error!(?e, "Failed to parse block user request");
                 }
             }
-        }
+        }.instrument(span)
     });
 }
 
 fn listen_for_unblock_user_requests<T: Database + Send + Sync + 'static>(
     socket_ref: &SocketRef,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     socket_ref.on(EVENT_SEND_USER_UNBLOCK, move |socket: SocketRef, Data::<Value>(data)| {
         async move {
@@ -695,57 +2213,1228 @@ fn listen_for_unblock_user_requests<T: Database + Send + Sync + 'static>(
                     match logged_in_user_id {
                         Some(user_id) => {
                             if let Err(e) = server.lock().await.unblock_user(user_id, data) {
-                                info!(ns = socket.ns(), ?socket.id, ?e, "Failed to unblock user");
+                                info!(?e, "Failed to unblock user");
                             }
                         },
                         None => {
-                            info!(ns = socket.ns(), ?socket.id, "Failed to get user ID - user might not be logged in");
+                            info!("Failed to get user ID - user might not be logged in");
                         }
                     }
                 },
                 Err(e) => {
-                    error!(ns = socket.ns(), ?socket.id, ?e, "Failed to parse unblock user request");
+                    error!(?e, "Failed to parse unblock user request");
                 }
             }
-        }
+        }.instrument(span)
+    });
+}
+
+fn listen_for_admin_kick<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ADMIN_KICK, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match AdminKick::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        let result = server.lock().await.kick_user(logged_in_user_id, data).await;
+                        if let Err(e) = result {
+                            info!(?e, "Failed to kick user");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse kick request");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+fn listen_for_admin_ban<T: Database + Send + Sync + 'static>(socket_ref: &SocketRef, server: Arc<Mutex<WarhorseServer<T>>>, span: Span) {
+    socket_ref.on(EVENT_SEND_ADMIN_BAN, move |socket: SocketRef, Data::<Value>(data)| {
+        async move {
+            match AdminBan::from_json(data) {
+                Ok(data) => {
+                    let logged_in_user_id = server.lock().await.get_logged_in_user_id(socket.id);
+                    if let Some(logged_in_user_id) = logged_in_user_id {
+                        let result = server.lock().await.ban_user(logged_in_user_id, data).await;
+                        if let Err(e) = result {
+                            info!(?e, "Failed to ban user");
+                            match RequestError::from(e).to_json() {
+                                Ok(json) => {
+                                    let _ = socket.emit(EVENT_RECEIVE_ERROR, &json);
+                                },
+                                Err(e) => {
+                                    error!(?e, "Failed to serialize error");
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!(?e, "Failed to parse ban request");
+                }
+            };
+        }.instrument(span)
+    });
+}
+
+/// Frees up the socket's connection slot when it disconnects before logging in.
+fn handle_socket_disconnect<T: Database + Send + Sync + 'static>(
+    socket: &SocketRef,
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
+) {
+    let socket_id = socket.id;
+    socket.on_disconnect(move || {
+        let server = server.clone();
+        async move {
+            server.lock().await.close_connection(socket_id);
+        }.instrument(span.clone())
     });
 }
 
+/// Replaces the socket's disconnect handler once a user is logged in on it, so they're cleaned up as well.
 fn handle_user_disconnect<T: Database + Send + Sync + 'static>(
     socket: SocketRef,
     user_id: UserId,
-    server: Arc<Mutex<WarhorseServer<T>>>
+    server: Arc<Mutex<WarhorseServer<T>>>,
+    span: Span
 ) {
     let server_clone = server.clone();
+    let socket_id = socket.id;
     socket.on_disconnect(move || {
         let server = server_clone.clone();
         let user_id = user_id.clone();
         async move {
-            server.lock().await.remove_user(&user_id).await;
-        }
+            let mut server = server.lock().await;
+            server.close_connection(socket_id);
+            server.handle_disconnect(&user_id).await;
+        }.instrument(span.clone())
     });
 }
 
+/// The address a socket connected from, only known when the app is served with its connect info.
+fn socket_ip(socket: &SocketRef) -> Option<IpAddr> {
+    socket.req_parts()
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())
+}
+
+/// The span a connection's handlers run in, `user_id` is recorded on it once they log in.
+fn connection_span(ns: &str, socket_id: SocketId) -> Span {
+    info_span!("connection", ns, socket.id = ?socket_id, user_id = field::Empty)
+}
+
+/// Periodically sends the queued presence refreshes.
+pub fn spawn_presence_refresh_task<T: Database + Send + Sync + 'static>(
+    server: Arc<Mutex<WarhorseServer<T>>>
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRESENCE_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            server.lock().await.flush_presence_refreshes();
+        }
+    })
+}
+
+/// Periodically shows users who've gone idle as away.
+pub fn spawn_idle_check_task<T: Database + Send + Sync + 'static>(
+    server: Arc<Mutex<WarhorseServer<T>>>
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            server.lock().await.mark_idle_users_away(Instant::now());
+        }
+    })
+}
+
+pub fn spawn_heartbeat_task<T: Database + Send + Sync + 'static>(
+    server: Arc<Mutex<WarhorseServer<T>>>
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let heartbeat_interval = server.lock().await.config().heartbeat_interval;
+        let mut interval = tokio::time::interval(heartbeat_interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = server.lock().await.send_heartbeat() {
+                error!(?e, "Failed to send heartbeat");
+            }
+        }
+    })
+}
+
 pub async fn handle_connection<T: Database + Send + Sync + 'static>(
     socket: SocketRef,
     _data: Value,
     server: Arc<Mutex<WarhorseServer<T>>>
 ) {
 
-    info!(ns = socket.ns(), ?socket.id, "Socket.IO connected");
+    // every handler runs in this span, so their logs say which socket and, once logged in, which user they're for
+    let span = connection_span(socket.ns(), socket.id);
+    let ip = socket_ip(&socket);
+    if let Err(e) = server.lock().await.open_connection(socket.id, ip) {
+        info!(parent: &span, ?ip, ?e, "Rejecting connection");
+        socket.disconnect().ok();
+        return;
+    }
+    info!(parent: &span, ?ip, "Socket.IO connected");
+    handle_socket_disconnect(&socket, server.clone(), span.clone());
+
+    let hello = Hello {
+        message: crate::i18n::hello_message(Language::English),
+        protocol_version: Some(PROTOCOL_VERSION),
+        compression: true,
+    };
+    match hello.to_json() {
+        Ok(json) => {
+            socket.emit(EVENT_RECEIVE_HELLO, &json).ok();
+        },
+        Err(e) => {
+            error!(parent: &span, ?e, "Failed to serialize hello");
+        }
+    }
+
+    listen_for_user_login(&socket, server.clone(), span.clone());
+    listen_for_user_registration(&socket, server.clone(), span.clone());
+    listen_for_user_logout(&socket, server.clone(), span.clone());
+    listen_for_account_delete(&socket, server.clone(), span.clone());
+    listen_for_chat_messages(&socket, server.clone(), span.clone());
+    listen_for_typing(&socket, server.clone(), span.clone());
+    listen_for_chat_history_requests(&socket, server.clone(), span.clone());
+    listen_for_friends_page_requests(&socket, server.clone(), span.clone());
+    listen_for_room_join(&socket, server.clone(), span.clone());
+    listen_for_room_leave(&socket, server.clone(), span.clone());
+    listen_for_room_members(&socket, server.clone(), span.clone());
+    listen_for_user_search(&socket, server.clone(), span.clone());
+    listen_for_get_profile(&socket, server.clone(), span.clone());
+    listen_for_presence(&socket, server.clone(), span.clone());
+    listen_for_activity(&socket, server.clone(), span.clone());
+    listen_for_activity_ping(&socket, server.clone(), span.clone());
+    listen_for_set_avatar(&socket, server.clone(), span.clone());
+    listen_for_party_invite(&socket, server.clone(), span.clone());
+    listen_for_party_join(&socket, server.clone(), span.clone());
+    listen_for_party_leave(&socket, server.clone(), span.clone());
+    listen_for_friend_requests(&socket, server.clone(), span.clone());
+    listen_for_accept_friend_requests(&socket, server.clone(), span.clone());
+    listen_for_reject_friend_requests(&socket, server.clone(), span.clone());
+    listen_for_cancel_friend_requests(&socket, server.clone(), span.clone());
+    listen_for_remove_friend(&socket, server.clone(), span.clone());
+    listen_for_block_user_requests(&socket, server.clone(), span.clone());
+    listen_for_unblock_user_requests(&socket, server.clone(), span.clone());
+    listen_for_admin_kick(&socket, server.clone(), span.clone());
+    listen_for_admin_ban(&socket, server.clone(), span.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_ROOM;
+    use crate::database::db_in_memory::InMemoryDatabase;
+    use crate::filter::WordListFilter;
+
+    async fn server_with_user() -> WarhorseServer<InMemoryDatabase> {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::new(io, "", RateLimit::default());
+        server.register_user(
+            UserRegistration {
+                language: Language::English,
+                account_name: "test".to_string(),
+                email: "test@example.com".to_string(),
+                display_name: "Test User".to_string(),
+                password: "password".to_string(),
+            },
+            None
+        ).await.unwrap();
+        server
+    }
+
+    /// A server with `test` and `test2`, who are friends and both offline.
+    async fn server_with_friends() -> (WarhorseServer<InMemoryDatabase>, UserId, UserId) {
+        let mut server = server_with_user().await;
+        let friend_id = server.register_user(
+            UserRegistration {
+                language: Language::English,
+                account_name: "test2".to_string(),
+                email: "test2@example.com".to_string(),
+                display_name: "Test User 2".to_string(),
+                password: "password".to_string(),
+            },
+            None
+        ).await.unwrap();
+        let user_id = server.data_service.users_get_by_account_name("test").unwrap().id;
+        server.send_friend_request(user_id.clone(), FriendRequest { language: Language::English, friend_id: friend_id.clone() }).unwrap();
+        server.accept_friend_request(friend_id.clone(), AcceptFriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+        (server, user_id, friend_id)
+    }
+
+    fn whisper(friend_id: &UserId, message: &str) -> SendChatMessage {
+        SendChatMessage {
+            language: Language::English,
+            message: message.to_string(),
+            channel: ChatChannel::PrivateMessage(friend_id.clone()),
+        }
+    }
+
+    fn login(account_name: &str, password: &str) -> UserLogin {
+        UserLogin {
+            language: Language::English,
+            identity: LoginUserIdentity::AccountName(account_name.to_string()),
+            password: password.to_string(),
+            compression: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registration_errors_are_localized() {
+        let mut server = server_with_user().await;
+        let result = server.register_user(
+            UserRegistration {
+                language: Language::Spanish,
+                account_name: "test3".to_string(),
+                email: "test3@example.com".to_string(),
+                display_name: "Test User 3".to_string(),
+                password: "pass".to_string(),
+            },
+            None
+        ).await;
+
+        assert_eq!(result.unwrap_err().message, crate::i18n::invalid_password(Language::Spanish).message);
+    }
+
+    #[tokio::test]
+    async fn test_friend_request_to_self_is_rejected() {
+        let mut server = server_with_user().await;
+        let user_id = server.data_service.users_get_by_account_name("test").unwrap().id;
+
+        let result = server.send_friend_request(user_id.clone(), FriendRequest { language: Language::English, friend_id: user_id.clone() });
+
+        assert_eq!(result.unwrap_err().message, crate::i18n::cannot_friend_self(Language::English).message);
+        // friends, pending requests and sent invites are all empty
+        assert!(server.data_service.friends_get(user_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_friend_request_is_rejected_when_one_was_already_received() {
+        let mut server = server_with_user().await;
+        let user_id = server.data_service.users_get_by_account_name("test").unwrap().id;
+        let friend_id = server.register_user(
+            UserRegistration {
+                language: Language::English,
+                account_name: "test2".to_string(),
+                email: "test2@example.com".to_string(),
+                display_name: "Test User 2".to_string(),
+                password: "password".to_string(),
+            },
+            None
+        ).await.unwrap();
+
+        server.send_friend_request(user_id.clone(), FriendRequest { language: Language::English, friend_id: friend_id.clone() }).unwrap();
+        let result = server.send_friend_request(friend_id.clone(), FriendRequest { language: Language::English, friend_id: user_id.clone() });
+
+        assert_eq!(result.unwrap_err().message, crate::i18n::friend_request_already_received(Language::English).message);
+        assert!(server.data_service.user_get_pending_friend_requests_for_user(user_id).is_empty());
+        assert_eq!(server.data_service.user_get_pending_friend_requests_for_user(friend_id).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_account() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+
+        let wrong_password = DeleteAccount { language: Language::English, password: "wrong password".to_string() };
+        assert_eq!(
+            server.delete_account(user_id.clone(), wrong_password).await.unwrap_err().message,
+            crate::i18n::incorrect_password(Language::English).message
+        );
+        assert!(server.data_service.user_exists(user_id.clone()));
+
+        let delete = DeleteAccount { language: Language::English, password: "password".to_string() };
+        server.delete_account(user_id.clone(), delete).await.unwrap();
+
+        assert!(!server.data_service.user_exists(user_id.clone()));
+        assert!(!server.user_sockets.contains_key(&user_id));
+        assert!(server.data_service.friends_get(friend_id).is_empty());
+        assert!(server.login_user(login("test", "password"), Sid::new()).await.is_err());
+    }
+
+    // keeps the tokens it's handed instead of mailing them
+    #[derive(Debug, Clone, Default)]
+    struct CapturedTokens(Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl crate::verification::EmailVerificationSender for CapturedTokens {
+        fn send(&self, _user: &UserPartial, token: &str) {
+            self.0.lock().unwrap().push(token.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_must_be_verified_before_sending_friend_requests() {
+        let (_layer, io) = SocketIo::new_layer();
+        let tokens = CapturedTokens::default();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder()
+            .email_verification(tokens.clone())
+            .require_verified_email(true)
+            .build(io);
+        let user_id = register(&mut server, "test").await;
+        let friend_id = register(&mut server, "test2").await;
+        assert!(!server.is_email_verified(user_id.clone()));
+
+        let request = || FriendRequest { language: Language::English, friend_id: friend_id.clone() };
+        assert_eq!(
+            server.send_friend_request(user_id.clone(), request()).unwrap_err().code,
+            ErrorCode::EmailNotVerified
+        );
+
+        assert_eq!(
+            server.verify_email("not a token", Language::English).unwrap_err().code,
+            ErrorCode::InvalidVerificationToken
+        );
+        let token = tokens.0.lock().unwrap()[0].clone();
+        assert_eq!(server.verify_email(&token, Language::English).unwrap(), user_id);
+        assert!(server.is_email_verified(user_id.clone()));
+        assert!(!server.is_email_verified(friend_id.clone()));
+        // tokens only work once
+        assert!(server.verify_email(&token, Language::English).is_err());
+
+        server.send_friend_request(user_id.clone(), request()).unwrap();
+        assert_eq!(server.data_service.user_get_pending_friend_requests_for_user(friend_id).len(), 1);
+    }
+
+    async fn register(server: &mut WarhorseServer<InMemoryDatabase>, name: &str) -> UserId {
+        server.register_user(
+            UserRegistration {
+                language: Language::English,
+                account_name: name.to_string(),
+                email: format!("{}@example.com", name),
+                display_name: name.to_string(),
+                password: "password".to_string(),
+            },
+            None
+        ).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_friends_page_boundaries_and_order() {
+        let mut server = server_with_user().await;
+        let user_id = server.data_service.users_get_by_account_name("test").unwrap().id;
+        for name in ["Charlie", "alice", "Bob"] {
+            let friend_id = register(&mut server, name).await;
+            server.data_service.friends_add(user_id.clone(), friend_id);
+        }
+        let requester_id = register(&mut server, "Dave").await;
+        server.send_friend_request(requester_id, FriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+
+        let page = |offset, limit| {
+            let page = server.get_friends_page(user_id.clone(), offset, limit);
+            assert_eq!(page.total, 4);
+            page.friends.into_iter().map(|friend| friend.display_name).collect::<Vec<_>>()
+        };
+
+        // requests waiting on us come before friends, each sorted by name ignoring case
+        assert_eq!(page(0, 10), vec!["Dave", "alice", "Bob", "Charlie"]);
+        assert_eq!(page(1, 2), vec!["alice", "Bob"]);
+        assert_eq!(page(3, 10), vec!["Charlie"]);
+        assert!(page(4, 10).is_empty());
+        assert!(page(0, 0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_friends_list_status_resolution() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder().build(io);
+        let user_id = register(&mut server, "test").await;
+        let mut ids = HashMap::new();
+        for name in ["online", "offline", "received", "sent", "blocked"] {
+            ids.insert(name, register(&mut server, name).await);
+        }
+        server.data_service.friends_add(user_id.clone(), ids["online"].clone());
+        server.data_service.friends_add(user_id.clone(), ids["offline"].clone());
+        server.data_service.friend_requests_insert(ids["received"].clone(), user_id.clone());
+        server.data_service.friend_requests_insert(user_id.clone(), ids["sent"].clone());
+        server.data_service.user_blocks_insert(user_id.clone(), ids["blocked"].clone());
+        // everyone but the offline friend is connected
+        for name in ["online", "received", "sent", "blocked"] {
+            server.user_sockets.insert(ids[name].clone(), ConnectedUser::new(Sid::new()));
+        }
+
+        let statuses: HashMap<UserId, FriendStatus> = server.get_friends_list(user_id)
+            .into_iter()
+            .map(|friend| (friend.id, friend.status))
+            .collect();
+
+        assert_eq!(statuses[&ids["online"]], FriendStatus::Online);
+        assert_eq!(statuses[&ids["offline"]], FriendStatus::Offline);
+        assert_eq!(statuses[&ids["received"]], FriendStatus::FriendRequestReceived);
+        assert_eq!(statuses[&ids["sent"]], FriendStatus::FriendRequestSent);
+        assert_eq!(statuses[&ids["blocked"]], FriendStatus::Blocked);
+    }
+
+    #[tokio::test]
+    async fn test_only_friend_requests_that_were_sent_can_be_accepted() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder().build(io);
+        let user_id = register(&mut server, "test").await;
+        let friend_id = register(&mut server, "test2").await;
+
+        let accept = AcceptFriendRequest { language: Language::English, friend_id: friend_id.clone() };
+        let result = server.accept_friend_request(user_id.clone(), accept);
+        assert_eq!(result.unwrap_err().code, ErrorCode::NoFriendRequest);
+        assert!(!server.are_friends(user_id.clone(), friend_id.clone()));
+
+        // the sender can't accept their own request either
+        server.send_friend_request(friend_id.clone(), FriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+        let accept = AcceptFriendRequest { language: Language::English, friend_id: user_id.clone() };
+        let result = server.accept_friend_request(friend_id.clone(), accept);
+        assert_eq!(result.unwrap_err().code, ErrorCode::NoFriendRequest);
+
+        let accept = AcceptFriendRequest { language: Language::English, friend_id: friend_id.clone() };
+        server.accept_friend_request(user_id.clone(), accept).unwrap();
+        assert!(server.are_friends(user_id.clone(), friend_id.clone()));
+        assert!(server.data_service.user_get_pending_friend_requests_for_user(user_id.clone()).is_empty());
+        assert!(server.data_service.user_get_friend_request_invites_sent_for_user(friend_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_friend_limit_blocks_requests_and_accepts() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder().max_friends(1).build(io);
+        let user_id = register(&mut server, "test").await;
+        let friend_id = register(&mut server, "test2").await;
+        let other_id = register(&mut server, "test3").await;
+        let fourth_id = register(&mut server, "test4").await;
+
+        // other asks before user is full, then user fills up
+        server.send_friend_request(other_id.clone(), FriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+        server.send_friend_request(user_id.clone(), FriendRequest { language: Language::English, friend_id: friend_id.clone() }).unwrap();
+        server.accept_friend_request(friend_id.clone(), AcceptFriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+
+        let result = server.send_friend_request(user_id.clone(), FriendRequest { language: Language::English, friend_id: fourth_id.clone() });
+        assert_eq!(result.unwrap_err().code, ErrorCode::FriendLimitReached);
+
+        let result = server.accept_friend_request(user_id.clone(), AcceptFriendRequest { language: Language::English, friend_id: other_id.clone() });
+        assert_eq!(result.unwrap_err().code, ErrorCode::FriendLimitReached);
+        assert!(!server.are_friends(user_id.clone(), other_id));
+
+        // the accepting side being empty doesn't help when the requester is full
+        server.send_friend_request(fourth_id.clone(), FriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+        server.data_service.friends_remove(user_id.clone(), friend_id.clone());
+        server.data_service.friends_add(fourth_id.clone(), friend_id.clone());
+        let result = server.accept_friend_request(user_id.clone(), AcceptFriendRequest { language: Language::English, friend_id: fourth_id.clone() });
+        assert_eq!(result.unwrap_err().code, ErrorCode::FriendLimitReached);
+        assert_eq!(server.data_service.friends_count(user_id), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_friend_request() {
+        let (mut server, user_id, _) = server_with_friends().await;
+        let stranger_id = register(&mut server, "stranger").await;
+        let cancel = |friend_id: &UserId| CancelFriendRequest { language: Language::English, friend_id: friend_id.clone() };
+        server.send_friend_request(stranger_id.clone(), FriendRequest { language: Language::English, friend_id: user_id.clone() }).unwrap();
+
+        // the recipient can't cancel an inbound request
+        assert!(server.cancel_friend_request(user_id.clone(), cancel(&stranger_id)).is_err());
+        assert_eq!(server.data_service.user_get_pending_friend_requests_for_user(user_id.clone()).len(), 1);
+
+        server.cancel_friend_request(stranger_id.clone(), cancel(&user_id)).unwrap();
+        assert!(server.data_service.user_get_pending_friend_requests_for_user(user_id).is_empty());
+        assert!(server.data_service.user_get_friend_request_invites_sent_for_user(stranger_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_large_payloads_are_only_compressed_for_users_who_asked() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder().build(io);
+        server.connect_user(UserId::from("plain"), Sid::new(), false);
+        server.connect_user(UserId::from("compressed"), Sid::new(), true);
+        let large = serde_json::json!(vec!["friend"; 10_000]);
+        let small = serde_json::json!(["friend"]);
+
+        assert_eq!(server.compress_for("plain", large.clone()), large);
+        assert_eq!(server.compress_for("compressed", small.clone()), small);
+
+        let compressed = server.compress_for("compressed", large.clone());
+        assert_ne!(compressed, large);
+        assert_eq!(warhorse_protocol::compression::decompress(&compressed).unwrap(), Some(large));
+    }
+
+    #[tokio::test]
+    async fn test_sent_friend_request_shows_on_both_sides() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder().build(io);
+        let sender_id = register(&mut server, "sender").await;
+        let receiver_id = register(&mut server, "receiver").await;
+        // both online, which a request must not reveal
+        for user_id in [&sender_id, &receiver_id] {
+            server.user_sockets.insert(user_id.clone(), ConnectedUser::new(Sid::new()));
+        }
+
+        server.send_friend_request(
+            sender_id.clone(),
+            FriendRequest { language: Language::English, friend_id: receiver_id.clone() },
+        ).unwrap();
+
+        let friends_list = |user_id: &UserId| server.get_friends_list(user_id.clone())
+            .into_iter()
+            .map(|friend| (friend.id, friend.status))
+            .collect::<Vec<_>>();
+        assert_eq!(friends_list(&sender_id), vec![(receiver_id.clone(), FriendStatus::FriendRequestSent)]);
+        assert_eq!(friends_list(&receiver_id), vec![(sender_id, FriendStatus::FriendRequestReceived)]);
+    }
+
+    #[tokio::test]
+    async fn test_login_with_correct_password() {
+        let mut server = server_with_user().await;
+        assert!(server.login_user(login("test", "password"), Sid::new()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_login_with_wrong_password() {
+        let mut server = server_with_user().await;
+        let result = server.login_user(login("test", "wrong password"), Sid::new()).await;
+        assert_eq!(result.unwrap_err().message, crate::i18n::invalid_login(Language::English).message);
+        assert!(server.user_sockets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_login_queues_presence_refresh_for_online_friends() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+
+        // nobody online to tell yet
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        assert!(server.presence_refreshes.is_empty());
+
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+        assert_eq!(server.presence_refreshes, HashSet::from([user_id.clone()]));
+
+        server.flush_presence_refreshes();
+        assert!(server.presence_refreshes.is_empty());
+
+        server.handle_disconnect(&friend_id).await;
+        assert_eq!(server.presence_refreshes, HashSet::from([user_id]));
+    }
+
+    #[tokio::test]
+    async fn test_friends_see_when_a_user_was_last_seen() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        let last_seen = |server: &WarhorseServer<InMemoryDatabase>| server.get_friends_list(user_id.clone())
+            .into_iter()
+            .find(|friend| friend.id == friend_id)
+            .unwrap()
+            .last_seen;
+
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+        assert_eq!(last_seen(&server), None);
+
+        let before = Timestamp::now();
+        server.handle_disconnect(&friend_id).await;
+        assert!(last_seen(&server).is_some_and(|last_seen| last_seen >= before));
+
+        // hidden again once they're back
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+        assert_eq!(last_seen(&server), None);
+    }
+
+    #[tokio::test]
+    async fn test_presence_kinds_as_seen_by_friends() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+        server.flush_presence_refreshes();
+
+        let status_seen_by_friend = |server: &WarhorseServer<InMemoryDatabase>| {
+            server.get_friends_list(friend_id.clone())
+                .into_iter()
+                .find(|friend| friend.id == user_id)
+                .unwrap()
+                .status
+        };
+        assert_eq!(status_seen_by_friend(&server), FriendStatus::Online);
+
+        for (presence, status) in [
+            (PresenceKind::Away, FriendStatus::Away),
+            (PresenceKind::Busy, FriendStatus::Busy),
+            (PresenceKind::Invisible, FriendStatus::Offline),
+            (PresenceKind::Online, FriendStatus::Online),
+        ] {
+            server.set_presence(user_id.clone(), SetPresence { language: Language::English, status: presence }).unwrap();
+            assert_eq!(status_seen_by_friend(&server), status);
+            assert_eq!(server.presence_refreshes, HashSet::from([friend_id.clone()]));
+            server.flush_presence_refreshes();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_users_are_shown_as_away_until_they_are_active() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+        server.flush_presence_refreshes();
+        let idle_timeout = server.config().idle_timeout;
+
+        let status_seen_by_friend = |server: &WarhorseServer<InMemoryDatabase>| {
+            server.get_friends_list(friend_id.clone())
+                .into_iter()
+                .find(|friend| friend.id == user_id)
+                .unwrap()
+                .status
+        };
+
+        // not idle for long enough yet
+        server.mark_idle_users_away(Instant::now() + idle_timeout / 2);
+        assert_eq!(status_seen_by_friend(&server), FriendStatus::Online);
+        assert!(server.presence_refreshes.is_empty());
+
+        server.mark_idle_users_away(Instant::now() + idle_timeout);
+        assert_eq!(status_seen_by_friend(&server), FriendStatus::Away);
+        assert!(server.presence_refreshes.contains(&friend_id));
+        server.flush_presence_refreshes();
+
+        server.note_activity(user_id.clone()).unwrap();
+        assert_eq!(status_seen_by_friend(&server), FriendStatus::Online);
+        assert_eq!(server.presence_refreshes, HashSet::from([friend_id.clone()]));
+
+        // a status they picked themselves is left alone
+        server.set_presence(user_id.clone(), SetPresence { language: Language::English, status: PresenceKind::Busy }).unwrap();
+        server.mark_idle_users_away(Instant::now() + idle_timeout);
+        assert_eq!(status_seen_by_friend(&server), FriendStatus::Busy);
+    }
+
+    #[tokio::test]
+    async fn test_activity_is_cleared_after_disconnect() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+
+        let activity_seen_by_friend = |server: &WarhorseServer<InMemoryDatabase>| {
+            server.get_friends_list(friend_id.clone())
+                .into_iter()
+                .find(|friend| friend.id == user_id)
+                .unwrap()
+                .activity
+        };
+
+        let activity = SetActivity {
+            language: Language::English,
+            activity: Some("Playing Ranked - Level 4".to_string()),
+        };
+        server.set_activity(user_id.clone(), activity).unwrap();
+        assert_eq!(activity_seen_by_friend(&server).as_deref(), Some("Playing Ranked - Level 4"));
+
+        server.handle_disconnect(&user_id).await;
+        assert_eq!(activity_seen_by_friend(&server), None);
+
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        assert_eq!(activity_seen_by_friend(&server), None);
+    }
+
+    #[tokio::test]
+    async fn test_avatar_is_shown_to_friends_and_validated() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+
+        let avatar_seen_by_friend = |server: &WarhorseServer<InMemoryDatabase>| {
+            server.get_friends_list(friend_id.clone())
+                .into_iter()
+                .find(|friend| friend.id == user_id)
+                .unwrap()
+                .avatar_url
+        };
+        let avatar = |avatar_url: &str| SetAvatar {
+            language: Language::English,
+            avatar_url: Some(avatar_url.to_string()),
+        };
+
+        server.set_avatar(user_id.clone(), avatar("https://cdn.example.com/avatars/1.png")).unwrap();
+        assert_eq!(avatar_seen_by_friend(&server).as_deref(), Some("https://cdn.example.com/avatars/1.png"));
+        assert_eq!(
+            server.data_service.users_get(user_id.clone()).unwrap().avatar_url.as_deref(),
+            Some("https://cdn.example.com/avatars/1.png")
+        );
+
+        let result = server.set_avatar(user_id.clone(), avatar("javascript:alert(1)"));
+        assert_eq!(result.unwrap_err().code, ErrorCode::InvalidAvatarUrl);
+        assert_eq!(avatar_seen_by_friend(&server).as_deref(), Some("https://cdn.example.com/avatars/1.png"));
+
+        server.set_avatar(user_id.clone(), SetAvatar { language: Language::English, avatar_url: None }).unwrap();
+        assert_eq!(avatar_seen_by_friend(&server), None);
+    }
+
+    fn party_invite(party_id: &str, friend_id: &UserId) -> PartyInvite {
+        PartyInvite {
+            language: Language::English,
+            party_id: party_id.to_string(),
+            friend_id: friend_id.clone(),
+        }
+    }
+
+    fn party_join(party_id: &str) -> PartyJoin {
+        PartyJoin {
+            language: Language::English,
+            party_id: party_id.to_string(),
+        }
+    }
+
+    fn party_leave(party_id: &str) -> PartyLeave {
+        PartyLeave {
+            language: Language::English,
+            party_id: party_id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_party_invite_join_leave_and_dissolve() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+
+        // can't join without an invite
+        assert!(server.join_party(friend_id.clone(), party_join("party")).is_err());
+
+        server.invite_to_party(user_id.clone(), party_invite("party", &friend_id)).unwrap();
+        assert_eq!(server.parties["party"], vec![user_id.clone()]);
+
+        server.join_party(friend_id.clone(), party_join("party")).unwrap();
+        assert_eq!(server.parties["party"], vec![user_id.clone(), friend_id.clone()]);
+
+        // the invite is used up
+        server.leave_party(friend_id.clone(), party_leave("party")).unwrap();
+        assert!(server.join_party(friend_id.clone(), party_join("party")).is_err());
+        assert_eq!(server.parties["party"], vec![user_id.clone()]);
+
+        // the last one out dissolves it
+        server.leave_party(user_id.clone(), party_leave("party")).unwrap();
+        assert!(server.parties.is_empty());
+        assert!(server.party_invites.is_empty());
+        assert!(server.leave_party(user_id, party_leave("party")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_party_members_are_ejected_on_disconnect() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+
+        server.invite_to_party(user_id.clone(), party_invite("party", &friend_id)).unwrap();
+        server.join_party(friend_id.clone(), party_join("party")).unwrap();
+
+        server.handle_disconnect(&user_id).await;
+        assert_eq!(server.parties["party"], vec![friend_id.clone()]);
+
+        server.handle_disconnect(&friend_id).await;
+        assert!(server.parties.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_clears_everything_the_user_was_part_of() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        server.login_user(login("test2", "password"), Sid::new()).await.unwrap();
+
+        server.set_activity(user_id.clone(), SetActivity { language: Language::English, activity: Some("Fishing".to_string()) }).unwrap();
+        server.join_room(user_id.clone(), JoinRoom { language: Language::English, room_id: RoomId::from("lobby") }).unwrap();
+        server.invite_to_party(friend_id.clone(), party_invite("pending", &user_id)).unwrap();
+        server.invite_to_party(user_id.clone(), party_invite("party", &friend_id)).unwrap();
+        server.join_party(friend_id.clone(), party_join("party")).unwrap();
+        server.presence_refreshes.clear();
+
+        server.handle_disconnect(&user_id).await;
+
+        assert!(!server.user_sockets.contains_key(&user_id));
+        assert!(server.room_members.values().all(|members| !members.contains(&user_id)));
+        assert!(server.parties.values().all(|members| !members.contains(&user_id)));
+        assert!(server.party_invites.values().all(|invited| !invited.contains(&user_id)));
+        assert!(server.presence_refreshes.contains(&friend_id));
+
+        let seen_by_friend = server.get_friends_list(friend_id.clone())
+            .into_iter()
+            .find(|friend| friend.id == user_id)
+            .unwrap();
+        assert_eq!(seen_by_friend.status, FriendStatus::Offline);
+        assert_eq!(seen_by_friend.activity, None);
+        assert!(seen_by_friend.last_seen.is_some());
+    }
+
+    #[test]
+    fn test_connections_past_the_caps_are_rejected() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder()
+            .max_connections(4)
+            .max_connections_per_ip(2)
+            .build(io);
+        let ip: IpAddr = "203.0.113.1".parse().unwrap();
+        let other_ip: IpAddr = "203.0.113.2".parse().unwrap();
+
+        let first = Sid::new();
+        server.open_connection(first, Some(ip)).unwrap();
+        server.open_connection(Sid::new(), Some(ip)).unwrap();
+        assert!(server.open_connection(Sid::new(), Some(ip)).is_err());
+        assert!(server.open_connection(Sid::new(), Some(ip)).is_err());
+        server.open_connection(Sid::new(), Some(other_ip)).unwrap();
+
+        // closing one frees up its slot
+        server.close_connection(first);
+        server.open_connection(Sid::new(), Some(ip)).unwrap();
+
+        // the global cap applies even when the address is unknown
+        server.open_connection(Sid::new(), None).unwrap();
+        assert!(server.open_connection(Sid::new(), None).is_err());
+        assert_eq!(server.connections.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_whisper_to_offline_friend_is_stored() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.set_max_offline_messages(2);
+
+        server.send_chat_message(user_id.clone(), whisper(&friend_id, "0")).unwrap();
+        server.send_chat_message(user_id.clone(), whisper(&friend_id, "1")).unwrap();
+        server.send_chat_message(user_id, whisper(&friend_id, "2")).unwrap();
+
+        let messages = server.data_service.offline_messages_drain(friend_id.clone());
+        let texts: Vec<&str> = messages.iter().map(|m| m.message.as_str()).collect();
+        assert_eq!(texts, vec!["1", "2"]);
+        assert!(server.data_service.offline_messages_drain(friend_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chat_message_length_is_checked() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+
+        let too_long = "m".repeat(CHAT_MESSAGE_MAX_LENGTH + 1);
+        let result = server.send_chat_message(user_id.clone(), whisper(&friend_id, &too_long));
+        assert_eq!(result.unwrap_err().code, ErrorCode::ChatMessageTooLong);
+
+        let result = server.send_chat_message(user_id.clone(), whisper(&friend_id, "  "));
+        assert_eq!(result.unwrap_err().code, ErrorCode::ChatMessageEmpty);
+
+        let longest = "m".repeat(CHAT_MESSAGE_MAX_LENGTH);
+        server.send_chat_message(user_id, whisper(&friend_id, &longest)).unwrap();
+        assert_eq!(server.data_service.offline_messages_drain(friend_id).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_chat_messages_are_rate_limited() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.chat_rate_limiter = RateLimiter::new(RateLimit {
+            max_messages: 3,
+            window: Duration::from_secs(60),
+        });
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+
+        for i in 0..3 {
+            assert!(server.send_chat_message(user_id.clone(), whisper(&friend_id, &i.to_string())).is_ok());
+        }
+        let result = server.send_chat_message(user_id, whisper(&friend_id, "3"));
+        assert_eq!(result.unwrap_err().message, crate::i18n::rate_limited(Language::English).message);
+        assert_eq!(server.data_service.offline_messages_drain(friend_id).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_builder_applies_config() {
+        let (_layer, io) = SocketIo::new_layer();
+        let mut server = WarhorseServer::<InMemoryDatabase>::builder()
+            .bind_address("127.0.0.1:4000")
+            .default_rooms(vec![RoomId::from("lobby"), RoomId::from("trade")])
+            .chat_rate_limit(RateLimit { max_messages: 1, window: Duration::from_secs(60) })
+            .max_offline_messages(1)
+            .heartbeat_interval(Duration::from_secs(5))
+            .seed_test_users(true)
+            .build(io);
+
+        assert_eq!(server.config().bind_address, "127.0.0.1:4000");
+        assert_eq!(server.config().max_offline_messages, 1);
+        assert_eq!(server.config().heartbeat_interval, Duration::from_secs(5));
+        assert!(server.config().database_connection_string.is_empty());
+
+        // the seeded users can log in and land in the configured rooms
+        let user_id = server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        assert!(server.user_in_room(user_id.clone(), RoomId::from("lobby")));
+        assert!(server.user_in_room(user_id.clone(), RoomId::from("trade")));
+        assert!(!server.user_in_room(user_id.clone(), RoomId::from(DEFAULT_ROOM)));
+
+        let friend_id = server.data_service.users_get_by_account_name("test2").unwrap().id;
+        server.data_service.friends_add(user_id.clone(), friend_id.clone());
+
+        assert!(server.send_chat_message(user_id.clone(), whisper(&friend_id, "0")).is_ok());
+        assert!(server.send_chat_message(user_id, whisper(&friend_id, "1")).is_err());
+        assert_eq!(server.data_service.offline_messages_drain(friend_id).len(), 1);
+    }
+
+    #[test]
+    fn test_default_config_does_not_seed_test_users() {
+        let (_layer, io) = SocketIo::new_layer();
+        let server = WarhorseServer::<InMemoryDatabase>::builder().build(io);
+        assert_eq!(server.config().default_rooms, vec![RoomId::from(DEFAULT_ROOM)]);
+        assert!(server.data_service.users_get_by_account_name("test").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_app_does_not_seed_test_users_unless_asked() {
+        let (_app, server) = crate::app::<InMemoryDatabase>("");
+        let mut server = server.lock().await;
+        assert!(server.data_service.users_get_by_account_name("test").is_none());
+        assert_eq!(code(server.login_user(login("test", "password"), Sid::new()).await), Some(ErrorCode::InvalidLogin));
+    }
+
+    fn room_message(room_id: &str, message: &str) -> SendChatMessage {
+        SendChatMessage {
+            language: Language::English,
+            message: message.to_string(),
+            channel: ChatChannel::Room(RoomId::from(room_id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_join_message_leave_room() {
+        let mut server = server_with_user().await;
+        let user_id = server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        assert!(server.user_in_room(user_id.clone(), RoomId::from(DEFAULT_ROOM)));
+
+        // can't talk in a room before joining it
+        assert!(server.send_chat_message(user_id.clone(), room_message("lobby", "0")).is_err());
+
+        server.join_room(user_id.clone(), JoinRoom { language: Language::English, room_id: RoomId::from("lobby") }).unwrap();
+        assert!(server.send_chat_message(user_id.clone(), room_message("lobby", "1")).is_ok());
+
+        server.leave_room(user_id.clone(), LeaveRoom { language: Language::English, room_id: RoomId::from("lobby") }).unwrap();
+        let result = server.send_chat_message(user_id.clone(), room_message("lobby", "2"));
+        assert_eq!(result.unwrap_err().message, format!("{} is not in room lobby", user_id));
+
+        // leaving again is an error, and the empty room is gone
+        assert!(server.leave_room(user_id, LeaveRoom { language: Language::English, room_id: RoomId::from("lobby") }).is_err());
+        assert!(!server.room_members.contains_key("lobby"));
+    }
 
-    socket.emit(EVENT_RECEIVE_HELLO, &crate::i18n::hello_message(Language::English)).ok();
+    #[tokio::test]
+    async fn test_room_messages_are_filtered() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        let history = |server: &WarhorseServer<InMemoryDatabase>| server.data_service
+            .chat_history_get(ChatChannel::Room(RoomId::from(DEFAULT_ROOM)), None, 10)
+            .into_iter()
+            .map(|message| message.message)
+            .collect::<Vec<_>>();
+
+        // allowed as is
+        server.config.message_filter = Arc::new(WordListFilter::masking(["darn"]));
+        server.send_chat_message(user_id.clone(), room_message(DEFAULT_ROOM, "hello")).unwrap();
+        assert_eq!(history(&server), vec!["hello"]);
+
+        // replaced
+        server.send_chat_message(user_id.clone(), room_message(DEFAULT_ROOM, "darn it")).unwrap();
+        assert_eq!(history(&server), vec!["hello", "**** it"]);
+
+        // rejected, and nothing is stored
+        server.config.message_filter = Arc::new(WordListFilter::rejecting(["darn"]));
+        let result = server.send_chat_message(user_id.clone(), room_message(DEFAULT_ROOM, "darn it"));
+        assert_eq!(code(result), Some(ErrorCode::MessageRejected));
+        assert_eq!(history(&server), vec!["hello", "**** it"]);
+
+        // private messages are exempt unless configured otherwise
+        assert!(server.send_chat_message(user_id.clone(), whisper(&friend_id, "darn")).is_ok());
+        server.config.filter_private_messages = true;
+        assert_eq!(code(server.send_chat_message(user_id, whisper(&friend_id, "darn"))), Some(ErrorCode::MessageRejected));
+    }
+
+    #[tokio::test]
+    async fn test_typing_indicator_from_blocked_user_is_suppressed() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        let typing = |user_id: &UserId| TypingIndicator {
+            language: Language::English,
+            channel: ChatChannel::PrivateMessage(user_id.clone()),
+            is_typing: true,
+        };
+        assert!(server.send_typing(friend_id.clone(), typing(&user_id)).is_ok());
+
+        server.block_user(user_id.clone(), BlockUserRequest { language: Language::English, user_id: friend_id.clone() }).unwrap();
+        let result = server.send_typing(friend_id, typing(&user_id));
+        assert_eq!(result.unwrap_err().message, crate::i18n::user_is_blocked(Language::English).message);
+    }
+
+    #[tokio::test]
+    async fn test_search_users_by_display_name_prefix() {
+        let (server, user_id, friend_id) = server_with_friends().await;
+        let search = |query: &str| server.search_users(user_id.clone(), SearchUsers {
+            language: Language::English,
+            query: query.to_string(),
+        });
+
+        // case-insensitive, and we never find ourselves
+        let results = search("TEST user");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, friend_id);
+
+        // private details are stripped
+        assert!(results[0].email.is_none());
+        assert!(results[0].account_name.is_none());
+        assert!(results[0].account_name_lower.is_none());
+
+        assert!(search("nobody").is_empty());
+        assert!(search("  ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_profile_is_redacted_between_blocked_users() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        let stranger_id = register(&mut server, "stranger").await;
+        server.data_service.friends_add(stranger_id.clone(), friend_id.clone());
+        let profile = |server: &WarhorseServer<InMemoryDatabase>| server.get_profile(user_id.clone(), GetProfile {
+            language: Language::English,
+            user_id: stranger_id.clone(),
+        }).unwrap();
+
+        let visible = profile(&server);
+        assert_eq!(visible.mutual_friends, 1);
+        assert!(visible.member_since.is_some());
+
+        server.block_user(stranger_id.clone(), BlockUserRequest { language: Language::English, user_id: user_id.clone() }).unwrap();
+        let redacted = profile(&server);
+        assert_eq!(redacted.display_name, visible.display_name);
+        assert_eq!(redacted.status, FriendStatus::Offline);
+        assert_eq!(redacted.member_since, None);
+        assert_eq!(redacted.mutual_friends, 0);
+    }
+
+    #[tokio::test]
+    async fn test_login_with_nonexistent_account() {
+        let mut server = server_with_user().await;
+        let result = server.login_user(login("nobody", "password"), Sid::new()).await;
+        assert_eq!(result.unwrap_err().message, crate::i18n::invalid_login(Language::English).message);
+    }
+
+    fn registration(account_name: &str, email: &str, display_name: &str, password: &str) -> UserRegistration {
+        UserRegistration {
+            language: Language::English,
+            account_name: account_name.to_string(),
+            email: email.to_string(),
+            display_name: display_name.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    fn code<T>(result: Result<T, ServerError>) -> Option<ErrorCode> {
+        result.err().map(|e| e.code)
+    }
 
-    // add them to the general chat room, everyone is in general
-    socket.join("general").ok();
+    #[tokio::test]
+    async fn test_error_paths_have_codes() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+
+        assert_eq!(code(server.login_user(login("test", "wrong password"), Sid::new()).await), Some(ErrorCode::InvalidLogin));
+        assert_eq!(code(server.register_user(registration("test", "new@example.com", "New User", "password"), None).await), Some(ErrorCode::AccountNameTaken));
+        assert_eq!(code(server.register_user(registration("new_user", "test@example.com", "New User", "password"), None).await), Some(ErrorCode::EmailTaken));
+        assert_eq!(code(server.register_user(registration("new_user", "new@", "New User", "password"), None).await), Some(ErrorCode::InvalidEmail));
+        assert_eq!(code(server.register_user(registration("new_user", "new@example.com", "New User", "pass"), None).await), Some(ErrorCode::PasswordTooShort));
+        assert_eq!(code(server.register_user(registration("n", "new@example.com", "New User", "password"), None).await), Some(ErrorCode::InvalidAccountName));
+        assert_eq!(code(server.register_user(registration("new_user", "new@example.com", "N", "password"), None).await), Some(ErrorCode::InvalidDisplayName));
+
+        let wrong_password = DeleteAccount { language: Language::English, password: "wrong password".to_string() };
+        assert_eq!(code(server.delete_account(user_id.clone(), wrong_password).await), Some(ErrorCode::IncorrectPassword));
+
+        server.login_user(login("test", "password"), Sid::new()).await.unwrap();
+        let friend_request = |friend_id: &UserId| FriendRequest { language: Language::English, friend_id: friend_id.clone() };
+        assert_eq!(code(server.send_friend_request(user_id.clone(), friend_request(&user_id))), Some(ErrorCode::CannotFriendSelf));
+        assert_eq!(code(server.send_friend_request(user_id.clone(), friend_request(&friend_id))), Some(ErrorCode::AlreadyFriends));
+
+        let stranger_id = register(&mut server, "stranger").await;
+        server.send_friend_request(stranger_id.clone(), friend_request(&user_id)).unwrap();
+        assert_eq!(code(server.send_friend_request(user_id.clone(), friend_request(&stranger_id))), Some(ErrorCode::FriendRequestAlreadyReceived));
+
+        let history = GetChatHistory { language: Language::English, channel: ChatChannel::PrivateMessage(friend_id.clone()), before: None, limit: 10 };
+        assert_eq!(code(server.send_chat_history(user_id.clone(), history)), Some(ErrorCode::ChatHistoryRoomsOnly));
+        let room = JoinRoom { language: Language::English, room_id: RoomId::from(" ") };
+        assert_eq!(code(server.join_room(user_id.clone(), room)), Some(ErrorCode::InvalidRoomId));
+        assert_eq!(code(server.invite_to_party(user_id.clone(), party_invite(" ", &friend_id))), Some(ErrorCode::InvalidPartyId));
+        let activity = SetActivity { language: Language::English, activity: Some("a".repeat(MAX_ACTIVITY_LENGTH + 1)) };
+        assert_eq!(code(server.set_activity(user_id.clone(), activity)), Some(ErrorCode::ActivityTooLong));
+
+        server.chat_rate_limiter = RateLimiter::new(RateLimit { max_messages: 1, window: Duration::from_secs(60) });
+        server.send_chat_message(user_id.clone(), whisper(&friend_id, "0")).unwrap();
+        assert_eq!(code(server.send_chat_message(user_id.clone(), whisper(&friend_id, "1"))), Some(ErrorCode::RateLimited));
+
+        server.block_user(friend_id.clone(), BlockUserRequest { language: Language::English, user_id: user_id.clone() }).unwrap();
+        assert_eq!(code(server.invite_to_party(user_id, party_invite("party", &friend_id))), Some(ErrorCode::UserBlocked));
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_can_kick_and_ban() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        let kick = |user_id: &UserId| AdminKick { language: Language::English, user_id: user_id.clone() };
+        let ban = |user_id: &UserId| AdminBan { language: Language::English, user_id: user_id.clone(), reason: None };
+
+        assert_eq!(code(server.kick_user(user_id.clone(), kick(&friend_id)).await), Some(ErrorCode::NotAdmin));
+        assert_eq!(code(server.ban_user(user_id.clone(), ban(&friend_id)).await), Some(ErrorCode::NotAdmin));
+        assert!(server.data_service.bans_get(friend_id.clone()).is_none());
+
+        server.set_admin(user_id.clone(), true);
+        server.ban_user(user_id, ban(&friend_id)).await.unwrap();
+        assert!(server.data_service.bans_get(friend_id).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_banned_user_cannot_log_in() {
+        let (mut server, user_id, friend_id) = server_with_friends().await;
+        server.set_admin(user_id.clone(), true);
+        let ban = AdminBan { language: Language::English, user_id: friend_id.clone(), reason: Some("spam".to_string()) };
+        server.ban_user(user_id, ban).await.unwrap();
+
+        assert_eq!(code(server.login_user(login("test2", "password"), Sid::new()).await), Some(ErrorCode::Banned));
+        assert!(server.get_socket_id(friend_id).is_err());
+    }
 
-    listen_for_user_login(&socket, server.clone());
-    listen_for_user_registration(&socket, server.clone());
-    listen_for_chat_messages(&socket, server.clone());
-    listen_for_friend_requests(&socket, server.clone());
-    listen_for_accept_friend_requests(&socket, server.clone());
-    listen_for_reject_friend_requests(&socket, server.clone());
-    listen_for_remove_friend(&socket, server.clone());
-    listen_for_block_user_requests(&socket, server.clone());
-    listen_for_unblock_user_requests(&socket, server.clone());
+    // collects everything a test subscriber logs
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_connection_span_tags_logs_with_socket_and_user() {
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .finish();
+        let socket_id = Sid::new();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = connection_span("/", socket_id);
+            span.in_scope(|| info!("before login"));
+            span.record("user_id", "user-1");
+            span.in_scope(|| info!("after login"));
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let line = |message: &str| logs.lines().find(|line| line.contains(message)).unwrap().to_string();
+        assert!(line("before login").contains(&format!("{:?}", socket_id)));
+        assert!(!line("before login").contains("user-1"));
+        assert!(line("after login").contains(&format!("{:?}", socket_id)));
+        assert!(line("after login").contains("user-1"));
+    }
 }
@@ -1,39 +1,67 @@
-use regex::Regex;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use warhorse_protocol::Language;
-use warhorse_protocol::{ACCOUNT_NAME_MAX_LENGTH, ACCOUNT_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH, DISPLAY_NAME_MIN_LENGTH, PASSWORD_MIN_LENGTH};
+use warhorse_protocol::validation::{self, ValidationError};
 use crate::error::ServerError;
 
-pub fn validate_password(password: &String, language: Language) -> Result<(), ServerError> {
-    if password.len() < PASSWORD_MIN_LENGTH {
-        return Err(crate::i18n::invalid_password(language));
+/// Turns a validation failure into an error message in the user's language.
+pub fn localize_validation_error(error: ValidationError, language: Language) -> ServerError {
+    match error {
+        ValidationError::PasswordTooShort => crate::i18n::invalid_password(language),
+        ValidationError::AccountNameLength => crate::i18n::invalid_account_name(language),
+        ValidationError::DisplayNameLength => crate::i18n::invalid_display_name(language),
+        ValidationError::InvalidEmail => crate::i18n::invalid_email(language),
+        ValidationError::ChatMessageEmpty => crate::i18n::chat_message_empty(language),
+        ValidationError::ChatMessageTooLong => crate::i18n::chat_message_too_long(language),
+        ValidationError::InvalidAvatarUrl => crate::i18n::invalid_avatar_url(language),
     }
-    Ok(())
+}
+
+pub fn validate_password(password: &String, language: Language) -> Result<(), ServerError> {
+    validation::validate_password(password).map_err(|e| localize_validation_error(e, language))
 }
 
 pub fn validate_account_name(account_name: &String, language: Language) -> Result<(), ServerError> {
-    if account_name.len() < ACCOUNT_NAME_MIN_LENGTH || account_name.len() > ACCOUNT_NAME_MAX_LENGTH {
-        return Err(crate::i18n::invalid_account_name(language));
-    }
-    Ok(())
+    validation::validate_account_name(account_name).map_err(|e| localize_validation_error(e, language))
 }
 
 pub fn validate_display_name(display_name: &String, language: Language) -> Result<(), ServerError> {
-    if display_name.len() < DISPLAY_NAME_MIN_LENGTH  || display_name.len() > DISPLAY_NAME_MAX_LENGTH {
-        return Err(crate::i18n::invalid_display_name(language));
-    }
-    Ok(())
+    validation::validate_display_name(display_name).map_err(|e| localize_validation_error(e, language))
 }
 
-pub fn is_valid_email(email: &String) -> bool {
+pub fn validate_chat_message(message: &str, language: Language) -> Result<(), ServerError> {
+    validation::validate_chat_message(message).map_err(|e| localize_validation_error(e, language))
+}
+
+pub fn validate_avatar_url(avatar_url: &str, language: Language) -> Result<(), ServerError> {
+    validation::validate_avatar_url(avatar_url).map_err(|e| localize_validation_error(e, language))
+}
 
-    // thanks AI!
-    let email_regex = Regex::new(r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$").unwrap();
+pub fn hash_password(password: &str) -> Result<String, ServerError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| ServerError::from(format!("Failed to hash password: {}", e)))
+}
 
-    if email.len() > 254 {
-        return false;
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(hash) => Argon2::default().verify_password(password.as_bytes(), &hash).is_ok(),
+        Err(_) => false,
     }
+}
 
-    email_regex.is_match(email)
+/// Makes a token that's hard to guess, hex encoded so it can go in a link.
+pub fn generate_verification_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn is_valid_email(email: &String) -> bool {
+    validation::validate_email(email).is_ok()
 }
 
 #[cfg(test)]
@@ -97,4 +125,13 @@ mod tests {
         let long_email = format!("{}@example.com", "a".repeat(250));
         assert!(!is_valid_email(&long_email));
     }
+
+    #[test]
+    fn test_hash_and_verify_password() {
+        let hash = hash_password("password").unwrap();
+        assert_ne!(hash, "password");
+        assert!(verify_password("password", &hash));
+        assert!(!verify_password("wrong password", &hash));
+        assert!(!verify_password("password", "not a hash"));
+    }
 }
\ No newline at end of file
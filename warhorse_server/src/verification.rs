@@ -0,0 +1,20 @@
+use std::fmt::Debug;
+
+use tracing::info;
+use warhorse_protocol::UserPartial;
+
+/// Gets a new user's email verification token to them, there's no mailer built in so this is where one plugs in.
+/// The token verifies the email through `WarhorseServer::verify_email` or the `/verify-email?token=` route.
+pub trait EmailVerificationSender: Debug + Send + Sync {
+    fn send(&self, user: &UserPartial, token: &str);
+}
+
+/// Logs the token, the default, handy for local development.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogVerificationToken;
+
+impl EmailVerificationSender for LogVerificationToken {
+    fn send(&self, user: &UserPartial, token: &str) {
+        info!("Email verification token for {}: {}", user.id, token);
+    }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::thread;
+
+use warhorse_client::warhorse_protocol::{ErrorCode, Language, UserId};
+use warhorse_client::{WarhorseClient, WarhorseEvent};
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+use common::{login, registration, wait_for};
+
+/// A server with an admin, `admin`, and a regular user, `player`, whose id is returned.
+fn start_server_with_admin() -> (String, UserId) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+            let player_id = {
+                let mut server = server.lock().await;
+                let admin_id = server.register_user(registration("admin"), None).await.unwrap();
+                server.set_admin(admin_id, true);
+                server.register_user(registration("player"), None).await.unwrap()
+            };
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send((listener.local_addr().unwrap(), player_id)).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+
+    let (address, player_id) = receiver.recv().unwrap();
+    (format!("http://{}", address), player_id)
+}
+
+#[test]
+fn test_kicked_user_is_disconnected() {
+    let (connection_string, player_id) = start_server_with_admin();
+    let admin = login(&connection_string, "admin");
+    let player = login(&connection_string, "player");
+
+    admin.send_admin_kick(player_id).unwrap();
+
+    wait_for(&player, |event| matches!(event, WarhorseEvent::Reconnecting { .. }));
+}
+
+#[test]
+fn test_banned_user_is_disconnected_and_cannot_log_in() {
+    let (connection_string, player_id) = start_server_with_admin();
+    let admin = login(&connection_string, "admin");
+    let player = login(&connection_string, "player");
+
+    admin.send_admin_ban(player_id, Some("cheating".to_string())).unwrap();
+    wait_for(&player, |event| matches!(
+        event,
+        WarhorseEvent::Banned { reason: Some(reason) } if reason == "cheating"
+    ));
+
+    let client = WarhorseClient::new(Language::English, &connection_string).unwrap();
+    client.send_user_login_request("player".to_string(), "password".to_string()).unwrap();
+    wait_for(&client, |event| matches!(event, WarhorseEvent::Error { code: ErrorCode::Banned, .. }));
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use warhorse_client::warhorse_protocol::Language;
+use warhorse_client::{WarhorseClient, WarhorseEvent};
+
+use common::{login, start_server_with_friends, wait_for};
+
+#[test]
+fn test_blocking_and_unblocking_send_the_blocked_list() {
+    let server = start_server_with_friends();
+    let user = login(&server.connection_string, "test");
+
+    user.send_block_friend(server.friend_id.clone()).unwrap();
+    wait_for(&user, |event| {
+        matches!(event, WarhorseEvent::BlockedList(blocked) if blocked.len() == 1 && blocked[0].id == server.friend_id)
+    });
+
+    user.send_unblock_friend(server.friend_id.clone()).unwrap();
+    wait_for(&user, |event| matches!(event, WarhorseEvent::BlockedList(blocked) if blocked.is_empty()));
+}
+
+#[test]
+fn test_blocked_list_is_sent_on_login() {
+    let server = start_server_with_friends();
+    let user = login(&server.connection_string, "test");
+    user.send_block_friend(server.friend_id.clone()).unwrap();
+    wait_for(&user, |event| matches!(event, WarhorseEvent::BlockedList(blocked) if !blocked.is_empty()));
+    drop(user);
+
+    // not `login`, which would skip past everything sent before the login response
+    let user = WarhorseClient::new(Language::English, &server.connection_string).unwrap();
+    user.send_user_login_request("test".to_string(), "password".to_string()).unwrap();
+    wait_for(&user, |event| {
+        matches!(event, WarhorseEvent::BlockedList(blocked) if blocked.iter().any(|blocked| blocked.id == server.friend_id))
+    });
+}
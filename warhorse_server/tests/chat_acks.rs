@@ -0,0 +1,25 @@
+mod common;
+
+use warhorse_client::warhorse_protocol::RoomId;
+use warhorse_client::WarhorseEvent;
+
+use common::{login, start_server_with_friends, wait_for};
+
+#[test]
+fn test_rejected_chat_message_gets_a_failed_ack() {
+    let server = start_server_with_friends();
+    let client = login(&server.connection_string, "test");
+
+    let accepted_id = client.send_room_message(RoomId::from("general"), "hello".to_string()).unwrap();
+    wait_for(&client, |event| matches!(
+        event,
+        WarhorseEvent::SendAck { request_id, result: Ok(()) } if *request_id == accepted_id
+    ));
+
+    // we never joined this room
+    let rejected_id = client.send_room_message(RoomId::from("elsewhere"), "hello".to_string()).unwrap();
+    wait_for(&client, |event| matches!(
+        event,
+        WarhorseEvent::SendAck { request_id, result: Err(_) } if *request_id == rejected_id
+    ));
+}
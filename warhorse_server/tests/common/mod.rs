@@ -0,0 +1,120 @@
+#![allow(dead_code)]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use warhorse_client::warhorse_protocol::{AcceptFriendRequest, FriendRequest, Language, UserId, UserRegistration};
+use warhorse_client::{WarhorseClient, WarhorseEvent};
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+pub const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A server running on an ephemeral port with `test` and `test2`, who are friends and both offline.
+pub struct TestServer {
+    pub connection_string: String,
+    pub user_id: UserId,
+    pub friend_id: UserId,
+}
+
+/// A server running on an ephemeral port with nobody registered, returning its connection string.
+pub fn start_empty_server() -> String {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, _server) = warhorse_server::app::<InMemoryDatabase>("");
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send(listener.local_addr().unwrap()).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+    format!("http://{}", receiver.recv().unwrap())
+}
+
+pub fn start_server_with_friends() -> TestServer {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+            let (user_id, friend_id) = {
+                let mut server = server.lock().await;
+                let user_id = server.register_user(registration("test"), None).await.unwrap();
+                let friend_id = server.register_user(registration("test2"), None).await.unwrap();
+                server.send_friend_request(
+                    user_id.clone(),
+                    FriendRequest { language: Language::English, friend_id: friend_id.clone() }
+                ).unwrap();
+                server.accept_friend_request(
+                    friend_id.clone(),
+                    AcceptFriendRequest { language: Language::English, friend_id: user_id.clone() }
+                ).unwrap();
+                (user_id, friend_id)
+            };
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send((listener.local_addr().unwrap(), user_id, friend_id)).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+
+    let (address, user_id, friend_id) = receiver.recv().unwrap();
+    TestServer {
+        connection_string: format!("http://{}", address),
+        user_id,
+        friend_id,
+    }
+}
+
+pub fn registration(account_name: &str) -> UserRegistration {
+    UserRegistration {
+        language: Language::English,
+        account_name: account_name.to_string(),
+        email: format!("{}@example.com", account_name),
+        display_name: account_name.to_string(),
+        password: "password".to_string(),
+    }
+}
+
+/// Connects and logs in, waiting until the server confirms it.
+pub fn login(connection_string: &str, account_name: &str) -> WarhorseClient {
+    let client = WarhorseClient::new(Language::English, connection_string).unwrap();
+    client.send_user_login_request(account_name.to_string(), "password".to_string()).unwrap();
+    wait_for(&client, |event| matches!(event, WarhorseEvent::LoggedIn));
+    client
+}
+
+/// Pumps the client until an event matches, or panics after a timeout.
+pub fn wait_for(client: &WarhorseClient, predicate: impl Fn(&WarhorseEvent) -> bool) {
+    let started = Instant::now();
+    while started.elapsed() < EVENT_TIMEOUT {
+        if client.pump().iter().any(&predicate) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("timed out waiting for event");
+}
+
+/// Pumps the client until an event matches and returns it, or panics after a timeout.
+pub fn next_matching(client: &WarhorseClient, predicate: impl Fn(&WarhorseEvent) -> bool) -> WarhorseEvent {
+    let started = Instant::now();
+    while started.elapsed() < EVENT_TIMEOUT {
+        if let Some(event) = client.pump().into_iter().find(&predicate) {
+            return event;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("timed out waiting for event");
+}
+
+/// Pumps the client for a while, returning everything it received.
+pub fn collect_for(client: &WarhorseClient, duration: Duration) -> Vec<WarhorseEvent> {
+    let started = Instant::now();
+    let mut events = Vec::new();
+    while started.elapsed() < duration {
+        events.extend(client.pump());
+        thread::sleep(Duration::from_millis(50));
+    }
+    events
+}
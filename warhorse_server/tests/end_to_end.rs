@@ -0,0 +1,71 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use warhorse_client::warhorse_protocol::{ChatChannel, Language, RoomId, UserId};
+use warhorse_client::{WarhorseClient, WarhorseEvent, WarhorseEventKind};
+
+use common::{next_matching, start_empty_server, wait_for, EVENT_TIMEOUT};
+
+/// Registers over the socket and waits for the login that follows, returning the new account's id.
+fn register(connection_string: &str, account_name: &str) -> (WarhorseClient, UserId) {
+    let client = WarhorseClient::new(Language::English, connection_string).unwrap();
+    client.send_user_registration_request(
+        account_name.to_string(),
+        "password".to_string(),
+        account_name.to_string(),
+        format!("{}@example.com", account_name),
+    ).unwrap();
+
+    // who we are has to be known by the time we're told we're logged in
+    let mut kinds = Vec::new();
+    let started = Instant::now();
+    while !kinds.contains(&WarhorseEventKind::LoggedIn) {
+        assert!(started.elapsed() < EVENT_TIMEOUT, "timed out waiting for the login after registering");
+        kinds.extend(client.pump().iter().map(WarhorseEvent::kind));
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let current_user = kinds.iter().position(|kind| *kind == WarhorseEventKind::CurrentUser);
+    let logged_in = kinds.iter().position(|kind| *kind == WarhorseEventKind::LoggedIn);
+    assert!(current_user < logged_in, "got {:?}", kinds);
+
+    let user_id = client.current_user().unwrap().id;
+    (client, user_id)
+}
+
+#[test]
+fn test_register_befriend_and_chat() {
+    let connection_string = start_empty_server();
+
+    let (alice, alice_id) = register(&connection_string, "alice");
+    let (bob, bob_id) = register(&connection_string, "bob");
+
+    alice.send_friend_request(bob_id.clone()).unwrap();
+    let received = next_matching(&bob, |event| matches!(event, WarhorseEvent::FriendRequestReceived(_)));
+    match received {
+        WarhorseEvent::FriendRequestReceived(friend) => assert_eq!(friend.id, alice_id),
+        _ => unreachable!(),
+    }
+
+    // the one accepting is told who they're now friends with, the one asking gets a fresh friends list
+    bob.send_accept_friend_request(alice_id.clone()).unwrap();
+    let accepted = next_matching(&bob, |event| matches!(event, WarhorseEvent::FriendRequestAccepted(_)));
+    match accepted {
+        WarhorseEvent::FriendRequestAccepted(friend) => assert_eq!(friend.id, alice_id),
+        _ => unreachable!(),
+    }
+    wait_for(&alice, |event| matches!(
+        event,
+        WarhorseEvent::FriendsList(friends) if friends.iter().any(|friend| friend.id == bob_id)
+    ));
+
+    alice.send_room_message(RoomId::from("general"), "hi bob".to_string()).unwrap();
+    let message = next_matching(&bob, |event| matches!(event, WarhorseEvent::ChatMessage(message) if message.message == "hi bob"));
+    match message {
+        WarhorseEvent::ChatMessage(message) => {
+            assert_eq!(message.display_name, "alice");
+            assert!(matches!(&message.channel, ChatChannel::Room(room_id) if *room_id == "general"));
+        },
+        _ => unreachable!(),
+    }
+}
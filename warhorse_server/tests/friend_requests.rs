@@ -0,0 +1,45 @@
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use warhorse_client::WarhorseEvent;
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+use common::{collect_for, login, registration};
+
+#[test]
+fn test_new_friend_request_is_a_single_notification() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+            let requested_id = {
+                let mut server = server.lock().await;
+                server.register_user(registration("requester"), None).await.unwrap();
+                server.register_user(registration("requested"), None).await.unwrap()
+            };
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send((listener.local_addr().unwrap(), requested_id)).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+    let (address, requested_id) = receiver.recv().unwrap();
+    let connection_string = format!("http://{}", address);
+
+    let requester = login(&connection_string, "requester");
+    let requested = login(&connection_string, "requested");
+    // everything sent after logging in, the empty friend requests list included
+    collect_for(&requested, Duration::from_millis(500));
+
+    requester.send_friend_request(requested_id).unwrap();
+
+    let events = collect_for(&requested, Duration::from_secs(2));
+    let received: Vec<_> = events.iter()
+        .filter(|event| matches!(event, WarhorseEvent::FriendRequestReceived(friend) if friend.display_name == "requester"))
+        .collect();
+    assert_eq!(received.len(), 1);
+    assert!(!events.iter().any(|event| matches!(event, WarhorseEvent::FriendRequests(_))));
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::thread;
+
+use warhorse_client::warhorse_protocol::Language;
+use warhorse_client::{WarhorseClient, WarhorseEvent};
+use warhorse_server::config::WarhorseServerConfig;
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+use common::{registration, wait_for};
+
+#[test]
+fn test_client_logs_in_on_a_custom_namespace() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app_with_config::<InMemoryDatabase>(WarhorseServerConfig {
+                namespace: "/warhorse".to_string(),
+                ..Default::default()
+            });
+            server.lock().await.register_user(registration("test"), None).await.unwrap();
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send(listener.local_addr().unwrap()).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+    let connection_string = format!("http://{}", receiver.recv().unwrap());
+
+    let client = WarhorseClient::builder(&connection_string)
+        .language(Language::English)
+        .namespace("/warhorse")
+        .connect()
+        .unwrap();
+    wait_for(&client, |event| matches!(event, WarhorseEvent::Hello));
+
+    client.send_user_login_request("test".to_string(), "password".to_string()).unwrap();
+    wait_for(&client, |event| matches!(event, WarhorseEvent::LoggedIn));
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use std::time::Duration;
+
+use warhorse_client::warhorse_protocol::Language;
+use warhorse_client::{WarhorseClient, WarhorseEvent};
+
+use common::{collect_for, login, start_server_with_friends};
+
+/// Logs in as `test2` and returns every whisper received shortly after.
+fn login_and_collect_whispers(connection_string: &str) -> Vec<String> {
+    let client = WarhorseClient::new(Language::English, connection_string).unwrap();
+    client.send_user_login_request("test2".to_string(), "password".to_string()).unwrap();
+    collect_for(&client, Duration::from_secs(2))
+        .into_iter()
+        .filter_map(|event| match event {
+            WarhorseEvent::ChatMessage(message) => Some(message.message),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_whisper_to_offline_friend_is_delivered_once_on_login() {
+    let server = start_server_with_friends();
+
+    let client = login(&server.connection_string, "test");
+    client.send_whisper_message(server.friend_id.clone(), "see you later".to_string()).unwrap();
+
+    assert_eq!(login_and_collect_whispers(&server.connection_string), vec!["see you later"]);
+
+    // logging in again shouldn't deliver it a second time
+    assert!(login_and_collect_whispers(&server.connection_string).is_empty());
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use warhorse_client::warhorse_protocol::{FriendStatus, Language};
+use warhorse_client::{WarhorseClient, WarhorseEvent};
+
+use common::{start_server_with_friends, wait_for};
+
+fn friend_status(event: &WarhorseEvent, display_name: &str) -> Option<FriendStatus> {
+    match event {
+        WarhorseEvent::FriendsList(friends) => friends.iter()
+            .find(|friend| friend.display_name == display_name)
+            .map(|friend| friend.status),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_friend_sees_user_come_online() {
+    let server = start_server_with_friends();
+
+    let client = WarhorseClient::new(Language::English, &server.connection_string).unwrap();
+    client.send_user_login_request("test".to_string(), "password".to_string()).unwrap();
+    wait_for(&client, |event| friend_status(event, "test2") == Some(FriendStatus::Offline));
+
+    // the friend logs in, and we should be told without asking
+    let _friend_client = common::login(&server.connection_string, "test2");
+    wait_for(&client, |event| friend_status(event, "test2") == Some(FriendStatus::Online));
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use warhorse_client::warhorse_protocol::RoomId;
+use warhorse_client::WarhorseEvent;
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+use common::{collect_for, login, registration, wait_for};
+
+fn is_chat_message(event: &WarhorseEvent, text: &str) -> bool {
+    matches!(event, WarhorseEvent::ChatMessage(message) if message.message == text)
+}
+
+#[test]
+fn test_blocker_does_not_receive_blocked_users_room_messages() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+            let blocked_id = {
+                let mut server = server.lock().await;
+                server.register_user(registration("blocker"), None).await.unwrap();
+                server.register_user(registration("bystander"), None).await.unwrap();
+                server.register_user(registration("blocked"), None).await.unwrap()
+            };
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send((listener.local_addr().unwrap(), blocked_id)).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+    let (address, blocked_id) = receiver.recv().unwrap();
+    let connection_string = format!("http://{}", address);
+
+    let blocker = login(&connection_string, "blocker");
+    blocker.send_block_friend(blocked_id).unwrap();
+    wait_for(&blocker, |event| matches!(event, WarhorseEvent::BlockedList(blocked) if !blocked.is_empty()));
+
+    let bystander = login(&connection_string, "bystander");
+    let blocked = login(&connection_string, "blocked");
+    blocked.send_room_message(RoomId::from("general"), "hello everyone".to_string()).unwrap();
+
+    wait_for(&bystander, |event| is_chat_message(event, "hello everyone"));
+    let events = collect_for(&blocker, Duration::from_millis(500));
+    assert!(!events.iter().any(|event| is_chat_message(event, "hello everyone")));
+}
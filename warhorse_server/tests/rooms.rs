@@ -0,0 +1,57 @@
+mod common;
+
+use std::thread;
+
+use warhorse_client::WarhorseEvent;
+use warhorse_client::warhorse_protocol::RoomId;
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+use common::{login, registration, start_server_with_friends, wait_for};
+
+fn is_chat_message(event: &WarhorseEvent, text: &str) -> bool {
+    matches!(event, WarhorseEvent::ChatMessage(message) if message.message == text)
+}
+
+#[test]
+fn test_room_messages_reach_everyone_including_the_sender() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+            {
+                let mut server = server.lock().await;
+                server.register_user(registration("talker"), None).await.unwrap();
+                server.register_user(registration("listener"), None).await.unwrap();
+            }
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send(listener.local_addr().unwrap()).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+    let connection_string = format!("http://{}", receiver.recv().unwrap());
+
+    let talker = login(&connection_string, "talker");
+    let listener = login(&connection_string, "listener");
+    talker.send_room_message(RoomId::from("general"), "hello everyone".to_string()).unwrap();
+
+    wait_for(&listener, |event| is_chat_message(event, "hello everyone"));
+    wait_for(&talker, |event| is_chat_message(event, "hello everyone"));
+}
+
+#[test]
+fn test_room_members_lists_everyone_in_the_room() {
+    let server = start_server_with_friends();
+    let user = login(&server.connection_string, "test");
+    let friend = login(&server.connection_string, "test2");
+
+    for (client, other_id) in [(&user, &server.friend_id), (&friend, &server.user_id)] {
+        client.request_room_members(RoomId::from("general")).unwrap();
+        wait_for(client, |event| matches!(
+            event,
+            WarhorseEvent::RoomMembers { room_id, members }
+                if *room_id == "general" && members.iter().any(|member| member.id == *other_id)
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use std::thread;
+
+use tokio::sync::oneshot;
+use warhorse_client::WarhorseEvent;
+use warhorse_server::database::db_in_memory::InMemoryDatabase;
+
+use common::{login, registration, wait_for};
+
+#[test]
+fn test_clients_are_told_about_shutdown() {
+    let (address_sender, address_receiver) = std::sync::mpsc::channel();
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<String>();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (app, server) = warhorse_server::app::<InMemoryDatabase>("");
+            server.lock().await.register_user(registration("test"), None).await.unwrap();
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            address_sender.send(listener.local_addr().unwrap()).unwrap();
+            warhorse_server::serve(listener, app, server, async move { shutdown_receiver.await.ok() })
+                .await
+                .unwrap();
+        });
+    });
+    let connection_string = format!("http://{}", address_receiver.recv().unwrap());
+
+    let client = login(&connection_string, "test");
+    shutdown_sender.send("Server going down for maintenance".to_string()).unwrap();
+
+    wait_for(&client, |event| matches!(
+        event,
+        WarhorseEvent::ServerShutdown { reason: Some(reason) } if reason == "Server going down for maintenance"
+    ));
+}